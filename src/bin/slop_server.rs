@@ -0,0 +1,71 @@
+// src/bin/slop_server.rs
+//! Headless lobby/relay server for matchmaking and local multiplayer tests
+//!
+//! Usage: slop_server [--bind ADDR] [--tick-rate HZ] [--max-peers N] [--max-lobbies N]
+//!
+//! Ctrl-C or SIGTERM finishes the current tick, disconnects every peer and exits.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use slop_engine::lobby::{LobbyServer, LobbyServerConfig};
+
+fn parse_args() -> Result<LobbyServerConfig, String> {
+    let mut config = LobbyServerConfig::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value for {}", flag));
+        match flag.as_str() {
+            "--bind" => config.bind_address = value()?,
+            "--tick-rate" => config.tick_rate = value()?.parse().map_err(|e| format!("--tick-rate: {}", e))?,
+            "--max-peers" => config.max_peers = value()?.parse().map_err(|e| format!("--max-peers: {}", e))?,
+            "--max-lobbies" => config.max_lobbies = value()?.parse().map_err(|e| format!("--max-lobbies: {}", e))?,
+            "--help" | "-h" => {
+                println!("Usage: slop_server [--bind ADDR] [--tick-rate HZ] [--max-peers N] [--max-lobbies N]");
+                std::process::exit(0);
+            }
+            other => return Err(format!("unknown argument {}", other)),
+        }
+    }
+    Ok(config)
+}
+
+fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let config = match parse_args() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("slop_server: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    println!("===========================================================");
+    println!("              SLOP ENGINE LOBBY / RELAY SERVER             ");
+    println!("===========================================================");
+
+    let mut server = match LobbyServer::bind(config.clone()) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("slop_server: failed to bind {}: {}", config.bind_address, e);
+            std::process::exit(1);
+        }
+    };
+
+    println!("Listening on {}", server.local_addr().map(|a| a.to_string()).unwrap_or_default());
+    println!("Tick rate: {} Hz, max peers: {}, max lobbies: {}", config.tick_rate, config.max_peers, config.max_lobbies);
+
+    let running = Arc::new(AtomicBool::new(true));
+    let stop = running.clone();
+    if let Err(e) = ctrlc::set_handler(move || stop.store(false, Ordering::Relaxed)) {
+        eprintln!("slop_server: cannot install signal handler: {}", e);
+        std::process::exit(1);
+    }
+
+    if let Err(e) = server.run(&running) {
+        eprintln!("slop_server: {}", e);
+        std::process::exit(1);
+    }
+    println!("Shut down after {} ticks", server.stats().ticks);
+}
@@ -1004,7 +1004,7 @@ mod tests {
             },
         };
         
-        let save = CausalSaveFile::new(world_seed, player_seed);
+        let mut save = CausalSaveFile::new(world_seed, player_seed);
         
         // Add some divergences
         save.record_divergence(DivergenceEvent::PlayerInput(PlayerInputDivergence {
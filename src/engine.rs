@@ -75,6 +75,7 @@ pub struct EngineStats {
     pub frame_time_ms: f32,
     pub predictive_hot_ratio: f32,
     pub gpu_time_saved_ms: f32,
+    pub pixels_skipped: u64,
     pub vram_used_mb: f64,
     pub vram_budget_mb: f64,
     pub entities_culled: usize,
@@ -175,6 +176,7 @@ impl Engine {
         let predictive_renderer = Some(PredictiveRenderer::new(
             todo!("Get device"),
             settings.predictive_rendering.clone(),
+            wgpu::TextureFormat::Bgra8UnormSrgb,
             1280,
             720,
         ));
@@ -289,10 +291,6 @@ impl Engine {
 
     /// Prepare scene for rendering
     fn prepare_scene(&mut self) {
-        // Get visible entities through culling
-        let visible_entities = crate::culling::FrustumCuller::cull(&self.scene, &self.camera);
-        self.stats.entities_culled = self.scene.entity_count() - visible_entities.len();
-
         // Generate prediction delta
         if let Some(ref mut pr) = self.predictive_renderer {
             let snapshot = self.create_scene_snapshot();
@@ -305,6 +303,7 @@ impl Engine {
             let tile_stats = pr.tile_manager.statistics();
             self.stats.predictive_hot_ratio = 1.0 - tile_stats.hot_ratio;
             self.stats.gpu_time_saved_ms = pr.stats.gpu_time_saved_ms;
+            self.stats.pixels_skipped = pr.stats.pixels_skipped;
         }
     }
 
@@ -352,8 +351,10 @@ impl Engine {
         self.stats.vram_used_mb = vram_used as f64 / (1024.0 * 1024.0);
         self.stats.vram_budget_mb = vram_budget as f64 / (1024.0 * 1024.0);
 
-        // Render scene
-        self.renderer.render()
+        // Render scene; instances are culled on the GPU
+        let stats = self.renderer.render()?;
+        self.stats.entities_culled = stats.instances_culled as usize;
+        Ok(())
     }
 
     /// Handle window resize
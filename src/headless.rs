@@ -0,0 +1,263 @@
+// src/headless.rs
+//! HEADLESS RENDERING
+//!
+//! Offscreen render targets that read back to RGBA8, so the renderers can
+//! run without a window (CI, tools, golden-image tests). Works with wgpu's
+//! fallback/software adapter when no GPU is present.
+
+use wgpu::*;
+
+/// Format of headless targets; matches the sRGB swapchains used on screen
+pub const HEADLESS_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// Offscreen color target standing in for a surface texture
+pub struct HeadlessTarget {
+    texture: Texture,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+}
+
+impl HeadlessTarget {
+    pub fn new(device: &Device, width: u32, height: u32) -> Self {
+        Self::with_format(device, width, height, HEADLESS_FORMAT)
+    }
+
+    /// `format` must be a 4-byte-per-pixel color format
+    pub fn with_format(device: &Device, width: u32, height: u32, format: TextureFormat) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("headless_target"),
+            size: Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        Self { texture, width, height, format }
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub fn create_view(&self) -> TextureView {
+        self.texture.create_view(&TextureViewDescriptor::default())
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    /// Read the target back as tightly packed RGBA8 rows. Blocks until the
+    /// GPU has finished all submitted work.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_rgba(&self, device: &Device, queue: &Queue) -> Result<Vec<u8>, BufferAsyncError> {
+        let mut pixels = read_texture_rgba(device, queue, &self.texture, self.width, self.height)?;
+        if matches!(self.format, TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb) {
+            pixels.chunks_exact_mut(4).for_each(|px| px.swap(0, 2));
+        }
+        Ok(pixels)
+    }
+}
+
+/// Fallback (software) adapter if the platform has one, else any adapter,
+/// with timestamp queries when supported. None when no adapter is available
+/// at all.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn request_headless_device() -> Option<(Device, Queue)> {
+    let instance = Instance::default();
+    let adapter = pollster::block_on(instance.request_adapter(&RequestAdapterOptions {
+        force_fallback_adapter: true,
+        ..Default::default()
+    }))
+    .or_else(|| pollster::block_on(instance.request_adapter(&RequestAdapterOptions::default())))?;
+    let descriptor = DeviceDescriptor { required_features: adapter.features() & Features::TIMESTAMP_QUERY, ..Default::default() };
+    pollster::block_on(adapter.request_device(&descriptor, None)).ok()
+}
+
+/// Device for GPU tests. None, after which the test should return early,
+/// when no adapter is available; on CI (`CI` set) that is a failure instead.
+#[cfg(test)]
+pub(crate) fn test_device() -> Option<(Device, Queue)> {
+    let device = request_headless_device();
+    if device.is_none() {
+        assert!(std::env::var_os("CI").is_none(), "no wgpu adapter available on CI");
+        eprintln!("no wgpu adapter available, skipping");
+    }
+    device
+}
+
+/// Copy a 4-byte-per-pixel texture (needs `COPY_SRC`) into tightly packed rows
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_texture_rgba(device: &Device, queue: &Queue, texture: &Texture, width: u32, height: u32) -> Result<Vec<u8>, BufferAsyncError> {
+    let padded = (width * 4).div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT;
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("headless_readback"),
+        size: (padded * height) as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: Some("headless_readback") });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout { offset: 0, bytes_per_row: Some(padded), rows_per_image: None },
+        },
+        Extent3d { width, height, depth_or_array_layers: 1 },
+    );
+    queue.submit(Some(encoder.finish()));
+    let (sender, receiver) = std::sync::mpsc::channel();
+    buffer.slice(..).map_async(MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(Maintain::Wait);
+    receiver.recv().unwrap_or(Err(BufferAsyncError))?;
+    let data = buffer.slice(..).get_mapped_range();
+    Ok(data.chunks(padded as usize).flat_map(|row| row[..(width * 4) as usize].to_vec()).collect())
+}
+
+// ============================================================================
+// GOLDEN IMAGES
+// ============================================================================
+
+/// Golden-image comparison for regression tests. Goldens live in
+/// `tests/golden/<name>.png` and are only written when `SLOP_UPDATE_GOLDEN=1`;
+/// otherwise a missing golden is an error. On mismatch the frame is saved
+/// next to the golden as `<name>.actual.png`.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod golden {
+    use std::path::PathBuf;
+
+    /// Environment variable that rewrites goldens instead of comparing
+    pub const UPDATE_ENV: &str = "SLOP_UPDATE_GOLDEN";
+
+    /// Software rasterizers differ slightly in filtering and rounding, so
+    /// small per-channel differences on a few pixels are accepted
+    #[derive(Debug, Clone, Copy)]
+    pub struct GoldenTolerance {
+        /// Largest per-channel difference that still counts as a match
+        pub max_channel_diff: u8,
+        /// Fraction of pixels allowed to exceed `max_channel_diff`
+        pub max_mismatch_ratio: f32,
+    }
+
+    impl Default for GoldenTolerance {
+        fn default() -> Self {
+            Self { max_channel_diff: 4, max_mismatch_ratio: 0.005 }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct GoldenDiff {
+        pub mismatched_pixels: usize,
+        pub total_pixels: usize,
+        pub max_channel_diff: u8,
+    }
+
+    #[derive(Debug)]
+    pub enum GoldenError {
+        Missing { name: String },
+        SizeMismatch { expected: (u32, u32), actual: (u32, u32) },
+        Mismatch { name: String, diff: GoldenDiff },
+        Io(String),
+    }
+
+    impl std::fmt::Display for GoldenError {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                GoldenError::Missing { name } => write!(f, "No golden {} (rerun with {}=1 to create it)", name, UPDATE_ENV),
+                GoldenError::SizeMismatch { expected, actual } => {
+                    write!(f, "Golden is {}x{}, frame is {}x{}", expected.0, expected.1, actual.0, actual.1)
+                }
+                GoldenError::Mismatch { name, diff } => write!(
+                    f,
+                    "Frame differs from golden {}: {}/{} pixels off, max channel diff {} (rerun with {}=1 to accept)",
+                    name, diff.mismatched_pixels, diff.total_pixels, diff.max_channel_diff, UPDATE_ENV
+                ),
+                GoldenError::Io(msg) => write!(f, "Golden I/O failed: {}", msg),
+            }
+        }
+    }
+
+    impl std::error::Error for GoldenError {}
+
+    pub fn golden_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+    }
+
+    /// Per-pixel comparison of two RGBA8 images of equal size
+    pub fn diff_rgba(expected: &[u8], actual: &[u8], max_channel_diff: u8) -> GoldenDiff {
+        let mut diff = GoldenDiff { mismatched_pixels: 0, total_pixels: expected.len() / 4, max_channel_diff: 0 };
+        for (e, a) in expected.chunks_exact(4).zip(actual.chunks_exact(4)) {
+            let worst = e.iter().zip(a).map(|(e, a)| e.abs_diff(*a)).max().unwrap_or(0);
+            diff.max_channel_diff = diff.max_channel_diff.max(worst);
+            if worst > max_channel_diff {
+                diff.mismatched_pixels += 1;
+            }
+        }
+        diff
+    }
+
+    /// Compare an RGBA8 frame against `tests/golden/<name>.png`
+    pub fn compare_golden(name: &str, rgba: &[u8], width: u32, height: u32, tolerance: GoldenTolerance) -> Result<GoldenDiff, GoldenError> {
+        let path = golden_dir().join(format!("{}.png", name));
+        let update = std::env::var(UPDATE_ENV).map(|v| v == "1").unwrap_or(false);
+        if update {
+            save_png(&path, rgba, width, height)?;
+            return Ok(GoldenDiff { mismatched_pixels: 0, total_pixels: (width * height) as usize, max_channel_diff: 0 });
+        }
+        if !path.exists() {
+            return Err(GoldenError::Missing { name: name.to_string() });
+        }
+
+        let golden = image::open(&path).map_err(|e| GoldenError::Io(e.to_string()))?.to_rgba8();
+        if golden.dimensions() != (width, height) {
+            return Err(GoldenError::SizeMismatch { expected: golden.dimensions(), actual: (width, height) });
+        }
+        let diff = diff_rgba(golden.as_raw(), rgba, tolerance.max_channel_diff);
+        if diff.mismatched_pixels as f32 > diff.total_pixels as f32 * tolerance.max_mismatch_ratio {
+            save_png(&golden_dir().join(format!("{}.actual.png", name)), rgba, width, height)?;
+            return Err(GoldenError::Mismatch { name: name.to_string(), diff });
+        }
+        Ok(diff)
+    }
+
+    fn save_png(path: &std::path::Path, rgba: &[u8], width: u32, height: u32) -> Result<(), GoldenError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| GoldenError::Io(e.to_string()))?;
+        }
+        image::save_buffer(path, rgba, width, height, image::ExtendedColorType::Rgba8).map_err(|e| GoldenError::Io(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::golden::*;
+
+    #[test]
+    fn test_missing_golden_is_an_error() {
+        if std::env::var(UPDATE_ENV).is_ok_and(|v| v == "1") {
+            return;
+        }
+        let result = compare_golden("missing_golden", &[0; 4], 1, 1, Default::default());
+        assert!(matches!(result, Err(GoldenError::Missing { .. })));
+        assert!(!golden_dir().join("missing_golden.png").exists());
+    }
+
+    #[test]
+    fn test_diff_rgba_counts_pixels_over_tolerance() {
+        let expected = [10, 10, 10, 255, 200, 0, 0, 255, 0, 0, 0, 255];
+        let actual = [12, 10, 10, 255, 190, 0, 0, 255, 0, 0, 0, 255];
+        let diff = diff_rgba(&expected, &actual, 4);
+        assert_eq!(diff, GoldenDiff { mismatched_pixels: 1, total_pixels: 3, max_channel_diff: 10 });
+        assert_eq!(diff_rgba(&expected, &actual, 10).mismatched_pixels, 0);
+    }
+}
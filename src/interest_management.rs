@@ -0,0 +1,393 @@
+// src/interest_management.rs
+//! Spatial interest management for replication
+//!
+//! - Uniform spatial hash over entity positions for O(nearby) relevancy queries
+//! - Per-connection relevancy sets around each viewer
+//! - Per-entity priority accumulators so packets fill with the most urgent
+//!   entities first while distant ones still get their turn
+
+use std::collections::{HashMap, HashSet};
+
+use glam::Vec3;
+
+use crate::network::EntitySnapshot;
+
+// ============================================================================
+// CONFIG
+// ============================================================================
+
+#[derive(Clone, Copy, Debug)]
+pub struct InterestConfig {
+    /// Edge length of a spatial hash cell; roughly the relevancy radius / 2 works well
+    pub cell_size: f32,
+    /// Entities further than this from a viewer are not replicated to it
+    pub relevancy_radius: f32,
+    /// Extra priority per unit of speed (world units / second)
+    pub velocity_weight: f32,
+    /// Priority floor at the edge of the relevancy radius, as a fraction of the nearest
+    pub min_distance_factor: f32,
+}
+
+impl Default for InterestConfig {
+    fn default() -> Self {
+        Self {
+            cell_size: 64.0,
+            relevancy_radius: 150.0,
+            velocity_weight: 0.1,
+            min_distance_factor: 0.1,
+        }
+    }
+}
+
+// ============================================================================
+// SPATIAL HASH
+// ============================================================================
+
+type CellKey = (i32, i32, i32);
+
+/// Buckets entity ids by grid cell
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<CellKey, Vec<u64>>,
+    entity_cells: HashMap<u64, CellKey>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size: cell_size.max(f32::EPSILON),
+            cells: HashMap::new(),
+            entity_cells: HashMap::new(),
+        }
+    }
+
+    fn cell_of(&self, position: Vec3) -> CellKey {
+        let c = (position / self.cell_size).floor();
+        (c.x as i32, c.y as i32, c.z as i32)
+    }
+
+    /// Insert or move an entity
+    pub fn update(&mut self, entity_id: u64, position: Vec3) {
+        let cell = self.cell_of(position);
+        match self.entity_cells.insert(entity_id, cell) {
+            Some(old) if old == cell => return,
+            Some(old) => self.remove_from_cell(entity_id, old),
+            None => {}
+        }
+        self.cells.entry(cell).or_default().push(entity_id);
+    }
+
+    pub fn remove(&mut self, entity_id: u64) {
+        if let Some(cell) = self.entity_cells.remove(&entity_id) {
+            self.remove_from_cell(entity_id, cell);
+        }
+    }
+
+    fn remove_from_cell(&mut self, entity_id: u64, cell: CellKey) {
+        if let Some(bucket) = self.cells.get_mut(&cell) {
+            bucket.retain(|id| *id != entity_id);
+            if bucket.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    /// Ids in every cell overlapping the sphere's bounding box. Callers
+    /// needing an exact radius filter the result by distance.
+    pub fn query(&self, center: Vec3, radius: f32) -> Vec<u64> {
+        let min = self.cell_of(center - Vec3::splat(radius));
+        let max = self.cell_of(center + Vec3::splat(radius));
+        let mut found = Vec::new();
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                for z in min.2..=max.2 {
+                    if let Some(bucket) = self.cells.get(&(x, y, z)) {
+                        found.extend_from_slice(bucket);
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    pub fn len(&self) -> usize {
+        self.entity_cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entity_cells.is_empty()
+    }
+}
+
+// ============================================================================
+// INTEREST MANAGER
+// ============================================================================
+
+#[derive(Clone, Copy, Debug)]
+struct TrackedEntity {
+    position: Vec3,
+    velocity: Vec3,
+}
+
+#[derive(Default)]
+struct Viewer {
+    position: Vec3,
+    relevant: HashSet<u64>,
+    accumulators: HashMap<u64, f32>,
+}
+
+pub struct InterestManager {
+    config: InterestConfig,
+    grid: SpatialHash,
+    entities: HashMap<u64, TrackedEntity>,
+    viewers: HashMap<u64, Viewer>,
+}
+
+impl InterestManager {
+    pub fn new(config: InterestConfig) -> Self {
+        Self {
+            config,
+            grid: SpatialHash::new(config.cell_size),
+            entities: HashMap::new(),
+            viewers: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &InterestConfig {
+        &self.config
+    }
+
+    /// Change tuning; the spatial hash is rebuilt if the cell size changed
+    pub fn set_config(&mut self, config: InterestConfig) {
+        if config.cell_size != self.config.cell_size {
+            self.grid = SpatialHash::new(config.cell_size);
+            for (id, entity) in &self.entities {
+                self.grid.update(*id, entity.position);
+            }
+        }
+        self.config = config;
+    }
+
+    /// Replace the tracked entity set with this frame's snapshots. Velocities
+    /// are derived from the previous position over `dt` seconds.
+    pub fn update_entities(&mut self, snapshots: &[EntitySnapshot], dt: f32) {
+        let mut seen = HashSet::with_capacity(snapshots.len());
+        for snapshot in snapshots {
+            seen.insert(snapshot.id);
+            let velocity = match self.entities.get(&snapshot.id) {
+                Some(prev) if dt > 0.0 => (snapshot.position - prev.position) / dt,
+                Some(prev) => prev.velocity,
+                None => Vec3::ZERO,
+            };
+            self.entities.insert(snapshot.id, TrackedEntity { position: snapshot.position, velocity });
+            self.grid.update(snapshot.id, snapshot.position);
+        }
+
+        let stale: Vec<u64> = self.entities.keys().filter(|id| !seen.contains(id)).copied().collect();
+        for id in stale {
+            self.remove_entity(id);
+        }
+    }
+
+    pub fn remove_entity(&mut self, entity_id: u64) {
+        self.entities.remove(&entity_id);
+        self.grid.remove(entity_id);
+        for viewer in self.viewers.values_mut() {
+            viewer.relevant.remove(&entity_id);
+            viewer.accumulators.remove(&entity_id);
+        }
+    }
+
+    /// Set where a connection is looking from, usually its controlled pawn
+    pub fn set_viewer(&mut self, peer_id: u64, position: Vec3) {
+        self.viewers.entry(peer_id).or_default().position = position;
+    }
+
+    pub fn remove_viewer(&mut self, peer_id: u64) {
+        self.viewers.remove(&peer_id);
+    }
+
+    /// Recompute every viewer's relevancy set from the spatial hash
+    pub fn update_relevancy(&mut self) {
+        let radius = self.config.relevancy_radius;
+        for viewer in self.viewers.values_mut() {
+            let candidates = self.grid.query(viewer.position, radius);
+            viewer.relevant = candidates
+                .into_iter()
+                .filter(|id| {
+                    self.entities.get(id)
+                        .is_some_and(|e| e.position.distance_squared(viewer.position) <= radius * radius)
+                })
+                .collect();
+            // Entities leaving relevancy start from zero if they come back
+            let relevant = &viewer.relevant;
+            viewer.accumulators.retain(|id, _| relevant.contains(id));
+        }
+    }
+
+    pub fn relevant_entities(&self, peer_id: u64) -> Option<&HashSet<u64>> {
+        self.viewers.get(&peer_id).map(|v| &v.relevant)
+    }
+
+    pub fn is_relevant(&self, peer_id: u64, entity_id: u64) -> bool {
+        self.viewers.get(&peer_id).is_some_and(|v| v.relevant.contains(&entity_id))
+    }
+
+    /// Priority gained per update: 1 at the viewer falling to
+    /// `min_distance_factor` at the relevancy edge, boosted by speed
+    pub fn priority(&self, peer_id: u64, entity_id: u64) -> f32 {
+        let (viewer, entity) = match (self.viewers.get(&peer_id), self.entities.get(&entity_id)) {
+            (Some(v), Some(e)) => (v, e),
+            _ => return 0.0,
+        };
+        let t = (entity.position.distance(viewer.position) / self.config.relevancy_radius).min(1.0);
+        let distance_factor = 1.0 - t * (1.0 - self.config.min_distance_factor);
+        distance_factor * (1.0 + entity.velocity.length() * self.config.velocity_weight)
+    }
+
+    /// Accumulate priority for every relevant entity and pick the highest
+    /// until `budget` bytes are spent. `cost` returns an entity's encoded size,
+    /// or `None` to skip it this round; it is only called in priority order
+    /// until the first entity that doesn't fit, which leads the next round.
+    /// Chosen entities reset to zero.
+    pub fn select(&mut self, peer_id: u64, budget: usize, mut cost: impl FnMut(u64) -> Option<usize>) -> Vec<u64> {
+        let gains: Vec<(u64, f32)> = match self.viewers.get(&peer_id) {
+            Some(viewer) => viewer.relevant.iter().map(|&id| (id, self.priority(peer_id, id))).collect(),
+            None => return Vec::new(),
+        };
+
+        let viewer = self.viewers.get_mut(&peer_id).expect("viewer checked above");
+        let mut ranked: Vec<(u64, f32)> = gains.into_iter()
+            .map(|(id, gain)| {
+                let acc = viewer.accumulators.entry(id).or_insert(0.0);
+                *acc += gain;
+                (id, *acc)
+            })
+            .collect();
+        ranked.sort_unstable_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let mut remaining = budget;
+        let mut chosen = Vec::new();
+        for (id, _) in ranked {
+            let size = match cost(id) {
+                Some(size) => size,
+                None => continue,
+            };
+            if size > remaining {
+                break;
+            }
+            remaining -= size;
+            viewer.accumulators.insert(id, 0.0);
+            chosen.push(id);
+        }
+        chosen
+    }
+
+    pub fn entity_count(&self) -> usize {
+        self.entities.len()
+    }
+}
+
+impl Default for InterestManager {
+    fn default() -> Self {
+        Self::new(InterestConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(id: u64, position: Vec3) -> EntitySnapshot {
+        EntitySnapshot::new(id, position, Vec3::ZERO, Vec3::ONE)
+    }
+
+    #[test]
+    fn test_spatial_hash_matches_brute_force() {
+        let mut grid = SpatialHash::new(10.0);
+        let positions: Vec<Vec3> = (0..500)
+            .map(|i| Vec3::new((i * 37 % 200) as f32 - 100.0, (i % 7) as f32, (i * 91 % 200) as f32 - 100.0))
+            .collect();
+        for (i, p) in positions.iter().enumerate() {
+            grid.update(i as u64, *p);
+        }
+        grid.update(0, Vec3::new(500.0, 0.0, 500.0));
+        grid.remove(1);
+        assert_eq!(grid.len(), 499);
+
+        let center = Vec3::new(5.0, 0.0, -12.0);
+        let mut found: Vec<u64> = grid.query(center, 25.0)
+            .into_iter()
+            .filter(|id| positions[*id as usize].distance(center) <= 25.0)
+            .collect();
+        found.sort();
+        let expected: Vec<u64> = (2..500u64).filter(|i| positions[*i as usize].distance(center) <= 25.0).collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_relevancy_and_priority() {
+        let mut interest = InterestManager::new(InterestConfig { relevancy_radius: 100.0, ..Default::default() });
+        interest.update_entities(&[
+            snapshot(1, Vec3::new(10.0, 0.0, 0.0)),
+            snapshot(2, Vec3::new(90.0, 0.0, 0.0)),
+            snapshot(3, Vec3::new(90.0, 0.0, 5.0)),
+            snapshot(4, Vec3::new(400.0, 0.0, 0.0)),
+        ], 0.1);
+        // Entity 3 starts moving fast
+        interest.update_entities(&[
+            snapshot(1, Vec3::new(10.0, 0.0, 0.0)),
+            snapshot(2, Vec3::new(90.0, 0.0, 0.0)),
+            snapshot(3, Vec3::new(90.0, 0.0, 7.0)),
+            snapshot(4, Vec3::new(400.0, 0.0, 0.0)),
+        ], 0.1);
+        interest.set_viewer(7, Vec3::ZERO);
+        interest.update_relevancy();
+
+        assert!(interest.is_relevant(7, 1));
+        assert!(!interest.is_relevant(7, 4));
+        assert!(interest.priority(7, 1) > interest.priority(7, 2));
+        assert!(interest.priority(7, 3) > interest.priority(7, 2));
+
+        // Removing an entity drops it from relevancy
+        interest.update_entities(&[snapshot(1, Vec3::new(10.0, 0.0, 0.0))], 0.1);
+        assert_eq!(interest.relevant_entities(7).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_accumulators_fill_budget_without_starvation() {
+        let mut interest = InterestManager::default();
+        let snapshots: Vec<EntitySnapshot> = (0..2000)
+            .map(|i| snapshot(i, Vec3::new((i % 50) as f32 * 4.0 - 100.0, 0.0, (i / 50) as f32 * 4.0 - 80.0)))
+            .collect();
+        interest.update_entities(&snapshots, 1.0 / 30.0);
+        interest.set_viewer(1, Vec3::ZERO);
+        interest.update_relevancy();
+        let relevant = interest.relevant_entities(1).unwrap().len();
+        assert!(relevant > 500);
+
+        let mut sent = HashSet::new();
+        let mut first_round = Vec::new();
+        for round in 0..200 {
+            let chosen = interest.select(1, 1400, |_| Some(50));
+            assert!(chosen.len() * 50 <= 1400);
+            if round == 0 {
+                first_round = chosen.clone();
+            }
+            sent.extend(chosen);
+        }
+
+        // The nearest entity goes first, yet every relevant entity eventually gets sent
+        let nearest = snapshots.iter().min_by(|a, b| a.position.length().total_cmp(&b.position.length())).unwrap().id;
+        assert_eq!(first_round[0], nearest);
+        assert_eq!(sent.len(), relevant);
+
+        // Sizes are only asked for until the budget is full
+        let mut costed = 0;
+        let chosen = interest.select(1, 1400, |_| {
+            costed += 1;
+            Some(300)
+        });
+        assert_eq!((chosen.len(), costed), (4, 5));
+    }
+}
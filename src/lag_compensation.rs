@@ -0,0 +1,419 @@
+// src/lag_compensation.rs
+//! Server-side lag compensation for hitscan traces
+//!
+//! - Ring buffer of actor hitboxes (oriented boxes) recorded once per tick
+//! - `rewind_trace` rewinds to the moment the shooter actually saw, derived
+//!   from the peer's RTT plus the client interpolation delay
+//! - Hitboxes are interpolated between the two recorded ticks bracketing
+//!   the rewind time, then traced with a ray/OBB slab test
+
+use std::collections::{HashMap, VecDeque};
+
+use glam::{Quat, Vec3};
+
+use crate::network::NetworkSystem;
+use crate::unreal_framework::{AActor, FHitResult, UWorld};
+
+/// Half extents of an unscaled actor (engine basic shapes are 100 units across)
+const DEFAULT_ACTOR_HALF_EXTENTS: Vec3 = Vec3::splat(50.0);
+/// Character capsule (radius 34, half height 88) approximated as a box
+const CHARACTER_HALF_EXTENTS: Vec3 = Vec3::new(34.0, 34.0, 88.0);
+
+// ============================================================================
+// CONFIG
+// ============================================================================
+
+#[derive(Clone, Copy, Debug)]
+pub struct LagCompensationConfig {
+    /// How many recorded ticks to keep
+    pub max_ticks: usize,
+    /// Render delay clients apply to remote entities (snapshot interpolation)
+    pub interpolation_delay_ms: f32,
+    /// Shots never rewind further than this, whatever the peer's latency
+    pub max_rewind_ms: f32,
+}
+
+impl Default for LagCompensationConfig {
+    fn default() -> Self {
+        Self {
+            max_ticks: 64,
+            interpolation_delay_ms: 100.0,
+            max_rewind_ms: 500.0,
+        }
+    }
+}
+
+// ============================================================================
+// HITBOX HISTORY
+// ============================================================================
+
+/// Oriented bounding box of one actor at one tick
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hitbox {
+    pub actor_id: u64,
+    pub center: Vec3,
+    pub rotation: Quat,
+    pub half_extents: Vec3,
+}
+
+impl Hitbox {
+    fn lerp(&self, other: &Hitbox, t: f32) -> Hitbox {
+        Hitbox {
+            actor_id: self.actor_id,
+            center: self.center.lerp(other.center, t),
+            rotation: self.rotation.slerp(other.rotation, t),
+            half_extents: self.half_extents.lerp(other.half_extents, t),
+        }
+    }
+
+    /// Slab test in box space; returns entry distance and world normal
+    fn ray_intersect(&self, origin: Vec3, dir: Vec3, max_dist: f32) -> Option<(f32, Vec3)> {
+        let inv = self.rotation.inverse();
+        let local_origin = inv * (origin - self.center);
+        let local_dir = inv * dir;
+
+        let mut t_min = 0.0f32;
+        let mut t_max = max_dist;
+        let mut normal = Vec3::ZERO;
+        for axis in 0..3 {
+            let o = local_origin[axis];
+            let d = local_dir[axis];
+            let e = self.half_extents[axis];
+            if d.abs() < 1e-8 {
+                if o < -e || o > e {
+                    return None;
+                }
+                continue;
+            }
+            let (mut t0, mut t1) = ((-e - o) / d, (e - o) / d);
+            let mut sign = -1.0;
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+                sign = 1.0;
+            }
+            if t0 > t_min {
+                t_min = t0;
+                let mut n = Vec3::ZERO;
+                n[axis] = sign;
+                normal = n;
+            }
+            t_max = t_max.min(t1);
+            if t_min > t_max {
+                return None;
+            }
+        }
+        // Origin inside the box: report a hit at the origin facing the ray
+        let normal = if normal == Vec3::ZERO { -local_dir } else { normal };
+        Some((t_min, self.rotation * normal))
+    }
+}
+
+/// Every hitbox in the world at a recorded tick
+#[derive(Clone, Debug)]
+pub struct WorldFrame {
+    pub tick: u64,
+    pub time_ms: f64,
+    pub hitboxes: Vec<Hitbox>,
+}
+
+// ============================================================================
+// LAG COMPENSATOR
+// ============================================================================
+
+pub struct LagCompensator {
+    config: LagCompensationConfig,
+    frames: VecDeque<WorldFrame>,
+    /// Actor controlled by each peer, ignored by that peer's own traces
+    peer_actors: HashMap<u64, u64>,
+}
+
+impl LagCompensator {
+    pub fn new(config: LagCompensationConfig) -> Self {
+        Self {
+            config,
+            frames: VecDeque::with_capacity(config.max_ticks),
+            peer_actors: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &LagCompensationConfig {
+        &self.config
+    }
+
+    pub fn frames(&self) -> impl Iterator<Item = &WorldFrame> {
+        self.frames.iter()
+    }
+
+    pub fn set_peer_actor(&mut self, peer_id: u64, actor_id: u64) {
+        self.peer_actors.insert(peer_id, actor_id);
+    }
+
+    pub fn remove_peer(&mut self, peer_id: u64) {
+        self.peer_actors.remove(&peer_id);
+    }
+
+    // ============================================
+    // Recording
+    // ============================================
+
+    /// Snapshot all actor and character hitboxes; call once per server tick
+    /// after movement has been applied.
+    pub fn record(&mut self, world: &UWorld, tick: u64) {
+        let mut hitboxes = Vec::with_capacity(world.actors.len() + world.characters.len());
+        for actor in world.actors.values() {
+            if actor.is_pending_kill || actor.root_component_id.is_none() {
+                continue;
+            }
+            hitboxes.push(actor_hitbox(actor, DEFAULT_ACTOR_HALF_EXTENTS));
+        }
+        for character in world.characters.values() {
+            hitboxes.push(actor_hitbox(&character.base_actor, CHARACTER_HALF_EXTENTS));
+        }
+        self.push_frame(WorldFrame {
+            tick,
+            time_ms: world.real_time_seconds as f64 * 1000.0,
+            hitboxes,
+        });
+    }
+
+    /// Append a frame directly, e.g. from a simulation outside `UWorld`
+    pub fn push_frame(&mut self, frame: WorldFrame) {
+        if let Some(last) = self.frames.back() {
+            if frame.time_ms < last.time_ms {
+                log::warn!("Lag compensation: frame {} goes back in time, history cleared", frame.tick);
+                self.frames.clear();
+            }
+        }
+        self.frames.push_back(frame);
+        while self.frames.len() > self.config.max_ticks {
+            self.frames.pop_front();
+        }
+    }
+
+    // ============================================
+    // Rewinding
+    // ============================================
+
+    /// How far back a shot from this peer is evaluated
+    pub fn rewind_ms(&self, network: &NetworkSystem, peer_id: u64) -> f32 {
+        let rtt = network.get_rtt(peer_id).unwrap_or(0.0);
+        (rtt + self.config.interpolation_delay_ms).clamp(0.0, self.config.max_rewind_ms)
+    }
+
+    /// Hitboxes as they were at `time_ms`, interpolated between recorded
+    /// ticks and clamped to the oldest/newest frame.
+    pub fn hitboxes_at(&self, time_ms: f64) -> Vec<Hitbox> {
+        let (Some(oldest), Some(newest)) = (self.frames.front(), self.frames.back()) else {
+            return Vec::new();
+        };
+        if time_ms <= oldest.time_ms {
+            return oldest.hitboxes.clone();
+        }
+        if time_ms >= newest.time_ms {
+            return newest.hitboxes.clone();
+        }
+
+        let next = self.frames.partition_point(|f| f.time_ms <= time_ms);
+        let (a, b) = (&self.frames[next - 1], &self.frames[next]);
+        let span = b.time_ms - a.time_ms;
+        let t = if span > 0.0 { ((time_ms - a.time_ms) / span) as f32 } else { 0.0 };
+
+        // Actors that only exist in the older frame are still hittable
+        // (they were visible to the shooter); spawned-later actors are not.
+        let later: HashMap<u64, &Hitbox> = b.hitboxes.iter().map(|h| (h.actor_id, h)).collect();
+        a.hitboxes
+            .iter()
+            .map(|h| match later.get(&h.actor_id) {
+                Some(other) => h.lerp(other, t),
+                None => *h,
+            })
+            .collect()
+    }
+
+    /// Trace against the world as `peer_id` saw it when it fired
+    pub fn rewind_trace(&self, network: &NetworkSystem, peer_id: u64, start: Vec3, end: Vec3) -> FHitResult {
+        let Some(newest) = self.frames.back() else {
+            return miss(start, end);
+        };
+        let target = newest.time_ms - self.rewind_ms(network, peer_id) as f64;
+        self.trace_at(target, start, end, self.peer_actors.get(&peer_id).copied())
+    }
+
+    /// Trace against hitboxes at an explicit history time
+    pub fn trace_at(&self, time_ms: f64, start: Vec3, end: Vec3, ignore_actor: Option<u64>) -> FHitResult {
+        let max_dist = start.distance(end);
+        let dir = (end - start).normalize_or_zero();
+        if dir == Vec3::ZERO {
+            return miss(start, end);
+        }
+
+        let mut best: Option<(f32, Vec3, u64)> = None;
+        for hitbox in self.hitboxes_at(time_ms) {
+            if Some(hitbox.actor_id) == ignore_actor {
+                continue;
+            }
+            if let Some((dist, normal)) = hitbox.ray_intersect(start, dir, max_dist) {
+                if best.is_none_or(|(d, _, _)| dist < d) {
+                    best = Some((dist, normal, hitbox.actor_id));
+                }
+            }
+        }
+
+        match best {
+            Some((distance, normal, actor_id)) => FHitResult {
+                b_blocking_hit: true,
+                distance,
+                location: start + dir * distance,
+                normal,
+                actor_id: Some(actor_id),
+                component_name: "LagCompensatedHitbox".to_string(),
+            },
+            None => miss(start, end),
+        }
+    }
+}
+
+impl Default for LagCompensator {
+    fn default() -> Self {
+        Self::new(LagCompensationConfig::default())
+    }
+}
+
+fn actor_hitbox(actor: &AActor, base_half_extents: Vec3) -> Hitbox {
+    let scale = actor
+        .root_component_id
+        .and_then(|id| actor.components.get(&id))
+        .map(|c| c.world_transform.scale)
+        .unwrap_or(Vec3::ONE);
+    Hitbox {
+        actor_id: actor.id,
+        center: actor.get_actor_location(),
+        rotation: actor.get_actor_rotation(),
+        half_extents: base_half_extents * scale.abs(),
+    }
+}
+
+fn miss(start: Vec3, end: Vec3) -> FHitResult {
+    FHitResult {
+        b_blocking_hit: false,
+        distance: start.distance(end),
+        location: end,
+        normal: Vec3::ZERO,
+        actor_id: None,
+        component_name: String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::test_support::connected_pair;
+    use crate::network::{ChannelType, LinkConditionerConfig, NetworkMessage};
+    use std::time::{Duration, Instant};
+
+    fn frame(tick: u64, time_ms: f64, x: f32) -> WorldFrame {
+        WorldFrame {
+            tick,
+            time_ms,
+            hitboxes: vec![Hitbox {
+                actor_id: 7,
+                center: Vec3::new(x, 0.0, 0.0),
+                rotation: Quat::IDENTITY,
+                half_extents: Vec3::splat(10.0),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_rewind_hits_where_target_was() {
+        let mut comp = LagCompensator::new(LagCompensationConfig { max_ticks: 8, ..Default::default() });
+        // Target strafes +x at 1 unit/ms, one tick every 50ms
+        for tick in 0..10u64 {
+            comp.push_frame(frame(tick, tick as f64 * 50.0, tick as f32 * 50.0));
+        }
+        assert_eq!(comp.frames().count(), 8);
+
+        // Shot fired at where the target was at t=325ms (x=325), from above
+        let start = Vec3::new(325.0, 0.0, 500.0);
+        let end = Vec3::new(325.0, 0.0, -500.0);
+        let hit = comp.trace_at(325.0, start, end, None);
+        assert!(hit.b_blocking_hit);
+        assert_eq!(hit.actor_id, Some(7));
+        assert!((hit.distance - 490.0).abs() < 1e-3);
+        assert!((hit.normal - Vec3::Z).length() < 1e-5);
+
+        // The same shot misses against the present, and the shooter ignores itself
+        assert!(!comp.trace_at(450.0, start, end, None).b_blocking_hit);
+        assert!(!comp.trace_at(325.0, start, end, Some(7)).b_blocking_hit);
+    }
+
+    #[test]
+    fn test_rotated_hitbox_and_world_record() {
+        let hitbox = Hitbox {
+            actor_id: 1,
+            center: Vec3::ZERO,
+            rotation: Quat::from_rotation_z(std::f32::consts::FRAC_PI_4),
+            half_extents: Vec3::new(10.0, 1.0, 1.0),
+        };
+        // Along the box's long axis the ray hits at 10 units from the center
+        let dir = Vec3::new(1.0, 1.0, 0.0).normalize();
+        let (dist, _) = hitbox.ray_intersect(-dir * 50.0, dir, 100.0).unwrap();
+        assert!((dist - 40.0).abs() < 1e-3);
+        // A world-x ray crossing the diagonal beyond the box misses it
+        assert!(hitbox.ray_intersect(Vec3::new(-50.0, 20.0, 0.0), Vec3::X, 100.0).is_none());
+
+        let mut world = UWorld::new("Test");
+        let id = world.spawn_character("Target");
+        world.characters.get_mut(&id).unwrap().base_actor.set_actor_location(Vec3::new(0.0, 200.0, 0.0));
+        let mut comp = LagCompensator::default();
+        comp.record(&world, 0);
+        let hit = comp.trace_at(0.0, Vec3::ZERO, Vec3::new(0.0, 400.0, 0.0), None);
+        assert_eq!(hit.actor_id, Some(id));
+        assert!((hit.distance - (200.0 - CHARACTER_HALF_EXTENTS.y)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_rewind_uses_measured_rtt() {
+        let (mut server, mut client, server_peer) = connected_pair();
+        let client_peer = server.connected_peers()[0];
+        let link = Some(LinkConditionerConfig { latency_ms: 30, ..Default::default() });
+        server.set_link_conditioner(link);
+        client.set_link_conditioner(link);
+
+        // Keep traffic flowing both ways until the server's RTT estimate reflects the link
+        let ping = NetworkMessage { id: 0, channel: ChannelType::UnreliableUnordered, data: vec![0], timestamp: 0, sequence: 0 };
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while server.get_rtt(client_peer).unwrap() < 40.0 && Instant::now() < deadline {
+            server.send_message(client_peer, ping.clone()).unwrap();
+            client.send_message(server_peer, ping.clone()).unwrap();
+            server.pump().unwrap();
+            client.pump().unwrap();
+            server.drain_received();
+            client.drain_received();
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        let rtt = server.get_rtt(client_peer).unwrap();
+        assert!(rtt >= 40.0, "RTT estimate stuck at {}ms", rtt);
+
+        // Target strafes +x at 1 unit/ms, one tick every 10ms
+        let mut comp = LagCompensator::default();
+        for tick in 0..64u64 {
+            comp.push_frame(frame(tick, tick as f64 * 10.0, tick as f32 * 10.0));
+        }
+        let newest = 630.0;
+        let rewind = comp.rewind_ms(&server, client_peer);
+        assert_eq!(rewind, rtt + comp.config().interpolation_delay_ms);
+
+        // The client fired at where it saw the target, one RTT plus the interpolation delay ago
+        let x = (newest - rewind as f64) as f32;
+        let (start, end) = (Vec3::new(x, 0.0, 500.0), Vec3::new(x, 0.0, -500.0));
+        let hit = comp.rewind_trace(&server, client_peer, start, end);
+        assert!(hit.b_blocking_hit);
+        assert_eq!(hit.actor_id, Some(7));
+
+        // Neither the present nor a rewind that ignores latency contains that pose
+        assert!(!comp.trace_at(newest, start, end, None).b_blocking_hit);
+        let without_rtt = newest - comp.config().interpolation_delay_ms as f64;
+        assert!(!comp.trace_at(without_rtt, start, end, None).b_blocking_hit);
+    }
+}
@@ -21,8 +21,21 @@ use winit::{
 use wasm_bindgen::prelude::*;
 
 pub mod predictive_renderer;
+pub mod shaders;
+pub mod renderer;
+pub mod render_graph;
+pub mod shader_preprocessor;
+pub mod headless;
 pub mod offload;
 pub mod network;
+pub mod rollback;
+pub mod interest_management;
+pub mod lag_compensation;
+pub mod snapshot_interpolation;
+pub mod lobby;
+pub mod net_profiler;
+pub mod replication;
+pub mod rpc;
 pub mod resource_manager;
 pub mod tdsp_engine;
 pub mod causal_save;
@@ -31,14 +44,15 @@ pub mod unreal_framework;
 pub mod editor;
 
 use predictive_renderer::*;
+use renderer::{DrawItem, MaterialUniforms, RenderConfig, RenderTarget, Renderer, SceneVertex};
 use offload::{OffloadManager, OffloadConfig};
 use network::{NetworkSystem, NetworkRole};
-use resource_manager::{ResourceManager, ResourceConfig};
+use resource_manager::{Handle, ResourceManager, ResourceConfig};
 use tdsp_engine::*;
 use causal_save::{CausalSaveFile, SaveManager, DeterministicRng, WorldSeed, PlayerSeed};
 use spectral_pss::{PSSManager, PSSConfig, SpectralAssetPool, PotentialityGrid, ClusterProcessor};
 
-use glam::{Vec3, vec3};
+use glam::{EulerRot, Mat4, Quat, Vec2, Vec3, vec3};
 
 // ============================================================================
 // ENGINE CONFIGURATION
@@ -122,6 +136,7 @@ pub struct EngineState {
     
     // Runtime
     frame_count: u64,
+    entities_culled: usize,
     config: EngineConfig,
 }
 
@@ -139,14 +154,16 @@ impl EngineState {
             camera_pitch: 0.0,
             camera_yaw: 0.0,
             frame_count: 0,
+            entities_culled: 0,
             config,
         }
     }
     
-    pub fn init_predictive_renderer(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+    pub fn init_predictive_renderer(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32) {
         self.predictive_renderer = Some(PredictiveRenderer::new(
             device,
             self.config.predictive_rendering.clone(),
+            format,
             width,
             height,
         ));
@@ -170,6 +187,11 @@ impl EngineState {
         }
     }
     
+    /// Entities the GPU culled, as of the latest culling readback
+    pub fn entities_culled(&self) -> usize {
+        self.entities_culled
+    }
+    
     pub fn update_camera(&mut self, position: Vec3, pitch: f32, yaw: f32) {
         self.camera_position = position;
         self.camera_pitch = pitch;
@@ -197,7 +219,14 @@ impl EngineState {
         
         // Update offload manager
         self.offload_manager.tick();
-        
+
+        // Pump network transport
+        if self.config.network.enabled {
+            if let Err(e) = self.network_system.pump() {
+                log::warn!("Network pump failed: {}", e);
+            }
+        }
+
         // Update resource manager
         if let Some(rm) = &self.resource_manager {
             rm.tick();
@@ -258,7 +287,8 @@ pub async fn run_with_config(config: EngineConfig) {
         .request_device(
             &wgpu::DeviceDescriptor {
                 label: Some("Slop_Device"),
-                required_features: wgpu::Features::empty(),
+                // Times scene passes for predictive rendering's stats
+                required_features: adapter.features() & wgpu::Features::TIMESTAMP_QUERY,
                 required_limits: if cfg!(target_arch = "wasm32") {
                     wgpu::Limits::downlevel_webgl2_defaults()
                 } else {
@@ -282,11 +312,8 @@ pub async fn run_with_config(config: EngineConfig) {
         adapter,
         engine_state: Some(engine_state),
         window: None,
-        surface: None,
-        config: None,
-        render_pipeline: None,
-        depth_texture_view: None,
-        hdr_texture_view: None,
+        renderer: None,
+        scene_assets: None,
         predictive_enabled: true,
         tdsp_enabled: true,
     };
@@ -312,6 +339,24 @@ pub fn run_native_with_config(config: EngineConfig) {
     pollster::block_on(run_with_config(config));
 }
 
+/// Renderer settings from `settings.json` in the working directory, or the
+/// defaults when it's missing or invalid
+#[cfg(not(target_arch = "wasm32"))]
+fn load_render_config() -> RenderConfig {
+    let Ok(json) = std::fs::read_to_string("settings.json") else {
+        return RenderConfig::default();
+    };
+    RenderConfig::from_settings_json(&json).unwrap_or_else(|e| {
+        log::warn!("Ignoring invalid settings.json: {}", e);
+        RenderConfig::default()
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+fn load_render_config() -> RenderConfig {
+    RenderConfig::default()
+}
+
 // ============================================================================
 // APP STATE
 // ============================================================================
@@ -324,15 +369,60 @@ struct EngineApp {
     engine_state: Option<EngineState>,
     
     window: Option<Arc<Window>>,
-    surface: Option<wgpu::Surface<'static>>,
-    config: Option<wgpu::SurfaceConfiguration>,
-    render_pipeline: Option<wgpu::RenderPipeline>,
-    depth_texture_view: Option<wgpu::TextureView>,
-    hdr_texture_view: Option<wgpu::TextureView>,
+    renderer: Option<Renderer>,
+    scene_assets: Option<SceneAssets>,
     predictive_enabled: bool,
     tdsp_enabled: bool,
 }
 
+/// Meshes and materials the app draws its entities with
+struct SceneAssets {
+    cube: Handle,
+    ground: Handle,
+    entity_material: Handle,
+    ground_material: Handle,
+}
+
+impl SceneAssets {
+    fn load(resources: &ResourceManager) -> anyhow::Result<Self> {
+        let stride = std::mem::size_of::<SceneVertex>() as u64;
+        let load_mesh = |(vertices, indices): (Vec<SceneVertex>, Vec<u16>)| {
+            resources.load_mesh(bytemuck::cast_slice(&vertices), bytemuck::cast_slice(&indices), stride, wgpu::IndexFormat::Uint16)
+        };
+        let create_material = |params: MaterialUniforms| resources.create_material(bytemuck::bytes_of(&params), None, None, None, None);
+        Ok(Self {
+            cube: load_mesh(renderer::cube_mesh())?,
+            ground: load_mesh(renderer::plane_mesh(20.0))?,
+            entity_material: create_material(MaterialUniforms::new([0.15, 0.35, 0.75, 1.0], 0.0, 0.5))?,
+            ground_material: create_material(MaterialUniforms::new([0.5, 0.5, 0.52, 1.0], 0.0, 0.9))?,
+        })
+    }
+
+    /// Ground plus one cube per entity, moving by its predicted motion vector
+    fn draw_items(&self, entities: &[network::EntitySnapshot], motion: Option<&HashMap<u64, MotionVector>>) -> Vec<DrawItem> {
+        let ground = DrawItem {
+            mesh: self.ground,
+            material: self.ground_material,
+            transform: Mat4::from_translation(vec3(0.0, -0.5, -8.0)),
+            motion: Vec2::ZERO,
+            bounds: None,
+        };
+        std::iter::once(ground)
+            .chain(entities.iter().map(|e| DrawItem {
+                mesh: self.cube,
+                material: self.entity_material,
+                transform: Mat4::from_scale_rotation_translation(
+                    e.scale,
+                    Quat::from_euler(EulerRot::XYZ, e.rotation.x, e.rotation.y, e.rotation.z),
+                    e.position,
+                ),
+                motion: motion.and_then(|m| m.get(&e.id)).map_or(Vec2::ZERO, |m| m.screen_delta),
+                bounds: Some((e.bounds_min, e.bounds_max)),
+            }))
+            .collect()
+    }
+}
+
 impl ApplicationHandler for EngineApp {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         if self.window.is_some() { return; }
@@ -367,8 +457,10 @@ impl ApplicationHandler for EngineApp {
             .find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
 
+        // Copying the frame lets the predictive renderer measure its
+        // validation tiles
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC),
             format: surface_format,
             width: size.width.max(1),
             height: size.height.max(1),
@@ -381,7 +473,14 @@ impl ApplicationHandler for EngineApp {
 
         if let Some(ref mut state) = self.engine_state {
             state.init_resource_manager(self.device.clone(), self.queue.clone());
-            state.init_predictive_renderer(&self.device, size.width, size.height);
+            state.init_predictive_renderer(&self.device, config.format, size.width, size.height);
+            
+            if let Some(rm) = &state.resource_manager {
+                match SceneAssets::load(rm) {
+                    Ok(assets) => self.scene_assets = Some(assets),
+                    Err(e) => log::error!("Failed to load scene assets: {}", e),
+                }
+            }
             
             for i in 0..20 {
                 state.add_entity(
@@ -393,15 +492,13 @@ impl ApplicationHandler for EngineApp {
             }
         }
 
-        let depth_view = self.create_depth_texture(&config);
-        let hdr_view = self.create_hdr_texture(&config);
-        let pipeline = self.build_pipeline(config.format);
-
-        self.surface = Some(surface);
-        self.config = Some(config);
-        self.depth_texture_view = Some(depth_view);
-        self.hdr_texture_view = Some(hdr_view);
-        self.render_pipeline = Some(pipeline);
+        let render_config = RenderConfig { width: config.width, height: config.height, ..load_render_config() };
+        self.renderer = Some(Renderer::new(
+            self.device.clone(),
+            self.queue.clone(),
+            render_config,
+            RenderTarget::Surface { surface, config },
+        ));
 
         log::info!("TDSP Engine initialized!");
         window.request_redraw();
@@ -418,29 +515,15 @@ impl ApplicationHandler for EngineApp {
             }
             WindowEvent::Resized(physical_size) => {
                 if physical_size.width > 0 && physical_size.height > 0 {
-                    let (new_w, new_h) = {
-                        if let (Some(surface), Some(config)) = (
-                            self.surface.as_mut(),
-                            self.config.as_mut()
-                        ) {
-                            config.width = physical_size.width;
-                            config.height = physical_size.height;
-                            surface.configure(&self.device, config);
-                            (config.width, config.height)
-                        } else {
-                            return;
-                        }
-                    };
+                    let Some(renderer) = self.renderer.as_mut() else { return };
+                    renderer.resize(physical_size.width, physical_size.height);
+                    let (new_w, new_h) = renderer.target().size();
 
                     if let Some(ref mut state) = self.engine_state {
                         if let Some(ref mut pr) = state.predictive_renderer {
                             pr.resize(new_w, new_h);
                         }
                     }
-
-                    let config = self.config.as_ref().unwrap();
-                    self.depth_texture_view = Some(self.create_depth_texture(config));
-                    self.hdr_texture_view = Some(self.create_hdr_texture(config));
                 }
             }
             WindowEvent::RedrawRequested => {
@@ -448,10 +531,9 @@ impl ApplicationHandler for EngineApp {
                 match self.render() {
                     Ok(_) => {}
                     Err(wgpu::SurfaceError::Lost) => {
-                        if let Some(surface) = self.surface.as_ref() {
-                            if let Some(config) = self.config.as_ref() {
-                                surface.configure(&self.device, config);
-                            }
+                        if let Some(renderer) = self.renderer.as_mut() {
+                            let (width, height) = renderer.target().size();
+                            renderer.resize(width, height);
                         }
                     }
                     Err(wgpu::SurfaceError::OutOfMemory) => event_loop.exit(),
@@ -479,8 +561,8 @@ impl EngineApp {
             state.tick();
             
             state.update_camera(
-                vec3((state.frame_count as f32 * 0.01).sin() * 3.0, 2.0, -5.0),
-                0.0,
+                vec3((state.frame_count as f32 * 0.01).sin() * 3.0, 2.0, -2.0),
+                -0.25,
                 state.frame_count as f32 * 0.001,
             );
             
@@ -495,185 +577,77 @@ impl EngineApp {
                      Latency Saved: {:.1}ms\n\
                      Optimistic Frames: {}\n\
                      Variance Compression: {:.0}% saved\n\
-                     Predictive: {:.0}% GPU saved\n\
+                     Predictive: {:.0}% pixels reused, {:.2}ms GPU saved/frame\n\
+                     Entities Culled: {}/{}\n\
                      ======================",
                     tdsp_stats.intent_confidence * 100.0,
                     tdsp_stats.total_latency_saved_ns as f64 / 1_000_000.0,
                     tdsp_stats.optimistic_frames,
                     (1.0 - tdsp_stats.variance_stats.compression_ratio) * 100.0,
-                    pr_stats.map(|s| s.gpu_time_saved_ms as f64 * 100.0 / state.frame_count.max(1) as f64).unwrap_or(0.0)
+                    pr_stats.as_ref().map(|s| s.pixels_skipped as f64 * 100.0 / s.pixels_total.max(1) as f64).unwrap_or(0.0),
+                    pr_stats.as_ref().map(|s| s.gpu_time_saved_ms as f64 / s.timed_frames.max(1) as f64).unwrap_or(0.0),
+                    state.entities_culled,
+                    state.entities.len()
                 );
             }
         }
     }
 
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let surface = self.surface.as_ref().ok_or(wgpu::SurfaceError::OutOfMemory)?;
-        let pipeline = self.render_pipeline.as_ref().ok_or(wgpu::SurfaceError::OutOfMemory)?;
-        let depth_view = self.depth_texture_view.as_ref().ok_or(wgpu::SurfaceError::OutOfMemory)?;
-        let config = self.config.as_ref().ok_or(wgpu::SurfaceError::OutOfMemory)?;
-
-        let output = surface.get_current_texture()?;
-        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
-
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("TDSP Encoder"),
-        });
-
-        if self.tdsp_enabled {
-            let scene = self.engine_state.as_ref().map(|s| s.get_scene_snapshot(config.width, config.height));
-            if let Some(ref mut state) = self.engine_state {
-                if let Some(ref mut pr) = state.predictive_renderer {
-                    if let Some(scene) = scene {
-                        let _ = pr.render(&self.device, &self.queue, &scene, &mut encoder, &view);
-                    }
-                }
-            }
-        }
-
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Main Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color { r: 0.01, g: 0.01, b: 0.02, a: 1.0 }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: depth_view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
-
-            render_pass.set_pipeline(pipeline);
-            render_pass.draw(0..3, 0..1);
-        }
-
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
-
+        let renderer = self.renderer.as_mut().ok_or(wgpu::SurfaceError::OutOfMemory)?;
+        let state = self.engine_state.as_mut().ok_or(wgpu::SurfaceError::OutOfMemory)?;
+        state.render_frame(renderer, self.scene_assets.as_ref(), self.tdsp_enabled)?;
         Ok(())
     }
+}
 
-    fn build_pipeline(&self, format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
-        let shader = self.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("TDSP Shader"),
-            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(MAIN_WGSL)),
-        });
-
-        self.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Main Pipeline"),
-            layout: None,
-            cache: None,
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                buffers: &[],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-        })
-    }
-
-    fn create_depth_texture(&self, config: &wgpu::SurfaceConfiguration) -> wgpu::TextureView {
-        let size = wgpu::Extent3d {
-            width: config.width,
-            height: config.height,
-            depth_or_array_layers: 1,
+impl EngineState {
+    /// Draw the entities through `renderer`, reprojecting with the predictive
+    /// renderer when `predictive` is set, and record the frame's culling stats
+    fn render_frame(
+        &mut self,
+        renderer: &mut Renderer,
+        assets: Option<&SceneAssets>,
+        predictive: bool,
+    ) -> Result<renderer::RenderStats, wgpu::SurfaceError> {
+        let (width, height) = renderer.target().size();
+
+        let scene = self.get_scene_snapshot(width, height);
+        let mut predictive = self.predictive_renderer.as_mut().filter(|_| predictive);
+
+        // Entities move every tick, so the draw list is rebuilt per frame,
+        // with this frame's predicted motion vectors for TAA
+        if let (Some(assets), Some(rm)) = (assets, &self.resource_manager) {
+            let motion = predictive.as_mut().map(|pr| &pr.predict(&scene).motion_vectors);
+            renderer.submit_draws(rm, &assets.draw_items(&self.entities, motion));
+        }
+        let forward = Quat::from_euler(EulerRot::YXZ, -self.camera_yaw, self.camera_pitch, 0.0) * Vec3::NEG_Z;
+        renderer.set_camera(self.camera_position, self.camera_position + forward);
+
+        // A depth and motion prepass feeds the predictive renderer's
+        // reprojection; the renderer then redraws only its hot tiles (and
+        // measures its validation tiles) unless a full refresh is due
+        let stats = match predictive.as_deref_mut() {
+            Some(pr) => renderer.render_reprojected(pr, &scene)?,
+            None => renderer.render()?,
         };
-        self.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Depth"),
-            size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        }).create_view(&wgpu::TextureViewDescriptor::default())
-    }
+        if let (Some(pr), Some(time)) = (predictive.as_deref_mut(), stats.scene_gpu_time) {
+            pr.record_gpu_time(time.gpu_ms, !time.tiles_only);
+        }
+        // Instances are culled on the GPU; the ground is never culled, so
+        // every culled instance is an entity
+        self.entities_culled = stats.instances_culled as usize;
+
+        // The full frame (TAA-resolved when TAA is on) doubles as
+        // reprojection history, at render size and without a copy
+        if let (Some(pr), Some((color, depth, view_proj))) = (predictive, renderer.history_frame()) {
+            pr.push_frame(color, depth, view_proj);
+        }
 
-    fn create_hdr_texture(&self, config: &wgpu::SurfaceConfiguration) -> wgpu::TextureView {
-        let size = wgpu::Extent3d {
-            width: config.width,
-            height: config.height,
-            depth_or_array_layers: 1,
-        };
-        self.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("HDR"),
-            size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba16Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        }).create_view(&wgpu::TextureViewDescriptor::default())
+        Ok(stats)
     }
 }
 
-// ============================================================================
-// SHADERS
-// ============================================================================
-
-const MAIN_WGSL: &str = r#"
-@vertex
-fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> @builtin(position) vec4<f32> {
-    let positions = array<vec2<f32>, 3>(
-        vec2<f32>(0.0, 0.5),
-        vec2<f32>(-0.5, -0.5),
-        vec2<f32>(0.5, -0.5)
-    );
-    let idx = in_vertex_index % 3u;
-    return vec4<f32>(positions[idx], 0.5, 1.0);
-}
-
-@fragment
-fn fs_main() -> @location(0) vec4<f32> {
-    return vec4<f32>(0.15, 0.35, 0.75, 1.0);
-}
-"#;
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -682,7 +656,7 @@ mod tests {
     fn test_engine_config_default() {
         let config = EngineConfig::default();
         assert_eq!(config.network.tick_rate, 60);
-        assert!(config.predictive_rendering.enabled);
+        assert!(config.predictive_rendering.statistics_enabled);
         assert!(config.tdsp.enabled);
     }
 
@@ -692,4 +666,58 @@ mod tests {
         let stats = engine.get_stats();
         assert_eq!(stats.registered_entities, 0);
     }
+
+    /// Engine state with its resources, the app's scene assets, a 160x120
+    /// headless renderer and its device; None without a GPU adapter
+    fn headless_engine() -> Option<(EngineState, SceneAssets, Renderer, Arc<wgpu::Device>)> {
+        let (device, queue) = headless::test_device()?;
+        let (device, queue) = (Arc::new(device), Arc::new(queue));
+        let mut state = EngineState::new(EngineConfig::default());
+        state.init_resource_manager(device.clone(), queue.clone());
+        state.init_predictive_renderer(&device, headless::HEADLESS_FORMAT, 160, 120);
+        let assets = SceneAssets::load(state.resource_manager.as_ref().unwrap()).unwrap();
+        let config = RenderConfig { width: 160, height: 120, ..Default::default() };
+        Some((state, assets, Renderer::new_headless(device.clone(), queue, config), device))
+    }
+
+    #[test]
+    fn test_render_frame_records_culled_entities() {
+        let Some((mut state, assets, mut renderer, device)) = headless_engine() else { return };
+
+        // The camera sits at the origin looking down -Z: two cubes in view,
+        // three behind it
+        for (id, x) in [(1, -1.5), (2, 1.5)] {
+            state.add_entity(id, vec3(x, 0.0, -5.0), Vec3::ZERO, Vec3::ONE);
+        }
+        for (id, x) in [(3, -2.0), (4, 0.0), (5, 2.0)] {
+            state.add_entity(id, vec3(x, 0.0, 5.0), Vec3::ZERO, Vec3::ONE);
+        }
+
+        // Culled counts are read back a frame or more late
+        for _ in 0..3 {
+            state.render_frame(&mut renderer, Some(&assets), false).unwrap();
+            device.poll(wgpu::Maintain::Wait);
+        }
+        assert_eq!(state.entities_culled(), 3);
+    }
+
+    #[test]
+    fn test_render_frame_measures_validation_tiles() {
+        let Some((mut state, assets, mut renderer, device)) = headless_engine() else { return };
+        for (id, x) in [(1, -1.5), (2, 1.5)] {
+            state.add_entity(id, vec3(x, 0.0, -5.0), Vec3::ZERO, Vec3::ONE);
+        }
+
+        // Validation tiles are only drawn on tile frames; their errors are
+        // read back a frame or two later
+        let mut tile_frames = 0;
+        for _ in 0..20 {
+            state.render_frame(&mut renderer, Some(&assets), true).unwrap();
+            device.poll(wgpu::Maintain::Wait);
+            tile_frames += !state.predictive_renderer.as_ref().unwrap().get_stats().last_frame.full_refresh as u32;
+        }
+        let stats = state.predictive_renderer.as_ref().unwrap().get_stats();
+        assert!(tile_frames > 0, "{:?}", stats);
+        assert!(stats.measured_error > 0.0, "{:?}", stats);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,553 @@
+// src/lobby.rs
+//! Lobby and relay server built on `NetworkSystem`
+//!
+//! - Clients create, list, join and leave named lobbies (sessions)
+//! - Peers that cannot reach each other directly relay packets through the
+//!   server, restricted to members of the same lobby
+//! - The server owns a headless `UWorld` ticked alongside the transport
+//! - `LobbyClient` wraps the client side for tools and tests; the
+//!   `slop_server` binary wraps `LobbyServer`
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::{
+    ChannelType, ConnectionEvent, NetworkError, NetworkMessage, NetworkRole, NetworkSystem,
+};
+use crate::unreal_framework::UWorld;
+
+/// Lobby names are truncated to this many characters
+const MAX_LOBBY_NAME_LEN: usize = 64;
+
+// ============================================================================
+// PROTOCOL
+// ============================================================================
+
+/// Public description of a hosted lobby
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub lobby_id: u64,
+    pub name: String,
+    pub host_peer: u64,
+    pub players: u32,
+    pub max_players: u32,
+}
+
+/// Messages exchanged between `LobbyClient` and `LobbyServer`. Peer ids are
+/// always the server's ids for each client.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LobbyMessage {
+    // Client -> server
+    CreateLobby { name: String, max_players: u32 },
+    ListSessions,
+    JoinLobby { lobby_id: u64 },
+    LeaveLobby,
+    Relay { to_peer: u64, payload: Vec<u8> },
+
+    // Server -> client
+    Welcome { peer_id: u64 },
+    SessionList { sessions: Vec<SessionInfo> },
+    Joined { session: SessionInfo, members: Vec<u64> },
+    MemberJoined { lobby_id: u64, peer_id: u64 },
+    MemberLeft { lobby_id: u64, peer_id: u64 },
+    Relayed { from_peer: u64, payload: Vec<u8> },
+    Error { reason: String },
+}
+
+impl LobbyMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap_or_default()
+    }
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        bincode::deserialize(data).ok()
+    }
+}
+
+fn lobby_message(channel: ChannelType, message: &LobbyMessage) -> NetworkMessage {
+    NetworkMessage {
+        id: 0,
+        channel,
+        data: message.encode(),
+        timestamp: 0,
+        sequence: 0,
+    }
+}
+
+// ============================================================================
+// SERVER
+// ============================================================================
+
+#[derive(Clone, Debug)]
+pub struct LobbyServerConfig {
+    pub bind_address: String,
+    pub tick_rate: u32,
+    pub max_peers: usize,
+    pub max_lobbies: usize,
+    pub world_name: String,
+}
+
+impl Default for LobbyServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: "0.0.0.0:7777".to_string(),
+            tick_rate: 30,
+            max_peers: 64,
+            max_lobbies: 256,
+            world_name: "ServerWorld".to_string(),
+        }
+    }
+}
+
+struct Lobby {
+    name: String,
+    host_peer: u64,
+    max_players: u32,
+    members: Vec<u64>,
+}
+
+impl Lobby {
+    fn info(&self, lobby_id: u64) -> SessionInfo {
+        SessionInfo {
+            lobby_id,
+            name: self.name.clone(),
+            host_peer: self.host_peer,
+            players: self.members.len() as u32,
+            max_players: self.max_players,
+        }
+    }
+}
+
+/// Counters reported by the server loop
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LobbyServerStats {
+    pub ticks: u64,
+    pub relayed_messages: u64,
+    pub relayed_bytes: u64,
+    pub rejected_messages: u64,
+}
+
+pub struct LobbyServer {
+    config: LobbyServerConfig,
+    network: NetworkSystem,
+    world: UWorld,
+    lobbies: BTreeMap<u64, Lobby>,
+    peer_lobby: HashMap<u64, u64>,
+    next_lobby_id: u64,
+    stats: LobbyServerStats,
+}
+
+impl LobbyServer {
+    pub fn bind(config: LobbyServerConfig) -> Result<Self, NetworkError> {
+        let mut network = NetworkSystem::new(NetworkRole::Server);
+        network.max_peers = config.max_peers;
+        network.listen(&config.bind_address)?;
+        let world = UWorld::new(config.world_name.clone());
+        Ok(Self {
+            config,
+            network,
+            world,
+            lobbies: BTreeMap::new(),
+            peer_lobby: HashMap::new(),
+            next_lobby_id: 1,
+            stats: LobbyServerStats::default(),
+        })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.network.local_addr()
+    }
+
+    pub fn network(&self) -> &NetworkSystem {
+        &self.network
+    }
+
+    pub fn world(&self) -> &UWorld {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut UWorld {
+        &mut self.world
+    }
+
+    pub fn stats(&self) -> LobbyServerStats {
+        self.stats
+    }
+
+    pub fn sessions(&self) -> Vec<SessionInfo> {
+        self.lobbies.iter().map(|(id, lobby)| lobby.info(*id)).collect()
+    }
+
+    // ============================================
+    // Main loop
+    // ============================================
+
+    /// One server frame: receive, handle lobby traffic, tick the world, send
+    pub fn tick(&mut self, delta_seconds: f32) -> Result<(), NetworkError> {
+        self.network.pump()?;
+
+        for event in self.network.poll_events() {
+            match event {
+                ConnectionEvent::Connected { peer_id } => {
+                    log::info!("Lobby: peer {} connected", peer_id);
+                    self.send(peer_id, &LobbyMessage::Welcome { peer_id });
+                }
+                ConnectionEvent::Disconnected { peer_id, reason } => {
+                    log::info!("Lobby: peer {} disconnected ({:?})", peer_id, reason);
+                    self.leave_lobby(peer_id);
+                }
+            }
+        }
+
+        for (peer_id, message) in self.network.drain_received() {
+            match LobbyMessage::decode(&message.data) {
+                Some(request) => self.handle(peer_id, message.channel, request),
+                None => {
+                    self.stats.rejected_messages += 1;
+                    log::debug!("Lobby: undecodable message from peer {}", peer_id);
+                }
+            }
+        }
+
+        self.world.tick(delta_seconds);
+        self.stats.ticks += 1;
+
+        self.network.pump()?;
+        Ok(())
+    }
+
+    /// Tick at the configured rate until `running` is cleared, then
+    /// `shutdown`. Misbehaving peers are dropped by the transport; only
+    /// socket failures end the loop early.
+    pub fn run(&mut self, running: &AtomicBool) -> Result<(), NetworkError> {
+        let frame = Duration::from_secs_f64(1.0 / self.config.tick_rate.max(1) as f64);
+        let mut last = Instant::now();
+        while running.load(Ordering::Relaxed) {
+            let start = Instant::now();
+            self.tick(start.duration_since(last).as_secs_f32())?;
+            last = start;
+            if let Some(remaining) = frame.checked_sub(start.elapsed()) {
+                std::thread::sleep(remaining);
+            }
+        }
+        self.shutdown()
+    }
+
+    /// Disconnect every peer, so clients see `RemoteClosed` rather than
+    /// waiting out the timeout
+    pub fn shutdown(&mut self) -> Result<(), NetworkError> {
+        for peer_id in self.network.connected_peers() {
+            self.leave_lobby(peer_id);
+            self.network.disconnect(peer_id);
+        }
+        self.network.pump()?;
+        Ok(())
+    }
+
+    // ============================================
+    // Request handling
+    // ============================================
+
+    fn handle(&mut self, peer_id: u64, channel: ChannelType, request: LobbyMessage) {
+        match request {
+            LobbyMessage::CreateLobby { name, max_players } => {
+                if self.lobbies.len() >= self.config.max_lobbies {
+                    self.reject(peer_id, "lobby limit reached");
+                    return;
+                }
+                self.leave_lobby(peer_id);
+                let lobby_id = self.next_lobby_id;
+                self.next_lobby_id += 1;
+                self.lobbies.insert(lobby_id, Lobby {
+                    name: name.chars().take(MAX_LOBBY_NAME_LEN).collect(),
+                    host_peer: peer_id,
+                    max_players: max_players.max(1),
+                    members: vec![peer_id],
+                });
+                self.peer_lobby.insert(peer_id, lobby_id);
+                log::info!("Lobby: peer {} created lobby {}", peer_id, lobby_id);
+                self.send_joined(peer_id, lobby_id);
+            }
+            LobbyMessage::ListSessions => {
+                let sessions = self.sessions();
+                self.send(peer_id, &LobbyMessage::SessionList { sessions });
+            }
+            LobbyMessage::JoinLobby { lobby_id } => self.join_lobby(peer_id, lobby_id),
+            LobbyMessage::LeaveLobby => self.leave_lobby(peer_id),
+            LobbyMessage::Relay { to_peer, payload } => {
+                let same_lobby = self.peer_lobby.contains_key(&peer_id)
+                    && self.peer_lobby.get(&peer_id) == self.peer_lobby.get(&to_peer);
+                if !same_lobby || to_peer == peer_id {
+                    self.reject(peer_id, "relay target is not in your lobby");
+                    return;
+                }
+                let bytes = payload.len() as u64;
+                // Keep the sender's delivery guarantees end to end
+                let relayed = LobbyMessage::Relayed { from_peer: peer_id, payload };
+                match self.network.send_message(to_peer, lobby_message(channel, &relayed)) {
+                    Ok(()) => {
+                        self.stats.relayed_messages += 1;
+                        self.stats.relayed_bytes += bytes;
+                    }
+                    Err(e) => self.reject(peer_id, &format!("relay failed: {}", e)),
+                }
+            }
+            _ => {
+                self.stats.rejected_messages += 1;
+                log::debug!("Lobby: peer {} sent a server-only message", peer_id);
+            }
+        }
+    }
+
+    fn join_lobby(&mut self, peer_id: u64, lobby_id: u64) {
+        if self.peer_lobby.get(&peer_id) == Some(&lobby_id) {
+            self.send_joined(peer_id, lobby_id);
+            return;
+        }
+        match self.lobbies.get(&lobby_id) {
+            None => return self.reject(peer_id, "no such lobby"),
+            Some(lobby) if lobby.members.len() as u32 >= lobby.max_players => {
+                return self.reject(peer_id, "lobby is full");
+            }
+            Some(_) => {}
+        }
+
+        self.leave_lobby(peer_id);
+        let Some(lobby) = self.lobbies.get_mut(&lobby_id) else {
+            return;
+        };
+        let existing = lobby.members.clone();
+        lobby.members.push(peer_id);
+        self.peer_lobby.insert(peer_id, lobby_id);
+
+        for member in existing {
+            self.send(member, &LobbyMessage::MemberJoined { lobby_id, peer_id });
+        }
+        self.send_joined(peer_id, lobby_id);
+    }
+
+    /// Remove `peer_id` from its lobby. Empty lobbies close; otherwise the
+    /// longest-standing member becomes host.
+    fn leave_lobby(&mut self, peer_id: u64) {
+        let Some(lobby_id) = self.peer_lobby.remove(&peer_id) else {
+            return;
+        };
+        let Some(lobby) = self.lobbies.get_mut(&lobby_id) else {
+            return;
+        };
+        lobby.members.retain(|m| *m != peer_id);
+        if lobby.members.is_empty() {
+            self.lobbies.remove(&lobby_id);
+            log::info!("Lobby: lobby {} closed", lobby_id);
+            return;
+        }
+        if lobby.host_peer == peer_id {
+            lobby.host_peer = lobby.members[0];
+        }
+        for member in lobby.members.clone() {
+            self.send(member, &LobbyMessage::MemberLeft { lobby_id, peer_id });
+        }
+    }
+
+    fn send_joined(&self, peer_id: u64, lobby_id: u64) {
+        if let Some(lobby) = self.lobbies.get(&lobby_id) {
+            self.send(peer_id, &LobbyMessage::Joined {
+                session: lobby.info(lobby_id),
+                members: lobby.members.clone(),
+            });
+        }
+    }
+
+    fn reject(&mut self, peer_id: u64, reason: &str) {
+        self.stats.rejected_messages += 1;
+        self.send(peer_id, &LobbyMessage::Error { reason: reason.to_string() });
+    }
+
+    fn send(&self, peer_id: u64, message: &LobbyMessage) {
+        if let Err(e) = self.network.send_message(peer_id, lobby_message(ChannelType::ReliableOrdered, message)) {
+            log::warn!("Lobby: dropping message for peer {}: {}", peer_id, e);
+        }
+    }
+}
+
+// ============================================================================
+// CLIENT
+// ============================================================================
+
+/// Client side of the lobby protocol
+pub struct LobbyClient {
+    network: NetworkSystem,
+    server: u64,
+    peer_id: Option<u64>,
+    inbox: VecDeque<LobbyMessage>,
+}
+
+impl LobbyClient {
+    pub fn connect(address: &str) -> Result<Self, NetworkError> {
+        let mut network = NetworkSystem::new(NetworkRole::Client);
+        let server = network.connect(address)?;
+        Ok(Self {
+            network,
+            server,
+            peer_id: None,
+            inbox: VecDeque::new(),
+        })
+    }
+
+    /// Our id as the server knows us, once welcomed
+    pub fn peer_id(&self) -> Option<u64> {
+        self.peer_id
+    }
+
+    pub fn network(&self) -> &NetworkSystem {
+        &self.network
+    }
+
+    pub fn create_lobby(&self, name: &str, max_players: u32) -> Result<(), NetworkError> {
+        self.send(ChannelType::ReliableOrdered, &LobbyMessage::CreateLobby {
+            name: name.to_string(),
+            max_players,
+        })
+    }
+
+    pub fn list_sessions(&self) -> Result<(), NetworkError> {
+        self.send(ChannelType::ReliableOrdered, &LobbyMessage::ListSessions)
+    }
+
+    pub fn join_lobby(&self, lobby_id: u64) -> Result<(), NetworkError> {
+        self.send(ChannelType::ReliableOrdered, &LobbyMessage::JoinLobby { lobby_id })
+    }
+
+    pub fn leave_lobby(&self) -> Result<(), NetworkError> {
+        self.send(ChannelType::ReliableOrdered, &LobbyMessage::LeaveLobby)
+    }
+
+    /// Send `payload` to another member of our lobby via the server. Fails
+    /// with `MessageTooLarge` if it can't fit in one datagram.
+    pub fn relay(&self, to_peer: u64, channel: ChannelType, payload: Vec<u8>) -> Result<(), NetworkError> {
+        self.send(channel, &LobbyMessage::Relay { to_peer, payload })
+    }
+
+    /// Pump the transport and collect server messages
+    pub fn update(&mut self) -> Result<(), NetworkError> {
+        self.network.pump()?;
+        for (_, message) in self.network.drain_received() {
+            match LobbyMessage::decode(&message.data) {
+                Some(LobbyMessage::Welcome { peer_id }) => {
+                    self.peer_id = Some(peer_id);
+                    self.inbox.push_back(LobbyMessage::Welcome { peer_id });
+                }
+                Some(msg) => self.inbox.push_back(msg),
+                None => log::debug!("Lobby client: undecodable message"),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn poll_messages(&mut self) -> Vec<LobbyMessage> {
+        self.inbox.drain(..).collect()
+    }
+
+    fn send(&self, channel: ChannelType, message: &LobbyMessage) -> Result<(), NetworkError> {
+        self.network.send_message(self.server, lobby_message(channel, message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_server() -> LobbyServer {
+        LobbyServer::bind(LobbyServerConfig {
+            bind_address: "127.0.0.1:0".to_string(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    /// Tick everything until `done` sees a matching message on `clients[index]`
+    fn run_until(
+        server: &mut LobbyServer,
+        clients: &mut [LobbyClient],
+        index: usize,
+        mut done: impl FnMut(&LobbyMessage) -> bool,
+    ) -> Option<LobbyMessage> {
+        for _ in 0..300 {
+            server.tick(0.016).unwrap();
+            for client in clients.iter_mut() {
+                client.update().unwrap();
+            }
+            if let Some(found) = clients[index].inbox.iter().position(&mut done) {
+                return clients[index].inbox.remove(found);
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        None
+    }
+
+    #[test]
+    fn test_lobby_create_list_join_and_relay() {
+        let mut server = local_server();
+        let addr = server.local_addr().unwrap().to_string();
+        let mut clients: Vec<LobbyClient> = (0..3).map(|_| LobbyClient::connect(&addr).unwrap()).collect();
+        for i in 0..3 {
+            assert!(run_until(&mut server, &mut clients, i, |m| matches!(m, LobbyMessage::Welcome { .. })).is_some());
+        }
+        let ids: Vec<u64> = clients.iter().map(|c| c.peer_id().unwrap()).collect();
+
+        clients[0].create_lobby("Deathmatch", 2).unwrap();
+        let lobby_id = match run_until(&mut server, &mut clients, 0, |m| matches!(m, LobbyMessage::Joined { .. })) {
+            Some(LobbyMessage::Joined { session, members }) => {
+                assert_eq!(members, vec![ids[0]]);
+                session.lobby_id
+            }
+            other => panic!("expected Joined, got {:?}", other),
+        };
+
+        clients[1].list_sessions().unwrap();
+        match run_until(&mut server, &mut clients, 1, |m| matches!(m, LobbyMessage::SessionList { .. })) {
+            Some(LobbyMessage::SessionList { sessions }) => {
+                assert_eq!(sessions.len(), 1);
+                assert_eq!(sessions[0].name, "Deathmatch");
+                assert_eq!(sessions[0].host_peer, ids[0]);
+            }
+            other => panic!("expected SessionList, got {:?}", other),
+        }
+
+        clients[1].join_lobby(lobby_id).unwrap();
+        assert!(run_until(&mut server, &mut clients, 0, |m| *m == LobbyMessage::MemberJoined { lobby_id, peer_id: ids[1] }).is_some());
+
+        // Lobby holds two players
+        clients[2].join_lobby(lobby_id).unwrap();
+        assert!(run_until(&mut server, &mut clients, 2, |m| matches!(m, LobbyMessage::Error { .. })).is_some());
+
+        clients[1].relay(ids[0], ChannelType::ReliableOrdered, b"hello host".to_vec()).unwrap();
+        let relayed = run_until(&mut server, &mut clients, 0, |m| matches!(m, LobbyMessage::Relayed { .. }));
+        assert_eq!(relayed, Some(LobbyMessage::Relayed { from_peer: ids[1], payload: b"hello host".to_vec() }));
+
+        // Outsiders cannot relay into the lobby
+        clients[2].relay(ids[0], ChannelType::ReliableOrdered, b"spam".to_vec()).unwrap();
+        assert!(run_until(&mut server, &mut clients, 2, |m| matches!(m, LobbyMessage::Error { .. })).is_some());
+        assert_eq!(server.stats().relayed_messages, 1);
+
+        // Host leaves: ownership passes on, and the last member closes it
+        clients[0].leave_lobby().unwrap();
+        assert!(run_until(&mut server, &mut clients, 1, |m| matches!(m, LobbyMessage::MemberLeft { .. })).is_some());
+        assert_eq!(server.sessions()[0].host_peer, ids[1]);
+        clients[1].leave_lobby().unwrap();
+        for _ in 0..20 {
+            server.tick(0.016).unwrap();
+            clients[1].update().unwrap();
+            if server.sessions().is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        assert!(server.sessions().is_empty());
+        assert!(server.world().real_time_seconds > 0.0);
+    }
+}
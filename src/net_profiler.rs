@@ -0,0 +1,325 @@
+// src/net_profiler.rs
+//! Rolling network statistics timeline
+//!
+//! - Samples `NetworkSystem` counters at a fixed cadence and stores the
+//!   per-interval deltas: per-peer and per-channel bytes/packets in and out,
+//!   RTT, loss, reliable resends, and per-entity replication cost
+//! - Bounded history, exportable as CSV (one row per peer, channel or
+//!   entity per sample) or JSON for offline analysis
+//! - `entities_over_budget` answers "which replicated entities eat the bandwidth"
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::network::{ChannelType, EntityReplicationCost, NetworkSystem, PeerTraffic};
+
+// ============================================================================
+// SAMPLES
+// ============================================================================
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ChannelSample {
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub messages_in: u64,
+    pub messages_out: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PeerSample {
+    pub peer_id: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub packets_in: u64,
+    pub packets_out: u64,
+    pub rtt_ms: f32,
+    pub packet_loss: f32,
+    pub resends: u64,
+    /// Indexed in `ChannelType::ALL` order
+    pub channels: Vec<ChannelSample>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct EntitySample {
+    pub entity_id: u64,
+    pub updates: u64,
+    pub delta_updates: u64,
+    pub bytes: u64,
+}
+
+/// Traffic during one profiler interval ending at `time_ms`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NetStatsSample {
+    pub time_ms: f64,
+    pub interval_ms: f64,
+    pub peers: Vec<PeerSample>,
+    /// Entities that replicated at least once in the interval
+    pub entities: Vec<EntitySample>,
+}
+
+// ============================================================================
+// PROFILER
+// ============================================================================
+
+pub struct NetProfiler {
+    capacity: usize,
+    samples: VecDeque<NetStatsSample>,
+    last_time_ms: Option<f64>,
+    last_peers: HashMap<u64, PeerTraffic>,
+    last_entities: HashMap<u64, EntityReplicationCost>,
+}
+
+impl NetProfiler {
+    /// Keep the most recent `capacity` samples
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            samples: VecDeque::with_capacity(capacity),
+            last_time_ms: None,
+            last_peers: HashMap::new(),
+            last_entities: HashMap::new(),
+        }
+    }
+
+    pub fn samples(&self) -> impl Iterator<Item = &NetStatsSample> {
+        self.samples.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Read the network's counters and store the change since the previous call.
+    /// The first call only establishes the baseline.
+    pub fn record(&mut self, network: &NetworkSystem, time_ms: f64) {
+        self.record_counters(&network.peer_traffic(), &network.entity_replication_costs(), time_ms);
+    }
+
+    fn record_counters(&mut self, peers: &[PeerTraffic], entities: &[EntityReplicationCost], time_ms: f64) {
+        let previous_time = self.last_time_ms.replace(time_ms);
+
+        let peer_samples: Vec<PeerSample> = peers
+            .iter()
+            .map(|now| peer_delta(self.last_peers.get(&now.peer_id), now))
+            .collect();
+        let entity_samples: Vec<EntitySample> = entities
+            .iter()
+            .map(|now| entity_delta(self.last_entities.get(&now.entity_id), now))
+            .filter(|e| e.updates > 0)
+            .collect();
+
+        // Counters of disconnected peers vanish; forgetting them keeps a
+        // reused peer id from producing a negative delta
+        self.last_peers = peers.iter().map(|p| (p.peer_id, p.clone())).collect();
+        self.last_entities = entities.iter().map(|e| (e.entity_id, *e)).collect();
+
+        let Some(previous_time) = previous_time else {
+            return;
+        };
+        self.samples.push_back(NetStatsSample {
+            time_ms,
+            interval_ms: (time_ms - previous_time).max(0.0),
+            peers: peer_samples,
+            entities: entity_samples,
+        });
+        while self.samples.len() > self.capacity {
+            self.samples.pop_front();
+        }
+    }
+
+    // ============================================
+    // Analysis
+    // ============================================
+
+    /// Per-entity totals over the retained history, most expensive first
+    pub fn entity_totals(&self) -> Vec<EntitySample> {
+        let mut totals: HashMap<u64, EntitySample> = HashMap::new();
+        for sample in &self.samples {
+            for e in &sample.entities {
+                let total = totals.entry(e.entity_id).or_insert_with(|| EntitySample {
+                    entity_id: e.entity_id,
+                    ..Default::default()
+                });
+                total.updates += e.updates;
+                total.delta_updates += e.delta_updates;
+                total.bytes += e.bytes;
+            }
+        }
+        let mut totals: Vec<EntitySample> = totals.into_values().collect();
+        totals.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.entity_id.cmp(&b.entity_id)));
+        totals
+    }
+
+    /// Entities averaging more than `bytes_per_second` over the retained
+    /// history, with their rate, most expensive first
+    pub fn entities_over_budget(&self, bytes_per_second: f64) -> Vec<(u64, f64)> {
+        let window_ms: f64 = self.samples.iter().map(|s| s.interval_ms).sum();
+        if window_ms <= 0.0 {
+            return Vec::new();
+        }
+        self.entity_totals()
+            .into_iter()
+            .map(|e| (e.entity_id, e.bytes as f64 * 1000.0 / window_ms))
+            .filter(|(_, rate)| *rate > bytes_per_second)
+            .collect()
+    }
+
+    // ============================================
+    // Export
+    // ============================================
+
+    /// One row per peer, per peer channel and per entity in every sample.
+    /// Columns that do not apply to a row's scope are left empty; channel rows
+    /// count messages in the packet columns and entity rows report `bytes_out`.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from(
+            "time_ms,interval_ms,scope,id,channel,bytes_in,bytes_out,packets_in,packets_out,rtt_ms,packet_loss,resends,updates,delta_updates\n",
+        );
+        for s in &self.samples {
+            for p in &s.peers {
+                let _ = writeln!(
+                    out,
+                    "{},{},peer,{},,{},{},{},{},{:.2},{:.4},{},,",
+                    s.time_ms, s.interval_ms, p.peer_id, p.bytes_in, p.bytes_out,
+                    p.packets_in, p.packets_out, p.rtt_ms, p.packet_loss, p.resends
+                );
+                for (channel, c) in ChannelType::ALL.iter().zip(&p.channels) {
+                    let _ = writeln!(
+                        out,
+                        "{},{},channel,{},{:?},{},{},{},{},,,,,",
+                        s.time_ms, s.interval_ms, p.peer_id, channel,
+                        c.bytes_in, c.bytes_out, c.messages_in, c.messages_out
+                    );
+                }
+            }
+            for e in &s.entities {
+                let _ = writeln!(
+                    out,
+                    "{},{},entity,{},,,{},,,,,,{},{}",
+                    s.time_ms, s.interval_ms, e.entity_id, e.bytes, e.updates, e.delta_updates
+                );
+            }
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        let samples: Vec<&NetStatsSample> = self.samples.iter().collect();
+        serde_json::to_string_pretty(&samples)
+    }
+
+    pub fn export_csv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_csv())
+    }
+
+    pub fn export_json(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let json = self.to_json().map_err(std::io::Error::other)?;
+        std::fs::write(path, json)
+    }
+}
+
+impl Default for NetProfiler {
+    /// Two minutes of history at 10 samples per second
+    fn default() -> Self {
+        Self::new(1200)
+    }
+}
+
+fn peer_delta(previous: Option<&PeerTraffic>, now: &PeerTraffic) -> PeerSample {
+    let delta = |f: fn(&PeerTraffic) -> u64| f(now).saturating_sub(previous.map_or(0, f));
+    let channels = now.channels
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let before = previous.map(|p| p.channels[i]).unwrap_or_default();
+            ChannelSample {
+                bytes_in: c.bytes_received.saturating_sub(before.bytes_received),
+                bytes_out: c.bytes_sent.saturating_sub(before.bytes_sent),
+                messages_in: c.messages_received.saturating_sub(before.messages_received),
+                messages_out: c.messages_sent.saturating_sub(before.messages_sent),
+            }
+        })
+        .collect();
+    PeerSample {
+        peer_id: now.peer_id,
+        bytes_in: delta(|p| p.bytes_received),
+        bytes_out: delta(|p| p.bytes_sent),
+        packets_in: delta(|p| p.packets_received),
+        packets_out: delta(|p| p.packets_sent),
+        rtt_ms: now.rtt_ms,
+        packet_loss: now.packet_loss,
+        resends: delta(|p| p.resends),
+        channels,
+    }
+}
+
+fn entity_delta(previous: Option<&EntityReplicationCost>, now: &EntityReplicationCost) -> EntitySample {
+    let before = previous.copied().unwrap_or_default();
+    EntitySample {
+        entity_id: now.entity_id,
+        updates: now.updates.saturating_sub(before.updates),
+        delta_updates: now.delta_updates.saturating_sub(before.delta_updates),
+        bytes: now.bytes.saturating_sub(before.bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::test_support::connected_pair;
+    use crate::network::NetworkMessage;
+
+    #[test]
+    fn test_profiler_records_channel_and_entity_deltas() {
+        let (mut server, client, peer) = connected_pair();
+
+        let mut profiler = NetProfiler::new(8);
+        profiler.record(&client, 0.0);
+        assert_eq!(profiler.samples().count(), 0);
+
+        for i in 0..5u8 {
+            client.send_message(peer, NetworkMessage {
+                id: i as u64,
+                channel: ChannelType::ReliableOrdered,
+                data: vec![i; 10],
+                timestamp: 0,
+                sequence: 0,
+            }).unwrap();
+        }
+        client.pump().unwrap();
+        server.register_replicated_entity(9, vec![0; 15], None);
+        server.update_entity_state(9, vec![1; 15]);
+        let update = server.build_entity_update(1, 9).unwrap();
+
+        profiler.record(&client, 100.0);
+        let sample = profiler.samples().last().unwrap();
+        assert_eq!(sample.interval_ms, 100.0);
+        let reliable = &sample.peers[0].channels[0];
+        assert_eq!(reliable.messages_out, 5);
+        assert!(reliable.bytes_out > 5 * 10);
+        assert!(sample.peers[0].bytes_out >= reliable.bytes_out);
+
+        // Nothing new: the next interval is empty
+        profiler.record(&client, 200.0);
+        assert_eq!(profiler.samples().last().unwrap().peers[0].channels[0].messages_out, 0);
+
+        let mut server_profiler = NetProfiler::default();
+        server_profiler.record(&server, 0.0);
+        server.build_entity_update(1, 9).unwrap();
+        server_profiler.record(&server, 1000.0);
+        let totals = server_profiler.entity_totals();
+        assert_eq!(totals[0].entity_id, 9);
+        assert_eq!(totals[0].bytes, update.state_data.len() as u64);
+        assert_eq!(server_profiler.entities_over_budget(0.0).len(), 1);
+        assert!(server_profiler.entities_over_budget(1e6).is_empty());
+
+        let csv = profiler.to_csv();
+        assert!(csv.lines().any(|l| l.starts_with("100,100,channel,") && l.contains("ReliableOrdered")));
+        assert_eq!(csv.lines().count(), 1 + 2 * 5);
+        let json: serde_json::Value = serde_json::from_str(&server_profiler.to_json().unwrap()).unwrap();
+        assert_eq!(json[0]["entities"][0]["entity_id"], 9);
+    }
+}
@@ -7,8 +7,16 @@
 //! - Interest management (only sync visible entities)
 //! - Rollback netcode support
 //! - RTT estimation and packet batching
-
-use std::collections::{HashMap, VecDeque, HashSet};
+//! - UDP transport pumping (send queues -> wire -> receive queues)
+//! - Challenge/response connection handshake with heartbeats and timeouts
+//! - Per-channel delivery: acked reliable channels, ordering buffers, sequenced drops
+//! - Bit-packed XOR delta snapshots against per-peer acknowledged baselines
+//! - Optional secure sessions: connect tokens, AES-GCM sealed packets, replay window
+//! - Seeded link conditioner (latency, jitter, loss, duplication, reordering) for tests
+//! - Spatial interest management with per-connection priority accumulators
+
+use std::collections::{BTreeMap, HashMap, VecDeque, HashSet};
+use std::io::ErrorKind;
 use std::net::{SocketAddr, UdpSocket};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -18,15 +26,46 @@ use bincode;
 
 use glam::{Vec3, Vec2};
 
+use crate::interest_management::{InterestConfig, InterestManager};
+use aes_gcm::aead::{Aead, KeyInit, Payload as AeadPayload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use hmac::{Hmac, Mac};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
 // ============================================================================
 // CONSTANTS
 // ============================================================================
 
-const PACKET_HEADER_SIZE: usize = 8;
-const INPUT_BUFFER_SIZE: usize = 128;
+const PACKET_HEADER_SIZE: usize = 37;
 const STATE_BUFFER_SIZE: usize = 30;
 const MAX_PACKET_SIZE: usize = 1400;
 const COMPRESSION_THRESHOLD: usize = 256;
+const RECV_BUFFER_SIZE: usize = 2048;
+const PROTOCOL_ID: u32 = 0x534C_4F50; // "SLOP"
+const PACKET_PREFIX_SIZE: usize = 24;
+const HANDSHAKE_RESEND_MS: u64 = 100;
+const HEARTBEAT_INTERVAL_MS: u64 = 250;
+const DEFAULT_CONNECTION_TIMEOUT_MS: u64 = 5000;
+const DISCONNECT_REDUNDANCY: usize = 3;
+/// Back-to-back failed sends to one address before its peer is dropped
+const MAX_SEND_FAILURES: u32 = 5;
+const ACK_WINDOW: u16 = 32;
+const INITIAL_RESEND_MS: f32 = 100.0;
+const MIN_RESEND_MS: f32 = 20.0;
+const MAX_RESEND_MS: f32 = 1000.0;
+const RESEND_RTT_FACTOR: f32 = 1.5;
+/// 64-bit crypto sequence plus the AES-GCM tag
+const SECURE_OVERHEAD: usize = 8 + 16;
+const REPLAY_WINDOW: u64 = 128;
+/// Reliable messages per channel that may be in flight past the oldest
+/// unacked one. Receivers buffer nothing further ahead than this.
+const RELIABLE_WINDOW: u32 = 256;
+/// Largest (possibly compressed) message payload that fits one datagram
+pub const MAX_MESSAGE_PAYLOAD: usize = MAX_PACKET_SIZE - PACKET_PREFIX_SIZE - SECURE_OVERHEAD - PACKET_HEADER_SIZE;
 
 // ============================================================================
 // ENUMS
@@ -49,12 +88,33 @@ pub enum ConnectionState {
 
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ChannelType {
+    /// Resent until acked, delivered strictly in send order
     ReliableOrdered,
+    /// Resent until acked, delivered as soon as it arrives
     ReliableUnordered,
+    /// Sequenced: never resent, anything older than the newest delivered is dropped
     UnreliableOrdered,
+    /// Fire and forget; `sequence` is left to the caller
     UnreliableUnordered,
 }
 
+impl ChannelType {
+    pub const ALL: [ChannelType; 4] = [
+        ChannelType::ReliableOrdered,
+        ChannelType::ReliableUnordered,
+        ChannelType::UnreliableOrdered,
+        ChannelType::UnreliableUnordered,
+    ];
+    
+    pub fn is_reliable(self) -> bool {
+        matches!(self, ChannelType::ReliableOrdered | ChannelType::ReliableUnordered)
+    }
+    
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
 // ============================================================================
 // CORE TYPES
 // ============================================================================
@@ -77,7 +137,7 @@ pub struct Connection {
     pub state: ConnectionState,
     pub rtt_ms: f32,
     pub packet_loss: f32,
-    pub send_queue: VecDeque<NetworkMessage>,
+    send_queue: VecDeque<OutgoingMessage>,
     pub recv_queue: VecDeque<NetworkMessage>,
     pub last_heartbeat: u64,
     pub bytes_sent: u64,
@@ -85,6 +145,15 @@ pub struct Connection {
     pub packets_sent: u64,
     pub packets_received: u64,
     pub last_update: Instant,
+    /// Shared secret agreed during the handshake; stamped on every packet
+    pub session_token: u64,
+    /// Peer id the remote host assigned to us when it accepted the handshake
+    pub assigned_id: Option<u64>,
+    /// Client id from the connect token, for connections accepted in secure mode
+    pub client_id: Option<u64>,
+    pub last_send: u64,
+    channels: ChannelState,
+    crypto: Option<SessionCrypto>,
 }
 
 impl Connection {
@@ -103,6 +172,36 @@ impl Connection {
             packets_sent: 0,
             packets_received: 0,
             last_update: Instant::now(),
+            session_token: 0,
+            assigned_id: None,
+            client_id: None,
+            last_send: 0,
+            channels: ChannelState::default(),
+            crypto: None,
+        }
+    }
+    
+    pub fn is_secure(&self) -> bool {
+        self.crypto.is_some()
+    }
+    
+    /// Number of reliable messages sent but not yet acknowledged
+    pub fn unacked_reliable(&self) -> usize {
+        self.channels.pending_reliable.len()
+    }
+    
+    /// Fold a remote ack header into RTT and loss estimates
+    fn apply_acks(&mut self, acks: &AckHeader, now: Instant) {
+        let (samples, lost) = self.channels.process_acks(acks, now);
+        for sample in &samples {
+            let ms = sample.as_secs_f32() * 1000.0;
+            self.rtt_ms = if self.rtt_ms <= 0.0 { ms } else { self.rtt_ms + (ms - self.rtt_ms) * 0.1 };
+        }
+        for _ in 0..samples.len() {
+            self.packet_loss *= 0.95;
+        }
+        for _ in 0..lost {
+            self.packet_loss = self.packet_loss * 0.95 + 0.05;
         }
     }
 }
@@ -195,16 +294,69 @@ struct PendingInput {
     acknowledged: bool,
 }
 
+/// Full entity states keyed by sequence, oldest first
+type StateHistory = VecDeque<(u32, Vec<u8>)>;
+
+/// Recent full states of one entity, plus the newest sequence each peer has acked
 struct DeltaContext {
-    last_state: Option<Vec<u8>>,
-    last_sequence: u32,
+    history: StateHistory,
+    acked: HashMap<u64, u32>,
+}
+
+impl DeltaContext {
+    fn record(&mut self, sequence: u32, state: Vec<u8>) {
+        self.history.push_back((sequence, state));
+        while self.history.len() > STATE_BUFFER_SIZE {
+            self.history.pop_front();
+        }
+    }
+    
+    fn state_at(&self, sequence: u32) -> Option<&[u8]> {
+        self.history.iter().find(|(seq, _)| *seq == sequence).map(|(_, s)| s.as_slice())
+    }
+}
+
+/// Client half of an in-flight handshake
+struct ClientHandshake {
+    client_salt: u64,
+    token: Option<u64>,
+    connect_token: Option<ConnectToken>,
+    keys: Option<SessionKeys>,
+}
+
+impl ClientHandshake {
+    /// Whether a `ConnectDenied` really answers this attempt. Secure servers
+    /// MAC the denial with the token's server-to-client key, so knowing the
+    /// salt alone isn't enough to tear down a pending connection.
+    fn accepts_denial(&self, client_salt: u64, mac: u64) -> bool {
+        let expected = self.connect_token.as_ref().map_or(0, |t| denial_mac(&t.server_to_client_key, client_salt));
+        self.client_salt == client_salt && expected == mac
+    }
+}
+
+/// Server half of an in-flight handshake, keyed by remote address
+struct PendingHandshake {
+    client_salt: u64,
+    server_salt: u64,
+    created: u64,
+    client_id: Option<u64>,
+    keys: Option<SessionKeys>,
+}
+
+impl PendingHandshake {
+    fn expected_token(&self) -> u64 {
+        match &self.keys {
+            Some(keys) => keys.token,
+            None => self.client_salt ^ self.server_salt,
+        }
+    }
 }
 
 impl Default for DeltaContext {
     fn default() -> Self {
         Self {
-            last_state: None,
-            last_sequence: 0,
+            history: VecDeque::with_capacity(STATE_BUFFER_SIZE),
+            acked: HashMap::new(),
         }
     }
 }
@@ -219,19 +371,29 @@ pub struct NetworkSystem {
     message_queue: RwLock<VecDeque<NetworkMessage>>,
     replicated_entities: RwLock<HashMap<u64, ReplicatedState>>,
     delta_contexts: RwLock<HashMap<u64, DeltaContext>>,
-    
-    input_buffer: RwLock<VecDeque<GameInput>>,
-    rollback_states: RwLock<VecDeque<RollbackState>>,
+    received_baselines: RwLock<HashMap<u64, StateHistory>>,
     
     local_player_id: Option<u64>,
     predicted_states: RwLock<HashMap<u64, PredictedState>>,
     pending_inputs: RwLock<VecDeque<PendingInput>>,
     
     visible_entities: RwLock<HashSet<u64>>,
+    interest: RwLock<InterestManager>,
     authority_map: RwLock<HashMap<u64, u64>>,
     
     next_peer_id: RwLock<u64>,
     socket: Option<Arc<UdpSocket>>,
+    listening: bool,
+    security_key: Option<[u8; 32]>,
+    conditioner: Mutex<Option<LinkConditioner>>,
+    client_handshakes: RwLock<HashMap<u64, ClientHandshake>>,
+    pending_handshakes: RwLock<HashMap<SocketAddr, PendingHandshake>>,
+    events: RwLock<VecDeque<ConnectionEvent>>,
+    /// Consecutive failed sends per address; reset by any send that succeeds
+    send_failures: Mutex<HashMap<SocketAddr, u32>>,
+    entity_costs: RwLock<HashMap<u64, EntityReplicationCost>>,
+    pub max_peers: usize,
+    pub connection_timeout_ms: u64,
     pub compression_enabled: bool,
     pub delta_compression_enabled: bool,
     pub prediction_enabled: bool,
@@ -248,15 +410,25 @@ impl NetworkSystem {
             message_queue: RwLock::new(VecDeque::new()),
             replicated_entities: RwLock::new(HashMap::new()),
             delta_contexts: RwLock::new(HashMap::new()),
-            input_buffer: RwLock::new(VecDeque::with_capacity(INPUT_BUFFER_SIZE)),
-            rollback_states: RwLock::new(VecDeque::with_capacity(STATE_BUFFER_SIZE)),
+            received_baselines: RwLock::new(HashMap::new()),
             local_player_id: None,
             predicted_states: RwLock::new(HashMap::new()),
             pending_inputs: RwLock::new(VecDeque::new()),
             visible_entities: RwLock::new(HashSet::new()),
+            interest: RwLock::new(InterestManager::default()),
             authority_map: RwLock::new(HashMap::new()),
             next_peer_id: RwLock::new(1),
             socket: None,
+            listening: false,
+            security_key: None,
+            conditioner: Mutex::new(None),
+            client_handshakes: RwLock::new(HashMap::new()),
+            pending_handshakes: RwLock::new(HashMap::new()),
+            events: RwLock::new(VecDeque::new()),
+            send_failures: Mutex::new(HashMap::new()),
+            entity_costs: RwLock::new(HashMap::new()),
+            max_peers: 64,
+            connection_timeout_ms: DEFAULT_CONNECTION_TIMEOUT_MS,
             compression_enabled: true,
             delta_compression_enabled: true,
             prediction_enabled: true,
@@ -270,31 +442,89 @@ impl NetworkSystem {
     // ============================================
 
     pub fn connect(&mut self, address: &str) -> Result<u64, NetworkError> {
+        self.start_connect(address, None)
+    }
+
+    /// Connect to a server running in secure mode. The token comes from the
+    /// matchmaking backend, or is self-issued when the key is pre-shared.
+    pub fn connect_with_token(&mut self, address: &str, token: ConnectToken) -> Result<u64, NetworkError> {
+        if token.expires_at <= current_timestamp_ms() {
+            return Err(NetworkError::ConnectionFailed("connect token expired".to_string()));
+        }
+        self.start_connect(address, Some(token))
+    }
+
+    fn start_connect(&mut self, address: &str, connect_token: Option<ConnectToken>) -> Result<u64, NetworkError> {
         let addr: SocketAddr = address.parse().map_err(|_| NetworkError::InvalidAddress)?;
         
-        let socket = UdpSocket::bind("0.0.0.0:0")
-            .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))?;
-        socket.set_nonblocking(true)
-            .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))?;
+        if self.socket.is_none() {
+            self.bind("0.0.0.0:0")?;
+        }
         
-        let shared_socket = Arc::new(socket);
-        self.socket = Some(shared_socket.clone());
+        let peer_id = self.allocate_peer_id();
         
-        shared_socket.connect(addr)
-            .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))?;
+        // The handshake runs inside `pump`; the peer stays `Connecting` until accepted
+        let mut conn = Connection::new(peer_id, addr);
+        conn.last_heartbeat = current_timestamp_ms();
+        self.connections.write().insert(peer_id, conn);
+        self.client_handshakes.write().insert(peer_id, ClientHandshake {
+            client_salt: rand::random(),
+            token: None,
+            connect_token,
+            keys: None,
+        });
         
-        let peer_id = {
-            let mut next = self.next_peer_id.write();
-            *next += 1;
-            *next
-        };
+        log::info!("Connecting to {} with peer_id {}", address, peer_id);
+        Ok(peer_id)
+    }
+
+    /// Bind `address` and start accepting handshakes from clients
+    pub fn listen(&mut self, address: &str) -> Result<SocketAddr, NetworkError> {
+        if self.role == NetworkRole::Client {
+            return Err(NetworkError::ConnectionFailed("client role cannot accept connections".to_string()));
+        }
         
-        let mut connections = self.connections.write();
-        connections.insert(peer_id, Connection::new(peer_id, addr));
-        connections.get_mut(&peer_id).unwrap().state = ConnectionState::Connected;
+        let local = self.bind(address)?;
+        self.listening = true;
+        log::info!("Listening for connections on {}", local);
+        Ok(local)
+    }
+
+    /// Require every client to present a `ConnectToken` sealed with
+    /// `private_key`; all session traffic is then encrypted and authenticated
+    pub fn enable_security(&mut self, private_key: [u8; 32]) {
+        self.security_key = Some(private_key);
+    }
+
+    /// Bind the local UDP socket. Hosts and servers bind a known port before
+    /// clients connect; `connect` binds an ephemeral port if nothing is bound yet.
+    pub fn bind(&mut self, address: &str) -> Result<SocketAddr, NetworkError> {
+        let addr: SocketAddr = address.parse().map_err(|_| NetworkError::InvalidAddress)?;
         
-        log::info!("Connected to {} with peer_id {}", address, peer_id);
-        Ok(peer_id)
+        let socket = UdpSocket::bind(addr)
+            .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))?;
+        socket.set_nonblocking(true)
+            .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))?;
+        let local = socket.local_addr()
+            .map_err(|e| NetworkError::ConnectionFailed(e.to_string()))?;
+        
+        self.socket = Some(Arc::new(socket));
+        log::info!("Network socket bound to {}", local);
+        Ok(local)
+    }
+
+    pub fn role(&self) -> NetworkRole {
+        self.role
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.socket.as_ref().and_then(|s| s.local_addr().ok())
+    }
+
+    fn allocate_peer_id(&self) -> u64 {
+        let mut next = self.next_peer_id.write();
+        *next += 1;
+        *next
     }
 
     pub fn disconnect(&mut self, peer_id: u64) {
@@ -313,20 +543,30 @@ impl NetworkSystem {
     // MESSAGING
     // ============================================
 
-    pub fn send_message(&self, peer_id: u64, mut message: NetworkMessage) {
-        if self.compression_enabled && message.data.len() > COMPRESSION_THRESHOLD {
-            message.data = compress_data(&message.data);
+    /// Queue a message for the next `pump`. Every channel except
+    /// `UnreliableUnordered` overwrites `sequence` with its own counter.
+    /// Messages that can never fit in one datagram are rejected before they
+    /// take a sequence, so ordered channels never wait on a gap. Peers still
+    /// handshaking queue until they connect; unknown or closing peers fail
+    /// with `NotConnected`.
+    pub fn send_message(&self, peer_id: u64, mut message: NetworkMessage) -> Result<(), NetworkError> {
+        let (payload, compressed) = self.wire_payload(std::mem::take(&mut message.data));
+        if payload.len() > MAX_MESSAGE_PAYLOAD {
+            return Err(NetworkError::MessageTooLarge { size: payload.len(), max: MAX_MESSAGE_PAYLOAD });
         }
         
         let mut connections = self.connections.write();
-        if let Some(conn) = connections.get_mut(&peer_id) {
-            conn.send_queue.push_back(message.clone());
-            conn.bytes_sent += message.data.len() as u64;
-            conn.packets_sent += 1;
-        }
+        let conn = connections
+            .get_mut(&peer_id)
+            .filter(|conn| matches!(conn.state, ConnectionState::Connecting | ConnectionState::Connected))
+            .ok_or(NetworkError::NotConnected)?;
+        conn.channels.assign_sequence(&mut message);
+        message.data = payload;
+        conn.send_queue.push_back(OutgoingMessage::encode(peer_id, &message, compressed));
+        Ok(())
     }
 
-    pub fn send_unreliable_position(&self, peer_id: u64, entity_id: u64, position: [f32; 3], sequence: u32) {
+    pub fn send_unreliable_position(&self, peer_id: u64, entity_id: u64, position: [f32; 3], sequence: u32) -> Result<(), NetworkError> {
         let update = PositionUpdate {
             entity_id,
             position,
@@ -343,38 +583,583 @@ impl NetworkSystem {
             sequence,
         };
         
-        self.send_message(peer_id, message);
+        self.send_message(peer_id, message)
+    }
+
+    // ============================================
+    // TRANSPORT PUMP
+    // ============================================
+
+    /// Drive the UDP transport once: read all pending datagrams into the
+    /// receive queues, advance handshakes, heartbeats and timeouts, then flush
+    /// every connected peer's send queue onto the wire. A peer whose last
+    /// `MAX_SEND_FAILURES` sends all failed is dropped with
+    /// `DisconnectReason::Unreachable`; only errors on the socket itself are
+    /// returned.
+    pub fn pump(&self) -> Result<PumpStats, NetworkError> {
+        let mut stats = PumpStats::default();
+        let socket = match &self.socket {
+            Some(s) => s.clone(),
+            None => return Ok(stats),
+        };
+        
+        self.poll_incoming(&socket, &mut stats)?;
+        self.update_connections(&socket, &mut stats);
+        self.send_pending(&socket, &mut stats);
+        self.flush_conditioner(&socket);
+        self.drop_unreachable();
+        Ok(stats)
+    }
+
+    /// Route outgoing datagrams through a simulated bad link, or pass `None`
+    /// to send directly again. Condition both ends for a symmetric link.
+    pub fn set_link_conditioner(&mut self, config: Option<LinkConditionerConfig>) {
+        *self.conditioner.lock() = config.map(LinkConditioner::new);
+    }
+
+    pub fn link_conditioner_stats(&self) -> Option<LinkConditionerStats> {
+        self.conditioner.lock().as_ref().map(|c| c.stats)
+    }
+
+    /// Put datagrams whose simulated delay has elapsed on the wire
+    fn flush_conditioner(&self, socket: &UdpSocket) {
+        let due = match self.conditioner.lock().as_mut() {
+            Some(conditioner) => conditioner.take_due(Instant::now()),
+            None => return,
+        };
+        for (to, packet) in due {
+            if let Err(e) = socket.send_to(&packet, to) {
+                log::debug!("Conditioned send to {} failed: {}", to, e);
+            }
+        }
+    }
+
+    /// Take connection lifecycle events raised since the last call
+    pub fn poll_events(&self) -> Vec<ConnectionEvent> {
+        self.events.write().drain(..).collect()
+    }
+
+    fn push_event(&self, event: ConnectionEvent) {
+        if let ConnectionEvent::Disconnected { peer_id, .. } = event {
+            self.interest.write().remove_viewer(peer_id);
+        }
+        self.events.write().push_back(event);
+    }
+
+    /// Send one datagram, returning the bytes sent. A failure only concerns
+    /// `to`, so it is counted for `drop_unreachable` instead of failing the pump.
+    fn transmit(&self, socket: &UdpSocket, to: SocketAddr, packet: &[u8], stats: &mut PumpStats) -> usize {
+        if let Some(conditioner) = self.conditioner.lock().as_mut() {
+            conditioner.submit(to, packet, Instant::now());
+            stats.packets_sent += 1;
+            stats.bytes_sent += packet.len();
+            return packet.len();
+        }
+        
+        match socket.send_to(packet, to) {
+            Ok(sent) => {
+                self.send_failures.lock().remove(&to);
+                stats.packets_sent += 1;
+                stats.bytes_sent += sent;
+                sent
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                log::debug!("Socket send buffer full, dropping packet to {}", to);
+                0
+            }
+            Err(e) => {
+                log::warn!("UDP send to {} failed: {}", to, e);
+                *self.send_failures.lock().entry(to).or_default() += 1;
+                0
+            }
+        }
+    }
+
+    fn send_control(&self, socket: &UdpSocket, conn: &mut Connection, kind: PacketKind, body: &[u8], stats: &mut PumpStats) {
+        let packet = seal_packet(conn, kind, body);
+        let sent = self.transmit(socket, conn.address, &packet, stats);
+        conn.bytes_sent += sent as u64;
+        conn.packets_sent += 1;
+        conn.last_send = current_timestamp_ms();
+    }
+
+    /// Drop every connection and pending handshake whose address has failed
+    /// `MAX_SEND_FAILURES` sends in a row. Fewer are treated as transient.
+    fn drop_unreachable(&self) {
+        let failed: Vec<SocketAddr> = {
+            let mut failures = self.send_failures.lock();
+            if failures.is_empty() {
+                return;
+            }
+            let failed = failures.iter().filter(|(_, n)| **n >= MAX_SEND_FAILURES).map(|(a, _)| *a).collect();
+            // Also forget streaks for addresses that have since gone away
+            let connections = self.connections.read();
+            let pending = self.pending_handshakes.read();
+            failures.retain(|address, n| {
+                *n < MAX_SEND_FAILURES
+                    && (pending.contains_key(address) || connections.values().any(|c| c.address == *address))
+            });
+            failed
+        };
+        if failed.is_empty() {
+            return;
+        }
+        
+        let mut closed = Vec::new();
+        self.connections.write().retain(|peer_id, conn| {
+            let drop = failed.contains(&conn.address);
+            if drop && conn.state != ConnectionState::Disconnected {
+                closed.push(*peer_id);
+            }
+            !drop
+        });
+        self.client_handshakes.write().retain(|peer_id, _| !closed.contains(peer_id));
+        self.pending_handshakes.write().retain(|address, _| !failed.contains(address));
+        
+        for peer_id in closed {
+            log::warn!("Dropping peer {}: its address is unreachable", peer_id);
+            self.push_event(ConnectionEvent::Disconnected { peer_id, reason: DisconnectReason::Unreachable });
+        }
+    }
+
+    /// Advance every connection's state machine: resend handshake packets,
+    /// keep idle links alive, flush disconnects and evict timed-out peers.
+    fn update_connections(&self, socket: &UdpSocket, stats: &mut PumpStats) {
+        let now = current_timestamp_ms();
+        let timeout = self.connection_timeout_ms;
+        let mut closed = Vec::new();
+        
+        {
+            let mut connections = self.connections.write();
+            let handshakes = self.client_handshakes.read();
+            
+            for conn in connections.values_mut() {
+                let silent_for = now.saturating_sub(conn.last_heartbeat);
+                match conn.state {
+                    ConnectionState::Connecting => {
+                        if silent_for > timeout {
+                            log::warn!("Handshake with {} timed out", conn.address);
+                            conn.state = ConnectionState::Disconnected;
+                            closed.push((conn.peer_id, DisconnectReason::TimedOut));
+                        } else if now.saturating_sub(conn.last_send) >= HANDSHAKE_RESEND_MS {
+                            if let Some(hs) = handshakes.get(&conn.peer_id) {
+                                match hs.token {
+                                    Some(token) => {
+                                        self.send_control(socket, conn, PacketKind::ChallengeResponse { token }, &[], stats);
+                                    }
+                                    None => {
+                                        let sealed = hs.connect_token.as_ref().map_or(&[][..], |t| &t.sealed[..]);
+                                        let kind = PacketKind::ConnectRequest { client_salt: hs.client_salt };
+                                        self.send_control(socket, conn, kind, sealed, stats);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    ConnectionState::Connected => {
+                        if silent_for > timeout {
+                            log::warn!("Peer {} timed out after {}ms of silence", conn.peer_id, silent_for);
+                            conn.state = ConnectionState::Disconnected;
+                            closed.push((conn.peer_id, DisconnectReason::TimedOut));
+                        } else if conn.send_queue.is_empty() && now.saturating_sub(conn.last_send) >= HEARTBEAT_INTERVAL_MS {
+                            let acks = conn.channels.begin_packet(Vec::new(), Instant::now());
+                            let kind = PacketKind::Heartbeat { token: conn.session_token, acks };
+                            self.send_control(socket, conn, kind, &[], stats);
+                        }
+                    }
+                    ConnectionState::Disconnecting => {
+                        // No acks for a goodbye, so send a few copies and hope one lands
+                        if conn.session_token != 0 {
+                            for _ in 0..DISCONNECT_REDUNDANCY {
+                                let kind = PacketKind::Disconnect { token: conn.session_token };
+                                self.send_control(socket, conn, kind, &[], stats);
+                            }
+                        }
+                        conn.state = ConnectionState::Disconnected;
+                        closed.push((conn.peer_id, DisconnectReason::Requested));
+                    }
+                    ConnectionState::Disconnected => {}
+                }
+            }
+            
+            connections.retain(|_, c| c.state != ConnectionState::Disconnected);
+        }
+        
+        {
+            let mut handshakes = self.client_handshakes.write();
+            let connections = self.connections.read();
+            handshakes.retain(|peer_id, _| connections.contains_key(peer_id));
+        }
+        self.pending_handshakes.write().retain(|_, p| now.saturating_sub(p.created) <= timeout);
+        
+        for (peer_id, reason) in closed {
+            self.push_event(ConnectionEvent::Disconnected { peer_id, reason });
+        }
     }
 
-    pub fn flush_batch(&self) -> Option<Vec<u8>> {
-        let mut batch = Vec::with_capacity(MAX_PACKET_SIZE);
+    fn send_pending(&self, socket: &UdpSocket, stats: &mut PumpStats) {
+        // Resolve resend timeouts up front; get_smoothed_rtt takes its own read lock
+        let resend: HashMap<u64, Duration> = self.connected_peers()
+            .into_iter()
+            .map(|peer_id| (peer_id, resend_timeout(self.get_smoothed_rtt(peer_id))))
+            .collect();
+        let now = Instant::now();
         
         let mut connections = self.connections.write();
-        for (peer_id, conn) in connections.iter_mut() {
-            if conn.send_queue.is_empty() {
+        for conn in connections.values_mut() {
+            if conn.state != ConnectionState::Connected {
                 continue;
             }
             
-            while let Some(msg) = conn.send_queue.pop_front() {
-                if batch.len() + msg.data.len() + PACKET_HEADER_SIZE > MAX_PACKET_SIZE {
-                    conn.send_queue.push_front(msg);
-                    break;
+            let timeout = resend.get(&conn.peer_id).copied().unwrap_or_else(|| resend_timeout(0.0));
+            let mut packets = self.build_packets(conn, timeout, now);
+            
+            // Nothing to say, but the remote is waiting on acks
+            if packets.is_empty() && conn.channels.ack_pending {
+                let acks = conn.channels.begin_packet(Vec::new(), now);
+                packets.push(seal_packet(conn, PacketKind::Heartbeat { token: conn.session_token, acks }, &[]));
+            }
+            
+            for packet in packets {
+                let sent = self.transmit(socket, conn.address, &packet, stats);
+                conn.bytes_sent += sent as u64;
+                conn.packets_sent += 1;
+                conn.last_send = current_timestamp_ms();
+            }
+        }
+    }
+
+    /// Pack due reliable resends followed by newly queued messages into
+    /// datagrams no larger than `MAX_PACKET_SIZE`, each stamped with acks.
+    fn build_packets(&self, conn: &mut Connection, resend: Duration, now: Instant) -> Vec<Vec<u8>> {
+        let mut unreliable = Vec::new();
+        while let Some(msg) = conn.send_queue.pop_front() {
+            if msg.channel.is_reliable() {
+                conn.channels.queue_reliable(msg);
+            } else {
+                unreliable.push(msg);
+            }
+        }
+        
+        let mut items: Vec<(Vec<u8>, Option<u64>)> = Vec::new();
+        for (id, msg) in conn.channels.due_reliable(now, resend) {
+            conn.channels.record_sent(msg.channel, msg.bytes.len());
+            items.push((msg.bytes, Some(id)));
+        }
+        for msg in unreliable {
+            conn.channels.record_sent(msg.channel, msg.bytes.len());
+            items.push((msg.bytes, None));
+        }
+        
+        let budget = MAX_PACKET_SIZE - PACKET_PREFIX_SIZE - SECURE_OVERHEAD;
+        let mut groups: Vec<(Vec<u8>, Vec<u64>)> = Vec::new();
+        let mut body = Vec::new();
+        let mut ids = Vec::new();
+        for (bytes, id) in items {
+            if !body.is_empty() && body.len() + bytes.len() > budget {
+                groups.push((std::mem::take(&mut body), std::mem::take(&mut ids)));
+            }
+            body.extend_from_slice(&bytes);
+            ids.extend(id);
+        }
+        if !body.is_empty() {
+            groups.push((body, ids));
+        }
+        
+        groups.into_iter()
+            .map(|(body, ids)| {
+                let acks = conn.channels.begin_packet(ids, now);
+                seal_packet(conn, PacketKind::Payload { token: conn.session_token, acks }, &body)
+            })
+            .collect()
+    }
+
+    /// Payload as it goes on the wire, compressed when that pays off
+    fn wire_payload(&self, data: Vec<u8>) -> (Vec<u8>, bool) {
+        if self.compression_enabled && data.len() > COMPRESSION_THRESHOLD {
+            let packed = compress_data(&data);
+            if packed.len() < data.len() {
+                return (packed, true);
+            }
+        }
+        (data, false)
+    }
+
+    fn poll_incoming(&self, socket: &UdpSocket, stats: &mut PumpStats) -> Result<(), NetworkError> {
+        let mut buf = [0u8; RECV_BUFFER_SIZE];
+        loop {
+            let (len, from) = match socket.recv_from(&mut buf) {
+                Ok(r) => r,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                // ICMP port unreachable from a vanished peer surfaces here on some platforms
+                Err(e) if matches!(e.kind(), ErrorKind::ConnectionReset | ErrorKind::ConnectionRefused) => continue,
+                Err(e) => {
+                    log::warn!("UDP receive failed: {}", e);
+                    return Err(NetworkError::RecvFailed);
+                }
+            };
+            
+            let (kind, body) = match decode_prefix(&buf[..len]) {
+                Some(p) => p,
+                None => {
+                    log::debug!("Discarding foreign or malformed packet from {}", from);
+                    continue;
+                }
+            };
+            
+            stats.packets_received += 1;
+            stats.bytes_received += len;
+            self.handle_packet(socket, from, kind, body, &buf[..len], stats);
+        }
+        
+        Ok(())
+    }
+
+    fn handle_packet(
+        &self,
+        socket: &UdpSocket,
+        from: SocketAddr,
+        kind: PacketKind,
+        body: &[u8],
+        packet: &[u8],
+        stats: &mut PumpStats,
+    ) {
+        let now = current_timestamp_ms();
+        let len = packet.len();
+        
+        match kind {
+            // ---- Server side ----
+            PacketKind::ConnectRequest { client_salt } => {
+                if !self.listening || self.connections.read().values().any(|c| c.address == from) {
+                    return;
+                }
+                
+                // In secure mode the request body is the sealed half of a connect token
+                let contents = match &self.security_key {
+                    Some(key) => match open_connect_token(key, body) {
+                        Some(c) if c.expires_at > now => Some(c),
+                        _ => {
+                            log::debug!("Ignoring connect request from {} without a valid token", from);
+                            return;
+                        }
+                    },
+                    None => None,
+                };
+                
+                if self.peer_count() >= self.max_peers {
+                    log::info!("Denying {}: server full", from);
+                    let mac = contents.as_ref().map_or(0, |c| denial_mac(&c.server_to_client_key, client_salt));
+                    self.transmit(socket, from, &encode_prefix(&PacketKind::ConnectDenied { client_salt, mac }), stats);
+                    return;
+                }
+                
+                let server_salt = {
+                    let mut pending = self.pending_handshakes.write();
+                    let fresh = |server_salt: u64| PendingHandshake {
+                        client_salt,
+                        server_salt,
+                        created: now,
+                        client_id: contents.as_ref().map(|c| c.client_id),
+                        keys: contents.as_ref().map(|c| SessionKeys::derive(c, client_salt, server_salt, false)),
+                    };
+                    let entry = pending.entry(from).or_insert_with(|| fresh(rand::random()));
+                    // A fresh salt means the client restarted its attempt
+                    if entry.client_salt != client_salt {
+                        *entry = fresh(rand::random());
+                    }
+                    entry.server_salt
+                };
+                self.transmit(socket, from, &encode_prefix(&PacketKind::Challenge { client_salt, server_salt }), stats);
+            }
+            PacketKind::ChallengeResponse { token } => {
+                if !self.listening {
+                    return;
                 }
                 
-                let header = PacketHeader {
-                    peer_id: *peer_id,
-                    channel: msg.channel,
-                    sequence: msg.sequence,
-                    size: msg.data.len() as u32,
+                // Our accept was lost: repeat it rather than allocating a second peer
+                let existing = self.connections.read()
+                    .values()
+                    .find(|c| c.address == from && c.session_token == token)
+                    .map(|c| c.peer_id);
+                if let Some(peer_id) = existing {
+                    self.transmit(socket, from, &encode_prefix(&PacketKind::ConnectAccepted { token, peer_id }), stats);
+                    return;
+                }
+                
+                let verified = {
+                    let mut pending = self.pending_handshakes.write();
+                    match pending.get(&from) {
+                        Some(p) if p.expected_token() == token => pending.remove(&from),
+                        _ => None,
+                    }
+                };
+                let handshake = match verified {
+                    Some(h) => h,
+                    None => {
+                        log::debug!("Rejecting challenge response with bad token from {}", from);
+                        return;
+                    }
+                };
+                if self.peer_count() >= self.max_peers {
+                    return;
+                }
+                
+                let peer_id = self.allocate_peer_id();
+                let mut conn = Connection::new(peer_id, from);
+                conn.state = ConnectionState::Connected;
+                conn.session_token = token;
+                conn.last_heartbeat = now;
+                conn.client_id = handshake.client_id;
+                conn.crypto = handshake.keys.map(SessionCrypto::new);
+                self.send_control(socket, &mut conn, PacketKind::ConnectAccepted { token, peer_id }, &[], stats);
+                self.connections.write().insert(peer_id, conn);
+                
+                log::info!("Accepted peer {} from {}", peer_id, from);
+                self.push_event(ConnectionEvent::Connected { peer_id });
+            }
+            
+            // ---- Client side ----
+            PacketKind::Challenge { client_salt, server_salt } => {
+                let mut connections = self.connections.write();
+                let mut handshakes = self.client_handshakes.write();
+                if let Some(conn) = connections.values_mut().find(|c| c.address == from && c.state == ConnectionState::Connecting) {
+                    if let Some(hs) = handshakes.get_mut(&conn.peer_id) {
+                        if hs.client_salt == client_salt {
+                            // Secure sessions prove key possession instead of echoing the salts
+                            hs.keys = hs.connect_token.as_ref()
+                                .map(|t| SessionKeys::derive(&t.contents(), client_salt, server_salt, true));
+                            let token = hs.keys.as_ref().map_or(client_salt ^ server_salt, |k| k.token);
+                            hs.token = Some(token);
+                            conn.last_heartbeat = now;
+                            self.send_control(socket, conn, PacketKind::ChallengeResponse { token }, &[], stats);
+                        }
+                    }
+                }
+            }
+            PacketKind::ConnectAccepted { token, peer_id: assigned } => {
+                let accepted = {
+                    let mut connections = self.connections.write();
+                    let handshakes = self.client_handshakes.read();
+                    connections.values_mut()
+                        .find(|c| c.address == from && c.state == ConnectionState::Connecting)
+                        .filter(|c| handshakes.get(&c.peer_id).and_then(|h| h.token) == Some(token))
+                        .map(|conn| {
+                            conn.crypto = handshakes[&conn.peer_id].keys.clone().map(SessionCrypto::new);
+                            conn.state = ConnectionState::Connected;
+                            conn.session_token = token;
+                            conn.assigned_id = Some(assigned);
+                            conn.last_heartbeat = now;
+                            conn.peer_id
+                        })
+                };
+                if let Some(peer_id) = accepted {
+                    self.client_handshakes.write().remove(&peer_id);
+                    log::info!("Connected to {} as remote peer {}", from, assigned);
+                    self.push_event(ConnectionEvent::Connected { peer_id });
+                }
+            }
+            PacketKind::ConnectDenied { client_salt, mac } => {
+                let denied = {
+                    let mut connections = self.connections.write();
+                    let handshakes = self.client_handshakes.read();
+                    connections.values_mut()
+                        .find(|c| c.address == from && c.state == ConnectionState::Connecting)
+                        .filter(|c| handshakes.get(&c.peer_id).is_some_and(|h| h.accepts_denial(client_salt, mac)))
+                        .map(|conn| {
+                            conn.state = ConnectionState::Disconnected;
+                            conn.peer_id
+                        })
                 };
-                if let Ok(h) = bincode::serialize(&header) {
-                    batch.extend_from_slice(&h);
-                    batch.extend_from_slice(&msg.data);
+                if let Some(peer_id) = denied {
+                    log::warn!("Connection to {} denied", from);
+                    self.push_event(ConnectionEvent::Disconnected { peer_id, reason: DisconnectReason::Denied });
+                }
+            }
+            
+            // ---- Established sessions ----
+            PacketKind::Payload { token, .. } | PacketKind::Heartbeat { token, .. } | PacketKind::Disconnect { token } => {
+                let mut closed = None;
+                {
+                    let mut connections = self.connections.write();
+                    let conn = match connections.values_mut()
+                        .find(|c| c.address == from && c.state == ConnectionState::Connected && c.session_token == token)
+                    {
+                        Some(c) => c,
+                        None => return,
+                    };
+                    
+                    // Nothing from a secure peer counts until it authenticates
+                    let plaintext = match conn.crypto.as_mut() {
+                        Some(crypto) => match crypto.open(&packet[..len - body.len()], body) {
+                            Some(p) => p,
+                            None => {
+                                log::debug!("Dropping unauthenticated or replayed packet from {}", from);
+                                return;
+                            }
+                        },
+                        None => body.to_vec(),
+                    };
+                    let messages = match kind {
+                        PacketKind::Payload { .. } => match decode_packet(&plaintext) {
+                            Some(m) => m,
+                            None => {
+                                log::debug!("Discarding malformed payload from {}", from);
+                                return;
+                            }
+                        },
+                        _ => Vec::new(),
+                    };
+                    
+                    conn.bytes_received += len as u64;
+                    conn.packets_received += 1;
+                    conn.last_heartbeat = now;
+                    
+                    match kind {
+                        PacketKind::Disconnect { .. } => {
+                            conn.state = ConnectionState::Disconnected;
+                            closed = Some(conn.peer_id);
+                        }
+                        PacketKind::Payload { acks, .. } | PacketKind::Heartbeat { acks, .. } => {
+                            if !conn.channels.on_packet_received(acks.sequence) {
+                                log::debug!("Dropping duplicate packet {} from peer {}", acks.sequence, conn.peer_id);
+                                return;
+                            }
+                            conn.apply_acks(&acks, Instant::now());
+                            
+                            let before = conn.recv_queue.len();
+                            for (msg, wire_size) in messages {
+                                conn.channels.record_received(msg.channel, wire_size);
+                                conn.channels.receive(msg, &mut conn.recv_queue);
+                            }
+                            stats.messages_received += conn.recv_queue.len() - before;
+                        }
+                        _ => {}
+                    }
+                }
+                
+                if let Some(peer_id) = closed {
+                    log::info!("Peer {} closed the connection", peer_id);
+                    self.push_event(ConnectionEvent::Disconnected { peer_id, reason: DisconnectReason::RemoteClosed });
                 }
             }
         }
-        
-        if batch.is_empty() { None } else { Some(batch) }
+    }
+
+    /// Take every message received from a peer since the last call
+    pub fn receive_messages(&self, peer_id: u64) -> Vec<NetworkMessage> {
+        self.connections.write()
+            .get_mut(&peer_id)
+            .map(|c| c.recv_queue.drain(..).collect())
+            .unwrap_or_default()
+    }
+
+    /// Take every received message across all peers, tagged with the sender's peer id
+    pub fn drain_received(&self) -> Vec<(u64, NetworkMessage)> {
+        let mut out = Vec::new();
+        for (peer_id, conn) in self.connections.write().iter_mut() {
+            out.extend(conn.recv_queue.drain(..).map(|m| (*peer_id, m)));
+        }
+        out
     }
 
     // ============================================
@@ -382,13 +1167,6 @@ impl NetworkSystem {
     // ============================================
 
     pub fn queue_input(&mut self, input: GameInput) {
-        let mut buffer = self.input_buffer.write();
-        buffer.push_back(input.clone());
-        
-        while buffer.len() > INPUT_BUFFER_SIZE {
-            buffer.pop_front();
-        }
-        
         if self.prediction_enabled {
             let mut pending = self.pending_inputs.write();
             pending.push_back(PendingInput {
@@ -508,7 +1286,53 @@ impl NetworkSystem {
         self.visible_entities.read().contains(&entity_id)
     }
 
+    pub fn set_interest_config(&self, config: InterestConfig) {
+        self.interest.write().set_config(config);
+    }
+
+    /// Feed this frame's entity positions into the spatial hash and rebuild
+    /// every connection's relevancy set
+    pub fn update_interest(&self, snapshots: &[EntitySnapshot], dt: f32) {
+        let mut interest = self.interest.write();
+        interest.update_entities(snapshots, dt);
+        interest.update_relevancy();
+    }
+
+    /// Where `peer_id` views the world from; relevancy is centred here
+    pub fn set_viewer_position(&self, peer_id: u64, position: Vec3) {
+        self.interest.write().set_viewer(peer_id, position);
+    }
+
+    pub fn is_relevant_to(&self, peer_id: u64, entity_id: u64) -> bool {
+        self.interest.read().is_relevant(peer_id, entity_id)
+    }
+
+    /// The entity updates for `peer_id` that fit in one datagram, picked by
+    /// accumulated priority among the entities relevant to it. Deltas are
+    /// built in priority order and stop once the datagram is full.
+    pub fn prioritized_entity_updates(&self, peer_id: u64) -> Vec<ReplicatedState> {
+        let budget = MAX_PACKET_SIZE - PACKET_PREFIX_SIZE - SECURE_OVERHEAD;
+        let mut built = HashMap::new();
+        
+        let chosen = self.interest.write().select(peer_id, budget, |entity_id| {
+            let update = self.make_entity_update(peer_id, entity_id)?;
+            let size = bincode::serialized_size(&update).ok()? as usize + PACKET_HEADER_SIZE;
+            built.insert(entity_id, update);
+            Some(size)
+        });
+        
+        let updates: Vec<ReplicatedState> = chosen.into_iter().filter_map(|id| built.remove(&id)).collect();
+        for update in &updates {
+            self.record_entity_cost(update);
+        }
+        updates
+    }
+
     pub fn register_replicated_entity(&mut self, entity_id: u64, initial_state: Vec<u8>, owner: Option<u64>) {
+        let mut ctx = DeltaContext::default();
+        ctx.record(0, initial_state.clone());
+        self.delta_contexts.write().insert(entity_id, ctx);
+        
         let state = ReplicatedState {
             entity_id,
             state_data: initial_state,
@@ -525,66 +1349,150 @@ impl NetworkSystem {
         }
     }
 
+    /// Stop replicating `entity_id` and forget its history and baselines
+    pub fn unregister_replicated_entity(&self, entity_id: u64) {
+        self.replicated_entities.write().remove(&entity_id);
+        self.delta_contexts.write().remove(&entity_id);
+        self.received_baselines.write().remove(&entity_id);
+        self.authority_map.write().remove(&entity_id);
+        self.interest.write().remove_entity(entity_id);
+    }
+    
+    /// Peer that owns (has authority over) `entity_id`, if any
+    pub fn entity_owner(&self, entity_id: u64) -> Option<u64> {
+        self.authority_map.read().get(&entity_id).copied()
+    }
+    
+    pub fn set_entity_owner(&self, entity_id: u64, owner: Option<u64>) {
+        let mut authority = self.authority_map.write();
+        match owner {
+            Some(o) => authority.insert(entity_id, o),
+            None => authority.remove(&entity_id),
+        };
+    }
+    
+    /// Whether `peer_id` has not yet acknowledged the latest state of `entity_id`
+    pub fn needs_entity_update(&self, peer_id: u64, entity_id: u64) -> bool {
+        let Some(sequence) = self.replicated_entities.read().get(&entity_id).map(|s| s.sequence_number) else {
+            return false;
+        };
+        self.delta_contexts.read()
+            .get(&entity_id)
+            .and_then(|ctx| ctx.acked.get(&peer_id))
+            .is_none_or(|acked| *acked < sequence)
+    }
+    
+    /// Store a new full state for `entity_id`. Deltas are produced per peer
+    /// by `build_entity_update`, since each peer acks a different baseline.
     pub fn update_entity_state(&mut self, entity_id: u64, new_state: Vec<u8>) {
         let mut entities = self.replicated_entities.write();
         if let Some(state) = entities.get_mut(&entity_id) {
             state.sequence_number += 1;
             state.timestamp = current_timestamp_ms();
             
-            if self.delta_compression_enabled {
-                let mut contexts = self.delta_contexts.write();
-                let ctx = contexts.entry(entity_id).or_default();
-                
-                if let Some(ref last) = ctx.last_state {
-                    if let Some(delta) = compute_delta(last, &new_state) {
-                        state.state_data = delta;
-                        state.delta_from = ctx.last_sequence;
-                        state.is_delta = true;
-                        ctx.last_state = Some(new_state);
-                        ctx.last_sequence = state.sequence_number;
-                        return;
-                    }
-                }
-                
-                ctx.last_state = Some(new_state.clone());
-                ctx.last_sequence = state.sequence_number;
-            }
+            self.delta_contexts.write()
+                .entry(entity_id)
+                .or_default()
+                .record(state.sequence_number, new_state.clone());
             
             state.state_data = new_state;
+            state.delta_from = 0;
             state.is_delta = false;
         }
     }
 
     // ============================================
-    // ROLLBACK NETCODE
+    // DELTA SNAPSHOTS
     // ============================================
 
-    pub fn save_state(&mut self, frame: u32, state_hash: u32) {
-        let mut states = self.rollback_states.write();
-        
-        while states.len() >= STATE_BUFFER_SIZE {
-            states.pop_front();
+    /// Record that `peer_id` has received `entity_id` at `sequence`, making it
+    /// the baseline for that peer's future deltas
+    pub fn acknowledge_entity_state(&self, peer_id: u64, entity_id: u64, sequence: u32) {
+        if let Some(ctx) = self.delta_contexts.write().get_mut(&entity_id) {
+            let acked = ctx.acked.entry(peer_id).or_insert(sequence);
+            *acked = (*acked).max(sequence);
+        }
+    }
+
+    /// Build the update to send `peer_id` for `entity_id`: an XOR delta against
+    /// the peer's last acked state when that baseline is still in history and
+    /// the delta is smaller, otherwise the full state.
+    pub fn build_entity_update(&self, peer_id: u64, entity_id: u64) -> Option<ReplicatedState> {
+        let update = self.make_entity_update(peer_id, entity_id)?;
+        self.record_entity_cost(&update);
+        Some(update)
+    }
+    
+    fn make_entity_update(&self, peer_id: u64, entity_id: u64) -> Option<ReplicatedState> {
+        let current = self.replicated_entities.read().get(&entity_id)?.clone();
+        if !self.delta_compression_enabled {
+            return Some(current);
         }
         
-        let inputs: Vec<GameInput> = self.input_buffer.read()
-            .iter()
-            .filter(|i| i.frame >= frame.saturating_sub(60))
-            .cloned()
-            .collect();
+        let contexts = self.delta_contexts.read();
+        let ctx = contexts.get(&entity_id)?;
+        let baseline_seq = match ctx.acked.get(&peer_id) {
+            Some(seq) if *seq < current.sequence_number => *seq,
+            _ => return Some(current),
+        };
+        let baseline = match ctx.state_at(baseline_seq) {
+            Some(b) => b,
+            None => return Some(current),
+        };
         
-        states.push_back(RollbackState {
-            frame,
-            state_hash,
-            inputs,
-            timestamp: current_timestamp_ms(),
-        });
+        let delta = encode_delta(baseline, &current.state_data);
+        if delta.len() >= current.state_data.len() {
+            return Some(current);
+        }
+        
+        Some(ReplicatedState {
+            state_data: delta,
+            delta_from: baseline_seq,
+            is_delta: true,
+            ..current
+        })
     }
 
-    pub fn rollback_to(&self, frame: u32) -> Option<RollbackState> {
-        let states = self.rollback_states.read();
-        states.iter().find(|s| s.frame == frame).cloned()
+    fn record_entity_cost(&self, update: &ReplicatedState) {
+        let mut costs = self.entity_costs.write();
+        let cost = costs.entry(update.entity_id).or_insert_with(|| EntityReplicationCost {
+            entity_id: update.entity_id,
+            ..Default::default()
+        });
+        cost.updates += 1;
+        cost.delta_updates += update.is_delta as u64;
+        cost.bytes += update.state_data.len() as u64;
     }
-
+    
+    /// Reconstruct the full state carried by `update`, keeping it as a future
+    /// baseline. Deltas against a baseline we no longer (or never) had are rejected.
+    pub fn apply_entity_update(&self, update: &ReplicatedState) -> Result<Vec<u8>, NetworkError> {
+        let mut baselines = self.received_baselines.write();
+        let history = baselines.entry(update.entity_id).or_default();
+        
+        let full = if update.is_delta {
+            let baseline = history.iter()
+                .find(|(seq, _)| *seq == update.delta_from)
+                .map(|(_, state)| state.as_slice())
+                .ok_or(NetworkError::MissingBaseline {
+                    entity_id: update.entity_id,
+                    sequence: update.delta_from,
+                })?;
+            decode_delta(baseline, &update.state_data).ok_or(NetworkError::CompressionError)?
+        } else {
+            update.state_data.clone()
+        };
+        
+        if !history.iter().any(|(seq, _)| *seq == update.sequence_number) {
+            history.push_back((update.sequence_number, full.clone()));
+            while history.len() > STATE_BUFFER_SIZE {
+                history.pop_front();
+            }
+        }
+        
+        Ok(full)
+    }
+
     // ============================================
     // RTT CALCULATION
     // ============================================
@@ -689,18 +1597,66 @@ impl NetworkSystem {
             predicted_entities: self.predicted_states.read().len(),
         }
     }
+    
+    /// Newest packet sequence accepted from `peer_id`, if any has arrived
+    pub fn remote_packet_sequence(&self, peer_id: u64) -> Option<u16> {
+        self.connections.read()
+            .get(&peer_id)
+            .filter(|c| c.channels.has_remote)
+            .map(|c| c.channels.remote_sequence)
+    }
+
+    /// True once packets from `peer_id` up to `sequence` can no longer be
+    /// accepted, because they fell out of the ack window or the peer is gone
+    pub fn packet_window_passed(&self, peer_id: u64, sequence: u16) -> bool {
+        self.remote_packet_sequence(peer_id)
+            .is_none_or(|newest| sequence_greater_than(newest, sequence) && newest.wrapping_sub(sequence) > ACK_WINDOW)
+    }
+
+    /// Cumulative per-connection and per-channel counters, sorted by peer id
+    pub fn peer_traffic(&self) -> Vec<PeerTraffic> {
+        let mut peers: Vec<PeerTraffic> = self.connections.read()
+            .values()
+            .map(|c| PeerTraffic {
+                peer_id: c.peer_id,
+                bytes_sent: c.bytes_sent,
+                bytes_received: c.bytes_received,
+                packets_sent: c.packets_sent,
+                packets_received: c.packets_received,
+                rtt_ms: c.rtt_ms,
+                packet_loss: c.packet_loss,
+                resends: c.channels.resends,
+                channels: c.channels.traffic,
+            })
+            .collect();
+        peers.sort_by_key(|p| p.peer_id);
+        peers
+    }
+    
+    /// Cumulative bytes handed out by `build_entity_update` and
+    /// `prioritized_entity_updates`, sorted by entity id
+    pub fn entity_replication_costs(&self) -> Vec<EntityReplicationCost> {
+        let mut costs: Vec<EntityReplicationCost> = self.entity_costs.read().values().copied().collect();
+        costs.sort_by_key(|c| c.entity_id);
+        costs
+    }
 }
 
 // ============================================================================
 // SUPPORTING TYPES
 // ============================================================================
 
+/// Per-message wire header. Encoded with bincode's fixed-width integers so
+/// every header occupies exactly `PACKET_HEADER_SIZE` bytes.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct PacketHeader {
     peer_id: u64,
     channel: ChannelType,
     sequence: u32,
     size: u32,
+    message_id: u64,
+    timestamp: u64,
+    compressed: bool,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -711,6 +1667,57 @@ struct PositionUpdate {
     timestamp: u64,
 }
 
+/// Handshake and session control carried in front of every datagram
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+enum PacketKind {
+    ConnectRequest { client_salt: u64 },
+    Challenge { client_salt: u64, server_salt: u64 },
+    ChallengeResponse { token: u64 },
+    ConnectAccepted { token: u64, peer_id: u64 },
+    /// `mac` is zero unless the server runs in secure mode
+    ConnectDenied { client_salt: u64, mac: u64 },
+    Payload { token: u64, acks: AckHeader },
+    Heartbeat { token: u64, acks: AckHeader },
+    Disconnect { token: u64 },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct PacketPrefix {
+    protocol_id: u32,
+    kind: PacketKind,
+}
+
+/// Connection lifecycle notifications surfaced through `NetworkSystem::poll_events`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConnectionEvent {
+    Connected { peer_id: u64 },
+    Disconnected { peer_id: u64, reason: DisconnectReason },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DisconnectReason {
+    /// `disconnect` was called locally
+    Requested,
+    /// The remote side sent a disconnect
+    RemoteClosed,
+    /// No packets arrived within `connection_timeout_ms`
+    TimedOut,
+    /// The server refused the handshake (e.g. it is full)
+    Denied,
+    /// Sending to the peer's address failed, e.g. the port was refused
+    Unreachable,
+}
+
+/// Traffic moved by a single `NetworkSystem::pump` call
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PumpStats {
+    pub packets_sent: usize,
+    pub bytes_sent: usize,
+    pub packets_received: usize,
+    pub bytes_received: usize,
+    pub messages_received: usize,
+}
+
 #[derive(Debug)]
 pub struct NetworkStats {
     pub connected_peers: usize,
@@ -720,6 +1727,41 @@ pub struct NetworkStats {
     pub predicted_entities: usize,
 }
 
+/// Message traffic on one channel of a connection; bytes include message headers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChannelTraffic {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+}
+
+/// Cumulative counters for one connection
+#[derive(Clone, Debug)]
+pub struct PeerTraffic {
+    pub peer_id: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    pub rtt_ms: f32,
+    pub packet_loss: f32,
+    /// Reliable messages sent again after their resend timeout
+    pub resends: u64,
+    /// Indexed in `ChannelType::ALL` order
+    pub channels: [ChannelTraffic; 4],
+}
+
+/// Cumulative replication cost of one entity across all peers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EntityReplicationCost {
+    pub entity_id: u64,
+    pub updates: u64,
+    pub delta_updates: u64,
+    /// `state_data` bytes, after delta encoding
+    pub bytes: u64,
+}
+
 #[derive(Debug)]
 pub enum NetworkError {
     ConnectionFailed(String),
@@ -729,6 +1771,13 @@ pub enum NetworkError {
     RecvFailed,
     SerializationError,
     CompressionError,
+    /// A delta referenced a baseline the receiver does not hold
+    MissingBaseline { entity_id: u64, sequence: u32 },
+    EncryptionError,
+    /// Payload larger than `MAX_MESSAGE_PAYLOAD` even after compression
+    MessageTooLarge { size: usize, max: usize },
+    /// No open connection to the peer
+    NotConnected,
 }
 
 impl std::fmt::Display for NetworkError {
@@ -741,93 +1790,1760 @@ impl std::fmt::Display for NetworkError {
             NetworkError::RecvFailed => write!(f, "Recv failed"),
             NetworkError::SerializationError => write!(f, "Serialization error"),
             NetworkError::CompressionError => write!(f, "Compression error"),
+            NetworkError::MissingBaseline { entity_id, sequence } => {
+                write!(f, "Missing baseline {} for entity {}", sequence, entity_id)
+            }
+            NetworkError::EncryptionError => write!(f, "Encryption error"),
+            NetworkError::MessageTooLarge { size, max } => {
+                write!(f, "Message of {} bytes exceeds the {} byte limit", size, max)
+            }
+            NetworkError::NotConnected => write!(f, "Not connected"),
         }
     }
 }
 
 // ============================================================================
-// COMPRESSION UTILITIES
+// RELIABLE CHANNELS
 // ============================================================================
 
-fn compress_data(data: &[u8]) -> Vec<u8> {
-    let mut compressed = Vec::with_capacity(data.len());
-    let mut i = 0;
+/// Packet-level sequence plus acks for the last `ACK_WINDOW + 1` packets
+/// received from the remote. Bit `n` of `ack_bits` acks `ack - 1 - n`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct AckHeader {
+    sequence: u16,
+    ack: u16,
+    ack_bits: u32,
+}
+
+#[derive(Clone, Debug)]
+struct SentPacket {
+    sent_at: Instant,
+    reliable_ids: Vec<u64>,
+}
+
+/// A message encoded for the wire when it was queued; resends reuse the bytes
+#[derive(Clone, Debug)]
+struct OutgoingMessage {
+    channel: ChannelType,
+    sequence: u32,
+    bytes: Vec<u8>,
+}
+
+impl OutgoingMessage {
+    /// `message.data` is the wire payload, compressed if `compressed`
+    fn encode(peer_id: u64, message: &NetworkMessage, compressed: bool) -> Self {
+        let mut bytes = Vec::with_capacity(PACKET_HEADER_SIZE + message.data.len());
+        encode_message(&mut bytes, peer_id, message, compressed);
+        Self { channel: message.channel, sequence: message.sequence, bytes }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct PendingReliable {
+    message: OutgoingMessage,
+    last_sent: Option<Instant>,
+}
+
+/// Per-connection reliability and ordering state
+#[derive(Clone, Debug)]
+struct ChannelState {
+    message_sequences: [u32; 4],
+    local_sequence: u16,
+    sent_packets: HashMap<u16, SentPacket>,
+    next_reliable_id: u64,
+    pending_reliable: BTreeMap<u64, PendingReliable>,
     
-    while i < data.len() {
-        let byte = data[i];
-        let mut count = 1;
-        
-        while i + count < data.len() && data[i + count] == byte && count < 255 {
-            count += 1;
+    remote_sequence: u16,
+    has_remote: bool,
+    received_bits: u32,
+    ack_pending: bool,
+    
+    ordered_next: u32,
+    ordered_buffer: BTreeMap<u32, NetworkMessage>,
+    unordered_floor: u32,
+    unordered_seen: HashSet<u32>,
+    sequenced_last: Option<u32>,
+    
+    resends: u64,
+    traffic: [ChannelTraffic; 4],
+}
+
+impl Default for ChannelState {
+    fn default() -> Self {
+        Self {
+            message_sequences: [0; 4],
+            local_sequence: 0,
+            sent_packets: HashMap::new(),
+            next_reliable_id: 0,
+            pending_reliable: BTreeMap::new(),
+            // Our peer numbers packets from 0, so this acks nothing until it has sent 64k
+            remote_sequence: u16::MAX,
+            has_remote: false,
+            received_bits: 0,
+            ack_pending: false,
+            ordered_next: 0,
+            ordered_buffer: BTreeMap::new(),
+            unordered_floor: 0,
+            unordered_seen: HashSet::new(),
+            sequenced_last: None,
+            resends: 0,
+            traffic: [ChannelTraffic::default(); 4],
         }
+    }
+}
+
+impl ChannelState {
+    fn assign_sequence(&mut self, msg: &mut NetworkMessage) {
+        if msg.channel == ChannelType::UnreliableUnordered {
+            return;
+        }
+        let counter = &mut self.message_sequences[msg.channel.index()];
+        msg.sequence = *counter;
+        *counter = counter.wrapping_add(1);
+    }
+    
+    fn queue_reliable(&mut self, message: OutgoingMessage) {
+        let id = self.next_reliable_id;
+        self.next_reliable_id += 1;
+        self.pending_reliable.insert(id, PendingReliable { message, last_sent: None });
+    }
+    
+    /// Reliable messages never sent, or unacked for longer than `resend`.
+    /// Messages `RELIABLE_WINDOW` or more past their channel's oldest unacked
+    /// one wait, so the receiver never has to buffer them.
+    fn due_reliable(&mut self, now: Instant, resend: Duration) -> Vec<(u64, OutgoingMessage)> {
+        let mut due = Vec::new();
+        let mut oldest: [Option<u32>; 4] = [None; 4];
+        for (id, pending) in self.pending_reliable.iter_mut() {
+            // Ids follow each channel's sequence order, so the first one seen is the oldest
+            let sequence = pending.message.sequence;
+            let oldest = *oldest[pending.message.channel.index()].get_or_insert(sequence);
+            if sequence.wrapping_sub(oldest) >= RELIABLE_WINDOW {
+                continue;
+            }
+            let is_due = match pending.last_sent {
+                Some(sent) => now.duration_since(sent) >= resend,
+                None => true,
+            };
+            if is_due && pending.last_sent.is_some() {
+                self.resends += 1;
+            }
+            if is_due {
+                pending.last_sent = Some(now);
+                due.push((*id, pending.message.clone()));
+            }
+        }
+        due
+    }
+    
+    fn record_sent(&mut self, channel: ChannelType, wire_bytes: usize) {
+        let traffic = &mut self.traffic[channel.index()];
+        traffic.bytes_sent += wire_bytes as u64;
+        traffic.messages_sent += 1;
+    }
+    
+    fn record_received(&mut self, channel: ChannelType, wire_bytes: usize) {
+        let traffic = &mut self.traffic[channel.index()];
+        traffic.bytes_received += wire_bytes as u64;
+        traffic.messages_received += 1;
+    }
+    
+    /// Allocate the next packet sequence and record which reliable messages it carries
+    fn begin_packet(&mut self, reliable_ids: Vec<u64>, now: Instant) -> AckHeader {
+        let sequence = self.local_sequence;
+        self.local_sequence = self.local_sequence.wrapping_add(1);
+        self.sent_packets.insert(sequence, SentPacket { sent_at: now, reliable_ids });
+        self.ack_pending = false;
         
-        if count > 3 {
-            compressed.push(0xFF);
-            compressed.push(byte);
-            compressed.push(count as u8);
+        AckHeader {
+            sequence,
+            ack: self.remote_sequence,
+            ack_bits: self.received_bits,
+        }
+    }
+    
+    /// Record an incoming packet sequence. Returns false for duplicates and
+    /// packets too old to be represented in the ack window.
+    fn on_packet_received(&mut self, sequence: u16) -> bool {
+        if !self.has_remote || sequence_greater_than(sequence, self.remote_sequence) {
+            let shift = sequence.wrapping_sub(self.remote_sequence) as u32;
+            self.received_bits = self.received_bits.checked_shl(shift).unwrap_or(0);
+            if self.has_remote && shift <= ACK_WINDOW as u32 {
+                self.received_bits |= 1 << (shift - 1);
+            }
+            self.remote_sequence = sequence;
+            self.has_remote = true;
         } else {
-            for _ in 0..count {
-                compressed.push(byte);
+            let age = self.remote_sequence.wrapping_sub(sequence);
+            if age == 0 || age > ACK_WINDOW {
+                return false;
+            }
+            let bit = 1u32 << (age - 1);
+            if self.received_bits & bit != 0 {
+                return false;
+            }
+            self.received_bits |= bit;
+        }
+        
+        self.ack_pending = true;
+        true
+    }
+    
+    /// Retire every sent packet the remote acknowledged, along with the
+    /// reliable messages it carried. Returns RTT samples and the number of
+    /// packets that fell out of the ack window unacknowledged.
+    fn process_acks(&mut self, acks: &AckHeader, now: Instant) -> (Vec<Duration>, usize) {
+        let mut samples = Vec::new();
+        let mut lost = 0;
+        
+        let sequences: Vec<u16> = self.sent_packets.keys().copied().collect();
+        for sequence in sequences {
+            let age = acks.ack.wrapping_sub(sequence);
+            let acked = age == 0 || (age <= ACK_WINDOW && acks.ack_bits & (1 << (age - 1)) != 0);
+            
+            if acked {
+                if let Some(packet) = self.sent_packets.remove(&sequence) {
+                    samples.push(now.duration_since(packet.sent_at));
+                    for id in packet.reliable_ids {
+                        self.pending_reliable.remove(&id);
+                    }
+                }
+            } else if sequence_greater_than(acks.ack, sequence) && age > ACK_WINDOW {
+                // Reliable payloads are still pending and will be resent on their timer
+                self.sent_packets.remove(&sequence);
+                lost += 1;
             }
         }
         
-        i += count;
+        (samples, lost)
     }
     
-    compressed
+    /// Apply the channel's delivery rules, pushing deliverable messages onto `out`.
+    /// Reliable messages `RELIABLE_WINDOW` or more past the next undelivered
+    /// one are dropped, which bounds the ordering buffers.
+    fn receive(&mut self, msg: NetworkMessage, out: &mut VecDeque<NetworkMessage>) {
+        match msg.channel {
+            ChannelType::ReliableOrdered => {
+                if message_sequence_greater_than(self.ordered_next, msg.sequence)
+                    || msg.sequence.wrapping_sub(self.ordered_next) >= RELIABLE_WINDOW
+                {
+                    return;
+                }
+                self.ordered_buffer.entry(msg.sequence).or_insert(msg);
+                while let Some(next) = self.ordered_buffer.remove(&self.ordered_next) {
+                    out.push_back(next);
+                    self.ordered_next = self.ordered_next.wrapping_add(1);
+                }
+            }
+            ChannelType::ReliableUnordered => {
+                if message_sequence_greater_than(self.unordered_floor, msg.sequence)
+                    || msg.sequence.wrapping_sub(self.unordered_floor) >= RELIABLE_WINDOW
+                    || !self.unordered_seen.insert(msg.sequence)
+                {
+                    return;
+                }
+                out.push_back(msg);
+                while self.unordered_seen.remove(&self.unordered_floor) {
+                    self.unordered_floor = self.unordered_floor.wrapping_add(1);
+                }
+            }
+            ChannelType::UnreliableOrdered => {
+                if self.sequenced_last.is_some_and(|last| !message_sequence_greater_than(msg.sequence, last)) {
+                    return;
+                }
+                self.sequenced_last = Some(msg.sequence);
+                out.push_back(msg);
+            }
+            ChannelType::UnreliableUnordered => out.push_back(msg),
+        }
+    }
 }
 
-fn compute_delta(old: &[u8], new: &[u8]) -> Option<Vec<u8>> {
-    if new.len() > old.len() {
-        return None;
+/// Wrap-aware comparison of 16-bit packet sequences
+fn sequence_greater_than(a: u16, b: u16) -> bool {
+    (a > b && a - b <= 32768) || (a < b && b - a > 32768)
+}
+
+/// Wrap-aware comparison of 32-bit message sequences
+fn message_sequence_greater_than(a: u32, b: u32) -> bool {
+    a != b && a.wrapping_sub(b) <= u32::MAX / 2
+}
+
+fn resend_timeout(smoothed_rtt_ms: f32) -> Duration {
+    let ms = if smoothed_rtt_ms > 0.0 {
+        (smoothed_rtt_ms * RESEND_RTT_FACTOR).clamp(MIN_RESEND_MS, MAX_RESEND_MS)
+    } else {
+        INITIAL_RESEND_MS
+    };
+    Duration::from_secs_f32(ms / 1000.0)
+}
+
+// ============================================================================
+// LINK CONDITIONER
+// ============================================================================
+
+/// Simulated network impairments. Probabilities are in `[0, 1]`.
+#[derive(Clone, Copy, Debug)]
+pub struct LinkConditionerConfig {
+    pub latency_ms: u64,
+    /// Uniform +/- variation applied to `latency_ms`
+    pub jitter_ms: u64,
+    pub loss: f32,
+    pub duplicate: f32,
+    /// Chance a packet is held back an extra `reorder_delay_ms` so later packets overtake it
+    pub reorder: f32,
+    pub reorder_delay_ms: u64,
+    pub seed: u64,
+}
+
+impl Default for LinkConditionerConfig {
+    fn default() -> Self {
+        Self {
+            latency_ms: 0,
+            jitter_ms: 0,
+            loss: 0.0,
+            duplicate: 0.0,
+            reorder: 0.0,
+            reorder_delay_ms: 30,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LinkConditionerStats {
+    pub submitted: u64,
+    pub dropped: u64,
+    pub duplicated: u64,
+    pub reordered: u64,
+}
+
+struct DelayedPacket {
+    deliver_at: Instant,
+    order: u64,
+    to: SocketAddr,
+    data: Vec<u8>,
+}
+
+/// Holds outgoing datagrams and releases them according to `LinkConditionerConfig`.
+/// Every decision comes from one seeded RNG, so a given packet sequence is
+/// always impaired the same way.
+pub struct LinkConditioner {
+    config: LinkConditionerConfig,
+    rng: SmallRng,
+    queue: Vec<DelayedPacket>,
+    next_order: u64,
+    stats: LinkConditionerStats,
+}
+
+impl LinkConditioner {
+    pub fn new(config: LinkConditionerConfig) -> Self {
+        Self {
+            config,
+            rng: SmallRng::seed_from_u64(config.seed),
+            queue: Vec::new(),
+            next_order: 0,
+            stats: LinkConditionerStats::default(),
+        }
+    }
+    
+    pub fn stats(&self) -> LinkConditionerStats {
+        self.stats
     }
     
-    let mut delta = Vec::with_capacity(new.len());
-    for (i, (o, n)) in old.iter().zip(new.iter()).enumerate() {
-        if *o != *n {
-            delta.push(i as u8);
-            delta.push(*n);
+    pub fn submit(&mut self, to: SocketAddr, packet: &[u8], now: Instant) {
+        self.stats.submitted += 1;
+        
+        // Draw every roll up front so the RNG stream doesn't depend on outcomes
+        let lost = self.rng.gen::<f32>() < self.config.loss;
+        let duplicated = self.rng.gen::<f32>() < self.config.duplicate;
+        let reordered = self.rng.gen::<f32>() < self.config.reorder;
+        
+        if lost {
+            self.stats.dropped += 1;
+            return;
+        }
+        
+        let copies = if duplicated { 2 } else { 1 };
+        self.stats.duplicated += duplicated as u64;
+        self.stats.reordered += reordered as u64;
+        
+        for _ in 0..copies {
+            let mut delay_ms = self.config.latency_ms as i64;
+            if self.config.jitter_ms > 0 {
+                let jitter = self.config.jitter_ms as i64;
+                delay_ms += self.rng.gen_range(-jitter..=jitter);
+            }
+            if reordered {
+                delay_ms += self.config.reorder_delay_ms as i64;
+            }
+            
+            self.queue.push(DelayedPacket {
+                deliver_at: now + Duration::from_millis(delay_ms.max(0) as u64),
+                order: self.next_order,
+                to,
+                data: packet.to_vec(),
+            });
+            self.next_order += 1;
         }
     }
     
-    if delta.len() < new.len() / 2 {
-        Some(delta)
-    } else {
-        None
+    /// Remove and return every packet due at `now`, in delivery order
+    pub fn take_due(&mut self, now: Instant) -> Vec<(SocketAddr, Vec<u8>)> {
+        let (mut due, pending): (Vec<_>, Vec<_>) = self.queue.drain(..).partition(|p| p.deliver_at <= now);
+        self.queue = pending;
+        due.sort_by_key(|p| (p.deliver_at, p.order));
+        due.into_iter().map(|p| (p.to, p.data)).collect()
+    }
+    
+    pub fn in_flight(&self) -> usize {
+        self.queue.len()
     }
 }
 
-fn current_timestamp_ms() -> u64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .map(|d| d.as_millis() as u64)
-        .unwrap_or(0)
+// ============================================================================
+// SECURE SESSIONS
+// ============================================================================
+
+/// Credentials for connecting to a server in secure mode. The keys travel to
+/// the client out of band (e.g. over HTTPS from matchmaking); only `sealed`
+/// is sent on the wire, and only the server's private key can open it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ConnectToken {
+    pub client_id: u64,
+    pub expires_at: u64,
+    pub client_to_server_key: [u8; 32],
+    pub server_to_client_key: [u8; 32],
+    pub sealed: Vec<u8>,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl ConnectToken {
+    /// Issue a token with fresh random keys, valid until `expires_at` (unix ms)
+    pub fn issue(private_key: &[u8; 32], client_id: u64, expires_at: u64) -> Result<Self, NetworkError> {
+        let contents = TokenContents {
+            client_id,
+            expires_at,
+            client_to_server_key: rand::random(),
+            server_to_client_key: rand::random(),
+        };
+        let plaintext = bincode::serialize(&contents).map_err(|_| NetworkError::SerializationError)?;
+        
+        let nonce: [u8; 12] = rand::random();
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(private_key));
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), AeadPayload { msg: &plaintext, aad: &PROTOCOL_ID.to_le_bytes() })
+            .map_err(|_| NetworkError::EncryptionError)?;
+        
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        
+        Ok(Self {
+            client_id,
+            expires_at,
+            client_to_server_key: contents.client_to_server_key,
+            server_to_client_key: contents.server_to_client_key,
+            sealed,
+        })
+    }
+    
+    fn contents(&self) -> TokenContents {
+        TokenContents {
+            client_id: self.client_id,
+            expires_at: self.expires_at,
+            client_to_server_key: self.client_to_server_key,
+            server_to_client_key: self.server_to_client_key,
+        }
+    }
+}
 
-    #[test]
-    fn test_delta_compression() {
-        let old = vec![1, 2, 3, 4, 5, 6, 7, 8];
-        let new = vec![1, 2, 3, 9, 5, 10, 7, 8];
+#[derive(Clone, Serialize, Deserialize)]
+struct TokenContents {
+    client_id: u64,
+    expires_at: u64,
+    client_to_server_key: [u8; 32],
+    server_to_client_key: [u8; 32],
+}
+
+fn open_connect_token(private_key: &[u8; 32], sealed: &[u8]) -> Option<TokenContents> {
+    if sealed.len() < 12 {
+        return None;
+    }
+    let (nonce, ciphertext) = sealed.split_at(12);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(private_key));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), AeadPayload { msg: ciphertext, aad: &PROTOCOL_ID.to_le_bytes() })
+        .ok()?;
+    bincode::deserialize(&plaintext).ok()
+}
+
+/// Per-connection keys, bound to this handshake's salts so a reused token
+/// never reuses a key
+#[derive(Clone)]
+struct SessionKeys {
+    send: [u8; 32],
+    recv: [u8; 32],
+    token: u64,
+}
+
+impl SessionKeys {
+    fn derive(contents: &TokenContents, client_salt: u64, server_salt: u64, is_client: bool) -> Self {
+        let mut salts = [0u8; 16];
+        salts[..8].copy_from_slice(&client_salt.to_le_bytes());
+        salts[8..].copy_from_slice(&server_salt.to_le_bytes());
+        
+        let c2s = hmac_sha256(&contents.client_to_server_key, &[b"slop c2s", &salts]);
+        let s2c = hmac_sha256(&contents.server_to_client_key, &[b"slop s2c", &salts]);
+        let id = hmac_sha256(&c2s, &[b"slop token", &salts]);
+        let token = u64::from_le_bytes(id[..8].try_into().unwrap_or_default()).max(1);
         
-        if let Some(delta) = compute_delta(&old, &new) {
-            assert!(delta.len() < new.len());
+        if is_client {
+            Self { send: c2s, recv: s2c, token }
+        } else {
+            Self { send: s2c, recv: c2s, token }
         }
     }
+}
 
-    #[test]
-    fn test_entity_snapshot() {
-        let entity = EntitySnapshot::new(1, Vec3::new(0.0, 0.0, 0.0), Vec3::ZERO, Vec3::new(1.0, 1.0, 1.0));
-        assert_eq!(entity.id, 1);
-        assert_eq!(entity.center(), Vec3::ZERO);
+/// Proof that a denial came from a server able to open the client's token
+fn denial_mac(server_to_client_key: &[u8; 32], client_salt: u64) -> u64 {
+    let mac = hmac_sha256(server_to_client_key, &[b"slop deny", &client_salt.to_le_bytes()]);
+    u64::from_le_bytes(mac[..8].try_into().unwrap_or_default())
+}
+
+fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    for part in parts {
+        mac.update(part);
     }
+    mac.finalize().into_bytes().into()
+}
 
-    #[test]
-    fn test_network_system_creation() {
-        let net = NetworkSystem::new(NetworkRole::Server);
-        assert_eq!(net.peer_count(), 0);
+/// Sliding bitmap of recently accepted sequences
+#[derive(Clone, Debug, Default)]
+struct ReplayWindow {
+    highest: Option<u64>,
+    seen: u128,
+}
+
+impl ReplayWindow {
+    fn is_fresh(&self, sequence: u64) -> bool {
+        match self.highest {
+            None => true,
+            Some(highest) if sequence > highest => true,
+            Some(highest) => {
+                let age = highest - sequence;
+                age < REPLAY_WINDOW && self.seen & (1u128 << age) == 0
+            }
+        }
+    }
+    
+    fn mark(&mut self, sequence: u64) {
+        match self.highest {
+            Some(highest) if sequence <= highest => {
+                self.seen |= 1u128 << (highest - sequence);
+            }
+            Some(highest) => {
+                self.seen = self.seen.checked_shl((sequence - highest) as u32).unwrap_or(0) | 1;
+                self.highest = Some(sequence);
+            }
+            None => {
+                self.seen = 1;
+                self.highest = Some(sequence);
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct SessionCrypto {
+    keys: SessionKeys,
+    send_sequence: u64,
+    replay: ReplayWindow,
+}
+
+// Hand-written so keys never end up in logs
+impl std::fmt::Debug for SessionCrypto {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionCrypto")
+            .field("send_sequence", &self.send_sequence)
+            .field("replay", &self.replay)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SessionCrypto {
+    fn new(keys: SessionKeys) -> Self {
+        Self { keys, send_sequence: 0, replay: ReplayWindow::default() }
+    }
+    
+    /// `prefix || sequence || AES-GCM(body)`, with the plaintext prefix as associated data
+    fn seal(&mut self, prefix: Vec<u8>, body: &[u8]) -> Vec<u8> {
+        let sequence = self.send_sequence;
+        self.send_sequence += 1;
+        
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.keys.send));
+        let ciphertext = cipher
+            .encrypt(&sequence_nonce(sequence), AeadPayload { msg: body, aad: &prefix })
+            .unwrap_or_default();
+        
+        let mut packet = prefix;
+        packet.extend_from_slice(&sequence.to_le_bytes());
+        packet.extend_from_slice(&ciphertext);
+        packet
+    }
+    
+    /// Authenticate and decrypt; `None` for forgeries, tampering and replays
+    fn open(&mut self, prefix: &[u8], body: &[u8]) -> Option<Vec<u8>> {
+        if body.len() < SECURE_OVERHEAD {
+            return None;
+        }
+        let (sequence, ciphertext) = body.split_at(8);
+        let sequence = u64::from_le_bytes(sequence.try_into().ok()?);
+        if !self.replay.is_fresh(sequence) {
+            return None;
+        }
+        
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.keys.recv));
+        let plaintext = cipher
+            .decrypt(&sequence_nonce(sequence), AeadPayload { msg: ciphertext, aad: prefix })
+            .ok()?;
+        self.replay.mark(sequence);
+        Some(plaintext)
+    }
+}
+
+/// Each direction has its own key, so the sequence alone keeps nonces unique
+fn sequence_nonce(sequence: u64) -> Nonce<aes_gcm::aead::consts::U12> {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&sequence.to_le_bytes());
+    *Nonce::from_slice(&nonce)
+}
+
+/// Build a datagram, sealing session packets when the connection is secure
+fn seal_packet(conn: &mut Connection, kind: PacketKind, body: &[u8]) -> Vec<u8> {
+    let prefix = encode_prefix(&kind);
+    let is_session = matches!(kind, PacketKind::Payload { .. } | PacketKind::Heartbeat { .. } | PacketKind::Disconnect { .. });
+    match conn.crypto.as_mut() {
+        Some(crypto) if is_session => crypto.seal(prefix, body),
+        _ => {
+            let mut packet = prefix;
+            packet.extend_from_slice(body);
+            packet
+        }
+    }
+}
+
+// ============================================================================
+// BIT PACKING & DELTA SNAPSHOTS
+// ============================================================================
+
+/// Appends values of arbitrary bit width, least significant bit first
+#[derive(Default)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    scratch: u64,
+    scratch_bits: u32,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn write_bits(&mut self, value: u32, bits: u32) {
+        debug_assert!(bits <= 32);
+        if bits == 0 {
+            return;
+        }
+        let masked = value as u64 & ((1u64 << bits) - 1);
+        self.scratch |= masked << self.scratch_bits;
+        self.scratch_bits += bits;
+        while self.scratch_bits >= 8 {
+            self.bytes.push(self.scratch as u8);
+            self.scratch >>= 8;
+            self.scratch_bits -= 8;
+        }
+    }
+    
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_bits(value as u32, 1);
+    }
+    
+    /// Seven bits per group, high bit set while more groups follow
+    pub fn write_varint(&mut self, mut value: u32) {
+        loop {
+            let group = value & 0x7F;
+            value >>= 7;
+            self.write_bits(group | if value != 0 { 0x80 } else { 0 }, 8);
+            if value == 0 {
+                break;
+            }
+        }
+    }
+    
+    /// Flush any partial byte, zero-padded
+    pub fn finish(mut self) -> Vec<u8> {
+        if self.scratch_bits > 0 {
+            self.bytes.push(self.scratch as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Reads back what `BitWriter` produced; every read fails once the data runs out
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    bit_pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, bit_pos: 0 }
+    }
+    
+    pub fn read_bits(&mut self, bits: u32) -> Option<u32> {
+        debug_assert!(bits <= 32);
+        if self.bit_pos + bits as usize > self.data.len() * 8 {
+            return None;
+        }
+        let mut value = 0u64;
+        for i in 0..bits as usize {
+            let pos = self.bit_pos + i;
+            let bit = (self.data[pos / 8] >> (pos % 8)) & 1;
+            value |= (bit as u64) << i;
+        }
+        self.bit_pos += bits as usize;
+        Some(value as u32)
+    }
+    
+    pub fn read_bool(&mut self) -> Option<bool> {
+        self.read_bits(1).map(|b| b != 0)
+    }
+    
+    pub fn read_varint(&mut self) -> Option<u32> {
+        let mut value = 0u32;
+        for shift in (0..35).step_by(7) {
+            let group = self.read_bits(8)?;
+            value |= (group & 0x7F).checked_shl(shift)?;
+            if group & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+/// Map `value` in `[min, max]` onto an unsigned integer of `bits` bits
+pub fn quantize_float(value: f32, min: f32, max: f32, bits: u32) -> u32 {
+    let steps = ((1u64 << bits) - 1) as f32;
+    let t = ((value - min) / (max - min)).clamp(0.0, 1.0);
+    (t * steps).round() as u32
+}
+
+pub fn dequantize_float(quantized: u32, min: f32, max: f32, bits: u32) -> f32 {
+    let steps = ((1u64 << bits) - 1) as f32;
+    min + (quantized as f32 / steps) * (max - min)
+}
+
+/// Ranges and precision used to quantize `EntitySnapshot` transforms
+#[derive(Clone, Copy, Debug)]
+pub struct SnapshotQuantization {
+    pub world_min: Vec3,
+    pub world_max: Vec3,
+    pub position_bits: u32,
+    pub rotation_bits: u32,
+    pub max_scale: f32,
+    pub scale_bits: u32,
+}
+
+impl Default for SnapshotQuantization {
+    fn default() -> Self {
+        // ~0.8cm position and ~0.09 degree rotation precision
+        Self {
+            world_min: Vec3::splat(-1024.0),
+            world_max: Vec3::splat(1024.0),
+            position_bits: 18,
+            rotation_bits: 12,
+            max_scale: 16.0,
+            scale_bits: 10,
+        }
+    }
+}
+
+impl EntitySnapshot {
+    /// Bit-pack the transform for replication; suitable as `ReplicatedState::state_data`
+    pub fn quantize(&self, q: &SnapshotQuantization) -> Vec<u8> {
+        let mut writer = BitWriter::new();
+        for axis in 0..3 {
+            writer.write_bits(quantize_float(self.position[axis], q.world_min[axis], q.world_max[axis], q.position_bits), q.position_bits);
+        }
+        for axis in 0..3 {
+            writer.write_bits(quantize_float(self.rotation[axis], -std::f32::consts::PI, std::f32::consts::PI, q.rotation_bits), q.rotation_bits);
+        }
+        for axis in 0..3 {
+            writer.write_bits(quantize_float(self.scale[axis], 0.0, q.max_scale, q.scale_bits), q.scale_bits);
+        }
+        writer.finish()
+    }
+    
+    pub fn dequantize(id: u64, data: &[u8], q: &SnapshotQuantization) -> Option<Self> {
+        let mut reader = BitReader::new(data);
+        let mut position = Vec3::ZERO;
+        let mut rotation = Vec3::ZERO;
+        let mut scale = Vec3::ZERO;
+        for axis in 0..3 {
+            position[axis] = dequantize_float(reader.read_bits(q.position_bits)?, q.world_min[axis], q.world_max[axis], q.position_bits);
+        }
+        for axis in 0..3 {
+            rotation[axis] = dequantize_float(reader.read_bits(q.rotation_bits)?, -std::f32::consts::PI, std::f32::consts::PI, q.rotation_bits);
+        }
+        for axis in 0..3 {
+            scale[axis] = dequantize_float(reader.read_bits(q.scale_bits)?, 0.0, q.max_scale, q.scale_bits);
+        }
+        Some(Self::new(id, position, rotation, scale))
+    }
+}
+
+/// XOR `current` against `baseline` word by word and bit-pack the result.
+/// Unchanged words cost one bit; changed words store only their significant bits.
+pub fn encode_delta(baseline: &[u8], current: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    writer.write_varint(current.len() as u32);
+    
+    for (index, chunk) in current.chunks(4).enumerate() {
+        let word = xor_word(baseline, index * 4, chunk);
+        if word == 0 {
+            writer.write_bool(false);
+        } else {
+            let significant = 32 - word.leading_zeros();
+            writer.write_bool(true);
+            writer.write_bits(significant - 1, 5);
+            writer.write_bits(word, significant);
+        }
+    }
+    
+    writer.finish()
+}
+
+/// Inverse of `encode_delta`. `None` if the delta is truncated or malformed.
+pub fn decode_delta(baseline: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let mut reader = BitReader::new(delta);
+    let len = reader.read_varint()? as usize;
+    if len > delta.len().saturating_mul(32) {
+        return None;
+    }
+    
+    let mut out = Vec::with_capacity(len);
+    for offset in (0..len).step_by(4) {
+        let width = (len - offset).min(4);
+        let word = if reader.read_bool()? {
+            let significant = reader.read_bits(5)? + 1;
+            reader.read_bits(significant)?
+        } else {
+            0
+        };
+        let bytes = word.to_le_bytes();
+        for (i, byte) in bytes.iter().enumerate().take(width) {
+            out.push(byte ^ baseline.get(offset + i).copied().unwrap_or(0));
+        }
+        if width < 4 && bytes[width..].iter().any(|b| *b != 0) {
+            return None;
+        }
+    }
+    
+    Some(out)
+}
+
+fn xor_word(baseline: &[u8], offset: usize, chunk: &[u8]) -> u32 {
+    let mut bytes = [0u8; 4];
+    for (i, byte) in chunk.iter().enumerate() {
+        bytes[i] = byte ^ baseline.get(offset + i).copied().unwrap_or(0);
+    }
+    u32::from_le_bytes(bytes)
+}
+
+// ============================================================================
+// PACKET ENCODING
+// ============================================================================
+
+fn encode_prefix(kind: &PacketKind) -> Vec<u8> {
+    let prefix = PacketPrefix { protocol_id: PROTOCOL_ID, kind: *kind };
+    bincode::serialize(&prefix).unwrap_or_default()
+}
+
+/// Strip the packet prefix, rejecting datagrams that don't carry our protocol id
+fn decode_prefix(mut packet: &[u8]) -> Option<(PacketKind, &[u8])> {
+    let prefix: PacketPrefix = bincode::deserialize_from(&mut packet).ok()?;
+    if prefix.protocol_id != PROTOCOL_ID {
+        return None;
+    }
+    Some((prefix.kind, packet))
+}
+
+fn encode_message(packet: &mut Vec<u8>, peer_id: u64, msg: &NetworkMessage, compressed: bool) {
+    let header = PacketHeader {
+        peer_id,
+        channel: msg.channel,
+        sequence: msg.sequence,
+        size: msg.data.len() as u32,
+        message_id: msg.id,
+        timestamp: msg.timestamp,
+        compressed,
+    };
+    if let Ok(h) = bincode::serialize(&header) {
+        packet.extend_from_slice(&h);
+        packet.extend_from_slice(&msg.data);
+    }
+}
+
+/// Split a datagram back into messages, each with its size on the wire. Returns
+/// `None` if any header or payload is truncated or a compressed payload fails to expand.
+fn decode_packet(mut packet: &[u8]) -> Option<Vec<(NetworkMessage, usize)>> {
+    let mut messages = Vec::new();
+    
+    while !packet.is_empty() {
+        let header: PacketHeader = bincode::deserialize_from(&mut packet).ok()?;
+        let size = header.size as usize;
+        if size > packet.len() {
+            return None;
+        }
+        let (payload, rest) = packet.split_at(size);
+        packet = rest;
+        
+        let data = if header.compressed {
+            decompress_data(payload)?
+        } else {
+            payload.to_vec()
+        };
+        
+        messages.push((NetworkMessage {
+            id: header.message_id,
+            channel: header.channel,
+            data,
+            timestamp: header.timestamp,
+            sequence: header.sequence,
+        }, PACKET_HEADER_SIZE + size));
+    }
+    
+    Some(messages)
+}
+
+/// Generic payload packing: a delta against an empty baseline, which trims
+/// zero bytes and leading zero bits from every word
+fn compress_data(data: &[u8]) -> Vec<u8> {
+    encode_delta(&[], data)
+}
+
+fn decompress_data(data: &[u8]) -> Option<Vec<u8>> {
+    decode_delta(&[], data)
+}
+
+fn current_timestamp_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Loopback harness shared by the tests of every module built on `NetworkSystem`
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+
+    /// Pump both ends until `done` holds. False if it never did.
+    pub(crate) fn pump_until(a: &NetworkSystem, b: &NetworkSystem, mut done: impl FnMut() -> bool) -> bool {
+        for _ in 0..200 {
+            a.pump().unwrap();
+            b.pump().unwrap();
+            if done() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        false
+    }
+
+    /// Server and client connected over localhost. The `u64` is the
+    /// client's peer id for the server.
+    pub(crate) fn connected_pair() -> (NetworkSystem, NetworkSystem, u64) {
+        let mut server = NetworkSystem::new(NetworkRole::Server);
+        let server_addr = server.listen("127.0.0.1:0").unwrap();
+        
+        let mut client = NetworkSystem::new(NetworkRole::Client);
+        let peer = client.connect(&server_addr.to_string()).unwrap();
+        assert_eq!(client.peer_count(), 0);
+        
+        assert!(pump_until(&client, &server, || client.peer_count() == 1 && server.peer_count() == 1));
+        (server, client, peer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_support::{connected_pair, pump_until};
+    use crate::rollback::{RollbackCallbacks, RollbackConfig, RollbackError, RollbackEvent, RollbackSession};
+
+    #[test]
+    fn test_delta_compression() {
+        let old: Vec<u8> = (0..64).collect();
+        let mut new = old.clone();
+        new[3] = 9;
+        new[40] = 41;
+        new.extend_from_slice(&[7, 7]);
+        
+        let delta = encode_delta(&old, &new);
+        assert!(delta.len() < new.len() / 4);
+        assert_eq!(decode_delta(&old, &delta).unwrap(), new);
+        
+        // Shrinking states and empty baselines roundtrip too
+        assert_eq!(decode_delta(&new, &encode_delta(&new, &old)).unwrap(), old);
+        assert_eq!(decode_delta(&[], &encode_delta(&[], &new)).unwrap(), new);
+        assert!(decode_delta(&old, &delta[..delta.len() - 2]).is_none());
+    }
+
+    #[test]
+    fn test_entity_snapshot() {
+        let entity = EntitySnapshot::new(1, Vec3::new(0.0, 0.0, 0.0), Vec3::ZERO, Vec3::new(1.0, 1.0, 1.0));
+        assert_eq!(entity.id, 1);
+        assert_eq!(entity.center(), Vec3::ZERO);
+    }
+
+    #[test]
+    fn test_network_system_creation() {
+        let net = NetworkSystem::new(NetworkRole::Server);
+        assert_eq!(net.peer_count(), 0);
+    }
+
+    #[test]
+    fn test_bit_packing_and_quantization() {
+        let mut writer = BitWriter::new();
+        writer.write_bits(5, 3);
+        writer.write_bool(true);
+        writer.write_varint(300);
+        writer.write_bits(u32::MAX, 32);
+        let bytes = writer.finish();
+        
+        let mut reader = BitReader::new(&bytes);
+        assert_eq!(reader.read_bits(3), Some(5));
+        assert_eq!(reader.read_bool(), Some(true));
+        assert_eq!(reader.read_varint(), Some(300));
+        assert_eq!(reader.read_bits(32), Some(u32::MAX));
+        assert_eq!(reader.read_bits(8), None);
+        
+        let q = SnapshotQuantization::default();
+        let snapshot = EntitySnapshot::new(7, Vec3::new(12.34, -5.0, 900.0), Vec3::new(0.5, -3.0, 1.0), Vec3::ONE);
+        let packed = snapshot.quantize(&q);
+        assert_eq!(packed.len(), 15);
+        
+        let restored = EntitySnapshot::dequantize(7, &packed, &q).unwrap();
+        assert!((restored.position - snapshot.position).abs().max_element() < 0.01);
+        assert!((restored.rotation - snapshot.rotation).abs().max_element() < 0.002);
+        assert!((restored.scale - snapshot.scale).abs().max_element() < 0.01);
+    }
+
+    #[test]
+    fn test_entity_delta_against_acked_baseline() {
+        let mut server = NetworkSystem::new(NetworkRole::Server);
+        let client = NetworkSystem::new(NetworkRole::Client);
+        let q = SnapshotQuantization::default();
+        let mut snapshot = EntitySnapshot::new(1, Vec3::new(10.0, 0.0, 10.0), Vec3::ZERO, Vec3::ONE);
+        
+        server.register_replicated_entity(1, snapshot.quantize(&q), None);
+        let full = server.build_entity_update(5, 1).unwrap();
+        assert!(!full.is_delta);
+        assert_eq!(client.apply_entity_update(&full).unwrap(), full.state_data);
+        server.acknowledge_entity_state(5, 1, full.sequence_number);
+        
+        snapshot.position.x += 0.5;
+        server.update_entity_state(1, snapshot.quantize(&q));
+        let delta = server.build_entity_update(5, 1).unwrap();
+        assert!(delta.is_delta);
+        assert_eq!(delta.delta_from, 0);
+        assert!(delta.state_data.len() < full.state_data.len());
+        
+        let restored = client.apply_entity_update(&delta).unwrap();
+        assert_eq!(restored, snapshot.quantize(&q));
+        
+        // A client that never received the baseline must refuse the delta
+        let fresh = NetworkSystem::new(NetworkRole::Client);
+        assert!(matches!(
+            fresh.apply_entity_update(&delta),
+            Err(NetworkError::MissingBaseline { entity_id: 1, sequence: 0 })
+        ));
+        
+        // Unacked peers keep getting full states
+        assert!(!server.build_entity_update(6, 1).unwrap().is_delta);
+    }
+
+    #[test]
+    fn test_packet_encode_decode() {
+        let msg = NetworkMessage {
+            id: 42,
+            channel: ChannelType::ReliableOrdered,
+            data: vec![1, 2, 3],
+            timestamp: 1000,
+            sequence: 7,
+        };
+        let mut packet = Vec::new();
+        encode_message(&mut packet, 1, &msg, false);
+        encode_message(&mut packet, 1, &msg, false);
+        assert_eq!(packet.len(), 2 * (PACKET_HEADER_SIZE + 3));
+        
+        let decoded = decode_packet(&packet).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].0.id, 42);
+        assert_eq!(decoded[0].0.data, vec![1, 2, 3]);
+        assert_eq!(decoded[1].0.sequence, 7);
+        assert_eq!(decoded[1].1, PACKET_HEADER_SIZE + 3);
+        
+        assert!(decode_packet(&packet[..packet.len() - 1]).is_none());
+    }
+
+    #[test]
+    fn test_packet_prefix_size() {
+        let prefix = encode_prefix(&PacketKind::Payload { token: u64::MAX, acks: AckHeader::default() });
+        assert_eq!(prefix.len(), PACKET_PREFIX_SIZE);
+        assert!(decode_prefix(&[0u8; PACKET_PREFIX_SIZE]).is_none());
+    }
+
+    #[test]
+    fn test_handshake_assigns_peer_ids() {
+        let (server, client, peer) = connected_pair();
+        
+        assert_eq!(client.poll_events(), vec![ConnectionEvent::Connected { peer_id: peer }]);
+        let server_peer = match server.poll_events().as_slice() {
+            [ConnectionEvent::Connected { peer_id }] => *peer_id,
+            other => panic!("unexpected events {:?}", other),
+        };
+        
+        let conn = client.connections.read().get(&peer).cloned().unwrap();
+        assert_eq!(conn.assigned_id, Some(server_peer));
+        assert_ne!(conn.session_token, 0);
+        assert_eq!(server.connections.read()[&server_peer].session_token, conn.session_token);
+    }
+
+    #[test]
+    fn test_loopback_pump() {
+        let (server, client, peer) = connected_pair();
+        
+        // Payloads large enough to exercise compression and multi-datagram batching
+        for i in 0..8u32 {
+            client.send_message(peer, NetworkMessage {
+                id: i as u64,
+                channel: ChannelType::ReliableOrdered,
+                data: vec![i as u8; 600],
+                timestamp: 0,
+                sequence: i,
+            }).unwrap();
+        }
+        
+        let mut received = Vec::new();
+        assert!(pump_until(&client, &server, || {
+            received.extend(server.drain_received());
+            received.len() == 8
+        }));
+        assert_eq!(received[3].1.data, vec![3u8; 600]);
+        
+        // Reply travels back to the client over the same socket pair
+        let (client_peer, _) = received[0];
+        server.send_message(client_peer, NetworkMessage {
+            id: 99,
+            channel: ChannelType::UnreliableUnordered,
+            data: vec![9; 4],
+            timestamp: 0,
+            sequence: 0,
+        }).unwrap();
+        
+        let mut replies = Vec::new();
+        assert!(pump_until(&server, &client, || {
+            replies.extend(client.receive_messages(peer));
+            !replies.is_empty()
+        }));
+        assert_eq!(replies[0].id, 99);
+    }
+
+    #[test]
+    fn test_disconnect_notifies_remote() {
+        let (server, mut client, peer) = connected_pair();
+        server.poll_events();
+        client.poll_events();
+        
+        client.disconnect(peer);
+        assert_eq!(client.connections.read()[&peer].state, ConnectionState::Disconnecting);
+        
+        assert!(pump_until(&client, &server, || server.peer_count() == 0));
+        assert!(client.connections.read().is_empty());
+        assert!(matches!(
+            client.poll_events().as_slice(),
+            [ConnectionEvent::Disconnected { reason: DisconnectReason::Requested, .. }]
+        ));
+        assert!(matches!(
+            server.poll_events().as_slice(),
+            [ConnectionEvent::Disconnected { reason: DisconnectReason::RemoteClosed, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_silent_peer_times_out() {
+        let (mut server, _client, _) = connected_pair();
+        server.poll_events();
+        server.connection_timeout_ms = 20;
+        
+        // The client stops pumping, so its heartbeats stop too
+        std::thread::sleep(Duration::from_millis(40));
+        server.pump().unwrap();
+        
+        assert_eq!(server.peer_count(), 0);
+        assert!(matches!(
+            server.poll_events().as_slice(),
+            [ConnectionEvent::Disconnected { reason: DisconnectReason::TimedOut, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_unreachable_peer_does_not_stop_pump() {
+        let (server, client, peer) = connected_pair();
+        let client_peer = server.connected_peers()[0];
+        server.poll_events();
+        
+        // Broadcast without SO_BROADCAST: every send to this peer fails
+        let mut bogus = Connection::new(999, "255.255.255.255:9".parse().unwrap());
+        bogus.state = ConnectionState::Connected;
+        bogus.session_token = 1;
+        bogus.last_heartbeat = current_timestamp_ms();
+        server.connections.write().insert(999, bogus);
+        
+        server.send_message(999, message(ChannelType::ReliableOrdered, 0)).unwrap();
+        server.send_message(client_peer, message(ChannelType::ReliableOrdered, 0)).unwrap();
+        server.pump().unwrap();
+        
+        // One failed send could be transient, so the peer survives it
+        assert!(server.poll_events().is_empty());
+        assert_eq!(server.send_failures.lock().get(&"255.255.255.255:9".parse().unwrap()), Some(&1));
+        
+        // Resends keep failing until the streak reaches the limit
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut events = Vec::new();
+        while events.is_empty() && Instant::now() < deadline {
+            server.pump().unwrap();
+            events = server.poll_events();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(events, vec![ConnectionEvent::Disconnected { peer_id: 999, reason: DisconnectReason::Unreachable }]);
+        assert_eq!(server.connected_peers(), vec![client_peer]);
+        assert!(server.send_failures.lock().is_empty());
+        
+        let mut received = Vec::new();
+        assert!(pump_until(&server, &client, || {
+            received.extend(client.receive_messages(peer));
+            !received.is_empty()
+        }));
+    }
+
+    #[test]
+    fn test_full_server_denies() {
+        let mut server = NetworkSystem::new(NetworkRole::Server);
+        server.max_peers = 0;
+        let server_addr = server.listen("127.0.0.1:0").unwrap();
+        
+        let mut client = NetworkSystem::new(NetworkRole::Client);
+        let peer = client.connect(&server_addr.to_string()).unwrap();
+        
+        let mut events = Vec::new();
+        assert!(pump_until(&client, &server, || {
+            events.extend(client.poll_events());
+            !events.is_empty()
+        }));
+        assert_eq!(events[0], ConnectionEvent::Disconnected { peer_id: peer, reason: DisconnectReason::Denied });
+        assert_eq!(server.peer_count(), 0);
+    }
+
+    #[test]
+    fn test_secure_denial_needs_token_key() {
+        let key: [u8; 32] = rand::random();
+        
+        // Anyone who sees the request knows the salt, but not the token's keys
+        let spoofer = UdpSocket::bind("127.0.0.1:0").unwrap();
+        spoofer.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        let token = ConnectToken::issue(&key, 7, current_timestamp_ms() + 10_000).unwrap();
+        let mut client = NetworkSystem::new(NetworkRole::Client);
+        let peer = client.connect_with_token(&spoofer.local_addr().unwrap().to_string(), token).unwrap();
+        client.pump().unwrap();
+        
+        let mut buf = [0u8; RECV_BUFFER_SIZE];
+        let (len, client_addr) = spoofer.recv_from(&mut buf).unwrap();
+        let Some((PacketKind::ConnectRequest { client_salt }, _)) = decode_prefix(&buf[..len]) else {
+            panic!("expected a connect request");
+        };
+        for mac in [0, rand::random()] {
+            spoofer.send_to(&encode_prefix(&PacketKind::ConnectDenied { client_salt, mac }), client_addr).unwrap();
+        }
+        for _ in 0..10 {
+            client.pump().unwrap();
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        assert!(client.poll_events().is_empty());
+        assert!(client.connections.read().contains_key(&peer));
+        
+        // A full secure server signs its denial, so the real one still lands
+        let mut server = NetworkSystem::new(NetworkRole::Server);
+        server.enable_security(key);
+        server.max_peers = 0;
+        let server_addr = server.listen("127.0.0.1:0").unwrap();
+        
+        let token = ConnectToken::issue(&key, 8, current_timestamp_ms() + 10_000).unwrap();
+        let mut client = NetworkSystem::new(NetworkRole::Client);
+        let peer = client.connect_with_token(&server_addr.to_string(), token).unwrap();
+        let mut events = Vec::new();
+        assert!(pump_until(&client, &server, || {
+            events.extend(client.poll_events());
+            !events.is_empty()
+        }));
+        assert_eq!(events[0], ConnectionEvent::Disconnected { peer_id: peer, reason: DisconnectReason::Denied });
+    }
+
+    fn message(channel: ChannelType, sequence: u32) -> NetworkMessage {
+        NetworkMessage { id: sequence as u64, channel, data: vec![sequence as u8], timestamp: 0, sequence }
+    }
+
+    #[test]
+    fn test_ack_bitfield() {
+        let mut sender = ChannelState::default();
+        let mut receiver = ChannelState::default();
+        let now = Instant::now();
+        
+        let headers: Vec<AckHeader> = (0..5).map(|_| sender.begin_packet(Vec::new(), now)).collect();
+        for header in [headers[0], headers[1], headers[3], headers[4]] {
+            assert!(receiver.on_packet_received(header.sequence));
+        }
+        assert!(!receiver.on_packet_received(headers[3].sequence));
+        
+        let reply = receiver.begin_packet(Vec::new(), now);
+        assert_eq!(reply.ack, 4);
+        assert_eq!(reply.ack_bits, 0b1101);
+        
+        let (samples, lost) = sender.process_acks(&reply, now);
+        assert_eq!((samples.len(), lost), (4, 0));
+        assert_eq!(sender.sent_packets.keys().copied().collect::<Vec<_>>(), vec![2]);
+        
+        assert!(sequence_greater_than(0, u16::MAX));
+        assert!(!sequence_greater_than(u16::MAX, 0));
+    }
+
+    #[test]
+    fn test_channel_delivery_rules() {
+        let mut channels = ChannelState::default();
+        let mut out = VecDeque::new();
+        
+        for seq in [2, 0, 0, 1, 3] {
+            channels.receive(message(ChannelType::ReliableOrdered, seq), &mut out);
+        }
+        let ordered: Vec<u32> = out.drain(..).map(|m| m.sequence).collect();
+        assert_eq!(ordered, vec![0, 1, 2, 3]);
+        
+        for seq in [1, 0, 1, 2, 0] {
+            channels.receive(message(ChannelType::ReliableUnordered, seq), &mut out);
+        }
+        let unordered: Vec<u32> = out.drain(..).map(|m| m.sequence).collect();
+        assert_eq!(unordered, vec![1, 0, 2]);
+        
+        for seq in [0, 3, 2, 4] {
+            channels.receive(message(ChannelType::UnreliableOrdered, seq), &mut out);
+        }
+        let sequenced: Vec<u32> = out.drain(..).map(|m| m.sequence).collect();
+        assert_eq!(sequenced, vec![0, 3, 4]);
+    }
+
+    #[test]
+    fn test_reliable_resend_after_loss() {
+        let (server, client, peer) = connected_pair();
+        
+        for i in 0..3 {
+            client.send_message(peer, message(ChannelType::ReliableOrdered, 0)).unwrap();
+            client.send_message(peer, message(ChannelType::UnreliableUnordered, 100 + i)).unwrap();
+        }
+        client.pump().unwrap();
+        
+        // Swallow the first transmission before the server sees it
+        let mut buf = [0u8; RECV_BUFFER_SIZE];
+        std::thread::sleep(Duration::from_millis(5));
+        while server.socket.as_ref().unwrap().recv_from(&mut buf).is_ok() {}
+        
+        let mut received = Vec::new();
+        assert!(pump_until(&client, &server, || {
+            received.extend(server.drain_received());
+            received.len() == 3
+        }));
+        let sequences: Vec<u32> = received.iter().map(|(_, m)| m.sequence).collect();
+        assert_eq!(sequences, vec![0, 1, 2]);
+        assert!(received.iter().all(|(_, m)| m.channel == ChannelType::ReliableOrdered));
+        
+        assert!(pump_until(&client, &server, || client.connections.read()[&peer].unacked_reliable() == 0));
+        assert!(client.get_rtt(peer).unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_oversize_message_rejected_before_sequencing() {
+        let (server, client, peer) = connected_pair();
+        
+        // Incompressible, so compression can't rescue it
+        let mut rng = SmallRng::seed_from_u64(7);
+        let data: Vec<u8> = (0..MAX_MESSAGE_PAYLOAD + 1).map(|_| rng.gen()).collect();
+        let oversize = NetworkMessage { data, ..message(ChannelType::ReliableOrdered, 0) };
+        assert!(matches!(
+            client.send_message(peer, oversize),
+            Err(NetworkError::MessageTooLarge { max: MAX_MESSAGE_PAYLOAD, .. })
+        ));
+        
+        // The next ordered message takes sequence 0 and isn't stuck behind a gap
+        client.send_message(peer, message(ChannelType::ReliableOrdered, 5)).unwrap();
+        let mut received = Vec::new();
+        assert!(pump_until(&client, &server, || {
+            received.extend(server.drain_received());
+            !received.is_empty()
+        }));
+        assert_eq!(received[0].1.sequence, 0);
+        assert_eq!(received[0].1.data, vec![5]);
+    }
+
+    #[test]
+    fn test_send_to_unknown_peer_fails() {
+        let (_server, client, peer) = connected_pair();
+        assert!(matches!(
+            client.send_message(peer + 1, message(ChannelType::ReliableOrdered, 0)),
+            Err(NetworkError::NotConnected)
+        ));
+    }
+
+    #[test]
+    fn test_reliable_window_bounds_buffers() {
+        // The sender holds back everything a window past the oldest unacked message
+        let mut sender = ChannelState::default();
+        for seq in 0..RELIABLE_WINDOW + 10 {
+            let mut msg = message(ChannelType::ReliableOrdered, 0);
+            sender.assign_sequence(&mut msg);
+            assert_eq!(msg.sequence, seq);
+            sender.queue_reliable(OutgoingMessage::encode(1, &msg, false));
+        }
+        let due = sender.due_reliable(Instant::now(), Duration::ZERO);
+        assert_eq!(due.len(), RELIABLE_WINDOW as usize);
+        assert_eq!(due.last().unwrap().1.sequence, RELIABLE_WINDOW - 1);
+        
+        // The receiver drops anything further ahead than that
+        let mut receiver = ChannelState::default();
+        let mut out = VecDeque::new();
+        for seq in 1..RELIABLE_WINDOW + 10 {
+            receiver.receive(message(ChannelType::ReliableOrdered, seq), &mut out);
+            receiver.receive(message(ChannelType::ReliableUnordered, seq), &mut out);
+        }
+        assert_eq!(receiver.ordered_buffer.len(), RELIABLE_WINDOW as usize - 1);
+        assert_eq!(receiver.unordered_seen.len(), RELIABLE_WINDOW as usize - 1);
+        assert_eq!(out.len(), RELIABLE_WINDOW as usize - 1);
+    }
+
+    #[test]
+    fn test_receive_wraps_message_sequences() {
+        let mut receiver = ChannelState {
+            ordered_next: u32::MAX - 1,
+            unordered_floor: u32::MAX - 1,
+            sequenced_last: Some(u32::MAX - 1),
+            ..Default::default()
+        };
+        let mut out = VecDeque::new();
+        
+        // Ordered delivery holds the post-wrap messages until the gap before them fills
+        for seq in [0, 1, u32::MAX, u32::MAX - 1] {
+            receiver.receive(message(ChannelType::ReliableOrdered, seq), &mut out);
+        }
+        let delivered: Vec<u32> = out.drain(..).map(|m| m.sequence).collect();
+        assert_eq!(delivered, vec![u32::MAX - 1, u32::MAX, 0, 1]);
+        assert_eq!(receiver.ordered_next, 2);
+        assert!(receiver.ordered_buffer.is_empty());
+        
+        // Pre-wrap duplicates are stale rather than a window ahead
+        receiver.receive(message(ChannelType::ReliableOrdered, u32::MAX), &mut out);
+        assert!(out.is_empty());
+        
+        // Unordered delivery advances its floor across the wrap and still drops duplicates
+        for seq in [0, u32::MAX - 1, u32::MAX, 0, u32::MAX - 1] {
+            receiver.receive(message(ChannelType::ReliableUnordered, seq), &mut out);
+        }
+        let delivered: Vec<u32> = out.drain(..).map(|m| m.sequence).collect();
+        assert_eq!(delivered, vec![0, u32::MAX - 1, u32::MAX]);
+        assert_eq!(receiver.unordered_floor, 1);
+        assert!(receiver.unordered_seen.is_empty());
+        
+        // Sequenced delivery treats 0 as newer than u32::MAX and drops what came before it
+        for seq in [u32::MAX, 0, u32::MAX, 1] {
+            receiver.receive(message(ChannelType::UnreliableOrdered, seq), &mut out);
+        }
+        let delivered: Vec<u32> = out.drain(..).map(|m| m.sequence).collect();
+        assert_eq!(delivered, vec![u32::MAX, 0, 1]);
+    }
+    
+    #[test]
+    fn test_secure_session_roundtrip() {
+        let key: [u8; 32] = rand::random();
+        let mut server = NetworkSystem::new(NetworkRole::Server);
+        server.enable_security(key);
+        let server_addr = server.listen("127.0.0.1:0").unwrap();
+        
+        let token = ConnectToken::issue(&key, 42, current_timestamp_ms() + 10_000).unwrap();
+        let mut client = NetworkSystem::new(NetworkRole::Client);
+        let peer = client.connect_with_token(&server_addr.to_string(), token).unwrap();
+        assert!(pump_until(&client, &server, || client.peer_count() == 1 && server.peer_count() == 1));
+        
+        let server_peer = server.connected_peers()[0];
+        assert_eq!(server.connections.read()[&server_peer].client_id, Some(42));
+        assert!(client.connections.read()[&peer].is_secure());
+        
+        client.send_message(peer, message(ChannelType::ReliableOrdered, 0)).unwrap();
+        let mut received = Vec::new();
+        assert!(pump_until(&client, &server, || {
+            received.extend(server.drain_received());
+            !received.is_empty()
+        }));
+        assert_eq!(received[0].1.data, vec![0]);
+        
+        // A plaintext client never gets past the handshake
+        let mut intruder = NetworkSystem::new(NetworkRole::Client);
+        intruder.connect(&server_addr.to_string()).unwrap();
+        assert!(!pump_until(&intruder, &server, || server.peer_count() > 1));
+    }
+
+    #[test]
+    fn test_secure_packets_reject_tampering_and_replay() {
+        let key: [u8; 32] = rand::random();
+        let token = ConnectToken::issue(&key, 7, u64::MAX).unwrap();
+        assert_eq!(open_connect_token(&key, &token.sealed).unwrap().client_id, 7);
+        assert!(open_connect_token(&[0u8; 32], &token.sealed).is_none());
+        
+        let mut client = SessionCrypto::new(SessionKeys::derive(&token.contents(), 1, 2, true));
+        let mut server = SessionCrypto::new(SessionKeys::derive(&token.contents(), 1, 2, false));
+        assert_eq!(client.keys.token, server.keys.token);
+        
+        let prefix = encode_prefix(&PacketKind::Heartbeat { token: 9, acks: AckHeader::default() });
+        let packet = client.seal(prefix.clone(), b"hello");
+        let body = &packet[prefix.len()..];
+        
+        let mut tampered = body.to_vec();
+        tampered[10] ^= 1;
+        assert!(server.open(&prefix, &tampered).is_none());
+        assert!(server.open(&prefix[1..], body).is_none());
+        
+        assert_eq!(server.open(&prefix, body).unwrap(), b"hello");
+        assert!(server.open(&prefix, body).is_none(), "replay must be rejected");
+        
+        // Late but unseen packets inside the window are still accepted
+        let later: Vec<Vec<u8>> = (0..3).map(|_| client.seal(prefix.clone(), b"x")).collect();
+        assert!(server.open(&prefix, &later[2][prefix.len()..]).is_some());
+        assert!(server.open(&prefix, &later[0][prefix.len()..]).is_some());
+        
+        let mut window = ReplayWindow::default();
+        window.mark(500);
+        assert!(!window.is_fresh(500 - REPLAY_WINDOW));
+        assert!(window.is_fresh(499));
+    }
+
+    #[test]
+    fn test_link_conditioner_is_deterministic() {
+        let config = LinkConditionerConfig {
+            latency_ms: 50,
+            jitter_ms: 20,
+            loss: 0.25,
+            duplicate: 0.1,
+            reorder: 0.1,
+            seed: 1234,
+            ..Default::default()
+        };
+        let to: SocketAddr = "127.0.0.1:9".parse().unwrap();
+        let start = Instant::now();
+        
+        let run = || {
+            let mut conditioner = LinkConditioner::new(config);
+            for i in 0..200u8 {
+                conditioner.submit(to, &[i], start);
+            }
+            assert!(conditioner.take_due(start).is_empty());
+            let delivered: Vec<u8> = conditioner.take_due(start + Duration::from_secs(1))
+                .into_iter()
+                .map(|(_, p)| p[0])
+                .collect();
+            (delivered, conditioner.stats())
+        };
+        
+        let (first, stats) = run();
+        assert_eq!(run(), (first.clone(), stats));
+        assert!(stats.dropped > 20 && stats.duplicated > 5 && stats.reordered > 5);
+        assert_eq!(first.len() as u64, 200 - stats.dropped + stats.duplicated);
+        assert!(first.windows(2).any(|w| w[0] > w[1]), "jitter should reorder some packets");
+    }
+
+    #[test]
+    fn test_reliable_ordered_over_bad_link() {
+        let bad_link = |seed| Some(LinkConditionerConfig {
+            latency_ms: 10,
+            jitter_ms: 5,
+            loss: 0.2,
+            duplicate: 0.1,
+            reorder: 0.1,
+            reorder_delay_ms: 15,
+            seed,
+        });
+        
+        let mut server = NetworkSystem::new(NetworkRole::Server);
+        server.set_link_conditioner(bad_link(1));
+        let server_addr = server.listen("127.0.0.1:0").unwrap();
+        let mut client = NetworkSystem::new(NetworkRole::Client);
+        client.set_link_conditioner(bad_link(2));
+        let peer = client.connect(&server_addr.to_string()).unwrap();
+        assert!(pump_until(&client, &server, || client.peer_count() == 1 && server.peer_count() == 1));
+        
+        for seq in 0..40 {
+            client.send_message(peer, message(ChannelType::ReliableOrdered, seq)).unwrap();
+        }
+        
+        let mut received = Vec::new();
+        for _ in 0..400 {
+            client.pump().unwrap();
+            server.pump().unwrap();
+            received.extend(server.drain_received().into_iter().map(|(_, m)| m.data[0]));
+            if received.len() == 40 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        
+        assert_eq!(received, (0..40).collect::<Vec<u8>>());
+        assert!(client.link_conditioner_stats().unwrap().dropped > 0);
+    }
+
+    /// Order-sensitive toy simulation for a rollback session
+    #[derive(Default)]
+    struct RollbackCounter(u64);
+
+    impl RollbackCallbacks for RollbackCounter {
+        fn save_state(&mut self, _frame: u32) -> (Vec<u8>, u32) {
+            (self.0.to_le_bytes().to_vec(), (self.0 ^ (self.0 >> 32)) as u32 | 1)
+        }
+
+        fn load_state(&mut self, _frame: u32, state: &[u8]) {
+            self.0 = u64::from_le_bytes(state.try_into().unwrap());
+        }
+
+        fn advance_frame(&mut self, _frame: u32, inputs: &[GameInput]) {
+            for input in inputs {
+                let sum: u64 = input.inputs.iter().map(|b| *b as u64).sum();
+                self.0 = self.0.wrapping_mul(31).wrapping_add(sum * input.player_id + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rollback_converges_over_bad_link() {
+        const FRAMES: u32 = 60;
+        let bad_link = |seed| Some(LinkConditionerConfig {
+            latency_ms: 10,
+            jitter_ms: 5,
+            loss: 0.2,
+            duplicate: 0.1,
+            reorder: 0.1,
+            reorder_delay_ms: 15,
+            seed,
+        });
+        
+        let mut server = NetworkSystem::new(NetworkRole::Server);
+        server.set_link_conditioner(bad_link(3));
+        let server_addr = server.listen("127.0.0.1:0").unwrap();
+        let mut client = NetworkSystem::new(NetworkRole::Client);
+        client.set_link_conditioner(bad_link(4));
+        let server_peer = client.connect(&server_addr.to_string()).unwrap();
+        assert!(pump_until(&client, &server, || client.peer_count() == 1 && server.peer_count() == 1));
+        let client_peer = server.connected_peers()[0];
+        
+        let config = RollbackConfig { input_delay: 2, max_prediction_frames: 8, desync_detection: true };
+        let mut peers = [
+            (&client, server_peer, RollbackSession::new(config, 1, &[1, 2]).unwrap(), RollbackCounter::default()),
+            (&server, client_peer, RollbackSession::new(config, 2, &[1, 2]).unwrap(), RollbackCounter::default()),
+        ];
+        let mut events = Vec::new();
+        
+        // Inputs travel as reliable messages; each side stalls whenever it
+        // runs out of prediction window
+        for _ in 0..2000 {
+            for (network, peer, session, sim) in peers.iter_mut() {
+                network.pump().unwrap();
+                for (_, msg) in network.drain_received() {
+                    session.add_remote_input(GameInput::deserialize_compressed(&msg.data).unwrap()).unwrap();
+                }
+                if session.current_frame() < FRAMES {
+                    match session.add_local_input(vec![(session.current_frame() / 3) as u8]) {
+                        Ok(_) | Err(RollbackError::InputAlreadyAdded(_)) => {}
+                        Err(e) => panic!("{}", e),
+                    }
+                    match session.advance_frame(sim) {
+                        Ok(()) | Err(RollbackError::PredictionThreshold) => {}
+                        Err(e) => panic!("{}", e),
+                    }
+                }
+                for input in session.drain_outgoing_inputs() {
+                    let data = input.serialize_compressed();
+                    let msg = NetworkMessage { id: input.frame as u64, channel: ChannelType::ReliableOrdered, data, timestamp: 0, sequence: 0 };
+                    network.send_message(*peer, msg).unwrap();
+                }
+                events.extend(session.poll_events());
+            }
+            if peers.iter().all(|(_, _, session, _)| session.current_frame() == FRAMES && session.confirmed_frame() >= Some(FRAMES)) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        
+        // One more frame, all on confirmed input, resolves any pending rollback
+        for (_, _, session, sim) in peers.iter_mut() {
+            assert!(session.confirmed_frame() >= Some(FRAMES));
+            session.advance_frame(sim).unwrap();
+            events.extend(session.poll_events());
+        }
+        let [(.., sim_a), (.., sim_b)] = &peers;
+        assert_eq!(sim_a.0, sim_b.0);
+        assert!(events.iter().any(|e| matches!(e, RollbackEvent::Rollback { .. })));
+        assert!(!events.iter().any(|e| matches!(e, RollbackEvent::Desync { .. })));
+        assert!(client.link_conditioner_stats().unwrap().dropped > 0);
+        assert!(server.link_conditioner_stats().unwrap().dropped > 0);
+    }
+
+    #[test]
+    fn test_prioritized_entity_updates_fit_packet() {
+        let mut server = NetworkSystem::new(NetworkRole::Server);
+        let snapshots: Vec<EntitySnapshot> = (0..300)
+            .map(|i| EntitySnapshot::new(i, Vec3::new(i as f32, 0.0, 0.0), Vec3::ZERO, Vec3::ONE))
+            .collect();
+        for s in &snapshots {
+            server.register_replicated_entity(s.id, vec![s.id as u8; 40], None);
+        }
+        server.update_interest(&snapshots, 1.0 / 30.0);
+        server.set_viewer_position(9, Vec3::ZERO);
+        server.update_interest(&snapshots, 1.0 / 30.0);
+        
+        let updates = server.prioritized_entity_updates(9);
+        let size: usize = updates.iter()
+            .map(|u| bincode::serialized_size(u).unwrap() as usize + PACKET_HEADER_SIZE)
+            .sum();
+        assert!(!updates.is_empty());
+        assert!(size <= MAX_PACKET_SIZE - PACKET_PREFIX_SIZE - SECURE_OVERHEAD);
+        assert!(updates.iter().all(|u| server.is_relevant_to(9, u.entity_id)));
+        assert_eq!(updates[0].entity_id, 0);
+        
+        // Out-of-range entities never make it in
+        assert!(!server.is_relevant_to(9, 299));
+        assert!(server.prioritized_entity_updates(10).is_empty());
+    }
+
+    #[test]
+    fn test_client_cannot_listen() {
+        let mut client = NetworkSystem::new(NetworkRole::Client);
+        assert!(client.listen("127.0.0.1:0").is_err());
     }
 }
\ No newline at end of file
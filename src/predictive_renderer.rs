@@ -34,17 +34,19 @@ pub struct PredictiveRenderConfig {
     pub animation_threshold: f32,    // Min animation change to track
     
     // Frame reuse settings
-    pub reuse_depth: u32,            // History depth for reprojection
-    pub reprojection_blend: f32,     // Alpha for blending reprojected pixels
+    pub reuse_depth: u32,            // Frames one history frame is reprojected for
+    pub reprojection_blend: f32,     // Weight of warped history vs. the unwarped previous frame
     
     // Error watchdog
     pub error_threshold: f32,        // Error threshold for full refresh
     pub max_accumulated_error: f32,  // Max error before forced refresh
     pub watchdog_check_interval: u32,// Frames between error checks
+    pub validation_tiles: u32,       // Tiles rendered per check to measure error
     
     // Performance
     pub enable_debug_overlay: bool,
     pub statistics_enabled: bool,
+    pub full_frame_gpu_ms: f32,      // Initial estimate of a full scene render
 }
 
 impl Default for PredictiveRenderConfig {
@@ -60,8 +62,10 @@ impl Default for PredictiveRenderConfig {
             error_threshold: 0.02,
             max_accumulated_error: 0.15,
             watchdog_check_interval: 4,
+            validation_tiles: 32,
             enable_debug_overlay: false,
             statistics_enabled: true,
+            full_frame_gpu_ms: 4.0,
         }
     }
 }
@@ -129,15 +133,17 @@ struct Tile {
     accumulated_error: f32,
     last_render_frame: u64,
     reprojection_offset: Vec2,
+    /// Hot last frame but over the tile budget; stays hot
+    deferred: bool,
 }
 
-/// Frame history for reprojection
+/// Latest finished frame for reprojection, shared with the renderer that
+/// produced it rather than copied
 #[derive(Debug)]
 struct FrameHistory {
-    frame: Vec<u8>,
-    depth: Vec<f32>,
-    motion_vectors: Vec<Vec2>, // Per-pixel motion
-    timestamp: u64,
+    color: Arc<Texture>,
+    depth: Arc<Texture>,
+    view_proj: Mat4,
 }
 
 // ============================================================================
@@ -150,6 +156,8 @@ pub struct DeltaPredictor {
     previous_transforms: HashMap<u64, TransformCache>,
     previous_animations: HashMap<u64, AnimationCache>,
     camera_history: VecDeque<CameraMotion>,
+    /// Position and (yaw, pitch) of the previous snapshot's camera
+    last_camera: Option<(Vec3, Vec2)>,
     motion_model: SimpleMotionPredictor,
 }
 
@@ -188,6 +196,7 @@ impl DeltaPredictor {
             previous_transforms: HashMap::new(),
             previous_animations: HashMap::new(),
             camera_history: VecDeque::with_capacity(4),
+            last_camera: None,
             motion_model: SimpleMotionPredictor {
                 velocity_history: HashMap::new(),
             },
@@ -223,14 +232,14 @@ impl DeltaPredictor {
     }
     
     fn predict_camera_motion(&mut self, delta: &mut DeltaPrediction, scene: &SceneSnapshot) {
+        let rotation = Vec2::new(scene.camera_yaw, scene.camera_pitch);
+        let (last_position, last_rotation) = self.last_camera.unwrap_or((scene.camera_position, rotation));
         let motion = CameraMotion {
-            position_delta: scene.camera_position - self.get_last_camera_pos(),
-            rotation_delta: Vec2::new(
-                scene.camera_yaw - self.camera_history.back().map(|c| c.rotation_delta.x).unwrap_or(0.0),
-                scene.camera_pitch - self.camera_history.back().map(|c| c.rotation_delta.y).unwrap_or(0.0),
-            ),
+            position_delta: scene.camera_position - last_position,
+            rotation_delta: rotation - last_rotation,
             view_proj_change: self.estimate_view_proj_change(scene),
         };
+        self.last_camera = Some((scene.camera_position, rotation));
         
         delta.camera_motion = motion.clone();
         
@@ -241,10 +250,6 @@ impl DeltaPredictor {
         self.camera_history.push_back(motion);
     }
     
-    fn get_last_camera_pos(&self) -> Vec3 {
-        self.camera_history.back().map(|c| c.position_delta).unwrap_or(Vec3::ZERO)
-    }
-    
     fn estimate_view_proj_change(&self, scene: &SceneSnapshot) -> f32 {
         if let Some(prev) = self.camera_history.back() {
             prev.view_proj_change * 0.8 + // Damped prediction
@@ -462,6 +467,7 @@ impl TileManager {
                 accumulated_error: 0.0,
                 last_render_frame: 0,
                 reprojection_offset: Vec2::ZERO,
+                deferred: false,
             }
         }).collect();
         
@@ -477,9 +483,9 @@ impl TileManager {
     pub fn update(&mut self, delta: &DeltaPrediction) {
         self.frame_count += 1;
         
-        // Reset all tiles
+        // Reset all tiles, keeping the ones the budget pushed back
         for tile in &mut self.tiles {
-            tile.state = TileState::Clean;
+            tile.state = if tile.deferred { TileState::Hot } else { TileState::Clean };
             tile.error_score = 0.0;
         }
         
@@ -531,6 +537,17 @@ impl TileManager {
         }
     }
     
+    /// Tiles per row and per column
+    pub fn grid_size(&self) -> (u32, u32) {
+        (self.tiles_per_row, self.tiles_per_col)
+    }
+    
+    pub fn force_refresh(&mut self, coord: TileCoord) {
+        if let Some(tile) = self.get_tile_mut(coord) {
+            tile.state = TileState::ForceRefresh;
+        }
+    }
+    
     pub fn hot_tiles(&self) -> Vec<TileCoord> {
         self.tiles.iter()
             .filter(|t| matches!(t.state, TileState::Hot | TileState::ForceRefresh))
@@ -538,6 +555,54 @@ impl TileManager {
             .collect()
     }
     
+    /// Hot tiles to re-render this frame, at most `max_tiles_per_frame`
+    /// less the `reserved` slots kept for validation tiles.
+    /// Forced refreshes go first, then tiles with measured error, then the
+    /// tiles rendered longest ago. The rest are reprojected now and stay hot
+    /// next frame. Returns the scheduled tiles and the number deferred.
+    pub fn schedule_hot_tiles(&mut self, reserved: usize) -> (Vec<TileCoord>, usize) {
+        let priority = |state: TileState| match state {
+            TileState::ForceRefresh => 0,
+            TileState::Error => 1,
+            _ => 2,
+        };
+        let mut candidates: Vec<(u8, u64, TileCoord)> = self.tiles.iter()
+            .filter(|t| matches!(t.state, TileState::Hot | TileState::Error | TileState::ForceRefresh))
+            .map(|t| (priority(t.state), t.last_render_frame, t.coord))
+            .collect();
+        candidates.sort_by_key(|&(priority, last, coord)| (priority, last, coord.y, coord.x));
+
+        let budget = (self.config.max_tiles_per_frame as usize).saturating_sub(reserved);
+        let frame = self.frame_count;
+        let mut scheduled = Vec::with_capacity(candidates.len().min(budget));
+        for (i, (_, _, coord)) in candidates.iter().enumerate() {
+            let Some(tile) = self.get_tile_mut(*coord) else { continue };
+            if i < budget {
+                tile.last_render_frame = frame;
+                tile.accumulated_error = 0.0;
+                tile.deferred = false;
+                scheduled.push(*coord);
+            } else {
+                tile.state = TileState::Reprojected;
+                tile.deferred = true;
+            }
+        }
+        let deferred = candidates.len() - scheduled.len();
+        (scheduled, deferred)
+    }
+
+    /// Schedule every tile, ignoring the budget (no usable history)
+    pub fn schedule_full_refresh(&mut self) -> Vec<TileCoord> {
+        let frame = self.frame_count;
+        for tile in &mut self.tiles {
+            tile.state = TileState::Hot;
+            tile.last_render_frame = frame;
+            tile.accumulated_error = 0.0;
+            tile.deferred = false;
+        }
+        self.tiles.iter().map(|t| t.coord).collect()
+    }
+
     pub fn statistics(&self) -> TileStatistics {
         let mut hot = 0;
         let mut clean = 0;
@@ -574,61 +639,715 @@ pub struct TileStatistics {
     pub hot_ratio: f32,
 }
 
+// ============================================================================
+// TILE RENDER PLAN
+// ============================================================================
+
+/// Pixel rectangle for `RenderPass::set_scissor_rect`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScissorRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ScissorRect {
+    pub fn pixel_count(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+    
+    /// This rect of a `from`-sized screen, covering the same area of a
+    /// `to`-sized attachment (rounded outwards, clamped to the attachment).
+    /// None when nothing of it is left.
+    pub fn scaled(&self, from: (u32, u32), to: (u32, u32)) -> Option<ScissorRect> {
+        let scale = |value: u32, from: u32, to: u32, round_up: bool| {
+            let scaled = value as u64 * to as u64;
+            let from = from.max(1) as u64;
+            ((if round_up { scaled.div_ceil(from) } else { scaled / from }) as u32).min(to)
+        };
+        let (left, top) = (scale(self.x, from.0, to.0, false), scale(self.y, from.1, to.1, false));
+        let right = scale(self.x.saturating_add(self.width), from.0, to.0, true);
+        let bottom = scale(self.y.saturating_add(self.height), from.1, to.1, true);
+        (right > left && bottom > top).then_some(ScissorRect { x: left, y: top, width: right - left, height: bottom - top })
+    }
+}
+
+/// What the scene pass must re-render this frame
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TileRenderPlan {
+    pub tiles: Vec<TileCoord>,
+    /// `tiles` batched into as few rectangles as possible
+    pub rects: Vec<ScissorRect>,
+    /// Hot tiles pushed to a later frame by `max_tiles_per_frame`
+    pub deferred_tiles: usize,
+    /// No usable history, so every tile is rendered
+    pub full_refresh: bool,
+    /// Size `rects` are in: the target the plan was made for
+    pub screen_size: (u32, u32),
+}
+
+impl TileRenderPlan {
+    pub fn pixel_count(&self) -> u64 {
+        self.rects.iter().map(|r| r.pixel_count()).sum()
+    }
+
+    /// Draw the scene once per rect, scaled to the pass's `attachment_size`
+    /// (e.g. render size under a resolution scale). `draw` records the
+    /// scene's draw calls.
+    pub fn render_tiles(&self, pass: &mut RenderPass, attachment_size: (u32, u32), mut draw: impl FnMut(&mut RenderPass)) {
+        for rect in self.rects.iter().filter_map(|rect| rect.scaled(self.screen_size, attachment_size)) {
+            pass.set_scissor_rect(rect.x, rect.y, rect.width, rect.height);
+            draw(pass);
+        }
+    }
+}
+
+/// Merge tiles into scissor rects: horizontal runs within a row, then runs
+/// with the same span in consecutive rows. Rects are clamped to the screen.
+pub fn batch_tile_rects(tiles: &[TileCoord], tile_size: u32, screen_width: u32, screen_height: u32) -> Vec<ScissorRect> {
+    let mut sorted: Vec<TileCoord> = tiles.to_vec();
+    sorted.sort_by_key(|t| (t.y, t.x));
+    sorted.dedup();
+
+    // (x_start, x_end) -> (y_start, y_end) of the rect still growing downwards
+    let mut open: HashMap<(u32, u32), (u32, u32)> = HashMap::new();
+    let mut closed: Vec<(u32, u32, u32, u32)> = Vec::new();
+    let mut i = 0;
+    while i < sorted.len() {
+        let y = sorted[i].y;
+        let mut row_spans = Vec::new();
+        while i < sorted.len() && sorted[i].y == y {
+            let start = sorted[i].x;
+            let mut end = start + 1;
+            i += 1;
+            while i < sorted.len() && sorted[i].y == y && sorted[i].x == end {
+                end += 1;
+                i += 1;
+            }
+            row_spans.push((start, end));
+        }
+
+        // Spans not continued in this row are finished
+        let finished: Vec<(u32, u32)> = open.iter()
+            .filter(|(span, (_, y_end))| *y_end != y || !row_spans.contains(span))
+            .map(|(span, _)| *span)
+            .collect();
+        for span in finished {
+            let (y_start, y_end) = open.remove(&span).unwrap_or_default();
+            closed.push((span.0, span.1, y_start, y_end));
+        }
+        for span in row_spans {
+            open.entry(span).or_insert((y, y)).1 = y + 1;
+        }
+    }
+    closed.extend(open.into_iter().map(|(span, (y_start, y_end))| (span.0, span.1, y_start, y_end)));
+    closed.sort_by_key(|&(x, _, y, _)| (y, x));
+
+    closed.into_iter()
+        .filter_map(|(x0, x1, y0, y1)| {
+            let (left, top) = (x0 * tile_size, y0 * tile_size);
+            let (right, bottom) = ((x1 * tile_size).min(screen_width), (y1 * tile_size).min(screen_height));
+            (right > left && bottom > top).then_some(ScissorRect { x: left, y: top, width: right - left, height: bottom - top })
+        })
+        .collect()
+}
+
 // ============================================================================
 // REPROJECTION ENGINE
 // ============================================================================
 
+/// Color format of history frames and of the reprojected output
+pub const HISTORY_COLOR_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+/// Device depth (0 near, 1 far) resolved into a sampleable float texture
+pub const HISTORY_DEPTH_FORMAT: TextureFormat = TextureFormat::R32Float;
+/// Per-pixel object motion in pixels, previous frame to current
+pub const MOTION_VECTOR_FORMAT: TextureFormat = TextureFormat::Rg16Float;
+
+/// Relative view-depth difference tolerated before a pixel counts as disoccluded
+const DISOCCLUSION_DEPTH_TOLERANCE: f32 = 0.02;
+
+/// Per-frame GPU inputs of the reprojection pass
+pub struct ReprojectionInputs<'a> {
+    pub view_proj: Mat4,
+    /// This frame's depth in `HISTORY_DEPTH_FORMAT`
+    pub depth: &'a TextureView,
+    /// This frame's motion vectors in `MOTION_VECTOR_FORMAT`
+    pub motion: &'a TextureView,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct ReprojectUniforms {
+    prev_view_proj: [[f32; 4]; 4],
+    inv_prev_view_proj: [[f32; 4]; 4],
+    inv_curr_view_proj: [[f32; 4]; 4],
+    screen_size: [f32; 2],
+    blend_factor: f32,
+    depth_tolerance: f32,
+}
+
+/// Compute pass that warps the previous frame into the current view.
+/// Pixels whose previous depth disagrees with the reprojected surface are
+/// disoccluded and written with zero alpha. The rest are opaque, with
+/// `reprojection_blend` weighting the warped history against the previous
+/// frame at the same pixel.
 pub struct ReprojectionEngine {
     config: PredictiveRenderConfig,
-    frame_history: VecDeque<FrameHistory>,
-    reprojection_shader: ShaderModule,
+    width: u32,
+    height: u32,
+    history: Option<FrameHistory>,
+    pipeline: ComputePipeline,
+    bind_group_layout: BindGroupLayout,
+    uniform_buffer: Buffer,
+    sampler: Sampler,
+    /// Created lazily so `resize` does not need the device
+    output: Option<(Texture, TextureView)>,
 }
 
 impl ReprojectionEngine {
-    pub fn new(device: &Device) -> Self {
+    pub fn new(device: &Device, config: &PredictiveRenderConfig, width: u32, height: u32) -> Self {
         let shader = device.create_shader_module(ShaderModuleDescriptor {
             label: Some("predictive_reproject"),
             source: ShaderSource::Wgsl(Cow::Borrowed(REPROJECT_WGSL)),
         });
-        
+
+        let texture_entry = |binding, filterable| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("predictive_reproject_layout"),
+            entries: &[
+                texture_entry(0, true),
+                texture_entry(1, false),
+                texture_entry(2, false),
+                texture_entry(3, false),
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: HISTORY_COLOR_FORMAT,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("predictive_reproject_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("predictive_reproject_pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: "reproject",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let uniform_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("predictive_reproject_uniforms"),
+            size: std::mem::size_of::<ReprojectUniforms>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("predictive_reproject_sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
         Self {
-            config: PredictiveRenderConfig::default(),
-            frame_history: VecDeque::with_capacity(4),
-            reprojection_shader: shader,
+            config: config.clone(),
+            width: width.max(1),
+            height: height.max(1),
+            history: None,
+            pipeline,
+            bind_group_layout,
+            uniform_buffer,
+            sampler,
+            output: None,
         }
     }
-    
-    /// Reproject previous frame to current view
+
+    /// A frame has been pushed since creation or the last resize
+    pub fn has_history(&self) -> bool {
+        self.history.is_some()
+    }
+
+    /// Texture written by `reproject` (`HISTORY_COLOR_FORMAT`). Alpha is the
+    /// blend weight of each pixel, zero where the history was rejected.
+    pub fn output(&mut self, device: &Device) -> &Texture {
+        &self.ensure_output(device).0
+    }
+
+    pub fn output_view(&mut self, device: &Device) -> &TextureView {
+        &self.ensure_output(device).1
+    }
+
+    /// Drop history and reallocate targets at the new size on next use
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width.max(1);
+        self.height = height.max(1);
+        self.history = None;
+        self.output = None;
+    }
+
+    fn ensure_output(&mut self, device: &Device) -> &(Texture, TextureView) {
+        let (width, height) = (self.width, self.height);
+        self.output.get_or_insert_with(|| {
+            let texture = create_frame_texture(
+                device,
+                "predictive_reprojected",
+                width,
+                height,
+                HISTORY_COLOR_FORMAT,
+                TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC,
+            );
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            (texture, view)
+        })
+    }
+
+    // ============================================
+    // Reprojection
+    // ============================================
+
+    /// Reproject the most recent history frame into the current view.
+    /// Returns false (and records nothing) when there is no history yet.
     pub fn reproject(
         &mut self,
         device: &Device,
         queue: &Queue,
-        current_view_proj: Mat4,
+        encoder: &mut CommandEncoder,
+        inputs: &ReprojectionInputs,
         delta: &DeltaPrediction,
-        output_texture: &TextureView,
-    ) {
-        if let Some(prev) = self.frame_history.back() {
-            // Reproject previous frame using motion vectors
-            // This is a simplified version - real impl would use compute shader
-            let _ = (device, queue, current_view_proj, delta, output_texture, prev);
-            // Implementation would:
-            // 1. Sample previous frame with motion offset
-            // 2. Apply camera motion correction
-            // 3. Blend with re-rendered tiles
+    ) -> bool {
+        self.ensure_output(device);
+        let (Some(prev), Some((_, output_view))) = (self.history.as_ref(), self.output.as_ref()) else {
+            return false;
+        };
+
+        // Faster camera motion makes resampled depth less exact, so widen
+        // the disocclusion test rather than reject whole moving frames
+        let camera = &delta.camera_motion;
+        let uniforms = ReprojectUniforms {
+            prev_view_proj: prev.view_proj.to_cols_array_2d(),
+            inv_prev_view_proj: prev.view_proj.inverse().to_cols_array_2d(),
+            inv_curr_view_proj: inputs.view_proj.inverse().to_cols_array_2d(),
+            screen_size: [self.width as f32, self.height as f32],
+            blend_factor: self.config.reprojection_blend,
+            depth_tolerance: DISOCCLUSION_DEPTH_TOLERANCE * (1.0 + camera.view_proj_change.max(0.0)),
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+
+        let prev_color = prev.color.create_view(&TextureViewDescriptor::default());
+        let prev_depth = prev.depth.create_view(&TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("predictive_reproject_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry { binding: 0, resource: BindingResource::TextureView(&prev_color) },
+                BindGroupEntry { binding: 1, resource: BindingResource::TextureView(&prev_depth) },
+                BindGroupEntry { binding: 2, resource: BindingResource::TextureView(inputs.depth) },
+                BindGroupEntry { binding: 3, resource: BindingResource::TextureView(inputs.motion) },
+                BindGroupEntry { binding: 4, resource: self.uniform_buffer.as_entire_binding() },
+                BindGroupEntry { binding: 5, resource: BindingResource::Sampler(&self.sampler) },
+                BindGroupEntry { binding: 6, resource: BindingResource::TextureView(output_view) },
+            ],
+        });
+
+        let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("predictive_reproject_pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(self.width.div_ceil(8), self.height.div_ceil(8), 1);
+        true
+    }
+
+    /// Keep a finished frame as history. `color` must be
+    /// `HISTORY_COLOR_FORMAT` and `depth` `HISTORY_DEPTH_FORMAT`, both with
+    /// `TEXTURE_BINDING` usage; they may be at a different size than the
+    /// engine (e.g. render size). Nothing is copied, so the caller must leave
+    /// them unchanged until the next `push_frame` or `resize`.
+    pub fn push_frame(&mut self, color: Arc<Texture>, depth: Arc<Texture>, view_proj: Mat4) {
+        self.history = Some(FrameHistory { color, depth, view_proj });
+    }
+}
+
+fn create_frame_texture(device: &Device, label: &str, width: u32, height: u32, format: TextureFormat, usage: TextureUsages) -> Texture {
+    device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d { width, height, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        usage,
+        view_formats: &[],
+    })
+}
+
+// ============================================================================
+// FRAME COMPOSITOR
+// ============================================================================
+
+/// Lays the reprojected frame into the render target before hot tiles are
+/// drawn over it. Disoccluded pixels (zero alpha) are left at clear color.
+pub struct FrameCompositor {
+    pipeline: RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+}
+
+impl FrameCompositor {
+    pub fn new(device: &Device, target_format: TextureFormat) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("predictive_composite"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(COMPOSITE_WGSL)),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("predictive_composite_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("predictive_composite_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("predictive_composite_pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_fullscreen",
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_composite",
+                compilation_options: Default::default(),
+                targets: &[Some(ColorTargetState {
+                    format: target_format,
+                    blend: Some(BlendState::REPLACE),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self { pipeline, bind_group_layout }
+    }
+
+    /// Clear `target_view` and copy every accepted reprojected pixel into it
+    pub fn composite(&self, device: &Device, encoder: &mut CommandEncoder, reprojected: &TextureView, target_view: &TextureView) {
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("predictive_composite_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[BindGroupEntry { binding: 0, resource: BindingResource::TextureView(reprojected) }],
+        });
+        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("predictive_composite_pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: target_view,
+                resolve_target: None,
+                ops: Operations { load: LoadOp::Clear(Color::BLACK), store: StoreOp::Store },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+// ============================================================================
+// ERROR ESTIMATOR
+// ============================================================================
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct ValidationUniforms {
+    screen_size: [u32; 2],
+    tile_size: u32,
+    tile_count: u32,
+}
+
+enum ValidationReadback {
+    Idle,
+    /// Copy recorded, waiting for the caller to submit
+    Recorded(Vec<TileCoord>),
+    /// `map_async` issued; holds the map result once it completes
+    Mapping(Vec<TileCoord>, Arc<Mutex<Option<bool>>>),
+}
+
+/// Measures reprojection error instead of assuming it. Every
+/// `watchdog_check_interval` frames a sparse set of otherwise reprojected
+/// tiles is re-rendered, and a compute pass compares them against the
+/// reprojected frame: mean absolute luminance difference per tile, with
+/// disoccluded pixels counting as full error. Results are read back
+/// asynchronously and arrive a frame or two later.
+pub struct ErrorEstimator {
+    config: PredictiveRenderConfig,
+    pipeline: ComputePipeline,
+    bind_group_layout: BindGroupLayout,
+    error_buffer: Buffer,
+    readback_buffer: Buffer,
+    readback: ValidationReadback,
+    /// Chosen by `select_tiles`, measured by `record`
+    pending: Vec<TileCoord>,
+    check_count: u32,
+}
+
+impl ErrorEstimator {
+    pub fn new(device: &Device, config: &PredictiveRenderConfig) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("predictive_tile_error"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(TILE_ERROR_WGSL)),
+        });
+
+        let texture_entry = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: false },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let buffer_entry = |binding, ty| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer { ty, has_dynamic_offset: false, min_binding_size: None },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("predictive_tile_error_layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                buffer_entry(2, BufferBindingType::Storage { read_only: true }),
+                buffer_entry(3, BufferBindingType::Storage { read_only: false }),
+                buffer_entry(4, BufferBindingType::Uniform),
+            ],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("predictive_tile_error_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("predictive_tile_error_pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: "tile_error",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let size = (config.validation_tiles.max(1) as u64) * 4;
+        let error_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("predictive_tile_errors"),
+            size,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("predictive_tile_errors_readback"),
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            config: config.clone(),
+            pipeline,
+            bind_group_layout,
+            error_buffer,
+            readback_buffer,
+            readback: ValidationReadback::Idle,
+            pending: Vec::new(),
+            check_count: 0,
         }
     }
-    
-    pub fn push_frame(&mut self, frame: Vec<u8>, depth: Vec<f32>, motion: Vec<Vec2>) {
-        if self.frame_history.len() >= self.config.reuse_depth as usize {
-            self.frame_history.pop_front();
+
+    /// Validation tiles `select_tiles` will want this frame: none when it is
+    /// not a check frame or the previous measurement is still in flight
+    pub fn tiles_due(&self, frame: u64) -> usize {
+        let interval = self.config.watchdog_check_interval.max(1) as u64;
+        if !frame.is_multiple_of(interval) || !matches!(self.readback, ValidationReadback::Idle) {
+            return 0;
         }
-        
-        self.frame_history.push_back(FrameHistory {
-            frame,
-            depth,
-            motion_vectors: motion,
-            timestamp: 0,
+        self.config.validation_tiles as usize
+    }
+
+    /// Pick up to `limit` of this frame's validation tiles (see `tiles_due`).
+    /// Tiles already in `scheduled` are skipped; the picks follow an R2
+    /// low-discrepancy sequence so successive checks cover the whole screen.
+    pub fn select_tiles(&mut self, frame: u64, grid: (u32, u32), scheduled: &[TileCoord], limit: usize) -> Vec<TileCoord> {
+        self.pending.clear();
+        let (cols, rows) = grid;
+        let wanted = self.tiles_due(frame).min(limit).min((cols * rows) as usize);
+        if wanted == 0 {
+            return Vec::new();
+        }
+
+        let scheduled: HashSet<TileCoord> = scheduled.iter().copied().collect();
+        let mut picked = HashSet::new();
+        let seed = self.check_count as f32 * 0.5;
+        self.check_count = self.check_count.wrapping_add(1);
+
+        // A few extra draws make up for collisions with scheduled tiles
+        for i in 0..wanted * 4 {
+            if self.pending.len() >= wanted {
+                break;
+            }
+            let x = (seed + i as f32 * 0.754_877_7).fract();
+            let y = (seed + i as f32 * 0.569_840_3).fract();
+            let coord = TileCoord::new(((x * cols as f32) as u32).min(cols - 1), ((y * rows as f32) as u32).min(rows - 1));
+            if !scheduled.contains(&coord) && picked.insert(coord) {
+                self.pending.push(coord);
+            }
+        }
+        self.pending.clone()
+    }
+
+    /// Record the comparison of the selected tiles. `reprojected` is the
+    /// reprojection output at `width` x `height` and `rendered` the finished
+    /// frame, in any float format and at that size or render size.
+    pub fn record(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        reprojected: &TextureView,
+        rendered: &TextureView,
+        width: u32,
+        height: u32,
+    ) {
+        if self.pending.is_empty() || !matches!(self.readback, ValidationReadback::Idle) {
+            return;
+        }
+        let tiles = std::mem::take(&mut self.pending);
+        let coords: Vec<[u32; 2]> = tiles.iter().map(|t| [t.x, t.y]).collect();
+        let tile_buffer = device.create_buffer_init(&util::BufferInitDescriptor {
+            label: Some("predictive_validation_tiles"),
+            contents: bytemuck::cast_slice(&coords),
+            usage: BufferUsages::STORAGE,
+        });
+        let uniforms = ValidationUniforms {
+            screen_size: [width, height],
+            tile_size: self.config.tile_size,
+            tile_count: tiles.len() as u32,
+        };
+        let uniform_buffer = device.create_buffer_init(&util::BufferInitDescriptor {
+            label: Some("predictive_validation_uniforms"),
+            contents: bytemuck::bytes_of(&uniforms),
+            usage: BufferUsages::UNIFORM,
         });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("predictive_tile_error_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry { binding: 0, resource: BindingResource::TextureView(reprojected) },
+                BindGroupEntry { binding: 1, resource: BindingResource::TextureView(rendered) },
+                BindGroupEntry { binding: 2, resource: tile_buffer.as_entire_binding() },
+                BindGroupEntry { binding: 3, resource: self.error_buffer.as_entire_binding() },
+                BindGroupEntry { binding: 4, resource: uniform_buffer.as_entire_binding() },
+            ],
+        });
+
+        {
+            let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("predictive_tile_error_pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(tiles.len() as u32, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&self.error_buffer, 0, &self.readback_buffer, 0, tiles.len() as u64 * 4);
+        self.readback = ValidationReadback::Recorded(tiles);
+    }
+
+    /// Advance the readback. Call once per frame after the encoder holding
+    /// `record` was submitted; returns the measured per-tile errors when
+    /// they arrive.
+    pub fn poll(&mut self, device: &Device) -> Option<Vec<(TileCoord, f32)>> {
+        match std::mem::replace(&mut self.readback, ValidationReadback::Idle) {
+            ValidationReadback::Idle => None,
+            ValidationReadback::Recorded(tiles) => {
+                let status = Arc::new(Mutex::new(None));
+                let callback_status = status.clone();
+                let len = tiles.len() as u64 * 4;
+                self.readback_buffer.slice(..len).map_async(MapMode::Read, move |result| {
+                    *callback_status.lock() = Some(result.is_ok());
+                });
+                self.readback = ValidationReadback::Mapping(tiles, status);
+                None
+            }
+            ValidationReadback::Mapping(tiles, status) => {
+                device.poll(Maintain::Poll);
+                let mapped = *status.lock();
+                match mapped {
+                    None => {
+                        self.readback = ValidationReadback::Mapping(tiles, status);
+                        return None;
+                    }
+                    // Lost this measurement; the next check frame tries again
+                    Some(false) => return None,
+                    Some(true) => {}
+                }
+                let len = tiles.len() as u64 * 4;
+                let errors: Vec<f32> = bytemuck::cast_slice(&self.readback_buffer.slice(..len).get_mapped_range()).to_vec();
+                self.readback_buffer.unmap();
+                Some(tiles.into_iter().zip(errors).collect())
+            }
+        }
     }
 }
 
@@ -700,8 +1419,18 @@ pub struct PredictiveRenderer {
     delta_predictor: DeltaPredictor,
     tile_manager: TileManager,
     reprojection_engine: ReprojectionEngine,
+    compositor: FrameCompositor,
+    error_estimator: ErrorEstimator,
     error_watchdog: ErrorWatchdog,
     stats: PredictiveStats,
+    /// Made by `predict` ahead of this frame's `render`
+    pending_delta: Option<DeltaPrediction>,
+    /// Running estimate of a full scene render, refined by `record_gpu_time`
+    full_frame_gpu_ms: f32,
+    /// `frames_rendered` when the latest history frame was pushed
+    history_pushed_at: Option<u64>,
+    /// Copy of the finished frame for targets that can't be bound
+    validation_copy: Option<Texture>,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -711,22 +1440,69 @@ pub struct PredictiveStats {
     pub reprojections: u64,
     pub forced_refreshes: u32,
     pub avg_hot_ratio: f32,
+    /// Pixels left to the reprojected frame instead of re-rendered, over all frames
+    pub pixels_skipped: u64,
+    /// Screen pixels over all frames
+    pub pixels_total: u64,
+    /// Measured scene time saved by tile frames against the full-render
+    /// estimate, over all frames given to `record_gpu_time`
     pub gpu_time_saved_ms: f32,
+    /// Frames given to `record_gpu_time`
+    pub timed_frames: u64,
+    /// Mean reprojection error of the latest validation readback
+    pub measured_error: f32,
+    pub last_frame: FrameTileStats,
+}
+
+/// Tile work of a single frame
+#[derive(Debug, Default, Clone)]
+pub struct FrameTileStats {
+    pub tiles_rendered: usize,
+    pub tiles_deferred: usize,
+    pub scissor_rects: usize,
+    pub pixels_rendered: u64,
+    pub pixels_total: u64,
+    pub full_refresh: bool,
+    /// Included in `tiles_rendered`
+    pub validation_tiles: usize,
 }
 
 impl PredictiveRenderer {
-    pub fn new(device: &Device, config: PredictiveRenderConfig, screen_width: u32, screen_height: u32) -> Self {
+    pub fn new(device: &Device, config: PredictiveRenderConfig, target_format: TextureFormat, screen_width: u32, screen_height: u32) -> Self {
         Self {
             config: config.clone(),
             delta_predictor: DeltaPredictor::new(config.clone()),
             tile_manager: TileManager::new(config.clone(), screen_width, screen_height),
-            reprojection_engine: ReprojectionEngine::new(device),
+            reprojection_engine: ReprojectionEngine::new(device, &config, screen_width, screen_height),
+            compositor: FrameCompositor::new(device, target_format),
+            error_estimator: ErrorEstimator::new(device, &config),
+            full_frame_gpu_ms: config.full_frame_gpu_ms,
             error_watchdog: ErrorWatchdog::new(config),
             stats: PredictiveStats::default(),
+            pending_delta: None,
+            history_pushed_at: None,
+            validation_copy: None,
         }
     }
     
-    /// Main render path
+    /// Predict this frame's changes ahead of `render`, e.g. to draw with its
+    /// motion vectors. The next `render` uses this prediction rather than
+    /// making another.
+    pub fn predict(&mut self, scene: &SceneSnapshot) -> &DeltaPrediction {
+        let delta = self.delta_predictor.predict(scene);
+        self.pending_delta.insert(delta)
+    }
+    
+    /// Main render path. With this frame's depth and motion vectors and a
+    /// history frame, the reprojected frame is composited into `target_view`
+    /// and the returned plan holds only the hot tiles. Otherwise the plan is
+    /// a full refresh. The caller draws the scene through
+    /// `TileRenderPlan::render_tiles` in a pass that loads `target_view`.
+    ///
+    /// History pushed more than `reuse_depth` frames ago is not reprojected,
+    /// so every few frames a full refresh renews it. `Renderer::render_reprojected`
+    /// supplies `inputs` from a depth and motion prepass, and measures the
+    /// plan's validation tiles through `record_validation`.
     pub fn render(
         &mut self,
         device: &Device,
@@ -734,41 +1510,97 @@ impl PredictiveRenderer {
         scene: &SceneSnapshot,
         encoder: &mut CommandEncoder,
         target_view: &TextureView,
-    ) {
+        inputs: Option<&ReprojectionInputs>,
+    ) -> TileRenderPlan {
         self.stats.frames_rendered += 1;
         
-        // 1. PREDICT: Get delta prediction
-        let delta = self.delta_predictor.predict(scene);
+        // 1. PREDICT: Get delta prediction (unless `predict` already did)
+        let delta = match self.pending_delta.take() {
+            Some(delta) => delta,
+            None => self.delta_predictor.predict(scene),
+        };
         
         // 2. TILE: Update tile states
         self.tile_manager.update(&delta);
         
-        // 3. REPROJECT: Reproject previous frame
-        // (Skipped for now - full implementation would use compute shader)
-        
-        // 4. RENDER: Only render hot tiles
-        let hot_tiles = self.tile_manager.hot_tiles();
-        let hot_count = hot_tiles.len();
-        
-        // Update statistics
-        let tile_stats = self.tile_manager.statistics();
-        self.stats.tiles_reduced += (tile_stats.total_tiles - hot_count) as u64;
-        self.stats.avg_hot_ratio = self.stats.avg_hot_ratio * 0.9 + tile_stats.hot_ratio * 0.1;
+        // 3. MEASURE: Apply validation errors read back from earlier frames
+        if let Some(errors) = self.error_estimator.poll(device) {
+            self.apply_measured_errors(&errors);
+        }
         
-        // 5. WATCHDOG: Check for error accumulation
+        // 4. WATCHDOG: Refresh tiles whose accumulated error is too high
+        let mut refresh = Vec::new();
         for tile in &self.tile_manager.tiles {
             if self.error_watchdog.check_tile(tile) {
                 self.stats.forced_refreshes += 1;
+                refresh.push(tile.coord);
             }
         }
+        for coord in refresh {
+            self.tile_manager.force_refresh(coord);
+        }
         
-        // Estimate GPU time saved
-        let full_frame_pixels = scene.screen_width * scene.screen_height;
-        let hot_pixels = hot_count as u32 * self.config.tile_size * self.config.tile_size;
-        let saved_ratio = 1.0 - (hot_pixels as f32 / full_frame_pixels as f32);
-        self.stats.gpu_time_saved_ms += saved_ratio * 0.5; // Rough estimate
+        // 5. REPROJECT: Reproject previous frame, while it is recent enough
+        let fresh = self.history_pushed_at.is_some_and(|at| self.stats.frames_rendered - at <= self.config.reuse_depth as u64);
+        let reprojected = match inputs.filter(|_| fresh) {
+            Some(inputs) => self.reprojection_engine.reproject(device, queue, encoder, inputs, &delta),
+            None => false,
+        };
         
-        let _ = (encoder, target_view); // Placeholder for actual rendering
+        // 6. SCHEDULE: Hot tiles within budget, or everything without history
+        let mut validation_tiles = 0;
+        let (tiles, deferred_tiles) = if reprojected {
+            self.stats.reprojections += 1;
+            self.compositor.composite(device, encoder, self.reprojection_engine.output_view(device), target_view);
+            // Validation tiles count against the budget, so room is kept for them
+            let reserved = self.error_estimator.tiles_due(self.stats.frames_rendered).min(self.config.max_tiles_per_frame as usize);
+            let (mut tiles, deferred) = self.tile_manager.schedule_hot_tiles(reserved);
+            let validation = self.error_estimator.select_tiles(self.stats.frames_rendered, self.tile_manager.grid_size(), &tiles, reserved);
+            validation_tiles = validation.len();
+            tiles.extend(validation);
+            (tiles, deferred)
+        } else {
+            self.error_estimator.pending.clear();
+            (self.tile_manager.schedule_full_refresh(), 0)
+        };
+        let rects = batch_tile_rects(&tiles, self.config.tile_size, scene.screen_width, scene.screen_height);
+        let plan = TileRenderPlan { tiles, rects, deferred_tiles, full_refresh: !reprojected, screen_size: (scene.screen_width, scene.screen_height) };
+        
+        // Update statistics
+        let tile_stats = self.tile_manager.statistics();
+        self.stats.tiles_reduced += (tile_stats.total_tiles - plan.tiles.len()) as u64;
+        self.stats.avg_hot_ratio = self.stats.avg_hot_ratio * 0.9 + tile_stats.hot_ratio * 0.1;
+        
+        let pixels_total = (scene.screen_width as u64 * scene.screen_height as u64).max(1);
+        let pixels_rendered = plan.pixel_count().min(pixels_total);
+        self.stats.pixels_skipped += pixels_total - pixels_rendered;
+        self.stats.pixels_total += pixels_total;
+        self.stats.last_frame = FrameTileStats {
+            tiles_rendered: plan.tiles.len(),
+            tiles_deferred: plan.deferred_tiles,
+            scissor_rects: plan.rects.len(),
+            pixels_rendered,
+            pixels_total,
+            full_refresh: plan.full_refresh,
+            validation_tiles,
+        };
+        
+        plan
+    }
+    
+    /// Feed back the measured GPU time of a scene render (e.g. from
+    /// timestamp queries). Full refreshes refine the full-render estimate;
+    /// tile frames add what they saved against it.
+    pub fn record_gpu_time(&mut self, scene_pass_ms: f32, full_refresh: bool) {
+        if !scene_pass_ms.is_finite() {
+            return;
+        }
+        if full_refresh {
+            self.full_frame_gpu_ms = self.full_frame_gpu_ms * 0.9 + scene_pass_ms * 0.1;
+        } else {
+            self.stats.gpu_time_saved_ms += (self.full_frame_gpu_ms - scene_pass_ms).max(0.0);
+        }
+        self.stats.timed_frames += 1;
     }
     
     /// Get current statistics
@@ -776,9 +1608,77 @@ impl PredictiveRenderer {
         self.stats.clone()
     }
     
+    /// A validation tile was just re-rendered, but its error is what the
+    /// reprojected tiles around it are drifting by
+    fn apply_measured_errors(&mut self, errors: &[(TileCoord, f32)]) {
+        if errors.is_empty() {
+            return;
+        }
+        let (cols, rows) = self.tile_manager.grid_size();
+        for &(coord, error) in errors {
+            for y in coord.y.saturating_sub(1)..=(coord.y + 1).min(rows - 1) {
+                for x in coord.x.saturating_sub(1)..=(coord.x + 1).min(cols - 1) {
+                    if (x, y) != (coord.x, coord.y) {
+                        self.tile_manager.mark_error(TileCoord::new(x, y), error);
+                    }
+                }
+            }
+        }
+        self.stats.measured_error = errors.iter().map(|(_, e)| e).sum::<f32>() / errors.len() as f32;
+    }
+    
+    /// Keep the finished frame as reprojection history (see
+    /// `ReprojectionEngine::push_frame`)
+    pub fn push_frame(&mut self, color: Arc<Texture>, depth: Arc<Texture>, view_proj: Mat4) {
+        self.reprojection_engine.push_frame(color, depth, view_proj);
+        self.history_pushed_at = Some(self.stats.frames_rendered);
+    }
+    
+    /// Compare this frame's validation tiles, now drawn into `target`, with
+    /// the reprojected frame. Call once the plan's tiles are drawn, before
+    /// the target is presented. `target` is bound directly when it has
+    /// `TEXTURE_BINDING`, else copied when it has `COPY_SRC`; with neither,
+    /// the tiles go unmeasured.
+    pub fn record_validation(&mut self, device: &Device, encoder: &mut CommandEncoder, target: &Texture) {
+        if self.error_estimator.pending.is_empty() {
+            return;
+        }
+        let rendered = if target.usage().contains(TextureUsages::TEXTURE_BINDING) {
+            target.create_view(&TextureViewDescriptor::default())
+        } else if target.usage().contains(TextureUsages::COPY_SRC) {
+            let size = target.size();
+            let copy = self
+                .validation_copy
+                .take()
+                .filter(|copy| copy.size() == size && copy.format() == target.format())
+                .unwrap_or_else(|| {
+                    let usage = TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING;
+                    create_frame_texture(device, "predictive_validation_copy", size.width, size.height, target.format(), usage)
+                });
+            encoder.copy_texture_to_texture(target.as_image_copy(), copy.as_image_copy(), size);
+            let view = copy.create_view(&TextureViewDescriptor::default());
+            self.validation_copy = Some(copy);
+            view
+        } else {
+            self.error_estimator.pending.clear();
+            return;
+        };
+        
+        let engine = &mut self.reprojection_engine;
+        let (width, height) = (engine.width, engine.height);
+        let reprojected = &engine.ensure_output(device).1;
+        self.error_estimator.record(device, encoder, reprojected, &rendered, width, height);
+    }
+
+    pub fn reprojection_engine(&mut self) -> &mut ReprojectionEngine {
+        &mut self.reprojection_engine
+    }
+
     /// Resize renderer
     pub fn resize(&mut self, screen_width: u32, screen_height: u32) {
         self.tile_manager = TileManager::new(self.config.clone(), screen_width, screen_height);
+        self.reprojection_engine.resize(screen_width, screen_height);
+        self.history_pushed_at = None;
     }
 }
 
@@ -786,55 +1686,152 @@ impl PredictiveRenderer {
 // WGSL SHADERS
 // ============================================================================
 
-const REPROJECT_WGSL: &str = r#"
+pub(crate) const REPROJECT_WGSL: &str = r#"
 struct ReprojectUniforms {
     prev_view_proj: mat4x4<f32>,
-    curr_view_proj: mat4x4<f32>,
+    inv_prev_view_proj: mat4x4<f32>,
     inv_curr_view_proj: mat4x4<f32>,
-    motion_scale: f32,
+    screen_size: vec2<f32>,
     blend_factor: f32,
+    depth_tolerance: f32,
 };
 
-@group(0) @binding(0) var prev_tex: texture_2d<f32>;
-@group(0) @binding(1) var prev_depth: texture_depth_2d;
-@group(0) @binding(2) var curr_depth: texture_depth_2d;
+@group(0) @binding(0) var prev_color: texture_2d<f32>;
+@group(0) @binding(1) var prev_depth: texture_2d<f32>;
+@group(0) @binding(2) var curr_depth: texture_2d<f32>;
 @group(0) @binding(3) var motion_tex: texture_2d<f32>;
 @group(0) @binding(4) var<uniform> uniforms: ReprojectUniforms;
-@group(0) @binding(5) var samp: sampler;
+@group(0) @binding(5) var color_sampler: sampler;
+@group(0) @binding(6) var output_tex: texture_storage_2d<rgba8unorm, write>;
 
-@fragment fn reproject(
-    @builtin(position) pos: vec4<f32>,
-    @location(0) uv: vec2<f32>
-) -> @location(0) vec4<f32> {
-    let tex_size = vec2<f32>(textureDimensions(prev_tex));
-    
-    // Sample current depth
-    let curr_d = textureSample(curr_depth, samp, uv).r;
-    
-    // Unproject to world space
-    let clip_pos = vec4<f32>(uv * 2.0 - 1.0, curr_d * 2.0 - 1.0, 1.0);
-    let world_pos = uniforms.inv_curr_view_proj * clip_pos;
-    let world_pos = world_pos / world_pos.w;
-    
-    // Project to previous frame
-    let prev_clip = uniforms.prev_view_proj * vec4<f32>(world_pos.xyz, 1.0);
-    let prev_uv = prev_clip.xy / prev_clip.w * 0.5 + 0.5;
-    
-    // Sample motion vectors for refinement
-    let motion = textureSample(motion_tex, samp, uv).rg;
-    let refined_uv = prev_uv + motion * uniforms.motion_scale;
-    
-    // Check depth validity
-    let prev_d = textureSample(prev_depth, samp, refined_uv).r;
-    let depth_diff = abs(curr_d - prev_d);
-    
-    if (depth_diff < 0.01 && prev_uv.x >= 0.0 && prev_uv.x <= 1.0 && prev_uv.y >= 0.0 && prev_uv.y <= 1.0) {
-        // Reprojection valid - blend with previous
-        let prev_color = textureSample(prev_tex, samp, refined_uv).rgb;
-        return vec4<f32>(prev_color, uniforms.blend_factor);
-    } else {
-        // Reprojection failed - no reuse
-        return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+// uv (y down) + device depth -> world position
+fn unproject(inv_view_proj: mat4x4<f32>, uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world = inv_view_proj * ndc;
+    return world.xyz / world.w;
+}
+
+@compute @workgroup_size(8, 8)
+fn reproject(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = vec2<u32>(uniforms.screen_size);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+    let pixel = vec2<i32>(id.xy);
+    let uv = (vec2<f32>(id.xy) + 0.5) / uniforms.screen_size;
+
+    // Depth and motion may be at render size under a resolution scale
+    let curr_pixel = vec2<i32>(uv * vec2<f32>(textureDimensions(curr_depth)));
+    let motion_pixel = vec2<i32>(uv * vec2<f32>(textureDimensions(motion_tex)));
+
+    // Current surface into the previous camera
+    let world_pos = unproject(uniforms.inv_curr_view_proj, uv, textureLoad(curr_depth, curr_pixel, 0).r);
+    let prev_clip = uniforms.prev_view_proj * vec4<f32>(world_pos, 1.0);
+    let prev_ndc = prev_clip.xy / prev_clip.w;
+
+    // Camera reprojection, then undo the object's own motion
+    let motion = textureLoad(motion_tex, motion_pixel, 0).rg / uniforms.screen_size;
+    let prev_uv = vec2<f32>(prev_ndc.x * 0.5 + 0.5, 0.5 - prev_ndc.y * 0.5) - motion;
+
+    var result = vec4<f32>(0.0);
+    let on_screen = prev_clip.w > 0.0 && all(prev_uv >= vec2<f32>(0.0)) && all(prev_uv <= vec2<f32>(1.0));
+    if (on_screen) {
+        // Disocclusion: whatever the previous frame stored there is at a
+        // different view depth, so the surface was hidden
+        let prev_size = vec2<i32>(textureDimensions(prev_depth));
+        let prev_pixel = min(vec2<i32>(prev_uv * vec2<f32>(prev_size)), prev_size - 1);
+        let stored = unproject(uniforms.inv_prev_view_proj, prev_uv, textureLoad(prev_depth, prev_pixel, 0).r);
+        let stored_w = (uniforms.prev_view_proj * vec4<f32>(stored, 1.0)).w;
+        if (abs(stored_w - prev_clip.w) <= uniforms.depth_tolerance * prev_clip.w) {
+            // The blend weights the motion-compensated history against the
+            // previous frame at this pixel
+            let warped = textureSampleLevel(prev_color, color_sampler, prev_uv, 0.0).rgb;
+            let still = textureSampleLevel(prev_color, color_sampler, uv, 0.0).rgb;
+            result = vec4<f32>(mix(still, warped, uniforms.blend_factor), 1.0);
+        }
+    }
+    textureStore(output_tex, pixel, result);
+}
+"#;
+
+pub(crate) const COMPOSITE_WGSL: &str = r#"
+@group(0) @binding(0) var reprojected: texture_2d<f32>;
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_composite(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let color = textureLoad(reprojected, vec2<i32>(pos.xy), 0);
+    if (color.a <= 0.0) {
+        discard;
+    }
+    return vec4<f32>(color.rgb, 1.0);
+}
+"#;
+
+pub(crate) const TILE_ERROR_WGSL: &str = r#"
+struct ValidationUniforms {
+    screen_size: vec2<u32>,
+    tile_size: u32,
+    tile_count: u32,
+};
+
+@group(0) @binding(0) var reprojected: texture_2d<f32>;
+@group(0) @binding(1) var rendered: texture_2d<f32>;
+@group(0) @binding(2) var<storage, read> tiles: array<vec2<u32>>;
+@group(0) @binding(3) var<storage, read_write> errors: array<f32>;
+@group(0) @binding(4) var<uniform> uniforms: ValidationUniforms;
+
+const LUMA = vec3<f32>(0.2126, 0.7152, 0.0722);
+
+var<workgroup> partial_error: array<f32, 64>;
+var<workgroup> partial_count: array<f32, 64>;
+
+// One workgroup per validation tile
+@compute @workgroup_size(8, 8)
+fn tile_error(
+    @builtin(workgroup_id) group: vec3<u32>,
+    @builtin(local_invocation_id) local: vec3<u32>,
+    @builtin(local_invocation_index) lane: u32,
+) {
+    let origin = tiles[min(group.x, uniforms.tile_count - 1u)] * uniforms.tile_size;
+    var error_sum = 0.0;
+    var samples = 0.0;
+    for (var y = local.y; y < uniforms.tile_size; y += 8u) {
+        for (var x = local.x; x < uniforms.tile_size; x += 8u) {
+            let p = origin + vec2<u32>(x, y);
+            if (p.x < uniforms.screen_size.x && p.y < uniforms.screen_size.y) {
+                let reused = textureLoad(reprojected, vec2<i32>(p), 0);
+                // The rendered frame may be at render size
+                let scale = vec2<f32>(textureDimensions(rendered)) / vec2<f32>(uniforms.screen_size);
+                let truth = textureLoad(rendered, vec2<i32>(vec2<f32>(p) * scale), 0);
+                // Disoccluded pixels had nothing to show
+                var e = 1.0;
+                if (reused.a > 0.0) {
+                    e = abs(dot(reused.rgb - truth.rgb, LUMA));
+                }
+                error_sum += e;
+                samples += 1.0;
+            }
+        }
+    }
+    partial_error[lane] = error_sum;
+    partial_count[lane] = samples;
+    workgroupBarrier();
+
+    for (var stride = 32u; stride > 0u; stride >>= 1u) {
+        if (lane < stride) {
+            partial_error[lane] += partial_error[lane + stride];
+            partial_count[lane] += partial_count[lane + stride];
+        }
+        workgroupBarrier();
+    }
+    if (lane == 0u) {
+        errors[group.x] = partial_error[0] / max(partial_count[0], 1.0);
     }
 }
 "#;
@@ -872,6 +1869,7 @@ struct TileUniforms {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::headless::{golden, read_texture_rgba, test_device, HeadlessTarget};
 
     #[test]
     fn test_tile_coordinate() {
@@ -885,4 +1883,296 @@ mod tests {
         assert_eq!(config.tile_size, 16);
         assert_eq!(config.max_tiles_per_frame, 512);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_batch_tile_rects_merges_adjacent_tiles() {
+        // 2x2 block, a lone tile, and a 1-tile edge tile clipped by the screen
+        let tiles = [
+            TileCoord::new(0, 0), TileCoord::new(1, 0),
+            TileCoord::new(0, 1), TileCoord::new(1, 1),
+            TileCoord::new(4, 0),
+            TileCoord::new(6, 3),
+        ];
+        let rects = batch_tile_rects(&tiles, 16, 100, 60);
+        assert_eq!(rects, vec![
+            ScissorRect { x: 0, y: 0, width: 32, height: 32 },
+            ScissorRect { x: 64, y: 0, width: 16, height: 16 },
+            ScissorRect { x: 96, y: 48, width: 4, height: 12 },
+        ]);
+    }
+
+    #[test]
+    fn test_scissor_rects_scale_to_render_size() {
+        // resolution_scale 0.5: rects grow outwards and stay on the attachment
+        let rect = |x, y, width, height| ScissorRect { x, y, width, height };
+        let (screen, render) = ((100, 60), (50, 30));
+        assert_eq!(rect(64, 0, 16, 16).scaled(screen, render), Some(rect(32, 0, 8, 8)));
+        assert_eq!(rect(96, 48, 4, 12).scaled(screen, render), Some(rect(48, 24, 2, 6)));
+        assert_eq!(rect(1, 1, 1, 1).scaled(screen, render), Some(rect(0, 0, 1, 1)));
+        assert_eq!(rect(90, 0, 20, 10).scaled(screen, render), Some(rect(45, 0, 5, 5)));
+        assert_eq!(rect(100, 0, 8, 8).scaled(screen, render), None);
+        assert_eq!(rect(96, 48, 4, 12).scaled(screen, screen), Some(rect(96, 48, 4, 12)));
+    }
+
+    #[test]
+    fn test_schedule_hot_tiles_honours_budget() {
+        let config = PredictiveRenderConfig { max_tiles_per_frame: 3, ..Default::default() };
+        let mut manager = TileManager::new(config, 64, 16);
+        let delta = DeltaPrediction {
+            changed_entities: HashSet::new(),
+            affected_tiles: (0..4).map(|x| TileCoord::new(x, 0)).collect(),
+            motion_vectors: HashMap::new(),
+            camera_motion: CameraMotion { position_delta: Vec3::ZERO, rotation_delta: Vec2::ZERO, view_proj_change: 0.0 },
+        };
+        manager.update(&delta);
+        manager.mark_error(TileCoord::new(3, 0), 1.0);
+
+        let (scheduled, deferred) = manager.schedule_hot_tiles(0);
+        assert_eq!(deferred, 1);
+        // Forced refresh first, then row order among equally stale tiles
+        assert_eq!(scheduled, vec![TileCoord::new(3, 0), TileCoord::new(0, 0), TileCoord::new(1, 0)]);
+
+        // The deferred tile stays hot into the next frame
+        manager.update(&DeltaPrediction { affected_tiles: HashSet::new(), ..delta.clone() });
+        let (scheduled, deferred) = manager.schedule_hot_tiles(0);
+        assert_eq!(deferred, 0);
+        assert!(scheduled.contains(&TileCoord::new(2, 0)));
+
+        // Slots reserved for validation tiles come out of the budget
+        manager.update(&DeltaPrediction { affected_tiles: (0..4).map(|x| TileCoord::new(x, 0)).collect(), ..delta });
+        let (scheduled, deferred) = manager.schedule_hot_tiles(2);
+        assert_eq!((scheduled.len(), deferred), (1, 3));
+    }
+
+    fn upload(device: &Device, queue: &Queue, format: TextureFormat, size: u32, data: &[u8]) -> Texture {
+        device.create_texture_with_data(queue, &TextureDescriptor {
+            label: None,
+            size: Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        }, util::TextureDataOrder::LayerMajor, data)
+    }
+
+    #[test]
+    fn test_gpu_reprojection_rejects_disoccluded_pixels() {
+        let Some((device, queue)) = test_device() else { return };
+        const SIZE: u32 = 16;
+        let view_proj = Mat4::perspective_rh(1.0, 1.0, 0.1, 100.0)
+            * Mat4::look_at_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+        let depth_at = |distance: f32| view_proj.project_point3(Vec3::new(0.0, 0.0, -distance)).z;
+        let pixels = (SIZE * SIZE) as usize;
+
+        // Previous frame: left half red, right half green, a wall 10 units away
+        let color: Vec<u8> = (0..pixels)
+            .flat_map(|i| if (i as u32 % SIZE) < SIZE / 2 { [255, 0, 0, 255] } else { [0, 255, 0, 255] })
+            .collect();
+        let wall: Vec<u8> = bytemuck::cast_slice(&vec![depth_at(10.0); pixels]).to_vec();
+        // Current frame: an object 5 units away now covers the top rows
+        let current: Vec<f32> = (0..pixels)
+            .map(|i| if (i as u32 / SIZE) < 4 { depth_at(5.0) } else { depth_at(10.0) })
+            .collect();
+        // Rows 8.. moved 2 pixels right since the previous frame (f16 2.0 = 0x4000)
+        let motion: Vec<u8> = (0..pixels)
+            .flat_map(|i| if (i as u32 / SIZE) >= 8 { [0x00, 0x40, 0, 0] } else { [0; 4] })
+            .collect();
+
+        let prev_color = upload(&device, &queue, HISTORY_COLOR_FORMAT, SIZE, &color);
+        let prev_depth = upload(&device, &queue, HISTORY_DEPTH_FORMAT, SIZE, &wall);
+        let curr_depth = upload(&device, &queue, HISTORY_DEPTH_FORMAT, SIZE, bytemuck::cast_slice(&current));
+        let motion = upload(&device, &queue, MOTION_VECTOR_FORMAT, SIZE, &motion);
+        let (curr_depth_view, motion_view) = (curr_depth.create_view(&Default::default()), motion.create_view(&Default::default()));
+        let inputs = ReprojectionInputs { view_proj, depth: &curr_depth_view, motion: &motion_view };
+
+        // Fully warped, so each pixel shows exactly where it came from
+        let config = PredictiveRenderConfig { reprojection_blend: 1.0, ..Default::default() };
+        let mut engine = ReprojectionEngine::new(&device, &config, SIZE, SIZE);
+        let delta = DeltaPrediction {
+            changed_entities: HashSet::new(),
+            affected_tiles: HashSet::new(),
+            motion_vectors: HashMap::new(),
+            camera_motion: CameraMotion { position_delta: Vec3::ZERO, rotation_delta: Vec2::ZERO, view_proj_change: 0.0 },
+        };
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+        assert!(!engine.reproject(&device, &queue, &mut encoder, &inputs, &delta));
+        engine.push_frame(Arc::new(prev_color), Arc::new(prev_depth), view_proj);
+        assert!(engine.reproject(&device, &queue, &mut encoder, &inputs, &delta));
+        queue.submit(Some(encoder.finish()));
+        assert!(engine.has_history());
+
+        let out = read_texture_rgba(&device, &queue, engine.output(&device), SIZE, SIZE).unwrap();
+        let px = |x: u32, y: u32| &out[((y * SIZE + x) * 4) as usize..][..4];
+        // Static wall reuses history
+        assert_eq!(px(1, 5), &[255, 0, 0, 255]);
+        assert_eq!(px(14, 5), &[0, 255, 0, 255]);
+        // The new object was hidden last frame: disoccluded
+        assert_eq!(px(3, 1)[3], 0);
+        // Moving pixels fetch history from 2 pixels to the left
+        assert_eq!(px(SIZE / 2 + 1, 10), &[255, 0, 0, 255]);
+        assert_eq!(px(SIZE / 2 + 2, 10), &[0, 255, 0, 255]);
+        // Nothing to reproject from on the left edge
+        assert_eq!(px(0, 10)[3], 0);
+    }
+
+    #[test]
+    fn test_composited_reprojection_matches_golden() {
+        let Some((device, queue)) = test_device() else { return };
+        const SIZE: u32 = 32;
+        let view_proj = Mat4::perspective_rh(1.0, 1.0, 0.1, 100.0)
+            * Mat4::look_at_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+        let pixels = (SIZE * SIZE) as usize;
+
+        // Gradient history on a static wall; everything moved 3 pixels right
+        let color: Vec<u8> = (0..pixels)
+            .flat_map(|i| [(i as u32 % SIZE * 8) as u8, (i as u32 / SIZE * 8) as u8, 96, 255])
+            .collect();
+        let depth = vec![view_proj.project_point3(Vec3::new(0.0, 0.0, -10.0)).z; pixels];
+        // f16 3.0 = 0x4200
+        let motion: Vec<u8> = (0..pixels).flat_map(|_| [0x00, 0x42, 0, 0]).collect();
+
+        let prev_color = upload(&device, &queue, HISTORY_COLOR_FORMAT, SIZE, &color);
+        let depth = upload(&device, &queue, HISTORY_DEPTH_FORMAT, SIZE, bytemuck::cast_slice(&depth));
+        let motion = upload(&device, &queue, MOTION_VECTOR_FORMAT, SIZE, &motion);
+        let (depth_view, motion_view) = (depth.create_view(&Default::default()), motion.create_view(&Default::default()));
+        let inputs = ReprojectionInputs { view_proj, depth: &depth_view, motion: &motion_view };
+
+        let config = PredictiveRenderConfig { reprojection_blend: 1.0, ..Default::default() };
+        let mut engine = ReprojectionEngine::new(&device, &config, SIZE, SIZE);
+        let compositor = FrameCompositor::new(&device, crate::headless::HEADLESS_FORMAT);
+        let target = HeadlessTarget::new(&device, SIZE, SIZE);
+        let delta = DeltaPrediction {
+            changed_entities: HashSet::new(),
+            affected_tiles: HashSet::new(),
+            motion_vectors: HashMap::new(),
+            camera_motion: CameraMotion { position_delta: Vec3::ZERO, rotation_delta: Vec2::ZERO, view_proj_change: 0.0 },
+        };
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+        engine.push_frame(Arc::new(prev_color), Arc::new(depth), view_proj);
+        assert!(engine.reproject(&device, &queue, &mut encoder, &inputs, &delta));
+        compositor.composite(&device, &mut encoder, engine.output_view(&device), &target.create_view());
+        queue.submit(Some(encoder.finish()));
+
+        let frame = target.read_rgba(&device, &queue).unwrap();
+        // Disoccluded left edge stays black for the hot tiles to fill
+        assert_eq!(&frame[..4], &[0, 0, 0, 255]);
+        golden::compare_golden("predictive_composite", &frame, SIZE, SIZE, Default::default()).unwrap();
+    }
+
+    #[test]
+    fn test_reprojection_blend_weights_warped_history() {
+        let Some((device, queue)) = test_device() else { return };
+        const SIZE: u32 = 16;
+        let view_proj = Mat4::perspective_rh(1.0, 1.0, 0.1, 100.0)
+            * Mat4::look_at_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+        let pixels = (SIZE * SIZE) as usize;
+
+        // Left half red, right half green on a static wall; everything moved
+        // 2 pixels right (f16 2.0 = 0x4000)
+        let color: Vec<u8> = (0..pixels)
+            .flat_map(|i| if (i as u32 % SIZE) < SIZE / 2 { [255, 0, 0, 255] } else { [0, 255, 0, 255] })
+            .collect();
+        let depth = vec![view_proj.project_point3(Vec3::new(0.0, 0.0, -10.0)).z; pixels];
+        let motion: Vec<u8> = (0..pixels).flat_map(|_| [0x00, 0x40, 0, 0]).collect();
+
+        let prev_color = Arc::new(upload(&device, &queue, HISTORY_COLOR_FORMAT, SIZE, &color));
+        let depth = Arc::new(upload(&device, &queue, HISTORY_DEPTH_FORMAT, SIZE, bytemuck::cast_slice(&depth)));
+        let motion = upload(&device, &queue, MOTION_VECTOR_FORMAT, SIZE, &motion);
+        let (depth_view, motion_view) = (depth.create_view(&Default::default()), motion.create_view(&Default::default()));
+        let inputs = ReprojectionInputs { view_proj, depth: &depth_view, motion: &motion_view };
+        let delta = DeltaPrediction {
+            changed_entities: HashSet::new(),
+            affected_tiles: HashSet::new(),
+            motion_vectors: HashMap::new(),
+            camera_motion: CameraMotion { position_delta: Vec3::ZERO, rotation_delta: Vec2::ZERO, view_proj_change: 0.0 },
+        };
+        let composite = |blend: f32| {
+            let config = PredictiveRenderConfig { reprojection_blend: blend, ..Default::default() };
+            let mut engine = ReprojectionEngine::new(&device, &config, SIZE, SIZE);
+            let compositor = FrameCompositor::new(&device, crate::headless::HEADLESS_FORMAT);
+            let target = HeadlessTarget::new(&device, SIZE, SIZE);
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+            engine.push_frame(prev_color.clone(), depth.clone(), view_proj);
+            assert!(engine.reproject(&device, &queue, &mut encoder, &inputs, &delta));
+            compositor.composite(&device, &mut encoder, engine.output_view(&device), &target.create_view());
+            queue.submit(Some(encoder.finish()));
+            target.read_rgba(&device, &queue).unwrap()
+        };
+        let (warped, blended) = (composite(1.0), composite(0.5));
+        let px = |frame: &[u8], x: u32| frame[((4 * SIZE + x) * 4) as usize..][..4].to_vec();
+
+        // Where warped and unwarped history agree, the blend changes nothing
+        assert_eq!(px(&warped, 4), px(&blended, 4));
+        assert_eq!(px(&warped, 13), px(&blended, 13));
+        // Pixels that moved in from red over green get half of each
+        assert_eq!(px(&warped, SIZE / 2 + 1), [255, 0, 0, 255]);
+        let mixed = px(&blended, SIZE / 2 + 1);
+        assert!(mixed[0] > 0 && mixed[0] < 255 && mixed[1] > 0 && mixed[1] < 255, "{:?}", mixed);
+    }
+
+    #[test]
+    fn test_validation_tiles_skip_scheduled_and_wait_for_interval() {
+        let Some((device, _queue)) = test_device() else { return };
+        let config = PredictiveRenderConfig { validation_tiles: 8, watchdog_check_interval: 4, ..Default::default() };
+        let mut estimator = ErrorEstimator::new(&device, &config);
+        let scheduled: Vec<TileCoord> = (0..8).map(|x| TileCoord::new(x, 0)).collect();
+
+        assert!(estimator.select_tiles(3, (8, 4), &scheduled, usize::MAX).is_empty());
+        let picked = estimator.select_tiles(4, (8, 4), &scheduled, usize::MAX);
+        assert_eq!(picked.len(), 8);
+        assert!(picked.iter().all(|t| t.y > 0 && t.x < 8 && t.y < 4));
+        let unique: HashSet<_> = picked.iter().collect();
+        assert_eq!(unique.len(), picked.len());
+        assert_eq!(estimator.select_tiles(4, (8, 4), &scheduled, 3).len(), 3);
+    }
+
+    #[test]
+    fn test_gpu_tile_error_measures_luminance_difference() {
+        let Some((device, queue)) = test_device() else { return };
+        const SIZE: u32 = 32;
+        let pixels = (SIZE * SIZE) as usize;
+        let tile_of = |i: usize| ((i as u32 % SIZE) / 16, (i as u32 / SIZE) / 16);
+
+        // Reprojection is exact in tile (0,0), black where white was
+        // rendered in tile (1,0), and disoccluded in the top half of (0,1)
+        let rendered: Vec<u8> = (0..pixels)
+            .flat_map(|i| if tile_of(i) == (1, 0) { [255, 255, 255, 255] } else { [128, 64, 32, 255] })
+            .collect();
+        let reprojected: Vec<u8> = (0..pixels)
+            .flat_map(|i| match tile_of(i) {
+                (1, 0) => [0, 0, 0, 217],
+                (0, 1) if (i as u32 / SIZE) < 24 => [0; 4],
+                _ => [128, 64, 32, 217],
+            })
+            .collect();
+        let rendered = upload(&device, &queue, HISTORY_COLOR_FORMAT, SIZE, &rendered);
+        let reprojected = upload(&device, &queue, HISTORY_COLOR_FORMAT, SIZE, &reprojected);
+
+        let config = PredictiveRenderConfig { validation_tiles: 3, watchdog_check_interval: 1, ..Default::default() };
+        let mut estimator = ErrorEstimator::new(&device, &config);
+        assert_eq!(estimator.select_tiles(0, (2, 2), &[TileCoord::new(1, 1)], usize::MAX).len(), 3);
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+        estimator.record(
+            &device,
+            &mut encoder,
+            &reprojected.create_view(&Default::default()),
+            &rendered.create_view(&Default::default()),
+            SIZE,
+            SIZE,
+        );
+        queue.submit(Some(encoder.finish()));
+
+        assert!(estimator.poll(&device).is_none());
+        device.poll(Maintain::Wait);
+        let errors: HashMap<TileCoord, f32> = estimator.poll(&device).expect("readback").into_iter().collect();
+        assert!(errors[&TileCoord::new(0, 0)] < 1e-6);
+        assert!((errors[&TileCoord::new(1, 0)] - 1.0).abs() < 1e-3);
+        assert!((errors[&TileCoord::new(0, 1)] - 0.5).abs() < 1e-3);
+        // The estimator is free for the next check
+        assert!(matches!(estimator.readback, ValidationReadback::Idle));
+    }
+}
@@ -0,0 +1,541 @@
+// src/render_graph.rs
+//! RENDER GRAPH
+//!
+//! Passes declare the textures they read and write instead of being wired
+//! by hand. Every write produces a new version of a resource, so a pass
+//! that reads an older version is ordered before the pass that overwrites
+//! it. From those declarations the graph:
+//! - derives execution order (read-after-write, write-after-write and
+//!   write-after-read dependencies, ties broken by declaration order)
+//! - culls passes whose results never reach an imported texture
+//! - aliases transient textures with disjoint lifetimes onto one physical
+//!   texture from a pool that persists across frames
+//! - records the hazards between passes. wgpu inserts the matching
+//!   transitions itself at pass boundaries, so they are kept for ordering
+//!   and inspection.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use wgpu::*;
+
+/// Description of a transient texture; equal descriptions can alias
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureDesc {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub usage: TextureUsages,
+}
+
+impl TextureDesc {
+    pub fn new(width: u32, height: u32, format: TextureFormat, usage: TextureUsages) -> Self {
+        Self { width: width.max(1), height: height.max(1), format, usage }
+    }
+}
+
+/// A specific version of a graph resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ResourceHandle {
+    index: u32,
+    version: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hazard {
+    ReadAfterWrite,
+    WriteAfterWrite,
+    WriteAfterRead,
+}
+
+/// Dependency between two passes through one resource
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Barrier {
+    pub resource: &'static str,
+    pub before: &'static str,
+    pub after: &'static str,
+    pub hazard: Hazard,
+}
+
+#[derive(Debug, Clone)]
+pub enum RenderGraphError {
+    /// The pass wrote an older version of a resource that was already overwritten
+    StaleWrite { pass: &'static str, resource: &'static str },
+    /// The pass read a transient texture nothing has written yet
+    ReadBeforeWrite { pass: &'static str, resource: &'static str },
+    /// The live passes depend on each other in a cycle and cannot be ordered
+    Cycle { passes: Vec<&'static str> },
+}
+
+impl std::fmt::Display for RenderGraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RenderGraphError::StaleWrite { pass, resource } => {
+                write!(f, "Pass {} writes a stale version of {}", pass, resource)
+            }
+            RenderGraphError::ReadBeforeWrite { pass, resource } => {
+                write!(f, "Pass {} reads {} before any pass writes it", pass, resource)
+            }
+            RenderGraphError::Cycle { passes } => {
+                write!(f, "Passes {} depend on each other in a cycle", passes.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for RenderGraphError {}
+
+enum ResourceSource<'a> {
+    Transient(TextureDesc),
+    Imported(&'a TextureView),
+}
+
+struct ResourceNode<'a> {
+    name: &'static str,
+    source: ResourceSource<'a>,
+    version: u32,
+}
+
+type PassExecute<'a> = Box<dyn FnOnce(&mut PassContext) + 'a>;
+
+struct PassNode<'a> {
+    name: &'static str,
+    reads: Vec<ResourceHandle>,
+    writes: Vec<ResourceHandle>,
+    side_effect: bool,
+    execute: Option<PassExecute<'a>>,
+}
+
+// ============================================================================
+// GRAPH BUILDING
+// ============================================================================
+
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    resources: Vec<ResourceNode<'a>>,
+    passes: Vec<PassNode<'a>>,
+    errors: Vec<RenderGraphError>,
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Transient texture owned by the graph; a pass must write it before use
+    pub fn create_texture(&mut self, name: &'static str, desc: TextureDesc) -> ResourceHandle {
+        self.add_resource(name, ResourceSource::Transient(desc))
+    }
+
+    /// Texture owned outside the graph (swapchain, shadow map, ...). Passes
+    /// that write an imported texture are never culled.
+    pub fn import_texture(&mut self, name: &'static str, view: &'a TextureView) -> ResourceHandle {
+        self.add_resource(name, ResourceSource::Imported(view))
+    }
+
+    pub fn add_pass<'g>(&'g mut self, name: &'static str) -> PassBuilder<'g, 'a> {
+        self.passes.push(PassNode { name, reads: Vec::new(), writes: Vec::new(), side_effect: false, execute: None });
+        let pass = self.passes.len() - 1;
+        PassBuilder { graph: self, pass }
+    }
+
+    fn add_resource(&mut self, name: &'static str, source: ResourceSource<'a>) -> ResourceHandle {
+        self.resources.push(ResourceNode { name, source, version: 0 });
+        ResourceHandle { index: self.resources.len() as u32 - 1, version: 0 }
+    }
+
+    fn is_imported(&self, index: u32) -> bool {
+        matches!(self.resources[index as usize].source, ResourceSource::Imported(_))
+    }
+
+    // ========================================================================
+    // COMPILATION
+    // ========================================================================
+
+    /// Derive order, culling, hazards and aliasing without touching the GPU
+    pub fn compile(&self) -> Result<CompiledGraph, RenderGraphError> {
+        if let Some(error) = self.errors.first() {
+            return Err(error.clone());
+        }
+
+        let mut writer = HashMap::new();
+        let mut readers: HashMap<ResourceHandle, Vec<usize>> = HashMap::new();
+        for (p, pass) in self.passes.iter().enumerate() {
+            for &h in &pass.writes {
+                writer.insert(h, p);
+            }
+            for &h in &pass.reads {
+                readers.entry(h).or_default().push(p);
+            }
+        }
+
+        // Edges (before, after, resource, hazard)
+        let mut edges = Vec::new();
+        for (p, pass) in self.passes.iter().enumerate() {
+            for &h in &pass.reads {
+                match writer.get(&h) {
+                    Some(&w) if w != p => edges.push((w, p, h.index, Hazard::ReadAfterWrite)),
+                    Some(_) => {}
+                    None if !self.is_imported(h.index) => {
+                        return Err(RenderGraphError::ReadBeforeWrite { pass: pass.name, resource: self.resources[h.index as usize].name });
+                    }
+                    None => {}
+                }
+            }
+            for &h in &pass.writes {
+                let prev = ResourceHandle { index: h.index, version: h.version - 1 };
+                if let Some(&w) = writer.get(&prev) {
+                    edges.push((w, p, h.index, Hazard::WriteAfterWrite));
+                }
+                for &r in readers.get(&prev).into_iter().flatten() {
+                    if r != p {
+                        edges.push((r, p, h.index, Hazard::WriteAfterRead));
+                    }
+                }
+            }
+        }
+
+        // Cull: keep roots and whatever produces data they (transitively) use
+        let mut live = vec![false; self.passes.len()];
+        let mut stack: Vec<usize> = (0..self.passes.len())
+            .filter(|&p| self.passes[p].side_effect || self.passes[p].writes.iter().any(|h| self.is_imported(h.index)))
+            .collect();
+        while let Some(p) = stack.pop() {
+            if std::mem::replace(&mut live[p], true) {
+                continue;
+            }
+            for &(before, after, _, hazard) in &edges {
+                if after == p && hazard != Hazard::WriteAfterRead && !live[before] {
+                    stack.push(before);
+                }
+            }
+        }
+
+        // Topological order among live passes, declaration order on ties
+        let mut in_degree = vec![0usize; self.passes.len()];
+        let live_edges: Vec<_> = edges.iter().filter(|e| live[e.0] && live[e.1]).collect();
+        let mut unique = HashSet::new();
+        for e in &live_edges {
+            if unique.insert((e.0, e.1)) {
+                in_degree[e.1] += 1;
+            }
+        }
+        let mut ready: BTreeSet<usize> = (0..self.passes.len()).filter(|&p| live[p] && in_degree[p] == 0).collect();
+        let mut order = Vec::new();
+        while let Some(p) = ready.pop_first() {
+            order.push(p);
+            for &(_, after) in unique.iter().filter(|(before, _)| *before == p) {
+                in_degree[after] -= 1;
+                if in_degree[after] == 0 {
+                    ready.insert(after);
+                }
+            }
+        }
+        if order.len() < live.iter().filter(|&&l| l).count() {
+            let passes = (0..self.passes.len()).filter(|&p| live[p] && in_degree[p] > 0).map(|p| self.passes[p].name).collect();
+            return Err(RenderGraphError::Cycle { passes });
+        }
+
+        let barriers = live_edges
+            .iter()
+            .map(|&&(before, after, resource, hazard)| Barrier {
+                resource: self.resources[resource as usize].name,
+                before: self.passes[before].name,
+                after: self.passes[after].name,
+                hazard,
+            })
+            .collect();
+
+        // Lifetimes of transient textures over the execution order
+        let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; self.resources.len()];
+        for (position, &p) in order.iter().enumerate() {
+            for h in self.passes[p].reads.iter().chain(&self.passes[p].writes) {
+                let span = lifetimes[h.index as usize].get_or_insert((position, position));
+                span.1 = position;
+            }
+        }
+
+        // Alias: first fit into a slot with the same description that is free again
+        let mut physical = vec![None; self.resources.len()];
+        let mut slot_descs: Vec<TextureDesc> = Vec::new();
+        let mut slot_free_after: Vec<usize> = Vec::new();
+        let mut transient: Vec<usize> = (0..self.resources.len())
+            .filter(|&r| lifetimes[r].is_some() && !self.is_imported(r as u32))
+            .collect();
+        transient.sort_by_key(|&r| lifetimes[r].map(|l| l.0));
+        for &r in &transient {
+            let ResourceSource::Transient(desc) = self.resources[r].source else { continue };
+            let (first, last) = lifetimes[r].unwrap();
+            let slot = (0..slot_descs.len()).find(|&s| slot_descs[s] == desc && slot_free_after[s] < first);
+            let slot = slot.unwrap_or_else(|| {
+                slot_descs.push(desc);
+                slot_free_after.push(0);
+                slot_descs.len() - 1
+            });
+            slot_free_after[slot] = last;
+            physical[r] = Some(slot);
+        }
+
+        Ok(CompiledGraph {
+            passes: order.iter().map(|&p| self.passes[p].name).collect(),
+            culled: (0..self.passes.len()).filter(|&p| !live[p]).map(|p| self.passes[p].name).collect(),
+            barriers,
+            transient_textures: transient.len(),
+            physical_textures: slot_descs.len(),
+            order,
+            physical,
+            slot_descs,
+        })
+    }
+
+    // ========================================================================
+    // EXECUTION
+    // ========================================================================
+
+    /// Compile, bind physical textures from `pool` and record the live passes
+    pub fn execute(mut self, device: &Device, encoder: &mut CommandEncoder, pool: &mut TransientTexturePool) -> Result<CompiledGraph, RenderGraphError> {
+        let compiled = self.compile()?;
+        pool.prepare(device, &compiled.slot_descs);
+        let slot_views = pool.views(&compiled.slot_descs);
+
+        let views: Vec<Option<&TextureView>> = self
+            .resources
+            .iter()
+            .zip(&compiled.physical)
+            .map(|(resource, slot)| match resource.source {
+                ResourceSource::Imported(view) => Some(view),
+                ResourceSource::Transient(_) => slot.map(|s| slot_views[s]),
+            })
+            .collect();
+
+        for &p in &compiled.order {
+            if let Some(execute) = self.passes[p].execute.take() {
+                encoder.push_debug_group(self.passes[p].name);
+                execute(&mut PassContext { device, encoder, views: &views });
+                encoder.pop_debug_group();
+            }
+        }
+        Ok(compiled)
+    }
+}
+
+/// Declares one pass's resource usage
+pub struct PassBuilder<'g, 'a> {
+    graph: &'g mut RenderGraph<'a>,
+    pass: usize,
+}
+
+impl<'a> PassBuilder<'_, 'a> {
+    pub fn read(&mut self, handle: ResourceHandle) -> ResourceHandle {
+        self.graph.passes[self.pass].reads.push(handle);
+        handle
+    }
+
+    /// Write `handle`, returning the new version later passes should use
+    pub fn write(&mut self, handle: ResourceHandle) -> ResourceHandle {
+        let resource = &mut self.graph.resources[handle.index as usize];
+        if resource.version != handle.version {
+            let pass = self.graph.passes[self.pass].name;
+            self.graph.errors.push(RenderGraphError::StaleWrite { pass, resource: resource.name });
+        }
+        resource.version += 1;
+        let written = ResourceHandle { index: handle.index, version: resource.version };
+        self.graph.passes[self.pass].writes.push(written);
+        written
+    }
+
+    /// Create a transient texture and write it in this pass
+    pub fn create(&mut self, name: &'static str, desc: TextureDesc) -> ResourceHandle {
+        let handle = self.graph.create_texture(name, desc);
+        self.write(handle)
+    }
+
+    /// Never cull this pass (e.g. it writes buffers the graph can't see)
+    pub fn side_effect(&mut self) {
+        self.graph.passes[self.pass].side_effect = true;
+    }
+
+    pub fn execute(self, execute: impl FnOnce(&mut PassContext) + 'a) {
+        self.graph.passes[self.pass].execute = Some(Box::new(execute));
+    }
+}
+
+/// What a pass sees while recording
+pub struct PassContext<'r> {
+    pub device: &'r Device,
+    pub encoder: &'r mut CommandEncoder,
+    views: &'r [Option<&'r TextureView>],
+}
+
+impl<'r> PassContext<'r> {
+    /// Physical view behind a resource (all versions share one texture).
+    /// Not tied to the borrow of the context, so it can be held while
+    /// recording into `encoder`.
+    pub fn view(&self, handle: ResourceHandle) -> &'r TextureView {
+        self.views[handle.index as usize].expect("resource not used by any live pass")
+    }
+}
+
+/// Result of compiling a graph
+#[derive(Debug, Clone)]
+pub struct CompiledGraph {
+    /// Live passes in execution order
+    pub passes: Vec<&'static str>,
+    pub culled: Vec<&'static str>,
+    pub barriers: Vec<Barrier>,
+    /// Transient textures used by live passes
+    pub transient_textures: usize,
+    /// Physical textures backing them after aliasing
+    pub physical_textures: usize,
+    order: Vec<usize>,
+    physical: Vec<Option<usize>>,
+    slot_descs: Vec<TextureDesc>,
+}
+
+// ============================================================================
+// TRANSIENT TEXTURE POOL
+// ============================================================================
+
+/// Physical textures for transient resources, reused across frames. Textures
+/// a frame no longer needs (e.g. after a resize) are released.
+#[derive(Default)]
+pub struct TransientTexturePool {
+    textures: HashMap<TextureDesc, Vec<(Texture, TextureView)>>,
+}
+
+impl TransientTexturePool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn prepare(&mut self, device: &Device, slots: &[TextureDesc]) {
+        let mut needed: HashMap<TextureDesc, usize> = HashMap::new();
+        for desc in slots {
+            *needed.entry(*desc).or_default() += 1;
+        }
+        self.textures.retain(|desc, _| needed.contains_key(desc));
+        for (desc, count) in needed {
+            let textures = self.textures.entry(desc).or_default();
+            textures.truncate(count);
+            while textures.len() < count {
+                let texture = device.create_texture(&TextureDescriptor {
+                    label: Some("Transient Texture"),
+                    size: Extent3d { width: desc.width, height: desc.height, depth_or_array_layers: 1 },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: desc.format,
+                    usage: desc.usage,
+                    view_formats: &[],
+                });
+                let view = texture.create_view(&TextureViewDescriptor::default());
+                textures.push((texture, view));
+            }
+        }
+    }
+
+    fn views(&self, slots: &[TextureDesc]) -> Vec<&TextureView> {
+        let mut taken: HashMap<TextureDesc, usize> = HashMap::new();
+        slots
+            .iter()
+            .map(|desc| {
+                let n = taken.entry(*desc).or_default();
+                *n += 1;
+                &self.textures[desc][*n - 1].1
+            })
+            .collect()
+    }
+
+    pub fn texture_count(&self) -> usize {
+        self.textures.values().map(Vec::len).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn desc() -> TextureDesc {
+        TextureDesc::new(64, 64, TextureFormat::Rgba16Float, TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING)
+    }
+
+    #[test]
+    fn test_order_follows_versions_and_unused_passes_are_culled() {
+        let mut graph = RenderGraph::new();
+
+        let mut pass = graph.add_pass("geometry");
+        let hdr = pass.create("hdr", desc());
+        let mut pass = graph.add_pass("particles");
+        let hdr_lit = pass.write(hdr);
+        // Declared after particles but reads the pre-particle image
+        let mut pass = graph.add_pass("bloom");
+        pass.read(hdr);
+        let bloom = pass.create("bloom", desc());
+        let mut pass = graph.add_pass("debug_view");
+        pass.read(hdr_lit);
+        pass.create("debug", desc());
+        let mut pass = graph.add_pass("post");
+        pass.read(hdr_lit);
+        pass.read(bloom);
+        pass.side_effect();
+
+        let compiled = graph.compile().unwrap();
+        assert_eq!(compiled.passes, vec!["geometry", "bloom", "particles", "post"]);
+        assert_eq!(compiled.culled, vec!["debug_view"]);
+        assert!(compiled.barriers.contains(&Barrier { resource: "hdr", before: "bloom", after: "particles", hazard: Hazard::WriteAfterRead }));
+        assert!(compiled.barriers.contains(&Barrier { resource: "hdr", before: "geometry", after: "particles", hazard: Hazard::WriteAfterWrite }));
+    }
+
+    #[test]
+    fn test_transients_with_disjoint_lifetimes_alias() {
+        let mut graph = RenderGraph::new();
+        let mut pass = graph.add_pass("prefilter");
+        let a = pass.create("a", desc());
+        let mut pass = graph.add_pass("blur_h");
+        pass.read(a);
+        let b = pass.create("b", desc());
+        let mut pass = graph.add_pass("blur_v");
+        pass.read(b);
+        let c = pass.create("c", desc());
+        let mut pass = graph.add_pass("present");
+        pass.read(c);
+        pass.side_effect();
+
+        let compiled = graph.compile().unwrap();
+        assert_eq!(compiled.transient_textures, 3);
+        assert_eq!(compiled.physical_textures, 2);
+    }
+
+    #[test]
+    fn test_invalid_access_is_rejected() {
+        let mut graph = RenderGraph::new();
+        let mut pass = graph.add_pass("geometry");
+        let hdr = pass.create("hdr", desc());
+        graph.add_pass("a").write(hdr);
+        graph.add_pass("b").write(hdr);
+        assert!(matches!(graph.compile(), Err(RenderGraphError::StaleWrite { pass: "b", resource: "hdr" })));
+
+        let mut graph = RenderGraph::new();
+        let never_written = graph.create_texture("gbuffer", desc());
+        graph.add_pass("lighting").read(never_written);
+        assert!(matches!(graph.compile(), Err(RenderGraphError::ReadBeforeWrite { pass: "lighting", .. })));
+    }
+
+    #[test]
+    fn test_dependency_cycle_is_rejected() {
+        let mut graph = RenderGraph::new();
+        let mut pass = graph.add_pass("geometry");
+        let hdr = pass.create("hdr", desc());
+        let mut pass = graph.add_pass("particles");
+        pass.write(hdr);
+        let mask = pass.create("mask", desc());
+        // Must run before particles overwrites hdr, but needs particles' mask
+        let mut pass = graph.add_pass("bloom");
+        pass.read(hdr);
+        pass.read(mask);
+        pass.side_effect();
+
+        match graph.compile() {
+            Err(RenderGraphError::Cycle { passes }) => assert_eq!(passes, vec!["particles", "bloom"]),
+            other => panic!("expected a cycle, got {:?}", other.map(|c| c.passes)),
+        }
+    }
+}
@@ -2,32 +2,81 @@
 //! OPTIMIZED RENDERER v2.0
 //! Uses high-performance shaders from shaders.rs
 //! Includes dynamic resolution, predictive rendering, and optimized pipelines
+//!
+//! Renders either to a window surface or to a headless offscreen target
+//! (see `headless.rs`), so the full pass chain can run in CI.
+//!
+//! Each frame is built as a render graph (see `render_graph.rs`): passes
+//! declare their textures, and which effects run, in what order, comes from
+//! `RenderConfig`.
+//!
+//! Scene geometry comes from `ResourceManager` meshes and materials submitted
+//! as `DrawItem`s; equal mesh/material pairs are drawn instanced. Instances
+//! are culled on the GPU and drawn with indirect draws.
 
-use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU8, Ordering};
 use std::sync::Arc;
 use wgpu::util::DeviceExt;
 use bytemuck::{Pod, Zeroable};
+use glam::{Mat3, Mat4, Vec2, Vec3, Vec4};
+use serde::Deserialize;
 
 use crate::shaders::*;
-use crate::predictive_renderer::{SceneSnapshot, TileCoord, TileManager};
-use crate::offload::{OffloadManager, ResourceTier};
+use crate::headless::HeadlessTarget;
+use crate::predictive_renderer::{
+    PredictiveRenderer, ReprojectionInputs, SceneSnapshot, TileRenderPlan, HISTORY_COLOR_FORMAT, HISTORY_DEPTH_FORMAT, MOTION_VECTOR_FORMAT,
+};
+use crate::render_graph::{RenderGraph, ResourceHandle, TextureDesc, TransientTexturePool};
+use crate::shader_preprocessor::{ShaderCache, ShaderFeatures};
+use crate::resource_manager::{Handle, MeshBuffers, ResourceManager};
+use crate::unreal_framework::{EComponentType, UWorld};
 
-#[cfg(not(target_arch = "wasm32"))]
-use dashmap::DashMap;
+const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+const AO_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+const Z_NEAR: f32 = 0.1;
+const Z_FAR: f32 = 100.0;
+const FOV_Y: f32 = std::f32::consts::FRAC_PI_3;
+/// Light clusters along screen x, screen y and (exponential) depth
+const CLUSTER_GRID: [u32; 3] = [16, 9, 24];
+/// Index slots per cluster; lights beyond this in one cluster are dropped
+const MAX_LIGHTS_PER_CLUSTER: u32 = 128;
+/// History weight of a static pixel whose history survived clamping
+const TAA_FEEDBACK: f32 = 0.9;
 
 // ============================================================================
 // RENDERER CONFIGURATION
 // ============================================================================
 
+/// Optional passes between the geometry pass and post-processing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EffectPass {
+    Ssao,
+    Ssr,
+    Particles,
+    Bloom,
+}
+
 #[derive(Debug, Clone)]
 pub struct RenderConfig {
     pub width: u32,
     pub height: u32,
     pub resolution_scale: f32,
-    pub shadow_resolution: u32,
+    pub shadows: ShadowSettings,
     pub enable_post_processing: bool,
     pub enable_ssao: bool,
     pub enable_bloom: bool,
+    pub enable_ssr: bool,
+    pub enable_particles: bool,
+    /// Temporal anti-aliasing on full (not tile-only) frames
+    pub enable_taa: bool,
+    pub taa: TaaSettings,
+    pub culling: CullingSettings,
+    /// Declaration order of the enabled effects. Effects that change the
+    /// HDR image see the ones listed before them (e.g. SSR after particles
+    /// reflects the particles, bloom before particles skips them).
+    pub effect_order: Vec<EffectPass>,
     pub vsync: bool,
 }
 
@@ -37,22 +86,142 @@ impl Default for RenderConfig {
             width: 1280,
             height: 720,
             resolution_scale: 1.0,
-            shadow_resolution: 2048,
+            shadows: ShadowSettings::default(),
             enable_post_processing: true,
             enable_ssao: true,
             enable_bloom: true,
+            enable_ssr: false,
+            enable_particles: true,
+            enable_taa: true,
+            taa: TaaSettings::default(),
+            culling: CullingSettings::default(),
+            effect_order: vec![EffectPass::Ssao, EffectPass::Ssr, EffectPass::Particles, EffectPass::Bloom],
             vsync: true,
         }
     }
 }
 
 impl RenderConfig {
+    /// Defaults overridden by the sections of a `settings.json` the renderer
+    /// reads (`shadows`, `rendering.anti_aliasing`, `rendering.culling`)
+    pub fn from_settings_json(json: &str) -> Result<Self, serde_json::Error> {
+        #[derive(Default, Deserialize)]
+        #[serde(default)]
+        struct Settings {
+            shadows: ShadowSettings,
+            rendering: RenderingSettings,
+        }
+        #[derive(Default, Deserialize)]
+        #[serde(default)]
+        struct RenderingSettings {
+            anti_aliasing: AntiAliasingSettings,
+            culling: CullingSettings,
+        }
+        #[derive(Deserialize)]
+        #[serde(default)]
+        struct AntiAliasingSettings {
+            enabled: bool,
+            mode: String,
+            taa: TaaSettings,
+        }
+        impl Default for AntiAliasingSettings {
+            fn default() -> Self {
+                Self { enabled: true, mode: "taa".into(), taa: TaaSettings::default() }
+            }
+        }
+        
+        let settings: Settings = serde_json::from_str(json)?;
+        let anti_aliasing = settings.rendering.anti_aliasing;
+        Ok(Self {
+            shadows: settings.shadows,
+            enable_taa: anti_aliasing.enabled && anti_aliasing.mode == "taa",
+            taa: anti_aliasing.taa,
+            culling: settings.rendering.culling,
+            ..Default::default()
+        })
+    }
+    
     pub fn effective_size(&self) -> (u32, u32) {
         (
-            (self.width as f32 * self.resolution_scale) as u32,
-            (self.height as f32 * self.resolution_scale) as u32,
+            ((self.width as f32 * self.resolution_scale) as u32).max(1),
+            ((self.height as f32 * self.resolution_scale) as u32).max(1),
         )
     }
+    
+    pub fn effect_enabled(&self, effect: EffectPass) -> bool {
+        match effect {
+            EffectPass::Ssao => self.enable_ssao,
+            EffectPass::Ssr => self.enable_ssr,
+            EffectPass::Particles => self.enable_particles,
+            EffectPass::Bloom => self.enable_bloom && self.enable_post_processing,
+        }
+    }
+    
+    /// Features the shaders are specialized for: effects that don't run are
+    /// compiled out instead of sampling white or black stand-ins
+    pub fn shader_features(&self) -> ShaderFeatures {
+        let runs = |effect| self.effect_order.contains(&effect) && self.effect_enabled(effect);
+        let mut features = ShaderFeatures::NONE;
+        features.set(ShaderFeatures::SHADOWS, self.shadows.active_cascades() > 0);
+        features.set(ShaderFeatures::SSAO, runs(EffectPass::Ssao));
+        features.set(ShaderFeatures::BLOOM, runs(EffectPass::Bloom));
+        features.set(ShaderFeatures::POST_EFFECTS, self.enable_post_processing);
+        features
+    }
+}
+
+// ============================================================================
+// RENDER TARGET
+// ============================================================================
+
+/// Where the final (post-processed) image goes
+pub enum RenderTarget {
+    Surface {
+        surface: wgpu::Surface<'static>,
+        config: wgpu::SurfaceConfiguration,
+    },
+    Headless(HeadlessTarget),
+}
+
+impl RenderTarget {
+    pub fn format(&self) -> wgpu::TextureFormat {
+        match self {
+            RenderTarget::Surface { config, .. } => config.format,
+            RenderTarget::Headless(target) => target.format(),
+        }
+    }
+    
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            RenderTarget::Surface { config, .. } => (config.width, config.height),
+            RenderTarget::Headless(target) => target.size(),
+        }
+    }
+    
+    /// Surface texture to present (None for headless) and the view to draw into
+    fn acquire(&self) -> Result<(Option<wgpu::SurfaceTexture>, wgpu::TextureView), wgpu::SurfaceError> {
+        match self {
+            RenderTarget::Surface { surface, .. } => {
+                let output = surface.get_current_texture()?;
+                let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+                Ok((Some(output), view))
+            }
+            RenderTarget::Headless(target) => Ok((None, target.create_view())),
+        }
+    }
+    
+    fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        match self {
+            RenderTarget::Surface { surface, config } => {
+                config.width = width.max(1);
+                config.height = height.max(1);
+                surface.configure(device, config);
+            }
+            RenderTarget::Headless(target) => {
+                *target = HeadlessTarget::with_format(device, width, height, target.format());
+            }
+        }
+    }
 }
 
 // ============================================================================
@@ -63,31 +232,93 @@ pub struct Renderer {
     device: Arc<wgpu::Device>,
     queue: Arc<wgpu::Queue>,
     config: RenderConfig,
+    target: RenderTarget,
     
     // Pipelines
     main_pipeline: wgpu::RenderPipeline,
     shadow_pipeline: wgpu::RenderPipeline,
+    ssao_pipeline: wgpu::RenderPipeline,
+    ssr_pipeline: wgpu::RenderPipeline,
+    particle_pipeline: wgpu::RenderPipeline,
+    bloom_prefilter_pipeline: wgpu::RenderPipeline,
+    bloom_blur_pipeline: wgpu::RenderPipeline,
     post_pipeline: wgpu::RenderPipeline,
-    mipmap_pipeline: wgpu::ComputePipeline,
+    /// Post-processing into the TAA input instead of the target
+    post_ldr_pipeline: wgpu::RenderPipeline,
+    taa_pipeline: wgpu::RenderPipeline,
+    /// Resolved frame from render size to the target
+    upscale_pipeline: wgpu::RenderPipeline,
+    depth_copy_pipeline: wgpu::ComputePipeline,
+    /// Depth and motion only, for `render_reprojected`
+    prepass_pipeline: wgpu::RenderPipeline,
+    /// Shader permutations for `config.shader_features()`
+    shaders: ShaderCache,
+    
+    // Layouts for bind groups rebuilt at runtime
+    main_bgl: wgpu::BindGroupLayout,
+    light_bgl: wgpu::BindGroupLayout,
+    material_bgl: wgpu::BindGroupLayout,
+    ssao_bgl: wgpu::BindGroupLayout,
+    ssr_bgl: wgpu::BindGroupLayout,
+    particle_bgl: wgpu::BindGroupLayout,
+    bloom_bgl: wgpu::BindGroupLayout,
+    post_bgl: wgpu::BindGroupLayout,
+    taa_bgl: wgpu::BindGroupLayout,
+    upscale_bgl: wgpu::BindGroupLayout,
+    depth_copy_bgl: wgpu::BindGroupLayout,
     
-    // Textures
-    depth_texture: wgpu::TextureView,
+    // Persistent textures (transients live in the graph pool)
     shadow_texture: wgpu::TextureView,
-    hdr_texture: wgpu::TextureView,
-    ssao_texture: wgpu::TextureView,
+    shadow_layers: Vec<wgpu::TextureView>,
+    black_texture: wgpu::TextureView,
+    white_texture: wgpu::TextureView,
+    transient_pool: TransientTexturePool,
+    taa_history: TaaHistory,
+    /// Created by the first `render_reprojected`
+    prepass: Option<ReprojectionPrepass>,
+    linear_sampler: wgpu::Sampler,
     
     // Bind Groups
-    main_bind_group: wgpu::BindGroup,
+    /// One per `CullPhase`, binding that phase's culled instances
+    main_bind_groups: [wgpu::BindGroup; 2],
+    /// Main layout over every submitted instance, unculled
+    prepass_bind_group: wgpu::BindGroup,
+    shadow_caster_bind_groups: Vec<wgpu::BindGroup>,
     shadow_bind_group: wgpu::BindGroup,
-    post_bind_group: wgpu::BindGroup,
+    
+    // Submitted draws
+    draw_batches: Vec<DrawBatch>,
+    material_bind_groups: Vec<wgpu::BindGroup>,
+    /// Every submitted instance; shadows draw from it directly, the main
+    /// pass from the culled copy
+    instance_buffer: wgpu::Buffer,
+    culling: GpuCulling,
+    
+    // Lights
+    sun: Option<DirectionalLight>,
+    clusters: ClusterLighting,
     
     // Buffers
-    vertex_buffer: wgpu::Buffer,
     camera_buffer: wgpu::Buffer,
     light_buffer: wgpu::Buffer,
+    cascade_buffers: Vec<wgpu::Buffer>,
     post_buffer: wgpu::Buffer,
+    ssao_buffer: wgpu::Buffer,
+    ssr_buffer: wgpu::Buffer,
+    bloom_buffers: [wgpu::Buffer; 3],
+    taa_buffer: wgpu::Buffer,
+    particle_buffer: wgpu::Buffer,
+    particle_count: u32,
+    
+    // Camera
+    camera_pos: Vec3,
+    camera_target: Vec3,
+    /// Unjittered
+    prev_view_proj: Mat4,
     
     // State
+    /// None without `Features::TIMESTAMP_QUERY`
+    scene_timer: Option<SceneTimer>,
     frame_count: u64,
     last_fps: f32,
     fps_accumulator: f32,
@@ -95,63 +326,343 @@ pub struct Renderer {
 }
 
 impl Renderer {
-    pub async fn new(
+    pub fn new(
         device: Arc<wgpu::Device>,
         queue: Arc<wgpu::Queue>,
         config: RenderConfig,
-    ) -> Result<Self, wgpu::RequestDeviceError> {
-        let (width, height) = config.effective_size();
+        target: RenderTarget,
+    ) -> Self {
+        // Create persistent textures
+        let (shadow_texture, shadow_layers) = Self::create_shadow_texture(&device, &config.shadows);
+        let black_texture = Self::create_constant_texture(&device, &queue, [0, 0, 0, 255]);
+        let white_texture = Self::create_constant_texture(&device, &queue, [255; 4]);
         
-        // Create textures
-        let depth_texture = Self::create_depth_texture(&device, width, height);
-        let shadow_texture = Self::create_shadow_texture(&device, config.shadow_resolution);
-        let hdr_texture = Self::create_hdr_texture(&device, width, height);
-        let ssao_texture = Self::create_ssao_texture(&device, width, height);
+        // Create layouts & pipelines
+        let main_bgl = Self::create_effect_bgl(
+            &device,
+            "Main BGL",
+            &[BindingKind::Uniform, BindingKind::Uniform, BindingKind::DynamicStorage, BindingKind::Uniform, BindingKind::Storage, BindingKind::Storage],
+        );
+        let light_bgl = Self::create_effect_bgl(&device, "Light BGL", &[BindingKind::Uniform, BindingKind::Storage]);
+        // Matches ResourceManager::get_bind_group_for_material
+        let material_bgl = Self::create_effect_bgl(
+            &device,
+            "Material BGL",
+            &[BindingKind::Uniform, BindingKind::Texture, BindingKind::Texture, BindingKind::Texture, BindingKind::Texture, BindingKind::Sampler],
+        );
+        let shadow_bgl = Self::create_shadow_bgl(&device);
+        let ssao_bgl = Self::create_effect_bgl(&device, "SSAO BGL", &[BindingKind::Uniform, BindingKind::Uniform, BindingKind::Depth]);
+        let ssr_bgl = Self::create_effect_bgl(
+            &device,
+            "SSR BGL",
+            &[BindingKind::Uniform, BindingKind::Uniform, BindingKind::Texture, BindingKind::Depth, BindingKind::Sampler],
+        );
+        let particle_bgl = Self::create_effect_bgl(&device, "Particle BGL", &[BindingKind::Uniform, BindingKind::Storage, BindingKind::Depth]);
+        let bloom_bgl = Self::create_effect_bgl(&device, "Bloom BGL", &[BindingKind::Uniform, BindingKind::Texture, BindingKind::Sampler]);
+        let post_bgl = Self::create_effect_bgl(
+            &device,
+            "Post BGL",
+            &[BindingKind::Uniform, BindingKind::Texture, BindingKind::Sampler, BindingKind::Texture, BindingKind::Texture],
+        );
+        let taa_bgl = Self::create_effect_bgl(
+            &device,
+            "TAA BGL",
+            &[BindingKind::Uniform, BindingKind::Texture, BindingKind::Texture, BindingKind::Texture, BindingKind::Depth, BindingKind::Sampler],
+        );
+        let upscale_bgl = Self::create_effect_bgl(&device, "Upscale BGL", &[BindingKind::Texture, BindingKind::Sampler]);
+        let depth_copy_bgl = Self::create_effect_bgl(&device, "Depth Copy BGL", &[BindingKind::Depth, BindingKind::StorageTexture(HISTORY_DEPTH_FORMAT)]);
         
-        // Create pipelines
-        let main_pipeline = Self::create_main_pipeline(&device, config);
-        let shadow_pipeline = Self::create_shadow_pipeline(&device);
-        let post_pipeline = Self::create_post_pipeline(&device, config);
-        let mipmap_pipeline = Self::create_mipmap_pipeline(&device);
+        let mut shaders = ShaderCache::new(config.shader_features());
+        let main_pipeline = Self::create_main_pipeline(&device, &mut shaders, &[&main_bgl, &shadow_bgl, &material_bgl]);
+        let shadow_pipeline = Self::create_shadow_pipeline(&device, &mut shaders, &light_bgl);
+        let ssao_pipeline = Self::create_fullscreen_pipeline(&device, &mut shaders, "SSAO", OPTIMIZED_SSAO_SHADER, "ssao_main", &ssao_bgl, AO_FORMAT);
+        let ssr_pipeline = Self::create_fullscreen_pipeline(&device, &mut shaders, "SSR", OPTIMIZED_SSR_SHADER, "ssr_main", &ssr_bgl, HDR_FORMAT);
+        let particle_pipeline = Self::create_particle_pipeline(&device, &mut shaders, &particle_bgl);
+        let bloom_prefilter_pipeline =
+            Self::create_fullscreen_pipeline(&device, &mut shaders, "Bloom Prefilter", OPTIMIZED_BLOOM_SHADER, "bloom_prefilter", &bloom_bgl, HDR_FORMAT);
+        let bloom_blur_pipeline = Self::create_fullscreen_pipeline(&device, &mut shaders, "Bloom Blur", OPTIMIZED_BLOOM_SHADER, "bloom_blur", &bloom_bgl, HDR_FORMAT);
+        let post_pipeline = Self::create_post_pipeline(&device, &mut shaders, &post_bgl, target.format());
+        let post_ldr_pipeline = Self::create_post_pipeline(&device, &mut shaders, &post_bgl, HISTORY_COLOR_FORMAT);
+        let taa_pipeline = Self::create_taa_pipeline(&device, &mut shaders, &taa_bgl, target.format());
+        let upscale_pipeline = Self::create_fullscreen_pipeline(&device, &mut shaders, "Upscale", OPTIMIZED_UPSCALE_SHADER, "upscale", &upscale_bgl, target.format());
+        let depth_copy_pipeline = Self::create_depth_copy_pipeline(&device, &mut shaders, &depth_copy_bgl);
+        let prepass_pipeline = Self::create_prepass_pipeline(&device, &mut shaders, &main_bgl);
+        let (render_width, render_height) = config.effective_size();
+        let taa_history = TaaHistory::new(&device, render_width, render_height);
         
         // Create buffers
-        let vertex_buffer = Self::create_vertex_buffer(&device);
-        let camera_buffer = Self::create_uniform_buffer(&device, 128);
-        let light_buffer = Self::create_uniform_buffer(&device, 128);
-        let post_buffer = Self::create_uniform_buffer(&device, 32);
+        let camera_buffer = Self::create_uniform_buffer(&device, std::mem::size_of::<FrameUniforms>() as u64);
+        let light_buffer = Self::create_uniform_buffer(&device, std::mem::size_of::<LightUniforms>() as u64);
+        let cascade_buffers = (0..shadow_layers.len()).map(|_| Self::create_uniform_buffer(&device, std::mem::size_of::<Mat4>() as u64)).collect::<Vec<_>>();
+        let post_buffer = Self::create_uniform_buffer(&device, std::mem::size_of::<PostUniforms>() as u64);
+        let ssao_buffer = Self::create_uniform_buffer(&device, std::mem::size_of::<SsaoUniforms>() as u64);
+        let ssr_buffer = Self::create_uniform_buffer(&device, std::mem::size_of::<SsrUniforms>() as u64);
+        let bloom_buffers = std::array::from_fn(|_| Self::create_uniform_buffer(&device, std::mem::size_of::<BloomUniforms>() as u64));
+        let taa_buffer = Self::create_uniform_buffer(&device, std::mem::size_of::<TaaUniforms>() as u64);
+        let particle_buffer = Self::create_particle_buffer(&device, 64);
+        let instance_buffer = Self::create_instance_buffer(&device, 64);
+        let clusters = ClusterLighting::new(&device, &mut shaders);
+        let culling = GpuCulling::new(&device, &mut shaders, config.effective_size());
         
         // Create bind groups
-        let main_bind_group = Self::create_main_bind_group(&device, &camera_buffer, &light_buffer);
-        let shadow_bind_group = Self::create_shadow_bind_group(&device, &light_buffer);
-        let post_bind_group = Self::create_post_bind_group(&device, &hdr_texture, &post_buffer);
+        let main_bind_groups = [CullPhase::Early, CullPhase::Late]
+            .map(|phase| Self::create_main_bind_group(&device, &main_bgl, &camera_buffer, &light_buffer, culling.instance_binding(phase), &clusters));
+        let prepass_bind_group =
+            Self::create_main_bind_group(&device, &main_bgl, &camera_buffer, &light_buffer, instance_buffer.as_entire_binding(), &clusters);
+        let shadow_caster_bind_groups = Self::create_shadow_caster_bind_groups(&device, &light_bgl, &cascade_buffers, &instance_buffer);
+        let shadow_bind_group = Self::create_shadow_bind_group(&device, &shadow_bgl, &shadow_texture);
+        let linear_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Linear Clamp Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let scene_timer = device.features().contains(wgpu::Features::TIMESTAMP_QUERY).then(|| SceneTimer::new(&device, &queue));
         
-        Ok(Self {
+        Self {
             device,
             queue,
             config,
+            target,
             main_pipeline,
             shadow_pipeline,
+            ssao_pipeline,
+            ssr_pipeline,
+            particle_pipeline,
+            bloom_prefilter_pipeline,
+            bloom_blur_pipeline,
             post_pipeline,
-            mipmap_pipeline,
-            depth_texture,
+            post_ldr_pipeline,
+            taa_pipeline,
+            upscale_pipeline,
+            depth_copy_pipeline,
+            prepass_pipeline,
+            shaders,
+            main_bgl,
+            light_bgl,
+            material_bgl,
+            ssao_bgl,
+            ssr_bgl,
+            particle_bgl,
+            bloom_bgl,
+            post_bgl,
+            taa_bgl,
+            upscale_bgl,
+            depth_copy_bgl,
             shadow_texture,
-            hdr_texture,
-            ssao_texture,
-            main_bind_group,
+            shadow_layers,
+            black_texture,
+            white_texture,
+            transient_pool: TransientTexturePool::new(),
+            taa_history,
+            prepass: None,
+            linear_sampler,
+            main_bind_groups,
+            prepass_bind_group,
+            shadow_caster_bind_groups,
             shadow_bind_group,
-            post_bind_group,
-            vertex_buffer,
+            draw_batches: Vec::new(),
+            material_bind_groups: Vec::new(),
+            instance_buffer,
+            culling,
+            sun: Some(DirectionalLight::default()),
+            clusters,
             camera_buffer,
             light_buffer,
+            cascade_buffers,
             post_buffer,
+            ssao_buffer,
+            ssr_buffer,
+            bloom_buffers,
+            taa_buffer,
+            particle_buffer,
+            particle_count: 0,
+            camera_pos: Vec3::new(0.0, 2.0, -5.0),
+            camera_target: Vec3::new(0.0, 0.5, 0.0),
+            prev_view_proj: Mat4::IDENTITY,
+            scene_timer,
             frame_count: 0,
             last_fps: 0.0,
             fps_accumulator: 0.0,
             fps_samples: 0,
-        })
+        }
+    }
+    
+    /// Renderer drawing into an offscreen texture of `config.width` x `config.height`
+    pub fn new_headless(device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue>, config: RenderConfig) -> Self {
+        let target = RenderTarget::Headless(HeadlessTarget::new(&device, config.width, config.height));
+        Self::new(device, queue, config, target)
+    }
+    
+    pub fn target(&self) -> &RenderTarget {
+        &self.target
+    }
+    
+    pub fn config(&self) -> &RenderConfig {
+        &self.config
+    }
+    
+    /// Toggle or reorder effects; takes effect next frame
+    pub fn set_effects(&mut self, effect_order: Vec<EffectPass>) {
+        self.config.effect_order = effect_order;
+        
+        // Only the post shader tests effect features; the main shader's
+        // SHADOWS can't change after creation
+        let features = self.config.shader_features();
+        if features != self.shaders.features() {
+            self.shaders.set_features(features);
+            self.post_pipeline = Self::create_post_pipeline(&self.device, &mut self.shaders, &self.post_bgl, self.target.format());
+            self.post_ldr_pipeline = Self::create_post_pipeline(&self.device, &mut self.shaders, &self.post_bgl, HISTORY_COLOR_FORMAT);
+        }
+    }
+    
+    pub fn set_camera(&mut self, position: Vec3, target: Vec3) {
+        self.camera_pos = position;
+        self.camera_target = target;
+    }
+    
+    /// Replace the particles drawn by the particle pass
+    pub fn set_particles(&mut self, particles: &[Particle]) {
+        let needed = std::mem::size_of_val(particles) as u64;
+        if needed > self.particle_buffer.size() {
+            self.particle_buffer = Self::create_particle_buffer(&self.device, particles.len().next_power_of_two());
+        }
+        self.queue.write_buffer(&self.particle_buffer, 0, bytemuck::cast_slice(particles));
+        self.particle_count = particles.len() as u32;
+    }
+    
+    /// Replace the scene with `items`. Items sharing a mesh and material are
+    /// drawn as one indirect instanced call, with the instances the GPU
+    /// culls left out; the list persists until the next submit.
+    /// Items whose mesh or material isn't loaded, or whose mesh doesn't use
+    /// the `SceneVertex` layout, are skipped. Returns the number queued.
+    pub fn submit_draws(&mut self, resources: &ResourceManager, items: &[DrawItem]) -> usize {
+        let mut batches: Vec<DrawBatch> = Vec::new();
+        let mut batch_items: Vec<Vec<&DrawItem>> = Vec::new();
+        let mut batch_lookup: HashMap<(Handle, Handle), usize> = HashMap::new();
+        let mut material_lookup: HashMap<Handle, usize> = HashMap::new();
+        self.material_bind_groups.clear();
+        
+        for item in items {
+            let batch = match batch_lookup.get(&(item.mesh, item.material)) {
+                Some(&batch) => batch,
+                None => {
+                    let Some(mesh) = resources.get_mesh(item.mesh) else { continue };
+                    if mesh.vertex_stride != std::mem::size_of::<SceneVertex>() as u64 {
+                        continue;
+                    }
+                    let material = match material_lookup.get(&item.material) {
+                        Some(&material) => material,
+                        None => {
+                            let Some(bind_group) = resources.get_bind_group_for_material(item.material, &self.material_bgl, 0) else { continue };
+                            self.material_bind_groups.push(bind_group);
+                            material_lookup.insert(item.material, self.material_bind_groups.len() - 1);
+                            self.material_bind_groups.len() - 1
+                        }
+                    };
+                    batches.push(DrawBatch { mesh, material, first_instance: 0, instance_count: 0, region: 0 });
+                    batch_items.push(Vec::new());
+                    batch_lookup.insert((item.mesh, item.material), batches.len() - 1);
+                    batches.len() - 1
+                }
+            };
+            batch_items[batch].push(item);
+        }
+        
+        // Instances laid out batch by batch so each batch is one instance range
+        let (mut instances, mut bounds) = (Vec::new(), Vec::new());
+        for (index, (batch, batch_items)) in batches.iter_mut().zip(batch_items).enumerate() {
+            batch.first_instance = instances.len() as u32;
+            batch.instance_count = batch_items.len() as u32;
+            for item in batch_items {
+                instances.push(InstanceData::new(item.transform, item.motion));
+                bounds.push(CullBounds::new(index as u32, item.bounds));
+            }
+        }
+        
+        let (regions, mut rebuild) = self.culling.set_batches(&self.device, &self.queue, &batches, &bounds);
+        for (batch, region) in batches.iter_mut().zip(regions) {
+            batch.region = region;
+        }
+        if std::mem::size_of_val(instances.as_slice()) as u64 > self.instance_buffer.size() {
+            self.instance_buffer = Self::create_instance_buffer(&self.device, instances.len().next_power_of_two());
+            rebuild = true;
+        }
+        if rebuild {
+            self.rebuild_frame_bind_groups();
+        }
+        self.queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+        self.draw_batches = batches;
+        instances.len()
+    }
+    
+    /// Replace the scene's lights. Without a directional light the scene is
+    /// lit by point lights and ambient only.
+    pub fn set_lights(&mut self, lights: &SceneLights) {
+        self.sun = lights.directional;
+        if self.clusters.set_lights(&self.device, &self.queue, &lights.points) {
+            self.rebuild_frame_bind_groups();
+        }
+    }
+    
+    fn rebuild_frame_bind_groups(&mut self) {
+        self.main_bind_groups = [CullPhase::Early, CullPhase::Late].map(|phase| {
+            Self::create_main_bind_group(&self.device, &self.main_bgl, &self.camera_buffer, &self.light_buffer, self.culling.instance_binding(phase), &self.clusters)
+        });
+        self.prepass_bind_group = Self::create_main_bind_group(
+            &self.device,
+            &self.main_bgl,
+            &self.camera_buffer,
+            &self.light_buffer,
+            self.instance_buffer.as_entire_binding(),
+            &self.clusters,
+        );
+        self.shadow_caster_bind_groups =
+            Self::create_shadow_caster_bind_groups(&self.device, &self.light_bgl, &self.cascade_buffers, &self.instance_buffer);
     }
     
     pub fn render(&mut self) -> Result<RenderStats, wgpu::SurfaceError> {
+        self.render_with(|_, _| None)
+    }
+    
+    /// Render a frame, first letting `prelude` record into the frame's encoder
+    /// and target (e.g. a predictive-renderer composite). If it returns a
+    /// plan that isn't a full refresh, only the plan's tiles are redrawn and
+    /// the rest of the target is kept.
+    pub fn render_with(
+        &mut self,
+        prelude: impl FnOnce(&mut wgpu::CommandEncoder, &wgpu::TextureView) -> Option<TileRenderPlan>,
+    ) -> Result<RenderStats, wgpu::SurfaceError> {
+        self.render_frame(&mut (), false, |_, encoder, view, _| prelude(encoder, view), |_, _, _| {})
+    }
+    
+    /// Render a frame through `predictive`: depth and motion of every
+    /// instance are drawn first, unlit and unculled, as the reprojection
+    /// inputs for `PredictiveRenderer::render`, whose plan is then drawn as
+    /// in `render_with`. Once the tiles are drawn, the plan's validation
+    /// tiles are measured in the finished frame.
+    pub fn render_reprojected(&mut self, predictive: &mut PredictiveRenderer, scene: &SceneSnapshot) -> Result<RenderStats, wgpu::SurfaceError> {
+        let (device, queue) = (self.device.clone(), self.queue.clone());
+        self.render_frame(
+            predictive,
+            true,
+            |predictive, encoder, view, inputs| Some(predictive.render(&device, &queue, scene, encoder, view, inputs)),
+            |predictive, encoder, target| predictive.record_validation(&device, encoder, target),
+        )
+    }
+    
+    /// `prelude` and `finish` share `state`: `prelude` records before the
+    /// scene, `finish` after it into the finished target, before it is
+    /// presented
+    fn render_frame<S: ?Sized>(
+        &mut self,
+        state: &mut S,
+        prepass: bool,
+        prelude: impl FnOnce(&mut S, &mut wgpu::CommandEncoder, &wgpu::TextureView, Option<&ReprojectionInputs>) -> Option<TileRenderPlan>,
+        finish: impl FnOnce(&mut S, &mut wgpu::CommandEncoder, &wgpu::Texture),
+    ) -> Result<RenderStats, wgpu::SurfaceError> {
         let start = std::time::Instant::now();
         
         // FPS calculation
@@ -167,202 +678,731 @@ impl Renderer {
         
         self.frame_count += 1;
         
-        // Update uniforms
-        self.update_uniforms();
+        // Fire any finished readback mapping, then take its culled count
+        // and scene pass time
+        self.device.poll(wgpu::Maintain::Poll);
+        let instances_culled = self.culling.collect_readback();
+        let scene_gpu_time = self.scene_timer.as_mut().and_then(SceneTimer::collect);
+        let timed = self.scene_timer.as_ref().is_some_and(SceneTimer::idle);
         
-        // Get surface texture
-        let output = self.device.get_current_texture()?;
-        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // Get target texture
+        let (output, view) = self.target.acquire()?;
         
         // Create command encoder
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder"),
         });
         
-        // Shadow pass
-        self.render_shadow_pass(&mut encoder);
+        // The prepass opens the timed span when it runs, so a tiled frame's
+        // time includes its reprojection and composite
+        let tiles = if prepass {
+            let targets = self.prepass.take().unwrap_or_else(|| ReprojectionPrepass::new(&self.device, self.config.effective_size()));
+            self.render_prepass(&mut encoder, &targets, timed);
+            let (view_matrix, proj) = self.camera_matrices();
+            let inputs = ReprojectionInputs { view_proj: proj * view_matrix, depth: &targets.depth_float, motion: &targets.motion };
+            let plan = prelude(state, &mut encoder, &view, Some(&inputs));
+            self.prepass = Some(targets);
+            plan
+        } else {
+            prelude(state, &mut encoder, &view, None)
+        }
+        .filter(|plan| !plan.full_refresh);
         
-        // Main geometry pass (to HDR texture)
-        self.render_geometry_pass(&mut encoder);
+        // TAA resolves whole frames only; a tile-only frame keeps the
+        // composited pixels and breaks the history
+        let taa = self.config.enable_taa && tiles.is_none();
+        // Without TAA, a full reprojected frame is still kept as history
+        let capture = prepass && !self.config.enable_taa && tiles.is_none();
+        self.update_uniforms(taa, taa || capture);
         
-        // Post-processing
-        if self.config.enable_post_processing {
-            self.render_post_processing(&mut encoder);
+        // Build & run this frame's graph
+        let mut pool = std::mem::take(&mut self.transient_pool);
+        let timing = self.scene_timer.as_ref().filter(|_| timed).map(|timer| (timer, !prepass));
+        let graph = self.build_graph(&view, tiles.as_ref(), taa, capture, timing);
+        let compiled = graph
+            .execute(&self.device, &mut encoder, &mut pool)
+            .expect("renderer graph is well-formed");
+        self.transient_pool = pool;
+        if let Some((timer, _)) = timing {
+            timer.resolve(&mut encoder);
+        }
+        let target = match (&output, &self.target) {
+            (Some(output), _) => &output.texture,
+            (None, RenderTarget::Headless(target)) => target.texture(),
+            (None, RenderTarget::Surface { .. }) => unreachable!("surface targets acquire a texture"),
+        };
+        finish(state, &mut encoder, target);
+        self.taa_history.advance((taa || capture).then_some(self.prev_view_proj));
+        if compiled.passes.contains(&"hiz") {
+            self.culling.hiz.view_proj = Some(self.prev_view_proj);
         }
         
         // Final present
         encoder.insert_debug_marker("Present");
         self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        if let Some(output) = output {
+            output.present();
+        }
+        if compiled.passes.contains(&"cull") {
+            self.culling.start_readback(&self.device, &self.queue);
+        }
+        if let Some(timer) = self.scene_timer.as_mut().filter(|_| timed) {
+            timer.start_readback(tiles.is_some());
+        }
         
         let render_time = start.elapsed().as_secs_f32() * 1000.0;
+        let particles_drawn = compiled.passes.contains(&"particles");
+        let dispatches = compiled.passes.iter().filter(|&&pass| matches!(pass, "light_cull" | "cull" | "cull_late" | "hiz" | "history_depth_copy")).count();
+        let geometry_passes = 1 + compiled.passes.contains(&"geometry_late") as usize;
+        let shadow_passes = compiled.passes.contains(&"shadow") as usize;
+        let cascades = self.config.shadows.active_cascades() as usize;
+        let batches = self.draw_batches.len();
         
         Ok(RenderStats {
             fps: self.last_fps,
             frame_time_ms: render_time,
-            draw_calls: 3, // Shadow, Geometry, Post
-            triangles: 2,
+            // One draw per effect pass; each geometry phase draws each batch,
+            // shadow each batch per cascade
+            draw_calls: (compiled.passes.len() - dispatches - geometry_passes - shadow_passes
+                + batches * (geometry_passes + shadow_passes * cascades)) as u32,
+            triangles: self.draw_batches.iter().map(|b| b.mesh.index_count / 3 * b.instance_count).sum::<u32>()
+                + if particles_drawn { self.particle_count * 2 } else { 0 },
             resolution: self.config.effective_size(),
+            render_passes: compiled.passes.len() as u32,
+            transient_textures: compiled.transient_textures as u32,
+            physical_textures: compiled.physical_textures as u32,
+            point_lights: self.clusters.light_count,
+            instances_culled,
+            scene_gpu_time,
         })
     }
     
+    /// Latest full frame as (color, depth, unjittered view-projection), at
+    /// render size in the predictive renderer's history formats. Pass it to
+    /// `PredictiveRenderer::push_frame` so reprojection reuses it. With TAA
+    /// this is the resolved frame; without, `render_reprojected` keeps its
+    /// post-processed full frames. None until such a frame was rendered, or
+    /// after a frame that wasn't one.
+    ///
+    /// The textures are the history itself, not a copy. They stay unchanged
+    /// until the next full frame, whose reprojection reads them before the
+    /// frame overwrites them.
+    pub fn history_frame(&self) -> Option<(Arc<wgpu::Texture>, Arc<wgpu::Texture>, Mat4)> {
+        let history = &self.taa_history;
+        history.view_proj.map(|view_proj| (history.color[history.latest].0.clone(), history.depth.0.clone(), view_proj))
+    }
+    
+    /// Read the last headless frame back as RGBA8 (None for surface targets)
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_frame(&self) -> Option<Result<Vec<u8>, wgpu::BufferAsyncError>> {
+        match &self.target {
+            RenderTarget::Headless(target) => Some(target.read_rgba(&self.device, &self.queue)),
+            RenderTarget::Surface { .. } => None,
+        }
+    }
+    
+    // ========================================================================
+    // FRAME GRAPH
+    // ========================================================================
+    
+    /// `capture` keeps the frame as history without TAA. `timing` is the
+    /// scene timer when this frame is timed, and whether the geometry pass
+    /// opens the span (no prepass ran)
+    fn build_graph<'a>(
+        &'a self,
+        target: &'a wgpu::TextureView,
+        tiles: Option<&'a TileRenderPlan>,
+        taa: bool,
+        capture: bool,
+        timing: Option<(&'a SceneTimer, bool)>,
+    ) -> RenderGraph<'a> {
+        let (width, height) = self.config.effective_size();
+        let attachment = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        let hdr_desc = TextureDesc::new(width, height, HDR_FORMAT, attachment);
+        let half_desc = |format| TextureDesc::new(width / 2, height / 2, format, attachment);
+        
+        let mut graph = RenderGraph::new();
+        let output = graph.import_texture("output", target);
+        let shadow_map = graph.import_texture("shadow_map", &self.shadow_texture);
+        let mut ao = graph.import_texture("white", &self.white_texture);
+        let mut bloom = graph.import_texture("black", &self.black_texture);
+        
+        // Light culling only writes buffers, which the graph doesn't track.
+        // Declared first, so it runs before the geometry pass reads them.
+        if self.clusters.light_count > 0 {
+            let mut pass = graph.add_pass("light_cull");
+            pass.side_effect();
+            pass.execute(move |ctx| self.clusters.dispatch(ctx.encoder));
+        }
+        
+        // Instance culling writes the indirect draws and culled instances the
+        // geometry pass reads, so it is declared before it too. Occlusion is
+        // tested against the Hi-Z pyramid of the frame that last built one.
+        let occlusion = self.config.culling.occlusion();
+        if !self.draw_batches.is_empty() {
+            let mut pass = graph.add_pass("cull");
+            pass.side_effect();
+            pass.execute(move |ctx| self.culling.dispatch(ctx.device, ctx.encoder, &self.instance_buffer, CullPhase::Early));
+        }
+        
+        // Shadow pass: the graph tracks the cascade array as one texture and
+        // the pass renders each layer
+        let shadow_map = if self.config.shadows.active_cascades() > 0 {
+            let mut pass = graph.add_pass("shadow");
+            let shadow_map = pass.write(shadow_map);
+            pass.execute(move |ctx| self.render_shadow_pass(ctx.encoder));
+            shadow_map
+        } else {
+            shadow_map
+        };
+        
+        // Main geometry pass (to HDR texture)
+        let mut pass = graph.add_pass("geometry");
+        pass.read(shadow_map);
+        let mut hdr = pass.create("hdr", hdr_desc);
+        let mut motion = pass.create("motion", TextureDesc::new(width, height, MOTION_VECTOR_FORMAT, attachment));
+        let mut depth = pass.create("depth", TextureDesc::new(width, height, DEPTH_FORMAT, attachment));
+        pass.execute(move |ctx| {
+            let (hdr, motion, depth) = (ctx.view(hdr), ctx.view(motion), ctx.view(depth));
+            let timestamps = timing.filter(|&(_, opens)| opens).map(|(timer, _)| timer.writes(true, false));
+            self.render_geometry_pass(ctx.encoder, (hdr, motion, depth), tiles, CullPhase::Early, timestamps);
+        });
+        
+        // The late cull and next frame's early cull read this frame's depth
+        // (the pyramid is a buffer, so the pass is kept as a side effect)
+        if occlusion && !self.draw_batches.is_empty() {
+            let mut pass = graph.add_pass("hiz");
+            pass.read(depth);
+            pass.side_effect();
+            pass.execute(move |ctx| self.culling.build_hiz(ctx.device, ctx.encoder, ctx.view(depth)));
+        }
+        
+        // Instances the early cull hid behind last frame's depth get another
+        // test against this frame's, and the visible ones are drawn on top.
+        // The pyramid isn't rebuilt after, which only leaves next frame's
+        // early test more conservative.
+        if self.culling.late_phase(&self.config.culling) && !self.draw_batches.is_empty() {
+            let mut pass = graph.add_pass("cull_late");
+            pass.side_effect();
+            pass.execute(move |ctx| self.culling.dispatch(ctx.device, ctx.encoder, &self.instance_buffer, CullPhase::Late));
+            
+            let mut pass = graph.add_pass("geometry_late");
+            pass.read(shadow_map);
+            (hdr, motion, depth) = (pass.write(hdr), pass.write(motion), pass.write(depth));
+            pass.execute(move |ctx| {
+                let (hdr, motion, depth) = (ctx.view(hdr), ctx.view(motion), ctx.view(depth));
+                self.render_geometry_pass(ctx.encoder, (hdr, motion, depth), tiles, CullPhase::Late, None);
+            });
+        }
+        
+        // Effects, in configured order
+        for &effect in self.config.effect_order.iter().filter(|&&e| self.config.effect_enabled(e)) {
+            match effect {
+                EffectPass::Ssao => {
+                    let mut pass = graph.add_pass("ssao");
+                    pass.read(depth);
+                    let out = pass.create("ssao", half_desc(AO_FORMAT));
+                    pass.execute(move |ctx| {
+                        let bind_group = self.effect_bind_group(
+                            ctx.device,
+                            &self.ssao_bgl,
+                            &[self.camera_buffer.as_entire_binding(), self.ssao_buffer.as_entire_binding(), wgpu::BindingResource::TextureView(ctx.view(depth))],
+                        );
+                        let out = ctx.view(out);
+                        fullscreen_pass(ctx.encoder, "SSAO", out, &self.ssao_pipeline, &bind_group, None, None);
+                    });
+                    ao = out;
+                }
+                EffectPass::Ssr => {
+                    let mut pass = graph.add_pass("ssr");
+                    pass.read(hdr);
+                    pass.read(depth);
+                    let out = pass.create("hdr_ssr", hdr_desc);
+                    pass.execute(move |ctx| {
+                        let bind_group = self.effect_bind_group(
+                            ctx.device,
+                            &self.ssr_bgl,
+                            &[
+                                self.camera_buffer.as_entire_binding(),
+                                self.ssr_buffer.as_entire_binding(),
+                                wgpu::BindingResource::TextureView(ctx.view(hdr)),
+                                wgpu::BindingResource::TextureView(ctx.view(depth)),
+                                wgpu::BindingResource::Sampler(&self.linear_sampler),
+                            ],
+                        );
+                        let out = ctx.view(out);
+                        fullscreen_pass(ctx.encoder, "SSR", out, &self.ssr_pipeline, &bind_group, None, None);
+                    });
+                    hdr = out;
+                }
+                EffectPass::Particles if self.particle_count > 0 => {
+                    // Blends into the HDR image in place
+                    let mut pass = graph.add_pass("particles");
+                    pass.read(depth);
+                    let out = pass.write(hdr);
+                    pass.execute(move |ctx| {
+                        let (hdr, depth) = (ctx.view(out), ctx.view(depth));
+                        self.render_particle_pass(ctx.device, ctx.encoder, hdr, depth);
+                    });
+                    hdr = out;
+                }
+                EffectPass::Particles => {}
+                EffectPass::Bloom => bloom = self.add_bloom_passes(&mut graph, hdr, half_desc(HDR_FORMAT)),
+            }
+        }
+        
+        // Post-processing (always runs: it tonemaps HDR into the target, or
+        // at render size into the TAA input or the next history frame)
+        let history = &self.taa_history;
+        let next_history = capture.then(|| graph.import_texture("history", &history.color[1 - history.latest].1));
+        let mut pass = graph.add_pass("post");
+        pass.read(hdr);
+        pass.read(bloom);
+        pass.read(ao);
+        let (post_out, post_pipeline) = if taa {
+            (pass.create("ldr", TextureDesc::new(width, height, HISTORY_COLOR_FORMAT, attachment)), &self.post_ldr_pipeline)
+        } else if let Some(next) = next_history {
+            (pass.write(next), &self.post_ldr_pipeline)
+        } else {
+            (pass.write(output), &self.post_pipeline)
+        };
+        pass.execute(move |ctx| {
+            let bind_group = self.effect_bind_group(
+                ctx.device,
+                &self.post_bgl,
+                &[
+                    self.post_buffer.as_entire_binding(),
+                    wgpu::BindingResource::TextureView(ctx.view(hdr)),
+                    wgpu::BindingResource::Sampler(&self.linear_sampler),
+                    wgpu::BindingResource::TextureView(ctx.view(bloom)),
+                    wgpu::BindingResource::TextureView(ctx.view(ao)),
+                ],
+            );
+            let out = ctx.view(post_out);
+            // Tiled frames skip TAA, so post writes the target
+            let tiles = tiles.map(|plan| (plan, self.target.size()));
+            let timestamps = timing.map(|(timer, _)| timer.writes(false, true));
+            fullscreen_pass(ctx.encoder, "Post Process", out, post_pipeline, &bind_group, tiles, timestamps);
+        });
+        
+        if taa {
+            self.add_taa_pass(&mut graph, [post_out, motion, depth], output);
+        } else if capture {
+            self.add_history_passes(&mut graph, [post_out, depth], output);
+        }
+        
+        graph
+    }
+    
+    /// Keep an unresolved frame as history: store its depth next to the
+    /// colour post wrote, and copy (or upscale) that colour into the target
+    fn add_history_passes<'a>(&'a self, graph: &mut RenderGraph<'a>, [color, depth]: [ResourceHandle; 2], output: ResourceHandle) {
+        let history = &self.taa_history;
+        let next_depth = graph.import_texture("history_depth", &history.depth.1);
+        
+        let mut pass = graph.add_pass("history_depth_copy");
+        pass.read(depth);
+        let next_depth = pass.write(next_depth);
+        pass.execute(move |ctx| {
+            self.copy_depth(ctx.device, ctx.encoder, "History Depth Copy", ctx.view(depth), ctx.view(next_depth), history.size);
+        });
+        
+        let mut pass = graph.add_pass("history_present");
+        pass.read(color);
+        let output = pass.write(output);
+        pass.execute(move |ctx| {
+            let bind_group = self.effect_bind_group(
+                ctx.device,
+                &self.upscale_bgl,
+                &[wgpu::BindingResource::TextureView(ctx.view(color)), wgpu::BindingResource::Sampler(&self.linear_sampler)],
+            );
+            let output = ctx.view(output);
+            fullscreen_pass(ctx.encoder, "History Present", output, &self.upscale_pipeline, &bind_group, None, None);
+        });
+    }
+    
+    /// Resolve `ldr` against the TAA history, writing the displayed frame and
+    /// the next history frame. Everything is at render size; the displayed
+    /// frame is upscaled into the target when that is larger or smaller.
+    fn add_taa_pass<'a>(&'a self, graph: &mut RenderGraph<'a>, [ldr, motion, depth]: [ResourceHandle; 3], output: ResourceHandle) {
+        let history = &self.taa_history;
+        let upscale = history.size != self.target.size();
+        let previous = graph.import_texture("taa_history", &history.color[history.latest].1);
+        let next = graph.import_texture("taa_resolved", &history.color[1 - history.latest].1);
+        let next_depth = graph.import_texture("taa_depth", &history.depth.1);
+        
+        let mut pass = graph.add_pass("taa");
+        for input in [ldr, previous, motion, depth] {
+            pass.read(input);
+        }
+        let display = if upscale {
+            let usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+            pass.create("taa_display", TextureDesc::new(history.size.0, history.size.1, self.target.format(), usage))
+        } else {
+            pass.write(output)
+        };
+        let (next, next_depth) = (pass.write(next), pass.write(next_depth));
+        pass.execute(move |ctx| {
+            let bind_group = self.effect_bind_group(
+                ctx.device,
+                &self.taa_bgl,
+                &[
+                    self.taa_buffer.as_entire_binding(),
+                    wgpu::BindingResource::TextureView(ctx.view(ldr)),
+                    wgpu::BindingResource::TextureView(ctx.view(previous)),
+                    wgpu::BindingResource::TextureView(ctx.view(motion)),
+                    wgpu::BindingResource::TextureView(ctx.view(depth)),
+                    wgpu::BindingResource::Sampler(&self.linear_sampler),
+                ],
+            );
+            let (display, next) = (ctx.view(display), ctx.view(next));
+            self.render_taa_pass(ctx.encoder, &bind_group, [display, next]);
+            self.copy_depth(ctx.device, ctx.encoder, "TAA Depth Copy", ctx.view(depth), ctx.view(next_depth), history.size);
+        });
+        
+        if upscale {
+            let mut pass = graph.add_pass("taa_upscale");
+            pass.read(display);
+            let output = pass.write(output);
+            pass.execute(move |ctx| {
+                let bind_group = self.effect_bind_group(
+                    ctx.device,
+                    &self.upscale_bgl,
+                    &[wgpu::BindingResource::TextureView(ctx.view(display)), wgpu::BindingResource::Sampler(&self.linear_sampler)],
+                );
+                let output = ctx.view(output);
+                fullscreen_pass(ctx.encoder, "TAA Upscale", output, &self.upscale_pipeline, &bind_group, None, None);
+            });
+        }
+    }
+    
+    /// Bright-pass downsample, then horizontal and vertical blur at half resolution
+    fn add_bloom_passes<'a>(&'a self, graph: &mut RenderGraph<'a>, hdr: ResourceHandle, desc: TextureDesc) -> ResourceHandle {
+        let stages = [
+            ("bloom_prefilter", "bloom_bright", &self.bloom_prefilter_pipeline),
+            ("bloom_blur_h", "bloom_blur_h", &self.bloom_blur_pipeline),
+            ("bloom_blur_v", "bloom", &self.bloom_blur_pipeline),
+        ];
+        let mut source = hdr;
+        for ((pass_name, texture_name, pipeline), uniforms) in stages.into_iter().zip(&self.bloom_buffers) {
+            let mut pass = graph.add_pass(pass_name);
+            let input = pass.read(source);
+            let out = pass.create(texture_name, desc);
+            pass.execute(move |ctx| {
+                let bind_group = self.effect_bind_group(
+                    ctx.device,
+                    &self.bloom_bgl,
+                    &[
+                        uniforms.as_entire_binding(),
+                        wgpu::BindingResource::TextureView(ctx.view(input)),
+                        wgpu::BindingResource::Sampler(&self.linear_sampler),
+                    ],
+                );
+                let out = ctx.view(out);
+                fullscreen_pass(ctx.encoder, pass_name, out, pipeline, &bind_group, None, None);
+            });
+            source = out;
+        }
+        source
+    }
+    
+    fn effect_bind_group(&self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout, resources: &[wgpu::BindingResource]) -> wgpu::BindGroup {
+        let entries: Vec<_> = resources
+            .iter()
+            .enumerate()
+            .map(|(binding, resource)| wgpu::BindGroupEntry { binding: binding as u32, resource: resource.clone() })
+            .collect();
+        device.create_bind_group(&wgpu::BindGroupDescriptor { label: Some("Effect Bind Group"), layout, entries: &entries })
+    }
+    
     fn render_shadow_pass(&self, encoder: &mut wgpu::CommandEncoder) {
+        let cascades = self.config.shadows.active_cascades() as usize;
+        for (layer, bind_group) in self.shadow_layers.iter().zip(&self.shadow_caster_bind_groups).take(cascades) {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: layer,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            
+            pass.set_pipeline(&self.shadow_pipeline);
+            pass.set_bind_group(0, bind_group, &[]);
+            for batch in &self.draw_batches {
+                batch.draw(&mut pass);
+            }
+        }
+    }
+    
+    fn render_geometry_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        (hdr, motion, depth): (&wgpu::TextureView, &wgpu::TextureView, &wgpu::TextureView),
+        tiles: Option<&TileRenderPlan>,
+        phase: CullPhase,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        // The late phase draws over the early phase's frame
+        let load = |clear| if phase == CullPhase::Early { wgpu::LoadOp::Clear(clear) } else { wgpu::LoadOp::Load };
         let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Shadow Pass"),
-            color_attachments: &[],
+            label: Some(if phase == CullPhase::Early { "Geometry Pass" } else { "Late Geometry Pass" }),
+            color_attachments: &[
+                Some(wgpu::RenderPassColorAttachment {
+                    view: hdr,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: load(wgpu::Color { r: 0.02, g: 0.02, b: 0.04, a: 1.0 }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                }),
+                // Background doesn't move on its own
+                Some(wgpu::RenderPassColorAttachment {
+                    view: motion,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: load(wgpu::Color::TRANSPARENT), store: wgpu::StoreOp::Store },
+                }),
+            ],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.shadow_texture,
+                view: depth,
                 depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
+                    load: if phase == CullPhase::Early { wgpu::LoadOp::Clear(1.0) } else { wgpu::LoadOp::Load },
                     store: wgpu::StoreOp::Store,
                 }),
                 stencil_ops: None,
             }),
             occlusion_query_set: None,
-            timestamp_writes: None,
+            timestamp_writes,
         });
         
-        pass.set_pipeline(&self.shadow_pipeline);
-        pass.set_bind_group(0, &self.shadow_bind_group, &[]);
-        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        pass.draw(0..6, 0..1); // Fullscreen quad
+        pass.set_pipeline(&self.main_pipeline);
+        pass.set_bind_group(1, &self.shadow_bind_group, &[]);
+        let draw = |pass: &mut wgpu::RenderPass| {
+            for (index, batch) in self.draw_batches.iter().enumerate() {
+                let region = batch.region as u64 * std::mem::size_of::<InstanceData>() as u64;
+                pass.set_bind_group(0, &self.main_bind_groups[phase as usize], &[region as u32]);
+                pass.set_bind_group(2, &self.material_bind_groups[batch.material], &[]);
+                batch.draw_indirect(pass, &self.culling.draw_buffers[phase as usize], index);
+            }
+        };
+        match tiles {
+            Some(plan) => plan.render_tiles(&mut pass, self.config.effective_size(), draw),
+            None => draw(&mut pass),
+        }
     }
     
-    fn render_geometry_pass(&self, encoder: &mut wgpu::CommandEncoder) {
+    /// Depth and motion of every instance, then the depth copied to a float
+    /// texture; opens the timed span when `timed`
+    fn render_prepass(&self, encoder: &mut wgpu::CommandEncoder, targets: &ReprojectionPrepass, timed: bool) {
         let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Geometry Pass"),
+            label: Some("Reprojection Prepass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &self.hdr_texture,
+                view: &targets.motion,
                 resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color { r: 0.02, g: 0.02, b: 0.04, a: 1.0 }),
-                    store: wgpu::StoreOp::Store,
-                },
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), store: wgpu::StoreOp::Store },
             })],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.depth_texture,
-                depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
-                    store: wgpu::StoreOp::Store,
-                }),
+                view: &targets.depth,
+                depth_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Clear(1.0), store: wgpu::StoreOp::Store }),
                 stencil_ops: None,
             }),
             occlusion_query_set: None,
-            timestamp_writes: None,
+            timestamp_writes: self.scene_timer.as_ref().filter(|_| timed).map(|timer| timer.writes(true, false)),
         });
+        pass.set_pipeline(&self.prepass_pipeline);
+        pass.set_bind_group(0, &self.prepass_bind_group, &[0]);
+        for batch in &self.draw_batches {
+            batch.draw(&mut pass);
+        }
+        drop(pass);
         
-        pass.set_pipeline(&self.main_pipeline);
-        pass.set_bind_group(0, &self.main_bind_group, &[]);
-        pass.set_bind_group(1, &self.shadow_bind_group, &[]);
-        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        pass.draw(0..6, 0..1);
+        self.copy_depth(&self.device, encoder, "Prepass Depth Copy", &targets.depth, &targets.depth_float, targets.size);
     }
     
-    fn render_post_processing(&self, encoder: &mut wgpu::CommandEncoder) {
-        // Create output view for post-processing
-        let surface = self.device.get_current_texture().unwrap();
-        let view = surface.texture.create_view(&wgpu::TextureViewDescriptor::default());
+    /// Device depth into a `HISTORY_DEPTH_FORMAT` texture of `size`
+    fn copy_depth(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, label: &str, depth: &wgpu::TextureView, out: &wgpu::TextureView, size: (u32, u32)) {
+        let bind_group = self.effect_bind_group(
+            device,
+            &self.depth_copy_bgl,
+            &[wgpu::BindingResource::TextureView(depth), wgpu::BindingResource::TextureView(out)],
+        );
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some(label), timestamp_writes: None });
+        pass.set_pipeline(&self.depth_copy_pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(size.0.div_ceil(8), size.1.div_ceil(8), 1);
+    }
+    
+    /// Displayed frame and next history colour in one pass
+    fn render_taa_pass(&self, encoder: &mut wgpu::CommandEncoder, bind_group: &wgpu::BindGroup, targets: [&wgpu::TextureView; 2]) {
+        let attachment = |view| {
+            Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+            })
+        };
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("TAA Resolve"),
+            color_attachments: &targets.map(attachment),
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
         
+        pass.set_pipeline(&self.taa_pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+    
+    fn render_particle_pass(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, hdr: &wgpu::TextureView, depth: &wgpu::TextureView) {
+        let bind_group = self.effect_bind_group(
+            device,
+            &self.particle_bgl,
+            &[self.camera_buffer.as_entire_binding(), self.particle_buffer.as_entire_binding(), wgpu::BindingResource::TextureView(depth)],
+        );
         let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Post Process"),
+            label: Some("Particle Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
+                view: hdr,
                 resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                    store: wgpu::StoreOp::Store,
-                },
+                ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
             })],
             depth_stencil_attachment: None,
             occlusion_query_set: None,
             timestamp_writes: None,
         });
         
-        pass.set_pipeline(&self.post_pipeline);
-        pass.set_bind_group(0, &self.post_bind_group, &[]);
-        pass.draw(0..3, 0..1);
+        pass.set_pipeline(&self.particle_pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.draw(0..6, 0..self.particle_count);
+    }
+    
+    /// Unjittered view and projection of the current camera
+    fn camera_matrices(&self) -> (Mat4, Mat4) {
+        let (width, height) = self.target.size();
+        let view = Mat4::look_at_rh(self.camera_pos, self.camera_target, Vec3::Y);
+        let proj = Mat4::perspective_rh(FOV_Y, width as f32 / height.max(1) as f32, Z_NEAR, Z_FAR);
+        (view, proj)
     }
     
-    fn update_uniforms(&self) {
-        // Camera uniform (simplified for demo)
-        let camera_data = CameraUniforms {
-            view_proj: glam::Mat4::IDENTITY,
-            camera_pos: glam::Vec3::new(0.0, 2.0, -5.0),
-            camera_forward: glam::Vec3::NEG_Z,
+    /// `history` when post writes the frame at render size for TAA or as
+    /// history
+    fn update_uniforms(&mut self, taa: bool, history: bool) {
+        let (width, height) = self.target.size();
+        let aspect = width as f32 / height.max(1) as f32;
+        let (view, proj) = self.camera_matrices();
+        let view_proj = proj * view;
+        
+        // TAA shifts the scene by a sub-pixel offset each frame (in NDC,
+        // after projection); everything that isn't resolved stays unjittered
+        let (render_width, render_height) = self.config.effective_size();
+        let jitter = if taa { taa_jitter(self.frame_count, &self.config.taa) } else { Vec2::ZERO };
+        let jitter_ndc = Vec3::new(2.0 * jitter.x / render_width as f32, -2.0 * jitter.y / render_height as f32, 0.0);
+        let jittered_view_proj = Mat4::from_translation(jitter_ndc) * view_proj;
+        
+        let camera_data = FrameUniforms {
+            view_proj: jittered_view_proj.to_cols_array_2d(),
+            prev_view_proj: self.prev_view_proj.to_cols_array_2d(),
+            inv_view_proj: jittered_view_proj.inverse().to_cols_array_2d(),
+            camera_pos: self.camera_pos.to_array(),
+            time: self.frame_count as f32 * 0.016,
+            camera_dir: (self.camera_target - self.camera_pos).normalize_or_zero().to_array(),
+            _pad: 0.0,
         };
+        self.prev_view_proj = view_proj;
         
-        self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[camera_data]));
+        self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::bytes_of(&camera_data));
+        
+        let taa_data = TaaUniforms {
+            inv_view_proj: jittered_view_proj.inverse().to_cols_array_2d(),
+            prev_view_proj: self.taa_history.view_proj.unwrap_or(view_proj).to_cols_array_2d(),
+            screen_size: [render_width as f32, render_height as f32],
+            feedback: TAA_FEEDBACK,
+            sharpness: self.config.taa.sharpness,
+            history_valid: self.taa_history.view_proj.is_some() as u32,
+            motion_scale: render_width as f32 / width as f32,
+            _pad: [0.0; 2],
+        };
+        self.queue.write_buffer(&self.taa_buffer, 0, bytemuck::bytes_of(&taa_data));
+        self.clusters.update(&self.queue, view, proj, self.config.effective_size());
+        self.culling.update(&self.queue, view_proj, self.camera_pos, &self.config.culling);
         
-        // Light uniform
-        let light_data = LightUniforms {
-            view_proj: glam::Mat4::IDENTITY,
-            light_pos: glam::Vec3::new(5.0, 10.0, -5.0),
-            light_color: glam::Vec3::new(1.0, 0.98, 0.95),
-            intensity: 1.5,
+        // Light uniform, with the sun's shadow cascades fitted to the view
+        let sun = self.sun.unwrap_or(DirectionalLight { intensity: 0.0, ..Default::default() });
+        let to_light = -sun.direction.try_normalize().unwrap_or(Vec3::NEG_Y);
+        let cascades = compute_cascades(view, aspect, to_light, &self.config.shadows);
+        let mut light_data = LightUniforms {
+            cascade_view_proj: [[[0.0; 4]; 4]; MAX_SHADOW_CASCADES as usize],
+            cascade_splits: [0.0; 4],
+            cascade_texel: [0.0; 4],
+            direction: to_light.to_array(),
+            cascade_count: cascades.len() as u32,
+            light_color: sun.color.to_array(),
+            intensity: sun.intensity,
+            bias: self.config.shadows.bias,
+            normal_offset: if self.config.shadows.normal_offset { 1.5 } else { 0.0 },
+            _pad: [0.0; 2],
         };
+        for (i, cascade) in cascades.iter().enumerate() {
+            light_data.cascade_view_proj[i] = cascade.view_proj.to_cols_array_2d();
+            light_data.cascade_splits[i] = cascade.split;
+            light_data.cascade_texel[i] = cascade.texel;
+            self.queue.write_buffer(&self.cascade_buffers[i], 0, bytemuck::bytes_of(&cascade.view_proj.to_cols_array_2d()));
+        }
+        
+        self.queue.write_buffer(&self.light_buffer, 0, bytemuck::bytes_of(&light_data));
         
-        self.queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&[light_data]));
+        // Effect uniforms
+        let ssao_data = SsaoUniforms { radius: 0.4, bias: 0.02, intensity: 1.0, _pad: 0.0 };
+        self.queue.write_buffer(&self.ssao_buffer, 0, bytemuck::bytes_of(&ssao_data));
+        let ssr_data = SsrUniforms { max_distance: 8.0, thickness: 0.3, edge_fade: 0.15, strength: 1.0 };
+        self.queue.write_buffer(&self.ssr_buffer, 0, bytemuck::bytes_of(&ssr_data));
+        
+        let (w, h) = self.config.effective_size();
+        let full_texel = [1.0 / w as f32, 1.0 / h as f32];
+        let half_texel = [1.0 / (w / 2).max(1) as f32, 1.0 / (h / 2).max(1) as f32];
+        let bloom_data = [
+            BloomUniforms { texel: full_texel, direction: [0.0, 0.0], threshold: 0.8, _pad: 0.0 },
+            BloomUniforms { texel: half_texel, direction: [1.0, 0.0], threshold: 0.8, _pad: 0.0 },
+            BloomUniforms { texel: half_texel, direction: [0.0, 1.0], threshold: 0.8, _pad: 0.0 },
+        ];
+        for (buffer, data) in self.bloom_buffers.iter().zip(&bloom_data) {
+            self.queue.write_buffer(buffer, 0, bytemuck::bytes_of(data));
+        }
         
-        // Post uniform
+        // Post uniform (effects off = plain exposure + tonemap)
+        let effects = self.config.enable_post_processing;
+        // With TAA or a history frame, post-processing writes at render size
+        let post_size = if history { (render_width, render_height) } else { (width, height) };
         let post_data = PostUniforms {
-            resolution: glam::Vec2::new(self.config.width as f32, self.config.height as f32),
+            resolution: [post_size.0 as f32, post_size.1 as f32],
             time: self.frame_count as f32 * 0.016,
-            bloom_threshold: 0.8,
-            vignette_strength: 0.3,
+            bloom_intensity: if effects && self.config.enable_bloom { 1.0 } else { 0.0 },
+            exposure: 1.0,
+            vignette_intensity: if effects { 0.3 } else { 0.0 },
+            chromatic_aberration: 0.0,
+            film_grain: 0.0,
         };
         
-        self.queue.write_buffer(&self.post_buffer, 0, bytemuck::cast_slice(&[post_data]));
+        self.queue.write_buffer(&self.post_buffer, 0, bytemuck::bytes_of(&post_data));
     }
     
     // ========================================================================
     // PIPELINE CREATION
     // ========================================================================
     
-    fn create_main_pipeline(device: &wgpu::Device, config: RenderConfig) -> wgpu::RenderPipeline {
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Main Shader"),
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(OPTIMIZED_MAIN_SHADER)),
-        });
-        
-        let main_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Main BGL"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
-                    count: None,
-                },
-            ],
-        });
-        
-        let shadow_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Shadow BGL"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture { sample_type: wgpu::TextureSampleType::Depth, view_dimension: wgpu::TextureViewDimension::D2, multisampled: false },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
-                    count: None,
-                },
-            ],
-        });
+    fn create_main_pipeline(device: &wgpu::Device, shaders: &mut ShaderCache, bind_group_layouts: &[&wgpu::BindGroupLayout]) -> wgpu::RenderPipeline {
+        let shader = shaders.module(device, "Main Shader", OPTIMIZED_MAIN_SHADER);
         
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Main Pipeline Layout"),
-            bind_group_layouts: &[&main_bgl, &shadow_bgl],
+            bind_group_layouts,
             push_constant_ranges: &[],
         });
         
@@ -370,113 +1410,162 @@ impl Renderer {
             label: Some("Main Pipeline"),
             layout: Some(&pipeline_layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: shader,
                 entry_point: "vs_main",
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
-                buffers: &[],
+                buffers: &[SceneVertex::LAYOUT],
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
+                module: shader,
                 entry_point: "fs_main",
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
-                targets: &[Some(wgpu::TextureFormat::Rgba16Float.into())],
+                targets: &[Some(HDR_FORMAT.into()), Some(MOTION_VECTOR_FORMAT.into())],
             }),
             primitive: wgpu::PrimitiveState {
                 topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: Some(wgpu::Face::Back),
                 ..Default::default()
             },
             depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
+                format: DEPTH_FORMAT,
                 depth_write_enabled: true,
                 depth_compare: wgpu::CompareFunction::Less,
-                ..Default::default()
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
             }),
             multisample: wgpu::MultisampleState::default(),
             multiview: None,
+            cache: None,
         })
     }
     
-    fn create_shadow_pipeline(device: &wgpu::Device) -> wgpu::RenderPipeline {
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shadow Shader"),
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(OPTIMIZED_SHADOW_SHADER)),
-        });
-        
-        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Shadow BGL"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
-                    count: None,
-                },
-            ],
-        });
+    fn create_shadow_pipeline(device: &wgpu::Device, shaders: &mut ShaderCache, light_bgl: &wgpu::BindGroupLayout) -> wgpu::RenderPipeline {
+        let shader = shaders.module(device, "Shadow Shader", OPTIMIZED_SHADOW_SHADER);
         
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Shadow Pipeline Layout"),
-            bind_group_layouts: &[&bgl],
+            bind_group_layouts: &[light_bgl],
             push_constant_ranges: &[],
         });
         
+        // Depth-only: no fragment stage
         device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("Shadow Pipeline"),
             layout: Some(&pipeline_layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: shader,
                 entry_point: "shadow_vs_main",
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
-                buffers: &[],
+                buffers: &[SceneVertex::LAYOUT],
             },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "shadow_fs_pcf4",
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                targets: &[],
-            }),
+            fragment: None,
             primitive: wgpu::PrimitiveState {
                 cull_mode: Some(wgpu::Face::Front),
                 ..Default::default()
             },
             depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
+                format: DEPTH_FORMAT,
                 depth_write_enabled: true,
                 depth_compare: wgpu::CompareFunction::Less,
-                ..Default::default()
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
             }),
             multisample: wgpu::MultisampleState::default(),
             multiview: None,
+            cache: None,
         })
     }
     
-    fn create_post_pipeline(device: &wgpu::Device, config: RenderConfig) -> wgpu::RenderPipeline {
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Post Shader"),
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(OPTIMIZED_POST_SHADER)),
+    /// Fullscreen-triangle pipeline using the shader's `fullscreen_vs`
+    fn create_fullscreen_pipeline(
+        device: &wgpu::Device,
+        shaders: &mut ShaderCache,
+        label: &str,
+        source: &'static str,
+        entry_point: &str,
+        bgl: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        let shader = shaders.module(device, label, source);
+        
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &[bgl],
+            push_constant_ranges: &[],
         });
         
-        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Post BGL"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture { sample_type: wgpu::TextureSampleType::Float { filterable: true }, view_dimension: wgpu::TextureViewDimension::D2, multisampled: false },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "fullscreen_vs",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point,
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+    
+    fn create_particle_pipeline(device: &wgpu::Device, shaders: &mut ShaderCache, bgl: &wgpu::BindGroupLayout) -> wgpu::RenderPipeline {
+        let shader = shaders.module(device, "Particle Shader", OPTIMIZED_PARTICLE_SHADER);
+        
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Particle Pipeline Layout"),
+            bind_group_layouts: &[bgl],
+            push_constant_ranges: &[],
         });
         
+        // Premultiplied additive
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Particle Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "particle_vs_main",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "particle_fs_soft",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: HDR_FORMAT,
+                    blend: Some(wgpu::BlendState { color: additive, alpha: additive }),
+                    write_mask: wgpu::ColorWrites::COLOR,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+    
+    fn create_post_pipeline(device: &wgpu::Device, shaders: &mut ShaderCache, post_bgl: &wgpu::BindGroupLayout, format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+        let shader = shaders.module(device, "Post Shader", OPTIMIZED_POST_SHADER);
+        
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Post Pipeline Layout"),
-            bind_group_layouts: &[&bgl],
+            bind_group_layouts: &[post_bgl],
             push_constant_ranges: &[],
         });
         
@@ -484,17 +1573,17 @@ impl Renderer {
             label: Some("Post Pipeline"),
             layout: Some(&pipeline_layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: shader,
                 entry_point: "vs_main",
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
                 buffers: &[],
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
+                module: shader,
                 entry_point: "fs_main",
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                    format,
                     blend: None,
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -503,50 +1592,97 @@ impl Renderer {
             depth_stencil: None,
             multisample: wgpu::MultisampleState::default(),
             multiview: None,
+            cache: None,
         })
     }
     
-    fn create_mipmap_pipeline(device: &wgpu::Device) -> wgpu::ComputePipeline {
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Mipmap Shader"),
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(OPTIMIZED_MIPMAP_SHADER)),
-        });
+    /// Resolve into the target plus the history colour target
+    fn create_taa_pipeline(device: &wgpu::Device, shaders: &mut ShaderCache, taa_bgl: &wgpu::BindGroupLayout, format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+        let shader = shaders.module(device, "TAA Shader", OPTIMIZED_TAA_SHADER);
         
-        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Mipmap BGL"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Texture { sample_type: wgpu::TextureSampleType::Float { filterable: false }, view_dimension: wgpu::TextureViewDimension::D2, multisampled: false },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::StorageTexture { access: wgpu::StorageTextureAccess::WriteOnly, format: wgpu::TextureFormat::Rgba8Unorm, view_dimension: wgpu::TextureViewDimension::D2 },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: wgpu::ShaderStages::COMPUTE,
-                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
-                    count: None,
-                },
-            ],
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("TAA Pipeline Layout"),
+            bind_group_layouts: &[taa_bgl],
+            push_constant_ranges: &[],
         });
         
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("TAA Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "taa_resolve",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(format.into()), Some(HISTORY_COLOR_FORMAT.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+    
+    fn create_depth_copy_pipeline(device: &wgpu::Device, shaders: &mut ShaderCache, bgl: &wgpu::BindGroupLayout) -> wgpu::ComputePipeline {
+        let shader = shaders.module(device, "Depth Copy Shader", OPTIMIZED_DEPTH_COPY_SHADER);
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Mipmap Pipeline Layout"),
-            bind_group_layouts: &[&bgl],
+            label: Some("Depth Copy Pipeline Layout"),
+            bind_group_layouts: &[bgl],
             push_constant_ranges: &[],
         });
-        
         device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Mipmap Pipeline"),
+            label: Some("Depth Copy Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: shader,
+            entry_point: "copy_depth",
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        })
+    }
+    
+    fn create_prepass_pipeline(device: &wgpu::Device, shaders: &mut ShaderCache, main_bgl: &wgpu::BindGroupLayout) -> wgpu::RenderPipeline {
+        let shader = shaders.module(device, "Main Shader", OPTIMIZED_MAIN_SHADER);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Prepass Pipeline Layout"),
+            bind_group_layouts: &[main_bgl],
+            push_constant_ranges: &[],
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Prepass Pipeline"),
             layout: Some(&pipeline_layout),
-            module: &shader,
-            entry_point: "generate_mip",
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[SceneVertex::LAYOUT],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_motion",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(MOTION_VECTOR_FORMAT.into())],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
         })
     }
     
@@ -554,181 +1690,195 @@ impl Renderer {
     // BIND GROUP CREATION
     // ========================================================================
     
+    /// Layout with one binding per entry of `kinds`, numbered in order
+    fn create_effect_bgl(device: &wgpu::Device, label: &str, kinds: &[BindingKind]) -> wgpu::BindGroupLayout {
+        let entries: Vec<_> = kinds
+            .iter()
+            .enumerate()
+            .map(|(binding, kind)| wgpu::BindGroupLayoutEntry {
+                binding: binding as u32,
+                visibility: match kind {
+                    BindingKind::StorageWrite | BindingKind::StorageTexture(_) => wgpu::ShaderStages::COMPUTE,
+                    _ => wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                },
+                ty: match kind {
+                    BindingKind::Uniform => wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    BindingKind::Storage | BindingKind::StorageWrite | BindingKind::DynamicStorage => wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: !matches!(kind, BindingKind::StorageWrite) },
+                        has_dynamic_offset: matches!(kind, BindingKind::DynamicStorage),
+                        min_binding_size: None,
+                    },
+                    BindingKind::Texture => wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    // Unfilterable float rather than depth: GL can't textureLoad depth textures
+                    BindingKind::Depth => wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    BindingKind::Sampler => wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    BindingKind::StorageTexture(format) => wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: *format,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                count: None,
+            })
+            .collect();
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor { label: Some(label), entries: &entries })
+    }
+    
+    fn create_shadow_bgl(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Shadow BGL"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture { sample_type: wgpu::TextureSampleType::Depth, view_dimension: wgpu::TextureViewDimension::D2Array, multisampled: false },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+            ],
+        })
+    }
+    
+    /// Frame, light, instance and cluster bindings of the main pass
     fn create_main_bind_group(
         device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
         camera_buffer: &wgpu::Buffer,
         light_buffer: &wgpu::Buffer,
+        instances: wgpu::BindingResource,
+        clusters: &ClusterLighting,
     ) -> wgpu::BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Main Bind Group"),
-            layout: &device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Main BGL"),
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
-                        count: None,
-                    },
-                ],
-            }),
+            layout,
             entries: &[
                 wgpu::BindGroupEntry { binding: 0, resource: camera_buffer.as_entire_binding() },
                 wgpu::BindGroupEntry { binding: 1, resource: light_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: instances },
+                wgpu::BindGroupEntry { binding: 3, resource: clusters.uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: clusters.light_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 5, resource: clusters.grid_buffer.as_entire_binding() },
             ],
         })
     }
     
+    /// One shadow-pass group (cascade matrix, instances) per cascade
+    fn create_shadow_caster_bind_groups(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        cascade_buffers: &[wgpu::Buffer],
+        instance_buffer: &wgpu::Buffer,
+    ) -> Vec<wgpu::BindGroup> {
+        cascade_buffers
+            .iter()
+            .map(|cascade_buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Shadow Caster Bind Group"),
+                    layout,
+                    entries: &[
+                        wgpu::BindGroupEntry { binding: 0, resource: cascade_buffer.as_entire_binding() },
+                        wgpu::BindGroupEntry { binding: 1, resource: instance_buffer.as_entire_binding() },
+                    ],
+                })
+            })
+            .collect()
+    }
+    
     fn create_shadow_bind_group(
         device: &wgpu::Device,
-        light_buffer: &wgpu::Buffer,
+        layout: &wgpu::BindGroupLayout,
+        shadow_texture: &wgpu::TextureView,
     ) -> wgpu::BindGroup {
         let shadow_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("Shadow Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
             compare: Some(wgpu::CompareFunction::LessEqual),
             ..Default::default()
         });
         
-        let shadow_texture = Self::create_shadow_texture(device, 2048);
-        
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Shadow Bind Group"),
-            layout: &device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Shadow BGL"),
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture { sample_type: wgpu::TextureSampleType::Depth, view_dimension: wgpu::TextureViewDimension::D2, multisampled: false },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
-                        count: None,
-                    },
-                ],
-            }),
+            layout,
             entries: &[
-                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&shadow_texture) },
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(shadow_texture) },
                 wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&shadow_sampler) },
             ],
         })
     }
     
-    fn create_post_bind_group(
-        device: &wgpu::Device,
-        hdr_texture: &wgpu::TextureView,
-        post_buffer: &wgpu::Buffer,
-    ) -> wgpu::BindGroup {
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("Post Sampler"),
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            ..Default::default()
-        });
-        
-        device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Post Bind Group"),
-            layout: &device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Post BGL"),
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture { sample_type: wgpu::TextureSampleType::Float { filterable: true }, view_dimension: wgpu::TextureViewDimension::D2, multisampled: false },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-            }),
-            entries: &[
-                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(hdr_texture) },
-                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&sampler) },
-            ],
-        })
-    }
-    
     // ========================================================================
     // TEXTURE CREATION
     // ========================================================================
     
-    fn create_depth_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::TextureView {
-        device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Depth Texture"),
-            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        }).create_view(&wgpu::TextureViewDescriptor::default())
-    }
-    
-    fn create_shadow_texture(device: &wgpu::Device, size: u32) -> wgpu::TextureView {
-        device.create_texture(&wgpu::TextureDescriptor {
+    /// Cascade array (sampled by the main pass) and one view per layer to
+    /// render into
+    fn create_shadow_texture(device: &wgpu::Device, shadows: &ShadowSettings) -> (wgpu::TextureView, Vec<wgpu::TextureView>) {
+        let layers = shadows.active_cascades().max(1);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Shadow Texture"),
-            size: wgpu::Extent3d { width: size, height: size, depth_or_array_layers: 1 },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        }).create_view(&wgpu::TextureViewDescriptor::default())
-    }
-    
-    fn create_hdr_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::TextureView {
-        device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("HDR Texture"),
-            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            size: wgpu::Extent3d { width: shadows.resolution, height: shadows.resolution, depth_or_array_layers: layers },
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba16Float,
+            format: DEPTH_FORMAT,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
-        }).create_view(&wgpu::TextureViewDescriptor::default())
+        });
+        let array = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let layer_views = (0..layers)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        (array, layer_views)
     }
     
-    fn create_ssao_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::TextureView {
-        // Half resolution for SSAO performance
-        device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("SSAO Texture"),
-            size: wgpu::Extent3d { width: width / 2, height: height / 2, depth_or_array_layers: 1 },
+    /// 1x1 stand-in for a disabled effect's output
+    fn create_constant_texture(device: &wgpu::Device, queue: &wgpu::Queue, rgba: [u8; 4]) -> wgpu::TextureView {
+        device.create_texture_with_data(queue, &wgpu::TextureDescriptor {
+            label: Some("Constant Texture"),
+            size: wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::R8Unorm,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
-        }).create_view(&wgpu::TextureViewDescriptor::default())
+        }, wgpu::util::TextureDataOrder::LayerMajor, &rgba).create_view(&wgpu::TextureViewDescriptor::default())
     }
     
-    fn create_vertex_buffer(device: &wgpu::Device) -> wgpu::Buffer {
-        // Fullscreen quad
-        let vertices: [f32; 18] = [
-            -1.0, -1.0, 0.0,   1.0, -1.0, 0.0,   1.0, 1.0, 0.0,
-            -1.0, -1.0, 0.0,   1.0, 1.0, 0.0,   -1.0, 1.0, 0.0,
-        ];
-        
-        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX,
+    fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Instance Buffer"),
+            size: (capacity.max(1) * std::mem::size_of::<InstanceData>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         })
     }
     
@@ -741,80 +1891,2018 @@ impl Renderer {
         })
     }
     
+    fn create_particle_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Buffer"),
+            size: (capacity.max(1) * std::mem::size_of::<Particle>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+    
+    /// Transient textures follow `config` on the next frame via the graph
+    /// pool; TAA history, prepass targets and the Hi-Z pyramid restart at
+    /// the new size
     pub fn resize(&mut self, width: u32, height: u32) {
         self.config.width = width;
         self.config.height = height;
-        
-        let (w, h) = self.config.effective_size();
-        
-        self.depth_texture = Self::create_depth_texture(&self.device, w, h);
-        self.hdr_texture = Self::create_hdr_texture(&self.device, w, h);
-        self.ssao_texture = Self::create_ssao_texture(&self.device, w, h);
+        self.target.resize(&self.device, width, height);
+        let (width, height) = self.config.effective_size();
+        self.taa_history = TaaHistory::new(&self.device, width, height);
+        self.prepass = None;
+        self.culling.hiz = HiZ::new(&self.device, self.config.effective_size());
     }
     
     pub fn set_resolution_scale(&mut self, scale: f32) {
         self.config.resolution_scale = scale.clamp(0.5, 2.0);
-        let (w, h) = self.config.effective_size();
-        self.resize(w, h);
+        self.resize(self.config.width, self.config.height);
+    }
+}
+
+/// Fullscreen triangle into `view`, replacing its contents (only inside the
+/// plan's tiles, given with the size of `view`)
+fn fullscreen_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &str,
+    view: &wgpu::TextureView,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+    tiles: Option<(&TileRenderPlan, (u32, u32))>,
+    timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+) {
+    let load = if tiles.is_some() { wgpu::LoadOp::Load } else { wgpu::LoadOp::Clear(wgpu::Color::BLACK) };
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations { load, store: wgpu::StoreOp::Store },
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes,
+    });
+    
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, bind_group, &[]);
+    match tiles {
+        Some((plan, size)) => plan.render_tiles(&mut pass, size, |pass| pass.draw(0..3, 0..1)),
+        None => pass.draw(0..3, 0..1),
     }
 }
 
+enum BindingKind {
+    Uniform,
+    Storage,
+    /// Read-write storage, compute stage only
+    StorageWrite,
+    /// Read-only storage bound at a dynamic offset
+    DynamicStorage,
+    Texture,
+    Depth,
+    Sampler,
+    /// Write-only storage texture, compute stage only
+    StorageTexture(wgpu::TextureFormat),
+}
+
 // ============================================================================
-// UNIFORMS
+// MESHES & DRAWS
 // ============================================================================
 
+/// Vertex layout of meshes drawn by `Renderer` (load with a stride of
+/// `size_of::<SceneVertex>()`)
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod, Zeroable)]
-struct CameraUniforms {
-    view_proj: glam::Mat4,
-    camera_pos: glam::Vec3,
-    _pad0: f32,
-    camera_forward: glam::Vec3,
-    _pad1: f32,
+pub struct SceneVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
 }
 
-#[repr(C)]
-#[derive(Debug, Clone, Copy, Pod, Zeroable)]
-struct LightUniforms {
-    view_proj: glam::Mat4,
-    light_pos: glam::Vec3,
-    light_color: glam::Vec3,
-    intensity: f32,
+impl SceneVertex {
+    const LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<SceneVertex>() as u64,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2],
+    };
 }
 
-#[repr(C)]
-#[derive(Debug, Clone, Copy, Pod, Zeroable)]
-struct PostUniforms {
-    resolution: glam::Vec2,
-    time: f32,
-    bloom_threshold: f32,
-    vignette_strength: f32,
+/// One mesh instance to draw
+#[derive(Debug, Clone, Copy)]
+pub struct DrawItem {
+    pub mesh: Handle,
+    pub material: Handle,
+    pub transform: Mat4,
+    /// Screen motion since the previous frame in pixels, excluding camera
+    /// motion (e.g. `MotionVector::screen_delta`); zero for static items
+    pub motion: Vec2,
+    /// World-space AABB (min, max) for GPU culling, e.g.
+    /// `EntitySnapshot::bounds_min`/`bounds_max`; None is never culled
+    pub bounds: Option<(Vec3, Vec3)>,
+}
+
+/// Instances of one mesh/material pair, contiguous in the instance buffer
+struct DrawBatch {
+    mesh: MeshBuffers,
+    material: usize,
+    first_instance: u32,
+    instance_count: u32,
+    /// First slot of the batch's culled instances
+    region: u32,
+}
+
+impl DrawBatch {
+    /// Every instance, from the instance buffer
+    fn draw(&self, pass: &mut wgpu::RenderPass) {
+        pass.set_vertex_buffer(0, self.mesh.vertex_buffer.slice(..));
+        pass.set_index_buffer(self.mesh.index_buffer.slice(..), self.mesh.index_format);
+        pass.draw_indexed(0..self.mesh.index_count, 0, self.first_instance..self.first_instance + self.instance_count);
+    }
+    
+    /// The instances that survived culling, with the batch's region bound
+    fn draw_indirect(&self, pass: &mut wgpu::RenderPass, draws: &wgpu::Buffer, index: usize) {
+        pass.set_vertex_buffer(0, self.mesh.vertex_buffer.slice(..));
+        pass.set_index_buffer(self.mesh.index_buffer.slice(..), self.mesh.index_format);
+        pass.draw_indexed_indirect(draws, (index * std::mem::size_of::<DrawCommand>()) as u64);
+    }
+}
+
+/// Quad facing `normal`, spanned by `u` x `v` (== normal, so CCW from outside)
+fn push_quad(vertices: &mut Vec<SceneVertex>, indices: &mut Vec<u16>, center: Vec3, normal: Vec3, u: Vec3, v: Vec3) {
+    let corner = |su: f32, sv: f32| SceneVertex {
+        position: (center + u * su + v * sv).to_array(),
+        normal: normal.to_array(),
+        uv: [su.max(0.0), sv.max(0.0)],
+    };
+    let base = vertices.len() as u16;
+    vertices.extend_from_slice(&[corner(-1.0, -1.0), corner(1.0, -1.0), corner(1.0, 1.0), corner(-1.0, 1.0)]);
+    indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+}
+
+/// Unit cube centred on the origin
+pub fn cube_mesh() -> (Vec<SceneVertex>, Vec<u16>) {
+    let (mut vertices, mut indices) = (Vec::with_capacity(24), Vec::with_capacity(36));
+    for (n, u, v) in [
+        (Vec3::X, Vec3::Y, Vec3::Z),
+        (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+        (Vec3::Y, Vec3::Z, Vec3::X),
+        (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+        (Vec3::Z, Vec3::X, Vec3::Y),
+        (Vec3::NEG_Z, Vec3::Y, Vec3::X),
+    ] {
+        push_quad(&mut vertices, &mut indices, n * 0.5, n, u * 0.5, v * 0.5);
+    }
+    (vertices, indices)
+}
+
+/// Upward-facing square on the XZ plane, `2 * half_extent` wide
+pub fn plane_mesh(half_extent: f32) -> (Vec<SceneVertex>, Vec<u16>) {
+    let (mut vertices, mut indices) = (Vec::with_capacity(4), Vec::with_capacity(6));
+    push_quad(&mut vertices, &mut indices, Vec3::ZERO, Vec3::Y, Vec3::Z * half_extent, Vec3::X * half_extent);
+    (vertices, indices)
 }
 
 // ============================================================================
-// STATS
+// LIGHTS
 // ============================================================================
 
-#[derive(Debug)]
-pub struct RenderStats {
-    pub fps: f32,
-    pub frame_time_ms: f32,
-    pub draw_calls: u32,
-    pub triangles: u32,
-    pub resolution: (u32, u32),
+/// Point light for clustered shading (matches `PointLight` in the WGSL)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct PointLight {
+    pub position: [f32; 3],
+    /// Distance at which the light's contribution reaches zero
+    pub attenuation_radius: f32,
+    pub color: [f32; 3],
+    pub intensity: f32,
 }
 
-impl std::fmt::Display for RenderStats {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "FPS: {:.1} | {:.2}ms | {} draw calls | {} triangles | {}x{}",
-            self.fps,
-            self.frame_time_ms,
-            self.draw_calls,
-            self.triangles,
-            self.resolution.0,
-            self.resolution.1
-        )
+impl PointLight {
+    pub fn new(position: Vec3, color: Vec3, intensity: f32, attenuation_radius: f32) -> Self {
+        Self { position: position.to_array(), attenuation_radius, color: color.to_array(), intensity }
+    }
+}
+
+/// Shadow-casting sun; `direction` is the way its light travels
+#[derive(Debug, Clone, Copy)]
+pub struct DirectionalLight {
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self { direction: Vec3::new(-5.0, -10.0, 5.0).normalize(), color: Vec3::new(1.0, 0.98, 0.95), intensity: 1.5 }
     }
-}
\ No newline at end of file
+}
+
+/// Lights for `Renderer::set_lights`
+#[derive(Debug, Clone, Default)]
+pub struct SceneLights {
+    pub directional: Option<DirectionalLight>,
+    pub points: Vec<PointLight>,
+}
+
+impl SceneLights {
+    /// Active `PointLight` and `DirectionalLight` components of the world's
+    /// visible actors and characters. Only one directional light is shaded:
+    /// the one on the lowest actor id.
+    pub fn from_world(world: &UWorld) -> Self {
+        let mut actors: Vec<_> = world
+            .actors
+            .values()
+            .chain(world.characters.values().map(|c| &c.base_actor))
+            .filter(|a| !a.is_hidden_in_game && !a.is_pending_kill)
+            .collect();
+        actors.sort_by_key(|a| a.id);
+        
+        let mut lights = Self::default();
+        for actor in actors {
+            let mut components: Vec<_> = actor.components.values().filter(|c| c.is_active).collect();
+            components.sort_by_key(|c| c.id);
+            for component in components {
+                let transform = &component.world_transform;
+                match component.component_type {
+                    EComponentType::PointLight { intensity, color, attenuation_radius } => {
+                        lights.points.push(PointLight::new(transform.location, color, intensity, attenuation_radius));
+                    }
+                    EComponentType::DirectionalLight { intensity, color } if lights.directional.is_none() => {
+                        lights.directional = Some(DirectionalLight { direction: transform.get_forward_vector(), color, intensity });
+                    }
+                    _ => {}
+                }
+            }
+        }
+        lights
+    }
+}
+
+/// Point lights and the per-cluster light lists the cull pass builds from
+/// them each frame
+struct ClusterLighting {
+    pipeline: wgpu::ComputePipeline,
+    bgl: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    light_buffer: wgpu::Buffer,
+    /// Per cluster: count, then `MAX_LIGHTS_PER_CLUSTER` light indices
+    grid_buffer: wgpu::Buffer,
+    light_count: u32,
+}
+
+impl ClusterLighting {
+    const CLUSTER_COUNT: u32 = CLUSTER_GRID[0] * CLUSTER_GRID[1] * CLUSTER_GRID[2];
+    
+    fn new(device: &wgpu::Device, shaders: &mut ShaderCache) -> Self {
+        let shader = shaders.module(device, "Light Cull Shader", OPTIMIZED_LIGHT_CULL_SHADER);
+        let bgl = Renderer::create_effect_bgl(device, "Light Cull BGL", &[BindingKind::Uniform, BindingKind::Storage, BindingKind::StorageWrite]);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Light Cull Pipeline Layout"),
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Light Cull Pipeline"),
+            layout: Some(&layout),
+            module: shader,
+            entry_point: "cull_lights",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        
+        let uniform_buffer = Renderer::create_uniform_buffer(device, std::mem::size_of::<ClusterUniforms>() as u64);
+        let light_buffer = Self::create_light_buffer(device, 64);
+        let grid_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cluster Light Grid"),
+            size: Self::CLUSTER_COUNT as u64 * (MAX_LIGHTS_PER_CLUSTER as u64 + 1) * 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let bind_group = Self::create_bind_group(device, &bgl, &uniform_buffer, &light_buffer, &grid_buffer);
+        Self { pipeline, bgl, bind_group, uniform_buffer, light_buffer, grid_buffer, light_count: 0 }
+    }
+    
+    fn create_light_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Point Light Buffer"),
+            size: (capacity.max(1) * std::mem::size_of::<PointLight>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+    
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        light_buffer: &wgpu::Buffer,
+        grid_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Light Cull Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: light_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: grid_buffer.as_entire_binding() },
+            ],
+        })
+    }
+    
+    /// Upload `lights`; true when the light buffer was reallocated and bind
+    /// groups holding it must be rebuilt
+    fn set_lights(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, lights: &[PointLight]) -> bool {
+        let grown = std::mem::size_of_val(lights) as u64 > self.light_buffer.size();
+        if grown {
+            self.light_buffer = Self::create_light_buffer(device, lights.len().next_power_of_two());
+            self.bind_group = Self::create_bind_group(device, &self.bgl, &self.uniform_buffer, &self.light_buffer, &self.grid_buffer);
+        }
+        queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(lights));
+        self.light_count = lights.len() as u32;
+        grown
+    }
+    
+    /// Clusters follow the camera and the size of the target being shaded
+    fn update(&self, queue: &wgpu::Queue, view: Mat4, proj: Mat4, (width, height): (u32, u32)) {
+        let uniforms = ClusterUniforms {
+            inv_proj: proj.inverse().to_cols_array_2d(),
+            view: view.to_cols_array_2d(),
+            grid: CLUSTER_GRID,
+            max_lights: MAX_LIGHTS_PER_CLUSTER,
+            screen_size: [width as f32, height as f32],
+            z_near: Z_NEAR,
+            z_far: Z_FAR,
+            light_count: self.light_count,
+            _pad: [0; 3],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+    
+    fn dispatch(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Light Cull Pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.dispatch_workgroups(Self::CLUSTER_COUNT.div_ceil(64), 1, 1);
+    }
+}
+
+// ============================================================================
+// GPU CULLING
+// ============================================================================
+
+/// GPU instance culling (the `rendering.culling` section of `settings.json`)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CullingSettings {
+    pub frustum_culling: bool,
+    /// Test instances against the previous frame's depth; only done with
+    /// `hierarchical_z`, the one occlusion method there is
+    pub occlusion_culling: bool,
+    pub hierarchical_z: bool,
+    pub distance_culling: bool,
+    /// Camera distance past which instances are dropped
+    pub distance_culling_max: f32,
+}
+
+impl Default for CullingSettings {
+    fn default() -> Self {
+        Self {
+            frustum_culling: true,
+            occlusion_culling: true,
+            hierarchical_z: true,
+            distance_culling: true,
+            distance_culling_max: 500.0,
+        }
+    }
+}
+
+impl CullingSettings {
+    /// Whether the Hi-Z pyramid is built and tested
+    pub fn occlusion(&self) -> bool {
+        self.occlusion_culling && self.hierarchical_z
+    }
+    
+    /// Matches the CULL_* flags of OPTIMIZED_CULL_SHADER
+    fn flags(&self) -> u32 {
+        self.frustum_culling as u32 | (self.distance_culling as u32) << 1 | (self.occlusion() as u32) << 2
+    }
+}
+
+/// Inward-facing planes (xyz normal, w distance) of a view-projection with
+/// [0, 1] depth: left, right, bottom, top, near, far
+fn frustum_planes(view_proj: Mat4) -> [Vec4; 6] {
+    let [r0, r1, r2, r3] = [0, 1, 2, 3].map(|i| view_proj.row(i));
+    [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2].map(|plane| plane / plane.truncate().length())
+}
+
+/// `draw_indexed_indirect` arguments of one batch, then where its culled
+/// instances start. `first_instance` stays 0 (GL ignores it); the main pass
+/// binds the batch's region at a dynamic offset instead.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct DrawCommand {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+    region: u32,
+}
+
+/// World bounds of one instance and the batch it belongs to
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct CullBounds {
+    bounds_min: [f32; 3],
+    batch: u32,
+    bounds_max: [f32; 3],
+    /// 0 for items without bounds, which are always drawn
+    cullable: u32,
+}
+
+impl CullBounds {
+    fn new(batch: u32, bounds: Option<(Vec3, Vec3)>) -> Self {
+        let (bounds_min, bounds_max) = bounds.unwrap_or_default();
+        Self { bounds_min: bounds_min.to_array(), batch, bounds_max: bounds_max.to_array(), cullable: bounds.is_some() as u32 }
+    }
+}
+
+/// Farthest-depth pyramid of the last frame that built one, starting at half
+/// the render resolution. Levels are packed into one storage buffer: GL can't
+/// write one mip of a texture while another mip of it is bound for sampling.
+struct HiZ {
+    buffer: wgpu::Buffer,
+    /// Sizes and offsets of each level's reduction (`HiZLevel` uniforms)
+    level_buffers: Vec<wgpu::Buffer>,
+    size: (u32, u32),
+    /// Unjittered view-projection it was built with; None until built
+    view_proj: Option<Mat4>,
+}
+
+impl HiZ {
+    fn new(device: &wgpu::Device, (width, height): (u32, u32)) -> Self {
+        let size = (width.div_ceil(2).max(1), height.div_ceil(2).max(1));
+        let level_count = size.0.max(size.1).ilog2() + 1;
+        let mut levels = Vec::with_capacity(level_count as usize);
+        let (mut src_size, mut src_offset, mut dst_offset) = ([width, height], 0, 0);
+        for level in 0..level_count {
+            let dst_size = [(size.0 >> level).max(1), (size.1 >> level).max(1)];
+            levels.push(HiZLevel { src_size, dst_size, src_offset, dst_offset, from_depth: (level == 0) as u32, _pad: 0 });
+            (src_size, src_offset) = (dst_size, dst_offset);
+            dst_offset += dst_size[0] * dst_size[1];
+        }
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Hi-Z Pyramid"),
+            size: dst_offset as u64 * 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let level_buffers = levels
+            .iter()
+            .map(|level| {
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Hi-Z Level Uniforms"),
+                    contents: bytemuck::bytes_of(level),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                })
+            })
+            .collect();
+        Self { buffer, level_buffers, size, view_proj: None }
+    }
+}
+
+/// `CullReadback::state` values
+const READBACK_IDLE: u8 = 0;
+const READBACK_PENDING: u8 = 1;
+const READBACK_MAPPED: u8 = 2;
+
+/// Per-batch visible counts copied out of both phases' draw buffers, read
+/// once mapped (a frame or more later) so the CPU never waits on the cull
+struct CullReadback {
+    buffer: wgpu::Buffer,
+    /// Idle, pending or mapped; a failed mapping goes back to idle so the
+    /// next frame copies again
+    state: Arc<AtomicU8>,
+    /// Instances and batches submitted in the frame being read back
+    in_flight: (u32, usize),
+    culled: u32,
+}
+
+impl CullReadback {
+    fn new(device: &wgpu::Device, size: u64) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cull Readback"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self { buffer, state: Arc::new(AtomicU8::new(READBACK_IDLE)), in_flight: (0, 0), culled: 0 }
+    }
+}
+
+/// GPU time of one frame's scene passes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScenePassTime {
+    pub gpu_ms: f32,
+    /// The frame only redrew a predictive tile plan
+    pub tiles_only: bool,
+}
+
+/// Timestamps from the start of the scene (the reprojection prepass, else
+/// the geometry pass) to the end of post-processing, read back like
+/// `CullReadback`. Only timed while no earlier readback is outstanding.
+struct SceneTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    state: Arc<AtomicU8>,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    tiles_only: bool,
+}
+
+impl SceneTimer {
+    const SIZE: u64 = 2 * std::mem::size_of::<u64>() as u64;
+    
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let buffer = |label, usage| device.create_buffer(&wgpu::BufferDescriptor { label: Some(label), size: Self::SIZE, usage, mapped_at_creation: false });
+        Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor { label: Some("Scene Timestamps"), ty: wgpu::QueryType::Timestamp, count: 2 }),
+            resolve_buffer: buffer("Scene Timestamp Resolve", wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC),
+            readback_buffer: buffer("Scene Timestamp Readback", wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST),
+            state: Arc::new(AtomicU8::new(READBACK_IDLE)),
+            period: queue.get_timestamp_period(),
+            tiles_only: false,
+        }
+    }
+    
+    fn idle(&self) -> bool {
+        self.state.load(Ordering::Acquire) == READBACK_IDLE
+    }
+    
+    /// Writes for a pass that opens and/or closes the timed span
+    fn writes(&self, begin: bool, end: bool) -> wgpu::RenderPassTimestampWrites<'_> {
+        wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: begin.then_some(0),
+            end_of_pass_write_index: end.then_some(1),
+        }
+    }
+    
+    fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.resolve_query_set(&self.query_set, 0..2, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.readback_buffer, 0, Self::SIZE);
+    }
+    
+    /// After the resolved frame was submitted
+    fn start_readback(&mut self, tiles_only: bool) {
+        let state = self.state.clone();
+        state.store(READBACK_PENDING, Ordering::Release);
+        self.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            state.store(if result.is_ok() { READBACK_MAPPED } else { READBACK_IDLE }, Ordering::Release);
+        });
+        self.tiles_only = tiles_only;
+    }
+    
+    /// The timed frame's span, once, when its readback has mapped
+    fn collect(&mut self) -> Option<ScenePassTime> {
+        if self.state.load(Ordering::Acquire) != READBACK_MAPPED {
+            return None;
+        }
+        let [begin, end] = *bytemuck::from_bytes::<[u64; 2]>(&self.readback_buffer.slice(..).get_mapped_range());
+        self.readback_buffer.unmap();
+        self.state.store(READBACK_IDLE, Ordering::Release);
+        let gpu_ms = end.saturating_sub(begin) as f32 * self.period / 1_000_000.0;
+        Some(ScenePassTime { gpu_ms, tiles_only: self.tiles_only })
+    }
+}
+
+/// Depth and motion of every submitted instance at render size, with depth
+/// copied into `HISTORY_DEPTH_FORMAT` for the reprojection pass
+struct ReprojectionPrepass {
+    depth: wgpu::TextureView,
+    motion: wgpu::TextureView,
+    depth_float: wgpu::TextureView,
+    size: (u32, u32),
+}
+
+impl ReprojectionPrepass {
+    fn new(device: &wgpu::Device, (width, height): (u32, u32)) -> Self {
+        let create = |label, format, usage| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: usage | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        Self {
+            depth: create("Prepass Depth", DEPTH_FORMAT, wgpu::TextureUsages::RENDER_ATTACHMENT),
+            motion: create("Prepass Motion", MOTION_VECTOR_FORMAT, wgpu::TextureUsages::RENDER_ATTACHMENT),
+            depth_float: create("Prepass Float Depth", HISTORY_DEPTH_FORMAT, wgpu::TextureUsages::STORAGE_BINDING),
+            size: (width, height),
+        }
+    }
+}
+
+/// Occlusion culling runs in two phases. Early tests against the previous
+/// frame's Hi-Z and feeds the geometry pass; late re-tests what early
+/// occlusion-culled against the pyramid of that pass's depth and draws the
+/// instances that turn out visible, so nothing pops in a frame late.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CullPhase {
+    Early,
+    Late,
+}
+
+/// Frustum, distance and Hi-Z occlusion culling of submitted instances into
+/// per-batch regions of `culled_buffers`, drawn with indirect draws. The
+/// uniform, draw and culled-instance buffers are per `CullPhase`.
+struct GpuCulling {
+    pipeline: wgpu::ComputePipeline,
+    bgl: wgpu::BindGroupLayout,
+    hiz_pipeline: wgpu::ComputePipeline,
+    hiz_bgl: wgpu::BindGroupLayout,
+    uniform_buffers: [wgpu::Buffer; 2],
+    bounds_buffer: wgpu::Buffer,
+    /// Instances the early phase occlusion-culled, for the late phase
+    retest_buffer: wgpu::Buffer,
+    draw_buffers: [wgpu::Buffer; 2],
+    culled_buffers: [wgpu::Buffer; 2],
+    /// Reset contents of `draw_buffers` (zero instance counts), uploaded
+    /// before every cull
+    draws: Vec<DrawCommand>,
+    /// Instances per region step, so every region starts at an offset the
+    /// device can bind storage at
+    region_granularity: u32,
+    /// Bytes of the largest region: the main pass's instance binding size
+    region_size: u64,
+    instance_count: u32,
+    /// Instances with bounds; without any there is nothing to re-test
+    cullable_count: u32,
+    hiz: HiZ,
+    readback: CullReadback,
+}
+
+impl GpuCulling {
+    fn new(device: &wgpu::Device, shaders: &mut ShaderCache, render_size: (u32, u32)) -> Self {
+        let mut compute_pipeline = |label: &str, source: &'static str, entry_point: &str, bgl: &wgpu::BindGroupLayout| {
+            let shader = shaders.module(device, label, source);
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[bgl],
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                module: shader,
+                entry_point,
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let bgl = Renderer::create_effect_bgl(
+            device,
+            "Instance Cull BGL",
+            &[
+                BindingKind::Uniform,
+                BindingKind::Storage,
+                BindingKind::Storage,
+                BindingKind::StorageWrite,
+                BindingKind::StorageWrite,
+                BindingKind::Storage,
+                BindingKind::StorageWrite,
+            ],
+        );
+        let hiz_bgl = Renderer::create_effect_bgl(device, "Hi-Z BGL", &[BindingKind::Depth, BindingKind::StorageWrite, BindingKind::Uniform]);
+        let pipeline = compute_pipeline("Instance Cull Pipeline", OPTIMIZED_CULL_SHADER, "cull_instances", &bgl);
+        let hiz_pipeline = compute_pipeline("Hi-Z Pipeline", OPTIMIZED_MIPMAP_SHADER, "generate_hiz", &hiz_bgl);
+        
+        let instance_size = std::mem::size_of::<InstanceData>() as u32;
+        let alignment = device.limits().min_storage_buffer_offset_alignment;
+        let region_granularity = (1..=alignment).find(|n| (n * instance_size).is_multiple_of(alignment)).unwrap_or(alignment);
+        let mut culling = Self {
+            pipeline,
+            bgl,
+            hiz_pipeline,
+            hiz_bgl,
+            uniform_buffers: [(); 2].map(|_| Renderer::create_uniform_buffer(device, std::mem::size_of::<CullUniforms>() as u64)),
+            bounds_buffer: Self::create_buffer(device, "Cull Bounds Buffer", 0, wgpu::BufferUsages::empty()),
+            retest_buffer: Self::create_buffer(device, "Cull Retest Buffer", 0, wgpu::BufferUsages::empty()),
+            draw_buffers: [(); 2].map(|_| Self::create_buffer(device, "Indirect Draw Buffer", 0, wgpu::BufferUsages::empty())),
+            culled_buffers: [(); 2].map(|_| Self::create_buffer(device, "Culled Instance Buffer", 0, wgpu::BufferUsages::empty())),
+            draws: Vec::new(),
+            region_granularity,
+            region_size: 0,
+            instance_count: 0,
+            cullable_count: 0,
+            hiz: HiZ::new(device, render_size),
+            readback: CullReadback::new(device, 4),
+        };
+        culling.resize_buffers(device, 1, 1, 1);
+        culling
+    }
+    
+    fn create_buffer(device: &wgpu::Device, label: &str, size: u64, usage: wgpu::BufferUsages) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: size.max(4),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | usage,
+            mapped_at_creation: false,
+        })
+    }
+    
+    /// Grow buffers to `instances` bounds, `batches` draw commands and
+    /// `culled` culled-instance slots; true when any was reallocated
+    fn resize_buffers(&mut self, device: &wgpu::Device, instances: usize, batches: usize, culled: usize) -> bool {
+        let mut grown = false;
+        let bounds_size = (instances * std::mem::size_of::<CullBounds>()) as u64;
+        if bounds_size > self.bounds_buffer.size() {
+            self.bounds_buffer = Self::create_buffer(device, "Cull Bounds Buffer", bounds_size.next_power_of_two(), wgpu::BufferUsages::empty());
+        }
+        let retest_size = (instances * std::mem::size_of::<u32>()) as u64;
+        if retest_size > self.retest_buffer.size() {
+            self.retest_buffer = Self::create_buffer(device, "Cull Retest Buffer", retest_size.next_power_of_two(), wgpu::BufferUsages::empty());
+        }
+        let draw_size = (batches * std::mem::size_of::<DrawCommand>()) as u64;
+        if draw_size > self.draw_buffers[0].size() {
+            let usage = wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_SRC;
+            self.draw_buffers = [(); 2].map(|_| Self::create_buffer(device, "Indirect Draw Buffer", draw_size.next_power_of_two(), usage));
+            // A mapping still pending on the old readback is abandoned
+            self.readback = CullReadback { culled: self.readback.culled, ..CullReadback::new(device, self.draw_buffers[0].size() * 2) };
+        }
+        let culled_size = (culled * std::mem::size_of::<InstanceData>()) as u64;
+        if culled_size > self.culled_buffers[0].size() {
+            let size = culled_size.next_power_of_two();
+            self.culled_buffers = [(); 2].map(|_| Self::create_buffer(device, "Culled Instance Buffer", size, wgpu::BufferUsages::empty()));
+            grown = true;
+        }
+        grown
+    }
+    
+    /// Lay out batches of `counts` instances (with `bounds` in batch order)
+    /// into regions; returns each batch's region and whether the instance
+    /// binding changed, so bind groups holding it must be rebuilt
+    fn set_batches(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, batches: &[DrawBatch], bounds: &[CullBounds]) -> (Vec<u32>, bool) {
+        let step = |count: u32| count.max(1).next_multiple_of(self.region_granularity);
+        let mut regions = Vec::with_capacity(batches.len());
+        self.draws.clear();
+        let mut next_region = 0;
+        for batch in batches {
+            regions.push(next_region);
+            self.draws.push(DrawCommand {
+                index_count: batch.mesh.index_count,
+                instance_count: 0,
+                first_index: 0,
+                base_vertex: 0,
+                first_instance: 0,
+                region: next_region,
+            });
+            next_region += step(batch.instance_count);
+        }
+        
+        // Room for the largest region past the last offset, since every
+        // dynamic binding has the same size
+        let largest = step(batches.iter().map(|b| b.instance_count).max().unwrap_or(0));
+        let region_size = largest as u64 * std::mem::size_of::<InstanceData>() as u64;
+        let grown = self.resize_buffers(device, bounds.len(), batches.len(), (next_region + largest) as usize);
+        let changed = grown || region_size != self.region_size;
+        self.region_size = region_size;
+        self.instance_count = bounds.len() as u32;
+        self.cullable_count = bounds.iter().filter(|b| b.cullable != 0).count() as u32;
+        queue.write_buffer(&self.bounds_buffer, 0, bytemuck::cast_slice(bounds));
+        (regions, changed)
+    }
+    
+    /// The main pass's view of a phase's culled instances, one region at a time
+    fn instance_binding(&self, phase: CullPhase) -> wgpu::BindingResource<'_> {
+        wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer: &self.culled_buffers[phase as usize],
+            offset: 0,
+            size: wgpu::BufferSize::new(self.region_size.max(std::mem::size_of::<InstanceData>() as u64)),
+        })
+    }
+    
+    /// True when this frame's early phase occlusion-tests, so a late phase
+    /// has something to re-test
+    fn late_phase(&self, settings: &CullingSettings) -> bool {
+        settings.occlusion() && self.hiz.view_proj.is_some() && self.cullable_count > 0
+    }
+    
+    fn update(&self, queue: &wgpu::Queue, view_proj: Mat4, camera_pos: Vec3, settings: &CullingSettings) {
+        let occlusion = settings.occlusion() && self.hiz.view_proj.is_some();
+        let early = CullUniforms {
+            hiz_view_proj: self.hiz.view_proj.unwrap_or(view_proj).to_cols_array_2d(),
+            planes: frustum_planes(view_proj).map(|plane| plane.to_array()),
+            camera_pos: camera_pos.to_array(),
+            max_distance: settings.distance_culling_max,
+            hiz_size: [self.hiz.size.0, self.hiz.size.1],
+            hiz_levels: self.hiz.level_buffers.len() as u32,
+            flags: CullingSettings { occlusion_culling: occlusion, ..settings.clone() }.flags(),
+            instance_count: self.instance_count,
+            phase: CullPhase::Early as u32,
+            _pad: [0; 2],
+        };
+        // The late phase runs after this frame's pyramid is built
+        let late = CullUniforms { hiz_view_proj: view_proj.to_cols_array_2d(), phase: CullPhase::Late as u32, ..early };
+        for ((uniforms, draws), buffer) in [early, late].iter().zip(&self.draw_buffers).zip(&self.uniform_buffers) {
+            queue.write_buffer(buffer, 0, bytemuck::bytes_of(uniforms));
+            queue.write_buffer(draws, 0, bytemuck::cast_slice(&self.draws));
+        }
+    }
+    
+    fn dispatch(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, instance_buffer: &wgpu::Buffer, phase: CullPhase) {
+        let phase = phase as usize;
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Instance Cull Bind Group"),
+            layout: &self.bgl,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: self.uniform_buffers[phase].as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: instance_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: self.bounds_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: self.draw_buffers[phase].as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: self.culled_buffers[phase].as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 5, resource: self.hiz.buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 6, resource: self.retest_buffer.as_entire_binding() },
+            ],
+        });
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Instance Cull Pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(self.instance_count.div_ceil(64), 1, 1);
+    }
+    
+    /// Reduce `depth` into the Hi-Z pyramid, one dispatch per level
+    fn build_hiz(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, depth: &wgpu::TextureView) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Hi-Z Pass"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.hiz_pipeline);
+        for (level, uniforms) in self.hiz.level_buffers.iter().enumerate() {
+            // Only level 0 reads the depth buffer, but every level binds it
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Hi-Z Bind Group"),
+                layout: &self.hiz_bgl,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(depth) },
+                    wgpu::BindGroupEntry { binding: 1, resource: self.hiz.buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 2, resource: uniforms.as_entire_binding() },
+                ],
+            });
+            let (width, height) = ((self.hiz.size.0 >> level).max(1), (self.hiz.size.1 >> level).max(1));
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(width.div_ceil(8), height.div_ceil(8), 1);
+        }
+    }
+    
+    /// Culled count of the latest frame read back so far
+    fn collect_readback(&mut self) -> u32 {
+        let readback = &mut self.readback;
+        if readback.state.load(Ordering::Acquire) == READBACK_MAPPED {
+            let (instances, batches) = readback.in_flight;
+            // Early then late phase counts, back to back
+            let slice = readback.buffer.slice(..(2 * batches * std::mem::size_of::<DrawCommand>()) as u64);
+            let visible: u32 = bytemuck::cast_slice::<u8, DrawCommand>(&slice.get_mapped_range()).iter().map(|d| d.instance_count).sum();
+            readback.buffer.unmap();
+            readback.culled = instances.saturating_sub(visible);
+            readback.state.store(READBACK_IDLE, Ordering::Release);
+        }
+        readback.culled
+    }
+    
+    /// Copy this frame's visible counts out (after its cull was submitted)
+    /// unless an earlier copy is still waiting to be mapped
+    fn start_readback(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let readback = &mut self.readback;
+        if readback.state.load(Ordering::Acquire) != READBACK_IDLE || self.draws.is_empty() {
+            return;
+        }
+        let size = (self.draws.len() * std::mem::size_of::<DrawCommand>()) as u64;
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Cull Readback Encoder") });
+        for (phase, draws) in self.draw_buffers.iter().enumerate() {
+            encoder.copy_buffer_to_buffer(draws, 0, &readback.buffer, phase as u64 * size, size);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+        
+        let state = readback.state.clone();
+        state.store(READBACK_PENDING, Ordering::Release);
+        readback.buffer.slice(..2 * size).map_async(wgpu::MapMode::Read, move |result| {
+            // A failed mapping leaves the buffer unmapped; copy again next frame
+            state.store(if result.is_ok() { READBACK_MAPPED } else { READBACK_IDLE }, Ordering::Release);
+        });
+        readback.in_flight = (self.instance_count, self.draws.len());
+    }
+}
+
+// ============================================================================
+// SHADOWS
+// ============================================================================
+
+pub const MAX_SHADOW_CASCADES: u32 = 4;
+
+/// Cascaded shadow maps for the directional light (the `shadows` section of
+/// `settings.json`)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ShadowSettings {
+    pub enabled: bool,
+    /// Width and height of each cascade's map
+    pub resolution: u32,
+    /// 1 to `MAX_SHADOW_CASCADES`
+    pub cascade_count: u32,
+    /// Blend from uniform (0.0) to logarithmic (1.0) split distances
+    pub cascade_split_lambda: f32,
+    /// View depth where the last cascade ends
+    pub max_distance: f32,
+    /// Depth bias, in shadow-map depth units
+    pub bias: f32,
+    /// Offset receivers along their normal by a cascade-sized texel amount
+    pub normal_offset: bool,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            resolution: 2048,
+            cascade_count: 3,
+            cascade_split_lambda: 0.95,
+            max_distance: 100.0,
+            bias: 0.0005,
+            normal_offset: true,
+        }
+    }
+}
+
+impl ShadowSettings {
+    /// Cascades rendered each frame (0 when shadows are off)
+    pub fn active_cascades(&self) -> u32 {
+        if self.enabled { self.cascade_count.clamp(1, MAX_SHADOW_CASCADES) } else { 0 }
+    }
+}
+
+/// One cascade: light view-projection, the view depth it ends at and the
+/// world size of one of its shadow texels
+#[derive(Debug, Clone, Copy)]
+struct Cascade {
+    view_proj: Mat4,
+    split: f32,
+    texel: f32,
+}
+
+/// View depths where each of `count` cascades ends: `lambda` blends
+/// logarithmic splits (1.0) with uniform ones (0.0)
+fn cascade_splits(near: f32, far: f32, count: u32, lambda: f32) -> Vec<f32> {
+    (1..=count)
+        .map(|i| {
+            let t = i as f32 / count as f32;
+            lambda * near * (far / near).powf(t) + (1.0 - lambda) * (near + (far - near) * t)
+        })
+        .collect()
+}
+
+/// Fit each cascade to its slice of the view frustum. The bounding sphere is
+/// found in view space, so its size doesn't change as the camera turns, and
+/// its centre is snapped to whole texels in light space, so shadow edges
+/// don't shimmer as the camera moves.
+fn compute_cascades(view: Mat4, aspect: f32, to_light: Vec3, shadows: &ShadowSettings) -> Vec<Cascade> {
+    let inv_view = view.inverse();
+    let up = if to_light.y.abs() > 0.99 { Vec3::Z } else { Vec3::Y };
+    let light_view = Mat4::look_at_rh(Vec3::ZERO, -to_light, up);
+    let tan_y = (FOV_Y * 0.5).tan();
+    let far = shadows.max_distance.clamp(Z_NEAR * 2.0, Z_FAR);
+    
+    let mut near = Z_NEAR;
+    cascade_splits(Z_NEAR, far, shadows.active_cascades(), shadows.cascade_split_lambda)
+        .into_iter()
+        .map(|split| {
+            let corners: Vec<Vec3> = [near, split]
+                .into_iter()
+                .flat_map(|d| [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].map(|(x, y)| Vec3::new(x * d * tan_y * aspect, y * d * tan_y, -d)))
+                .collect();
+            let center = corners.iter().sum::<Vec3>() / corners.len() as f32;
+            let radius = corners.iter().map(|c| c.distance(center)).fold(0.0, f32::max);
+            
+            let texel = 2.0 * radius / shadows.resolution as f32;
+            let center = light_view.transform_point3(inv_view.transform_point3(center));
+            let snapped = (center.truncate() / texel).floor() * texel;
+            // Casters up to `max_distance` towards the light still land in the map
+            let proj = Mat4::orthographic_rh(
+                snapped.x - radius,
+                snapped.x + radius,
+                snapped.y - radius,
+                snapped.y + radius,
+                -center.z - radius - shadows.max_distance,
+                -center.z + radius,
+            );
+            near = split;
+            Cascade { view_proj: proj * light_view, split, texel }
+        })
+        .collect()
+}
+
+// ============================================================================
+// TEMPORAL AA
+// ============================================================================
+
+/// Temporal anti-aliasing (the `rendering.anti_aliasing.taa` section of
+/// `settings.json`)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TaaSettings {
+    /// Scales the per-frame sample offsets, which span one pixel at 1.0
+    pub jitter_scale: f32,
+    /// Frames before the jitter pattern repeats
+    pub sample_count: u32,
+    /// Unsharp-mask strength on the displayed frame
+    pub sharpness: f32,
+}
+
+impl Default for TaaSettings {
+    fn default() -> Self {
+        Self { jitter_scale: 0.5, sample_count: 4, sharpness: 0.5 }
+    }
+}
+
+/// Sub-pixel sample offset of `frame` in pixels: the Halton (2, 3) sequence,
+/// repeating every `sample_count` frames
+fn taa_jitter(frame: u64, taa: &TaaSettings) -> Vec2 {
+    let index = (frame % taa.sample_count.max(1) as u64) as u32 + 1;
+    (Vec2::new(halton(index, 2), halton(index, 3)) - 0.5) * taa.jitter_scale
+}
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let (mut result, mut fraction) = (0.0, 1.0);
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+/// Resolved (or, without TAA, captured) frames at render size, in the
+/// reprojection history formats. The colour is ping-ponged: the latest frame
+/// is read while the next is written.
+struct TaaHistory {
+    /// Shared with the predictive renderer through `Renderer::history_frame`
+    color: [(Arc<wgpu::Texture>, wgpu::TextureView); 2],
+    depth: (Arc<wgpu::Texture>, wgpu::TextureView),
+    size: (u32, u32),
+    latest: usize,
+    /// Unjittered view-projection of the latest frame; None when there is
+    /// no usable history
+    view_proj: Option<Mat4>,
+}
+
+impl TaaHistory {
+    fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let create = |label, format, usage| {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: usage | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            (Arc::new(texture), view)
+        };
+        let color = |label| create(label, HISTORY_COLOR_FORMAT, wgpu::TextureUsages::RENDER_ATTACHMENT);
+        Self {
+            color: [color("TAA History A"), color("TAA History B")],
+            // Float formats aren't renderable everywhere, so depth is stored by a compute pass
+            depth: create("TAA History Depth", HISTORY_DEPTH_FORMAT, wgpu::TextureUsages::STORAGE_BINDING),
+            size: (width, height),
+            latest: 0,
+            view_proj: None,
+        }
+    }
+    
+    /// After a frame: the resolved or captured frame becomes the history, or
+    /// the history is dropped if the frame was neither
+    fn advance(&mut self, resolved: Option<Mat4>) {
+        if resolved.is_some() {
+            self.latest = 1 - self.latest;
+        }
+        self.view_proj = resolved;
+    }
+}
+
+// ============================================================================
+// UNIFORMS
+// ============================================================================
+// Plain arrays so the byte layout matches WGSL's uniform alignment rules.
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct FrameUniforms {
+    view_proj: [[f32; 4]; 4],
+    prev_view_proj: [[f32; 4]; 4],
+    inv_view_proj: [[f32; 4]; 4],
+    camera_pos: [f32; 3],
+    time: f32,
+    camera_dir: [f32; 3],
+    _pad: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct LightUniforms {
+    cascade_view_proj: [[[f32; 4]; 4]; MAX_SHADOW_CASCADES as usize],
+    cascade_splits: [f32; 4],
+    cascade_texel: [f32; 4],
+    direction: [f32; 3],
+    cascade_count: u32,
+    light_color: [f32; 3],
+    intensity: f32,
+    bias: f32,
+    normal_offset: f32,
+    _pad: [f32; 2],
+}
+
+/// Material parameters; pass `bytemuck::bytes_of` of this to
+/// `ResourceManager::create_material`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct MaterialUniforms {
+    base_color: [f32; 4],
+    metallic_rough: [f32; 2],
+    _pad0: [f32; 2],
+    ao_emissive_strength: [f32; 3],
+    flags: u32,
+}
+
+impl MaterialUniforms {
+    /// Receive-shadow bit in `flags`
+    pub const RECEIVE_SHADOWS: u32 = 8;
+    
+    /// Opaque material that receives shadows
+    pub fn new(base_color: [f32; 4], metallic: f32, roughness: f32) -> Self {
+        Self {
+            base_color,
+            metallic_rough: [metallic, roughness],
+            _pad0: [0.0; 2],
+            ao_emissive_strength: [1.0, 0.0, 0.0],
+            flags: Self::RECEIVE_SHADOWS,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct InstanceData {
+    model: [[f32; 4]; 4],
+    normal_matrix: [[f32; 4]; 4],
+    motion: [f32; 2],
+    _pad: [f32; 2],
+}
+
+impl InstanceData {
+    fn new(model: Mat4, motion: Vec2) -> Self {
+        let normal_matrix = Mat4::from_mat3(Mat3::from_mat4(model).inverse().transpose());
+        Self {
+            model: model.to_cols_array_2d(),
+            normal_matrix: normal_matrix.to_cols_array_2d(),
+            motion: motion.to_array(),
+            _pad: [0.0; 2],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct TaaUniforms {
+    inv_view_proj: [[f32; 4]; 4],
+    prev_view_proj: [[f32; 4]; 4],
+    screen_size: [f32; 2],
+    feedback: f32,
+    sharpness: f32,
+    history_valid: u32,
+    motion_scale: f32,
+    _pad: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct CullUniforms {
+    hiz_view_proj: [[f32; 4]; 4],
+    planes: [[f32; 4]; 6],
+    camera_pos: [f32; 3],
+    max_distance: f32,
+    hiz_size: [u32; 2],
+    hiz_levels: u32,
+    flags: u32,
+    instance_count: u32,
+    phase: u32,
+    _pad: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct HiZLevel {
+    src_size: [u32; 2],
+    dst_size: [u32; 2],
+    src_offset: u32,
+    dst_offset: u32,
+    from_depth: u32,
+    _pad: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct ClusterUniforms {
+    inv_proj: [[f32; 4]; 4],
+    view: [[f32; 4]; 4],
+    grid: [u32; 3],
+    max_lights: u32,
+    screen_size: [f32; 2],
+    z_near: f32,
+    z_far: f32,
+    light_count: u32,
+    _pad: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct PostUniforms {
+    resolution: [f32; 2],
+    time: f32,
+    bloom_intensity: f32,
+    exposure: f32,
+    vignette_intensity: f32,
+    chromatic_aberration: f32,
+    film_grain: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct SsaoUniforms {
+    radius: f32,
+    bias: f32,
+    intensity: f32,
+    _pad: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct SsrUniforms {
+    max_distance: f32,
+    thickness: f32,
+    edge_fade: f32,
+    strength: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct BloomUniforms {
+    texel: [f32; 2],
+    direction: [f32; 2],
+    threshold: f32,
+    _pad: f32,
+}
+
+/// One billboard for the particle pass (matches `Particle` in the WGSL)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct Particle {
+    pub position: [f32; 3],
+    pub size: f32,
+    pub color: [f32; 4],
+    /// Normalized age: fades in over 0..0.2 and out over 0.8..1
+    pub life: f32,
+    _pad: [f32; 3],
+}
+
+impl Particle {
+    pub fn new(position: Vec3, size: f32, color: [f32; 4], life: f32) -> Self {
+        Self { position: position.to_array(), size, color, life, _pad: [0.0; 3] }
+    }
+}
+
+// ============================================================================
+// STATS
+// ============================================================================
+
+#[derive(Debug)]
+pub struct RenderStats {
+    pub fps: f32,
+    pub frame_time_ms: f32,
+    pub draw_calls: u32,
+    pub triangles: u32,
+    pub resolution: (u32, u32),
+    pub render_passes: u32,
+    /// Transient textures the frame graph used, and the physical textures
+    /// backing them after aliasing
+    pub transient_textures: u32,
+    pub physical_textures: u32,
+    /// Point lights culled into clusters this frame
+    pub point_lights: u32,
+    /// Instances the GPU culled, as last read back (a frame or more behind)
+    pub instances_culled: u32,
+    /// Scene pass time of an earlier frame, when its timestamps were read
+    /// back this frame (needs `Features::TIMESTAMP_QUERY`)
+    pub scene_gpu_time: Option<ScenePassTime>,
+}
+
+impl std::fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "FPS: {:.1} | {:.2}ms | {} draw calls | {} triangles | {}x{}",
+            self.fps,
+            self.frame_time_ms,
+            self.draw_calls,
+            self.triangles,
+            self.resolution.0,
+            self.resolution.1
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::{golden, test_device};
+    use crate::resource_manager::ResourceConfig;
+    
+    /// Ground plane and one cube, loaded through the ResourceManager
+    struct DemoScene {
+        resources: ResourceManager,
+        ground: Handle,
+        cube: Handle,
+        ground_material: Handle,
+        cube_material: Handle,
+    }
+    
+    impl DemoScene {
+        fn load(device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue>) -> Self {
+            let resources = ResourceManager::new(device, queue, ResourceConfig::default());
+            let load = |(vertices, indices): (Vec<SceneVertex>, Vec<u16>)| {
+                let stride = std::mem::size_of::<SceneVertex>() as u64;
+                resources.load_mesh(bytemuck::cast_slice(&vertices), bytemuck::cast_slice(&indices), stride, wgpu::IndexFormat::Uint16).unwrap()
+            };
+            let (ground, cube) = (load(plane_mesh(5.0)), load(cube_mesh()));
+            let material = |params: MaterialUniforms| resources.create_material(bytemuck::bytes_of(&params), None, None, None, None).unwrap();
+            let ground_material = material(MaterialUniforms::new([0.6, 0.6, 0.62, 1.0], 0.0, 0.9));
+            let cube_material = material(MaterialUniforms::new([0.9, 0.35, 0.1, 1.0], 0.0, 0.4));
+            Self { resources, ground, cube, ground_material, cube_material }
+        }
+        
+        fn items(&self) -> Vec<DrawItem> {
+            vec![
+                DrawItem { mesh: self.ground, material: self.ground_material, transform: Mat4::IDENTITY, motion: Vec2::ZERO, bounds: None },
+                DrawItem { mesh: self.cube, material: self.cube_material, transform: Mat4::from_translation(Vec3::new(0.0, 0.5, 0.0)), motion: Vec2::ZERO, bounds: None },
+            ]
+        }
+    }
+    
+    fn headless_renderer(config: RenderConfig) -> Option<(Renderer, DemoScene)> {
+        let (device, queue) = test_device()?;
+        let (device, queue) = (Arc::new(device), Arc::new(queue));
+        let scene = DemoScene::load(device.clone(), queue.clone());
+        let mut renderer = Renderer::new_headless(device, queue, config);
+        renderer.submit_draws(&scene.resources, &scene.items());
+        Some((renderer, scene))
+    }
+    
+    fn golden_config() -> RenderConfig {
+        RenderConfig {
+            width: 160,
+            height: 120,
+            shadows: ShadowSettings { resolution: 512, ..Default::default() },
+            enable_taa: false,
+            ..Default::default()
+        }
+    }
+    
+    #[test]
+    fn test_uniform_sizes_match_wgsl() {
+        assert_eq!(std::mem::size_of::<FrameUniforms>(), 224);
+        assert_eq!(std::mem::size_of::<LightUniforms>(), 336);
+        assert_eq!(std::mem::size_of::<MaterialUniforms>(), 48);
+        assert_eq!(std::mem::size_of::<PostUniforms>(), 32);
+        assert_eq!(std::mem::size_of::<BloomUniforms>(), 24);
+        assert_eq!(std::mem::size_of::<Particle>(), 48);
+        assert_eq!(std::mem::size_of::<InstanceData>(), 144);
+        assert_eq!(std::mem::size_of::<TaaUniforms>(), 160);
+        assert_eq!(std::mem::size_of::<CullUniforms>(), 208);
+        assert_eq!(std::mem::size_of::<CullBounds>(), 32);
+        assert_eq!(std::mem::size_of::<DrawCommand>(), 24);
+        assert_eq!(std::mem::size_of::<HiZLevel>(), 32);
+        assert_eq!(std::mem::size_of::<ClusterUniforms>(), 176);
+        assert_eq!(std::mem::size_of::<PointLight>(), 32);
+    }
+    
+    #[test]
+    fn test_headless_frame_matches_golden() {
+        let Some((mut renderer, _scene)) = headless_renderer(golden_config()) else { return };
+        renderer.render().unwrap();
+        let frame = renderer.read_frame().unwrap().unwrap();
+        golden::compare_golden("renderer_demo_scene", &frame, 160, 120, Default::default()).unwrap();
+    }
+    
+    #[test]
+    fn test_headless_frame_without_post_effects_matches_golden() {
+        let config = RenderConfig { enable_post_processing: false, enable_ssao: false, ..golden_config() };
+        let Some((mut renderer, _scene)) = headless_renderer(config) else { return };
+        renderer.set_camera(Vec3::new(3.0, 3.0, 3.0), Vec3::new(0.0, 0.5, 0.0));
+        let stats = renderer.render().unwrap();
+        // cull, shadow, geometry, hiz, post
+        assert_eq!(stats.render_passes, 5);
+        let frame = renderer.read_frame().unwrap().unwrap();
+        golden::compare_golden("renderer_no_post_effects", &frame, 160, 120, Default::default()).unwrap();
+    }
+    
+    #[test]
+    fn test_shader_permutations_follow_effects() {
+        let all = ShaderFeatures::SHADOWS | ShaderFeatures::SSAO | ShaderFeatures::BLOOM | ShaderFeatures::POST_EFFECTS;
+        assert_eq!(golden_config().shader_features(), all);
+        let shadows = ShadowSettings { enabled: false, ..Default::default() };
+        let config = RenderConfig { enable_post_processing: false, enable_ssao: false, shadows, ..golden_config() };
+        assert_eq!(config.shader_features(), ShaderFeatures::NONE);
+        
+        let Some((mut renderer, _scene)) = headless_renderer(golden_config()) else { return };
+        let compiled = renderer.shaders.len();
+        
+        // Dropping SSAO compiles one more post permutation and nothing else,
+        // and renders what a renderer created without SSAO does
+        renderer.set_effects(vec![EffectPass::Particles, EffectPass::Bloom]);
+        assert_eq!(renderer.shaders.features(), ShaderFeatures::SHADOWS | ShaderFeatures::BLOOM | ShaderFeatures::POST_EFFECTS);
+        assert_eq!(renderer.shaders.len(), compiled + 1);
+        renderer.render().unwrap();
+        let (mut reference, _reference_scene) = headless_renderer(RenderConfig { enable_ssao: false, ..golden_config() }).unwrap();
+        reference.render().unwrap();
+        assert_eq!(renderer.read_frame().unwrap().unwrap(), reference.read_frame().unwrap().unwrap());
+        
+        // Switching back reuses the cached module
+        renderer.set_effects(golden_config().effect_order);
+        assert_eq!(renderer.shaders.features(), all);
+        assert_eq!(renderer.shaders.len(), compiled + 1);
+    }
+    
+    #[test]
+    fn test_effects_follow_config_and_share_textures() {
+        let config = RenderConfig { enable_ssr: true, ..golden_config() };
+        let Some((mut renderer, _scene)) = headless_renderer(config) else { return };
+        renderer.set_camera(Vec3::new(2.5, 1.5, -3.5), Vec3::new(0.0, 0.4, 0.0));
+        renderer.set_particles(&[
+            Particle::new(Vec3::new(-0.6, 1.3, -0.6), 0.8, [1.0, 0.7, 0.2, 1.0], 0.5),
+            Particle::new(Vec3::new(0.7, 1.0, -0.8), 0.6, [0.2, 0.6, 1.0, 1.0], 0.5),
+        ]);
+        
+        let stats = renderer.render().unwrap();
+        // cull, shadow, geometry, hiz, ssao, ssr, particles, 3x bloom, post
+        assert_eq!(stats.render_passes, 11);
+        // bloom_blur_v reuses bloom_bright's texture
+        assert!(stats.physical_textures < stats.transient_textures);
+        let frame = renderer.read_frame().unwrap().unwrap();
+        golden::compare_golden("renderer_all_effects", &frame, 160, 120, Default::default()).unwrap();
+        
+        // Dropping effects from the order removes their passes
+        renderer.set_effects(vec![EffectPass::Particles]);
+        assert_eq!(renderer.render().unwrap().render_passes, 6);
+    }
+    
+    #[test]
+    fn test_draw_items_are_instanced_per_mesh_and_material() {
+        let config = RenderConfig { enable_post_processing: false, enable_ssao: false, ..golden_config() };
+        let Some((mut renderer, scene)) = headless_renderer(config) else { return };
+        let mut items = vec![DrawItem { mesh: scene.ground, material: scene.ground_material, transform: Mat4::IDENTITY, motion: Vec2::ZERO, bounds: None }];
+        for i in 0..9 {
+            let (x, z) = ((i % 3) as f32 - 1.0, (i / 3) as f32 - 1.0);
+            let transform = Mat4::from_scale_rotation_translation(
+                Vec3::splat(0.6),
+                glam::Quat::from_rotation_y(i as f32 * 0.3),
+                Vec3::new(x * 1.8, 0.3, z * 1.8),
+            );
+            let material = if i % 2 == 0 { scene.cube_material } else { scene.ground_material };
+            items.push(DrawItem { mesh: scene.cube, material, transform, motion: Vec2::ZERO, bounds: None });
+        }
+        // Unknown handles are dropped rather than drawn
+        items.push(DrawItem { mesh: Handle::invalid(), material: scene.cube_material, transform: Mat4::IDENTITY, motion: Vec2::ZERO, bounds: None });
+        
+        assert_eq!(renderer.submit_draws(&scene.resources, &items), 10);
+        renderer.set_camera(Vec3::new(0.0, 5.0, -6.0), Vec3::ZERO);
+        let stats = renderer.render().unwrap();
+        // ground + two cube batches, drawn in 3 shadow cascades and the geometry pass, plus post
+        assert_eq!(stats.draw_calls, 13);
+        assert_eq!(stats.triangles, 2 + 9 * 12);
+        let frame = renderer.read_frame().unwrap().unwrap();
+        golden::compare_golden("renderer_instanced_meshes", &frame, 160, 120, Default::default()).unwrap();
+    }
+    
+    #[test]
+    fn test_scene_lights_come_from_light_components() {
+        use crate::unreal_framework::{AActor, UActorComponent};
+        
+        let mut world = UWorld::new("Lights");
+        let mut lamp = AActor::new(1, "Lamp");
+        lamp.add_component(UActorComponent::new(10, "Bulb", EComponentType::PointLight {
+            intensity: 4.0,
+            color: Vec3::new(1.0, 0.5, 0.2),
+            attenuation_radius: 3.0,
+        }));
+        lamp.set_actor_location(Vec3::new(1.0, 2.0, 3.0));
+        let mut switched_off = UActorComponent::new(11, "Spare", EComponentType::PointLight {
+            intensity: 1.0,
+            color: Vec3::ONE,
+            attenuation_radius: 1.0,
+        });
+        switched_off.is_active = false;
+        lamp.add_component(switched_off);
+        world.spawn_actor_direct(lamp);
+        
+        for id in [3, 2] {
+            let mut sun = AActor::new(id, "Sun");
+            sun.add_component(UActorComponent::new(id * 10, "Light", EComponentType::DirectionalLight { intensity: id as f32, color: Vec3::ONE }));
+            sun.set_actor_rotation(glam::Quat::from_rotation_z(-std::f32::consts::FRAC_PI_2));
+            world.spawn_actor_direct(sun);
+        }
+        
+        let lights = SceneLights::from_world(&world);
+        assert_eq!(lights.points.len(), 1);
+        assert_eq!(lights.points[0].position, [1.0, 2.0, 3.0]);
+        assert_eq!(lights.points[0].attenuation_radius, 3.0);
+        // Lowest actor id wins; forward (+X) pitched down to -Y
+        let sun = lights.directional.unwrap();
+        assert_eq!(sun.intensity, 2.0);
+        assert!(sun.direction.abs_diff_eq(Vec3::NEG_Y, 1e-5));
+    }
+    
+    #[test]
+    fn test_clustered_point_lights_match_golden() {
+        let config = RenderConfig { enable_post_processing: false, enable_ssao: false, ..golden_config() };
+        let Some((mut renderer, _scene)) = headless_renderer(config) else { return };
+        // 16x16 grid of small coloured lights over the ground, no sun
+        let points: Vec<_> = (0..256)
+            .map(|i| {
+                let (x, z) = ((i % 16) as f32 / 15.0 * 9.0 - 4.5, (i / 16) as f32 / 15.0 * 9.0 - 4.5);
+                let hue = i as f32 * 0.37;
+                let color = Vec3::new(hue.sin(), (hue + 2.1).sin(), (hue + 4.2).sin()) * 0.5 + 0.5;
+                PointLight::new(Vec3::new(x, 0.25, z), color, 1.5, 0.6)
+            })
+            .collect();
+        renderer.set_lights(&SceneLights { directional: None, points });
+        renderer.set_camera(Vec3::new(0.0, 5.0, -6.0), Vec3::ZERO);
+        
+        let stats = renderer.render().unwrap();
+        // light_cull, cull, shadow, geometry, hiz, post
+        assert_eq!(stats.render_passes, 6);
+        assert_eq!(stats.point_lights, 256);
+        assert_eq!(stats.draw_calls, 9);
+        let frame = renderer.read_frame().unwrap().unwrap();
+        golden::compare_golden("renderer_clustered_lights", &frame, 160, 120, Default::default()).unwrap();
+        
+        // Without lights the cull pass is skipped
+        renderer.set_lights(&SceneLights::default());
+        assert_eq!(renderer.render().unwrap().render_passes, 5);
+    }
+    
+    #[test]
+    fn test_render_config_reads_settings_json() {
+        let config = RenderConfig::from_settings_json(include_str!("../settings.json")).unwrap();
+        assert_eq!(config.shadows.cascade_count, 3);
+        assert_eq!(config.shadows.cascade_split_lambda, 0.95);
+        assert_eq!(config.shadows.resolution, 2048);
+        assert!(config.enable_taa);
+        assert_eq!(config.taa, TaaSettings::default());
+        assert_eq!(config.culling, CullingSettings::default());
+        
+        // Missing sections and fields fall back to the defaults
+        let config = RenderConfig::from_settings_json(r#"{"shadows": {"enabled": false}}"#).unwrap();
+        assert_eq!(config.shadows.active_cascades(), 0);
+        assert_eq!(config.shadows.bias, ShadowSettings::default().bias);
+        assert!(RenderConfig::from_settings_json("{").is_err());
+        
+        // TAA is only on for the "taa" anti-aliasing mode
+        let config = RenderConfig::from_settings_json(r#"{"rendering": {"anti_aliasing": {"mode": "fxaa"}}}"#).unwrap();
+        assert!(!config.enable_taa);
+        let config = RenderConfig::from_settings_json(r#"{"rendering": {"anti_aliasing": {"enabled": false, "mode": "taa"}}}"#).unwrap();
+        assert!(!config.enable_taa);
+        
+        // Occlusion culling needs the Hi-Z pyramid
+        let config = RenderConfig::from_settings_json(r#"{"rendering": {"culling": {"hierarchical_z": false}}}"#).unwrap();
+        assert!(config.culling.occlusion_culling && !config.culling.occlusion());
+        assert_eq!(config.culling.flags(), 0b011);
+    }
+    
+    #[test]
+    fn test_frustum_planes_bound_the_view() {
+        let view = Mat4::look_at_rh(Vec3::new(0.0, 2.0, -5.0), Vec3::ZERO, Vec3::Y);
+        let planes = frustum_planes(Mat4::perspective_rh(FOV_Y, 16.0 / 9.0, Z_NEAR, Z_FAR) * view);
+        let inside = |p: Vec3| planes.iter().all(|plane| plane.truncate().dot(p) + plane.w >= 0.0);
+        assert!(inside(Vec3::ZERO));
+        assert!(inside(Vec3::new(0.0, 2.0, -5.0 + Z_NEAR * 2.0)));
+        // Behind the camera, past the far plane, and off to the side
+        assert!(!inside(Vec3::new(0.0, 2.0, -6.0)));
+        assert!(!inside(Vec3::new(0.0, 2.0, Z_FAR * 1.2)));
+        assert!(!inside(Vec3::new(20.0, 0.0, 0.0)));
+        assert!(planes.iter().all(|plane| (plane.truncate().length() - 1.0).abs() < 1e-5));
+    }
+    
+    #[test]
+    fn test_taa_jitter_cycles_halton_offsets() {
+        let taa = TaaSettings { jitter_scale: 0.5, sample_count: 4, sharpness: 0.0 };
+        let offsets: Vec<Vec2> = (0..4).map(|frame| taa_jitter(frame, &taa)).collect();
+        assert!(offsets.iter().all(|o| o.abs().max_element() <= 0.25));
+        assert_eq!(offsets[0], Vec2::new(0.0, 1.0 / 3.0 - 0.5) * 0.5);
+        for (i, a) in offsets.iter().enumerate() {
+            assert!(offsets[i + 1..].iter().all(|b| a != b));
+        }
+        assert_eq!(taa_jitter(5, &taa), offsets[1]);
+        
+        // A zero sample count is treated as a single repeating sample
+        let still = TaaSettings { sample_count: 0, ..taa };
+        assert_eq!(taa_jitter(3, &still), taa_jitter(0, &still));
+    }
+    
+    #[test]
+    fn test_cascade_splits_blend_uniform_and_log() {
+        let splits = cascade_splits(0.1, 100.0, 3, 0.95);
+        assert!(splits.windows(2).all(|w| w[0] < w[1]));
+        assert!((splits[2] - 100.0).abs() < 1e-3);
+        // Log splits keep the first cascade close to the camera
+        assert!(splits[0] < 10.0);
+        
+        let uniform = cascade_splits(1.0, 91.0, 3, 0.0);
+        for (split, expected) in uniform.iter().zip([31.0, 61.0, 91.0]) {
+            assert!((split - expected).abs() < 1e-3);
+        }
+    }
+    
+    #[test]
+    fn test_cascades_snap_to_shadow_texels() {
+        let shadows = ShadowSettings { resolution: 1024, ..Default::default() };
+        let to_light = Vec3::new(0.3, 1.0, -0.2).normalize();
+        let project = |eye: Vec3| {
+            let view = Mat4::look_at_rh(eye, eye + Vec3::new(0.2, -0.3, -1.0), Vec3::Y);
+            compute_cascades(view, 16.0 / 9.0, to_light, &shadows)
+        };
+        let (a, b) = (project(Vec3::ZERO), project(Vec3::new(0.013, 0.0, -0.021)));
+        assert_eq!(a.len(), 3);
+        for (a, b) in a.iter().zip(&b) {
+            assert_eq!(a.texel, b.texel);
+            // A world point moves by whole shadow-map texels between frames
+            let texels = (b.view_proj.project_point3(Vec3::ZERO) - a.view_proj.project_point3(Vec3::ZERO)).truncate() * shadows.resolution as f32 * 0.5;
+            assert!((texels - texels.round()).abs().max_element() < 1e-2, "{texels}");
+        }
+        // Cascades grow with distance
+        assert!(a[0].texel < a[1].texel && a[1].texel < a[2].texel);
+    }
+    
+    #[test]
+    fn test_cascaded_shadows_match_golden() {
+        let config = RenderConfig { enable_post_processing: false, enable_ssao: false, ..golden_config() };
+        let Some((mut renderer, scene)) = headless_renderer(config) else { return };
+        let stride = std::mem::size_of::<SceneVertex>() as u64;
+        let (vertices, indices) = plane_mesh(40.0);
+        let ground = scene.resources.load_mesh(bytemuck::cast_slice(&vertices), bytemuck::cast_slice(&indices), stride, wgpu::IndexFormat::Uint16).unwrap();
+        // A row of cubes receding from the camera, each casting into a later cascade
+        let mut items = vec![DrawItem { mesh: ground, material: scene.ground_material, transform: Mat4::from_translation(Vec3::new(0.0, 0.0, -30.0)), motion: Vec2::ZERO, bounds: None }];
+        for i in 0..8 {
+            let z = -(i as f32).powf(1.6) * 2.5;
+            let transform = Mat4::from_translation(Vec3::new(if i % 2 == 0 { -1.2 } else { 1.2 }, 0.5, z));
+            items.push(DrawItem { mesh: scene.cube, material: scene.cube_material, transform, motion: Vec2::ZERO, bounds: None });
+        }
+        renderer.submit_draws(&scene.resources, &items);
+        renderer.set_camera(Vec3::new(0.0, 2.0, 4.0), Vec3::new(0.0, 0.0, -10.0));
+        
+        let stats = renderer.render().unwrap();
+        // ground + cube batch in 3 cascades and the geometry pass, plus post
+        assert_eq!(stats.draw_calls, 9);
+        let frame = renderer.read_frame().unwrap().unwrap();
+        golden::compare_golden("renderer_cascaded_shadows", &frame, 160, 120, Default::default()).unwrap();
+        
+        // Disabling shadows drops the shadow pass
+        let shadows = ShadowSettings { enabled: false, ..Default::default() };
+        let config = RenderConfig { enable_post_processing: false, enable_ssao: false, shadows, ..golden_config() };
+        let Some((mut renderer, _scene)) = headless_renderer(config) else { return };
+        assert_eq!(renderer.render().unwrap().render_passes, 4);
+    }
+    
+    #[test]
+    fn test_gpu_culling_drops_hidden_instances() {
+        let culling = CullingSettings { distance_culling_max: 20.0, ..Default::default() };
+        let config = RenderConfig { enable_post_processing: false, enable_ssao: false, culling: culling.clone(), ..golden_config() };
+        let Some((mut renderer, scene)) = headless_renderer(config.clone()) else { return };
+        let cube = |scene: &DemoScene, center: Vec3, size: Vec3| DrawItem {
+            mesh: scene.cube,
+            material: scene.cube_material,
+            transform: Mat4::from_scale_rotation_translation(size, glam::Quat::IDENTITY, center),
+            motion: Vec2::ZERO,
+            bounds: Some((center - size * 0.5, center + size * 0.5)),
+        };
+        // A wall with cubes in front (visible), behind it (occluded), behind
+        // the camera (outside the frustum) and past the distance limit
+        let items = |scene: &DemoScene| {
+            let mut items = vec![scene.items()[0], cube(scene, Vec3::new(0.0, 1.5, 0.0), Vec3::new(6.0, 3.0, 0.2))];
+            for x in [-1.5, -0.5, 0.5, 1.5] {
+                items.push(cube(scene, Vec3::new(x, 0.5, 3.0), Vec3::ONE));
+                items.push(cube(scene, Vec3::new(x, 0.5, -8.0), Vec3::ONE));
+            }
+            for x in [-1.5, 0.0, 1.5] {
+                items.push(cube(scene, Vec3::new(x, 0.5, -2.0), Vec3::ONE));
+            }
+            items.push(cube(scene, Vec3::new(-2.0, 0.5, 30.0), Vec3::ONE));
+            items.push(cube(scene, Vec3::new(2.0, 0.5, 30.0), Vec3::ONE));
+            items
+        };
+        assert_eq!(renderer.submit_draws(&scene.resources, &items(&scene)), 15);
+        
+        // Counts are read back a frame late; the first frame has no Hi-Z yet,
+        // so only frustum and distance culling apply
+        let mut culled = Vec::new();
+        for frame in 0..3 {
+            let stats = renderer.render().unwrap();
+            // ground + cube batch in 3 cascades and the geometry pass, then
+            // again in the late geometry pass once there is a Hi-Z to re-test
+            assert_eq!(stats.draw_calls, if frame == 0 { 9 } else { 11 });
+            culled.push(stats.instances_culled);
+            renderer.device.poll(wgpu::Maintain::Wait);
+        }
+        assert_eq!(culled, [0, 6, 10]);
+        let frame = renderer.read_frame().unwrap().unwrap();
+        
+        // Only distance culling changes the image
+        let reference = CullingSettings { frustum_culling: false, occlusion_culling: false, ..culling };
+        let Some((mut reference, reference_scene)) = headless_renderer(RenderConfig { culling: reference, ..config }) else { return };
+        reference.submit_draws(&reference_scene.resources, &items(&reference_scene));
+        for _ in 0..2 {
+            reference.render().unwrap();
+            reference.device.poll(wgpu::Maintain::Wait);
+        }
+        assert_eq!(reference.render().unwrap().instances_culled, 2);
+        assert!(frame == reference.read_frame().unwrap().unwrap());
+    }
+    
+    /// Pixel position of `point` under the renderer's default camera, unjittered
+    fn project_to_pixel(point: Vec3, width: u32, height: u32) -> Vec2 {
+        let view = Mat4::look_at_rh(Vec3::new(0.0, 2.0, -5.0), Vec3::new(0.0, 0.5, 0.0), Vec3::Y);
+        let proj = Mat4::perspective_rh(FOV_Y, width as f32 / height as f32, Z_NEAR, Z_FAR);
+        let ndc = (proj * view).project_point3(point);
+        Vec2::new((ndc.x * 0.5 + 0.5) * width as f32, (0.5 - ndc.y * 0.5) * height as f32)
+    }
+    
+    #[test]
+    fn test_taa_converges_and_rejects_stale_history() {
+        let config = RenderConfig { enable_post_processing: false, enable_ssao: false, enable_taa: true, ..golden_config() };
+        let Some((mut renderer, scene)) = headless_renderer(config.clone()) else { return };
+        for _ in 0..8 {
+            // cull, shadow, geometry, hiz, post, taa
+            assert_eq!(renderer.render().unwrap().render_passes, 6);
+        }
+        let (color, depth, _) = renderer.history_frame().unwrap();
+        assert_eq!((color.width(), color.height()), (160, 120));
+        assert_eq!(depth.format(), HISTORY_DEPTH_FORMAT);
+        let frame = renderer.read_frame().unwrap().unwrap();
+        golden::compare_golden("renderer_taa", &frame, 160, 120, Default::default()).unwrap();
+        
+        // Move the cube well clear of where it was; its motion vector plus the
+        // neighbourhood clamp must keep it from smearing over the ground
+        let (from, to) = (Vec3::new(0.0, 0.5, 0.0), Vec3::new(2.0, 0.5, 0.0));
+        let motion = project_to_pixel(to, 160, 120) - project_to_pixel(from, 160, 120);
+        let moved = |scene: &DemoScene| {
+            let mut items = scene.items();
+            items[1] = DrawItem { transform: Mat4::from_translation(to), motion, ..items[1] };
+            items
+        };
+        renderer.submit_draws(&scene.resources, &moved(&scene));
+        renderer.render().unwrap();
+        let frame = renderer.read_frame().unwrap().unwrap();
+        
+        let reference = RenderConfig { enable_taa: false, ..config };
+        let Some((mut reference, reference_scene)) = headless_renderer(reference) else { return };
+        reference.submit_draws(&reference_scene.resources, &moved(&reference_scene));
+        reference.render().unwrap();
+        let expected = reference.read_frame().unwrap().unwrap();
+        let old = project_to_pixel(from, 160, 120).as_uvec2();
+        let i = ((old.y * 160 + old.x) * 4) as usize;
+        for c in 0..3 {
+            assert!(frame[i + c].abs_diff(expected[i + c]) <= 12, "ghost at {:?}: {:?} vs {:?}", old, &frame[i..i + 4], &expected[i..i + 4]);
+        }
+        
+        // A tiled frame skips the resolve and drops the history
+        renderer.render_with(|_, _| Some(TileRenderPlan { screen_size: (160, 120), ..Default::default() })).unwrap();
+        assert!(renderer.history_frame().is_none());
+    }
+    
+    #[test]
+    fn test_taa_resolves_at_render_size_and_upscales() {
+        let config = RenderConfig { enable_post_processing: false, enable_ssao: false, enable_taa: true, resolution_scale: 0.5, ..golden_config() };
+        let Some((mut renderer, scene)) = headless_renderer(config.clone()) else { return };
+        for _ in 0..8 {
+            // cull, shadow, geometry, hiz, post, taa, taa_upscale
+            assert_eq!(renderer.render().unwrap().render_passes, 7);
+        }
+        let (color, depth, _) = renderer.history_frame().unwrap();
+        assert_eq!((color.width(), color.height()), (80, 60));
+        assert_eq!((depth.width(), depth.height()), (80, 60));
+        
+        // Motion stays in target pixels; the moved cube must not ghost
+        let (from, to) = (Vec3::new(0.0, 0.5, 0.0), Vec3::new(2.0, 0.5, 0.0));
+        let motion = project_to_pixel(to, 160, 120) - project_to_pixel(from, 160, 120);
+        let moved = |scene: &DemoScene| {
+            let mut items = scene.items();
+            items[1] = DrawItem { transform: Mat4::from_translation(to), motion, ..items[1] };
+            items
+        };
+        renderer.submit_draws(&scene.resources, &moved(&scene));
+        renderer.render().unwrap();
+        let frame = renderer.read_frame().unwrap().unwrap();
+        assert_eq!(frame.len(), 160 * 120 * 4);
+        
+        let Some((mut reference, reference_scene)) = headless_renderer(RenderConfig { enable_taa: false, ..config }) else { return };
+        reference.submit_draws(&reference_scene.resources, &moved(&reference_scene));
+        reference.render().unwrap();
+        let expected = reference.read_frame().unwrap().unwrap();
+        let old = project_to_pixel(from, 160, 120).as_uvec2();
+        let i = ((old.y * 160 + old.x) * 4) as usize;
+        for c in 0..3 {
+            assert!(frame[i + c].abs_diff(expected[i + c]) <= 12, "ghost at {:?}: {:?} vs {:?}", old, &frame[i..i + 4], &expected[i..i + 4]);
+        }
+    }
+    
+    /// The demo scene as the predictive renderer sees it, from the
+    /// renderer's default camera
+    fn predictive_scene() -> SceneSnapshot {
+        SceneSnapshot {
+            camera_position: Vec3::new(0.0, 2.0, -5.0),
+            camera_pitch: 0.0,
+            camera_yaw: 0.0,
+            screen_width: 160,
+            screen_height: 120,
+            entities: Vec::new(),
+            active_animations: HashMap::new(),
+            particle_systems: Vec::new(),
+            lighting_changes: Vec::new(),
+        }
+    }
+    
+    /// Render one reprojected frame, then hand its history to `predictive`
+    fn render_predictive(renderer: &mut Renderer, predictive: &mut PredictiveRenderer, scene: &SceneSnapshot) -> RenderStats {
+        let stats = renderer.render_reprojected(predictive, scene).unwrap();
+        if let Some((color, depth, view_proj)) = renderer.history_frame() {
+            predictive.push_frame(color, depth, view_proj);
+        }
+        stats
+    }
+    
+    /// `frame` must be close to a full render of the demo scene with TAA off
+    fn assert_matches_full_render(frame: &[u8], config: RenderConfig) {
+        let Some((mut reference, _reference_scene)) = headless_renderer(RenderConfig { enable_taa: false, ..config }) else { return };
+        reference.render().unwrap();
+        let expected = reference.read_frame().unwrap().unwrap();
+        let diff: u64 = frame.iter().zip(&expected).map(|(a, b)| a.abs_diff(*b) as u64).sum();
+        assert!(diff / (frame.len() as u64) < 4, "mean difference {}", diff as f64 / frame.len() as f64);
+    }
+    
+    #[test]
+    fn test_reprojected_frames_reuse_tiles_and_are_timed() {
+        use crate::headless::HEADLESS_FORMAT;
+        use crate::predictive_renderer::PredictiveRenderConfig;
+        
+        let config = RenderConfig { enable_post_processing: false, enable_ssao: false, enable_taa: true, ..golden_config() };
+        let Some((mut renderer, _scene)) = headless_renderer(config.clone()) else { return };
+        let device = renderer.device.clone();
+        let mut predictive = PredictiveRenderer::new(&device, PredictiveRenderConfig::default(), HEADLESS_FORMAT, 160, 120);
+        let scene = predictive_scene();
+        
+        let (mut tiled, mut timed) = (Vec::new(), 0);
+        for _ in 0..20 {
+            let stats = render_predictive(&mut renderer, &mut predictive, &scene);
+            if let Some(time) = stats.scene_gpu_time {
+                predictive.record_gpu_time(time.gpu_ms, !time.tiles_only);
+                timed += 1;
+            }
+            tiled.push(!predictive.get_stats().last_frame.full_refresh);
+            device.poll(wgpu::Maintain::Wait);
+        }
+        
+        // After the first refresh, a history frame is reused for
+        // `reuse_depth` frames before a full refresh renews it
+        let stats = predictive.get_stats();
+        let expected: Vec<bool> = (0..20).map(|frame| frame % 5 != 0).collect();
+        assert_eq!(tiled, expected, "{:?}", stats);
+        assert_eq!(stats.pixels_total, 20 * 160 * 120);
+        assert!(stats.pixels_skipped > 0, "{:?}", stats);
+        assert!(stats.last_frame.pixels_rendered < stats.last_frame.pixels_total / 2, "{:?}", stats);
+        // Validation tiles were measured in the finished tile frames
+        assert!(stats.measured_error > 0.0, "{:?}", stats);
+        if device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            assert!(timed > 0);
+            assert_eq!(stats.timed_frames, timed);
+        }
+        
+        // The last frame was tiled; it must match a full render of the scene
+        let frame = renderer.read_frame().unwrap().unwrap();
+        assert_matches_full_render(&frame, config);
+    }
+    
+    #[test]
+    fn test_tile_plan_scales_to_render_size() {
+        use crate::predictive_renderer::ScissorRect;
+        
+        let config = RenderConfig { enable_post_processing: false, enable_ssao: false, resolution_scale: 0.5, ..golden_config() };
+        let Some((mut renderer, _scene)) = headless_renderer(config) else { return };
+        renderer.render().unwrap();
+        let full = renderer.read_frame().unwrap().unwrap();
+        
+        // Target-sized rects, one ending past the screen; unscaled they would
+        // be out of bounds of the 80x60 geometry attachment
+        let rects = vec![
+            ScissorRect { x: 0, y: 0, width: 81, height: 120 },
+            ScissorRect { x: 81, y: 0, width: 100, height: 130 },
+        ];
+        let plan = TileRenderPlan { rects, screen_size: (160, 120), ..Default::default() };
+        renderer.render_with(|_, _| Some(plan)).unwrap();
+        let tiled = renderer.read_frame().unwrap().unwrap();
+        assert!(full == tiled, "tiled frame differs from the full frame");
+    }
+    
+    #[test]
+    fn test_predictive_history_shares_taa_history_at_render_size() {
+        use crate::headless::HEADLESS_FORMAT;
+        use crate::predictive_renderer::PredictiveRenderConfig;
+        
+        let config = RenderConfig { enable_post_processing: false, enable_ssao: false, enable_taa: true, resolution_scale: 0.5, ..golden_config() };
+        let Some((mut renderer, _scene)) = headless_renderer(config.clone()) else { return };
+        let mut predictive = PredictiveRenderer::new(&renderer.device, PredictiveRenderConfig::default(), HEADLESS_FORMAT, 160, 120);
+        let scene = predictive_scene();
+        
+        render_predictive(&mut renderer, &mut predictive, &scene);
+        assert!(predictive.get_stats().last_frame.full_refresh);
+        
+        // The history is the 80x60 TAA texture itself, held by the renderer,
+        // the predictive renderer and this reference
+        let (color, _, _) = renderer.history_frame().unwrap();
+        assert_eq!((color.width(), color.height()), (80, 60));
+        assert_eq!(Arc::strong_count(&color), 3);
+        drop(color);
+        
+        // and is reprojected into the 160x120 target
+        render_predictive(&mut renderer, &mut predictive, &scene);
+        let stats = predictive.get_stats();
+        assert!(!stats.last_frame.full_refresh, "{:?}", stats);
+        
+        let frame = renderer.read_frame().unwrap().unwrap();
+        assert_matches_full_render(&frame, config);
+    }
+    
+    #[test]
+    fn test_predictive_history_without_taa() {
+        use crate::headless::HEADLESS_FORMAT;
+        use crate::predictive_renderer::PredictiveRenderConfig;
+        
+        let config = RenderConfig { enable_post_processing: false, enable_ssao: false, enable_taa: false, ..golden_config() };
+        let Some((mut renderer, _scene)) = headless_renderer(config.clone()) else { return };
+        let mut predictive = PredictiveRenderer::new(&renderer.device, PredictiveRenderConfig::default(), HEADLESS_FORMAT, 160, 120);
+        let scene = predictive_scene();
+        
+        // Plain frames keep no history
+        renderer.render().unwrap();
+        assert!(renderer.history_frame().is_none());
+        
+        // A full reprojected frame is kept as history:
+        // cull, shadow, geometry, hiz, post, history_depth_copy, history_present
+        let stats = render_predictive(&mut renderer, &mut predictive, &scene);
+        assert_eq!(stats.render_passes, 7);
+        assert!(predictive.get_stats().last_frame.full_refresh);
+        let (color, depth, _) = renderer.history_frame().unwrap();
+        assert_eq!((color.width(), color.height()), (160, 120));
+        assert_eq!(depth.format(), HISTORY_DEPTH_FORMAT);
+        let frame = renderer.read_frame().unwrap().unwrap();
+        assert_matches_full_render(&frame, config.clone());
+        drop((color, depth));
+        
+        // so later ones reproject it and, once the predictor settles, redraw
+        // only their hot tiles
+        for _ in 1..15 {
+            render_predictive(&mut renderer, &mut predictive, &scene);
+            renderer.device.poll(wgpu::Maintain::Wait);
+        }
+        let stats = predictive.get_stats();
+        assert!(!stats.last_frame.full_refresh, "{:?}", stats);
+        assert!(stats.last_frame.pixels_rendered < stats.last_frame.pixels_total / 2, "{:?}", stats);
+        let frame = renderer.read_frame().unwrap().unwrap();
+        assert_matches_full_render(&frame, config);
+    }
+}
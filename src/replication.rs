@@ -0,0 +1,561 @@
+// src/replication.rs
+//! Actor property replication between `UWorld`s over `NetworkSystem`
+//!
+//! - Server: actors with `replicates` set have their root transform and
+//!   every `Replicated`/`RepNotify` property serialized into a canonical
+//!   state. Each change bumps the entity's `ReplicatedState`, and peers receive
+//!   XOR deltas against the state they last acknowledged.
+//! - Client: updates are applied to the local world (spawning proxies as
+//!   needed), acknowledged, and OnRep callbacks run for changed `RepNotify`
+//!   properties
+//! - Ownership comes from the network's `authority_map`
+//! - Every incarnation of an actor id gets a new epoch. Clients keep a
+//!   tombstone per destroyed id so late unreliable updates can't resurrect it,
+//!   until the packets that could carry them have left the ack window.
+
+use std::collections::{HashMap, HashSet};
+
+use glam::{Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::network::{ChannelType, NetworkError, NetworkMessage, NetworkSystem, ReplicatedState};
+use crate::unreal_framework::{
+    ACharacter, AActor, EComponentType, FTransform, UActorComponent, UValue, UWorld,
+};
+
+/// First byte of every replication message, so the protocol can share
+/// channels with game traffic
+const REPLICATION_TAG: u8 = 0xA7;
+
+// ============================================================================
+// WIRE FORMAT
+// ============================================================================
+
+/// Everything replicated for one actor. Properties are sorted by name so
+/// unchanged actors serialize to identical bytes and deltas stay small.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct ActorRepState {
+    name: String,
+    is_character: bool,
+    owner: Option<u64>,
+    location: Vec3,
+    rotation: Quat,
+    scale: Vec3,
+    properties: Vec<RepProperty>,
+}
+
+/// One replicated property. `notify` carries the `RepNotify` flag, since
+/// client proxies have no property metadata of their own.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct RepProperty {
+    name: String,
+    value: UValue,
+    notify: bool,
+}
+
+impl ActorRepState {
+    fn capture(actor: &AActor, is_character: bool, owner: Option<u64>) -> Self {
+        let root = root_transform(actor);
+        let mut properties: Vec<RepProperty> = actor
+            .property_metadata
+            .values()
+            .filter(|p| p.is_replicated())
+            .filter_map(|p| {
+                let value = actor.custom_properties.get(&p.name)?.clone();
+                Some(RepProperty { name: p.name.clone(), value, notify: p.is_rep_notify() })
+            })
+            .collect();
+        properties.sort_by(|a, b| a.name.cmp(&b.name));
+        Self {
+            name: actor.name.clone(),
+            is_character,
+            owner,
+            location: root.location,
+            rotation: root.rotation,
+            scale: root.scale,
+            properties,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum ReplicationMessage {
+    Update { epoch: u32, state: ReplicatedState },
+    Destroy { actor_id: u64, epoch: u32 },
+    Ack { actor_id: u64, epoch: u32, sequence: u32 },
+}
+
+impl ReplicationMessage {
+    fn encode(&self) -> Vec<u8> {
+        let mut data = vec![REPLICATION_TAG];
+        data.extend(bincode::serialize(self).unwrap_or_default());
+        data
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        match data.split_first() {
+            Some((&REPLICATION_TAG, rest)) => bincode::deserialize(rest).ok(),
+            _ => None,
+        }
+    }
+
+    fn into_message(self, channel: ChannelType) -> NetworkMessage {
+        NetworkMessage {
+            id: 0,
+            channel,
+            data: self.encode(),
+            timestamp: 0,
+            sequence: 0,
+        }
+    }
+}
+
+// ============================================================================
+// SERVER
+// ============================================================================
+
+/// Server side: gathers actor state each tick and feeds it to peers
+#[derive(Default)]
+pub struct ReplicationServer {
+    /// Last serialized state of each replicated actor
+    states: HashMap<u64, Vec<u8>>,
+    /// Incarnation of each actor id seen so far, bumped when a destroyed id
+    /// is replicated again
+    epochs: HashMap<u64, u32>,
+    pending_destroys: Vec<(u64, u32)>,
+}
+
+impl ReplicationServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn replicated_actor_count(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Capture every replicated actor in `world`. New actors are registered
+    /// with the network, changed ones get a new state sequence and destroyed
+    /// ones are scheduled for removal on clients. Returns how many changed.
+    pub fn gather(&mut self, world: &UWorld, network: &mut NetworkSystem) -> usize {
+        let mut seen = HashSet::new();
+        let mut changed = 0;
+
+        let actors = world.actors.values().map(|a| (a, false))
+            .chain(world.characters.values().map(|c| (&c.base_actor, true)));
+        for (actor, is_character) in actors {
+            if !actor.replicates || actor.is_pending_kill {
+                continue;
+            }
+            seen.insert(actor.id);
+
+            let state = ActorRepState::capture(actor, is_character, network.entity_owner(actor.id));
+            let Ok(bytes) = bincode::serialize(&state) else {
+                continue;
+            };
+            match self.states.get(&actor.id) {
+                Some(previous) if *previous == bytes => continue,
+                Some(_) => network.update_entity_state(actor.id, bytes.clone()),
+                None => {
+                    self.epochs.entry(actor.id).and_modify(|epoch| *epoch += 1).or_insert(0);
+                    network.register_replicated_entity(actor.id, bytes.clone(), state.owner);
+                }
+            }
+            self.states.insert(actor.id, bytes);
+            changed += 1;
+        }
+
+        let gone: Vec<u64> = self.states.keys().filter(|id| !seen.contains(id)).copied().collect();
+        for actor_id in gone {
+            self.states.remove(&actor_id);
+            network.unregister_replicated_entity(actor_id);
+            self.pending_destroys.push((actor_id, self.epochs[&actor_id]));
+        }
+        changed
+    }
+
+    /// Queue updates for every peer that has not acknowledged an actor's
+    /// latest state, plus any destroys. Returns the number of messages queued.
+    pub fn send_updates(&mut self, network: &NetworkSystem) -> usize {
+        let peers = network.connected_peers();
+        let destroys = std::mem::take(&mut self.pending_destroys);
+        let mut sent = 0;
+
+        for peer_id in peers {
+            for &(actor_id, epoch) in &destroys {
+                let msg = ReplicationMessage::Destroy { actor_id, epoch };
+                match network.send_message(peer_id, msg.into_message(ChannelType::ReliableOrdered)) {
+                    Ok(()) => sent += 1,
+                    Err(e) => log::warn!("Replication: destroy of actor {} for peer {} dropped: {}", actor_id, peer_id, e),
+                }
+            }
+            for actor_id in self.states.keys() {
+                if !network.needs_entity_update(peer_id, *actor_id) {
+                    continue;
+                }
+                if let Some(update) = network.build_entity_update(peer_id, *actor_id) {
+                    // Unacked updates are rebuilt every tick, so loss only delays them
+                    let msg = ReplicationMessage::Update { epoch: self.epochs[actor_id], state: update };
+                    match network.send_message(peer_id, msg.into_message(ChannelType::UnreliableUnordered)) {
+                        Ok(()) => sent += 1,
+                        Err(e) => log::warn!("Replication: update of actor {} for peer {} dropped: {}", actor_id, peer_id, e),
+                    }
+                }
+            }
+        }
+        sent
+    }
+
+    /// Consume a client's replication message. Returns false if `data` is
+    /// not part of the replication protocol.
+    pub fn handle_message(&mut self, network: &NetworkSystem, peer_id: u64, data: &[u8]) -> bool {
+        match ReplicationMessage::decode(data) {
+            Some(ReplicationMessage::Ack { actor_id, epoch, sequence }) => {
+                // Acks for an earlier incarnation would point deltas at the wrong baseline
+                if self.states.contains_key(&actor_id) && self.epochs.get(&actor_id) == Some(&epoch) {
+                    network.acknowledge_entity_state(peer_id, actor_id, sequence);
+                }
+                true
+            }
+            Some(_) => {
+                log::debug!("Replication: ignoring server-only message from peer {}", peer_id);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// ============================================================================
+// CLIENT
+// ============================================================================
+
+/// Callback run on a client after a replicated property changes. Receives
+/// the actor (already holding the new value) and the previous value.
+pub type RepNotifyFn = Box<dyn FnMut(&mut AActor, &UValue) + Send>;
+
+/// Last state applied to one actor and the incarnation it belongs to
+struct AppliedActor {
+    epoch: u32,
+    sequence: u32,
+    state: ActorRepState,
+}
+
+struct Tombstone {
+    epoch: u32,
+    /// Server packet sequence once the destroy was seen; filled in on the
+    /// next `flush_acks`
+    packet_sequence: Option<u16>,
+}
+
+/// Client side: applies server state to the local world
+#[derive(Default)]
+pub struct ReplicationClient {
+    applied: HashMap<u64, AppliedActor>,
+    /// Newest destroyed epoch of each actor id; updates at or below it are stale
+    tombstones: HashMap<u64, Tombstone>,
+    on_rep: HashMap<String, Vec<RepNotifyFn>>,
+    pending_acks: Vec<(u64, u32, u32)>,
+}
+
+impl ReplicationClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `callback` whenever `property` changes on any replicated actor
+    /// that declares it `RepNotify` (its OnRep_<Property>). Changes to plain
+    /// `Replicated` properties of the same name don't trigger it.
+    pub fn on_rep(&mut self, property: &str, callback: impl FnMut(&mut AActor, &UValue) + Send + 'static) {
+        self.on_rep.entry(property.to_string()).or_default().push(Box::new(callback));
+    }
+
+    /// Owning peer of a replicated actor, as seen by the server
+    pub fn owner_of(&self, actor_id: u64) -> Option<u64> {
+        self.applied.get(&actor_id).and_then(|applied| applied.state.owner)
+    }
+
+    /// Apply a server replication message to `world`. Returns Ok(false) if
+    /// `data` is not part of the replication protocol.
+    pub fn handle_message(&mut self, network: &NetworkSystem, world: &mut UWorld, data: &[u8]) -> Result<bool, NetworkError> {
+        match ReplicationMessage::decode(data) {
+            Some(ReplicationMessage::Update { epoch, state }) => {
+                self.apply_update(network, world, epoch, &state)?;
+                Ok(true)
+            }
+            Some(ReplicationMessage::Destroy { actor_id, epoch }) => {
+                let dead = self.tombstones.entry(actor_id).or_insert(Tombstone { epoch, packet_sequence: None });
+                dead.epoch = dead.epoch.max(epoch);
+                dead.packet_sequence = None;
+                // A newer incarnation may already have overtaken this destroy
+                if self.applied.get(&actor_id).is_none_or(|applied| applied.epoch <= epoch) {
+                    self.remove_actor(network, world, actor_id);
+                }
+                Ok(true)
+            }
+            Some(ReplicationMessage::Ack { .. }) => Ok(true),
+            None => Ok(false),
+        }
+    }
+
+    /// Send acknowledgements for everything applied since the last flush, and
+    /// forget tombstones no late update can reach any more
+    pub fn flush_acks(&mut self, network: &NetworkSystem, server_peer: u64) -> Result<(), NetworkError> {
+        let newest = network.remote_packet_sequence(server_peer);
+        self.tombstones.retain(|_, dead| match dead.packet_sequence {
+            Some(sequence) => !network.packet_window_passed(server_peer, sequence),
+            None => {
+                dead.packet_sequence = newest;
+                true
+            }
+        });
+        for (actor_id, epoch, sequence) in self.pending_acks.drain(..) {
+            let msg = ReplicationMessage::Ack { actor_id, epoch, sequence };
+            network.send_message(server_peer, msg.into_message(ChannelType::UnreliableUnordered))?;
+        }
+        Ok(())
+    }
+
+    fn apply_update(&mut self, network: &NetworkSystem, world: &mut UWorld, epoch: u32, update: &ReplicatedState) -> Result<(), NetworkError> {
+        let actor_id = update.entity_id;
+        if self.tombstones.get(&actor_id).is_some_and(|dead| epoch <= dead.epoch) {
+            log::debug!("Replication: ignoring late update for destroyed actor {}", actor_id);
+            return Ok(());
+        }
+        match self.applied.get(&actor_id).map(|applied| applied.epoch) {
+            Some(current) if current > epoch => return Ok(()),
+            // Reused id whose destroy is still in flight: start the new incarnation clean
+            Some(current) if current < epoch => self.remove_actor(network, world, actor_id),
+            _ => {}
+        }
+
+        let full = network.apply_entity_update(update)?;
+        self.pending_acks.push((actor_id, epoch, update.sequence_number));
+
+        if self.applied.get(&actor_id).is_some_and(|applied| applied.sequence >= update.sequence_number) {
+            return Ok(());
+        }
+        let state: ActorRepState = bincode::deserialize(&full).map_err(|_| NetworkError::SerializationError)?;
+
+        if !world.actors.contains_key(&actor_id) && !world.characters.contains_key(&actor_id) {
+            spawn_proxy(world, actor_id, &state);
+        }
+        let actor = match world.characters.get_mut(&actor_id) {
+            Some(character) => &mut character.base_actor,
+            None => world.actors.get_mut(&actor_id).expect("proxy spawned above"),
+        };
+
+        set_root_transform(actor, FTransform {
+            location: state.location,
+            rotation: state.rotation,
+            scale: state.scale,
+        });
+        for property in &state.properties {
+            let old = actor.custom_properties.insert(property.name.clone(), property.value.clone()).unwrap_or(UValue::None);
+            if !property.notify || old == property.value {
+                continue;
+            }
+            if let Some(callbacks) = self.on_rep.get_mut(&property.name) {
+                for callback in callbacks.iter_mut() {
+                    callback(actor, &old);
+                }
+            }
+        }
+
+        self.applied.insert(actor_id, AppliedActor { epoch, sequence: update.sequence_number, state });
+        Ok(())
+    }
+
+    fn remove_actor(&mut self, network: &NetworkSystem, world: &mut UWorld, actor_id: u64) {
+        self.applied.remove(&actor_id);
+        network.unregister_replicated_entity(actor_id);
+        world.actors.remove(&actor_id);
+        world.characters.remove(&actor_id);
+    }
+}
+
+fn spawn_proxy(world: &mut UWorld, actor_id: u64, state: &ActorRepState) {
+    if state.is_character {
+        let mut character = ACharacter::new(actor_id, state.name.clone());
+        character.base_actor.replicates = true;
+        world.characters.insert(actor_id, character);
+    } else {
+        let mut actor = AActor::new(actor_id, state.name.clone());
+        actor.replicates = true;
+        let root_id = actor.next_component_id();
+        actor.add_component(UActorComponent::new(root_id, "DefaultSceneRoot", EComponentType::Scene));
+        world.spawn_actor_direct(actor);
+    }
+}
+
+fn root_transform(actor: &AActor) -> FTransform {
+    actor
+        .root_component_id
+        .and_then(|id| actor.components.get(&id))
+        .map(|c| c.world_transform)
+        .unwrap_or_default()
+}
+
+fn set_root_transform(actor: &mut AActor, transform: FTransform) {
+    if let Some(root) = actor.root_component_id.and_then(|id| actor.components.get_mut(&id)) {
+        root.relative_transform = transform;
+        root.world_transform = transform;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::test_support::connected_pair;
+    use crate::unreal_framework::{PropertyFlags, UProperty};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// One server/client frame: gather, send, receive, apply, ack
+    fn exchange(
+        rep_server: &mut ReplicationServer,
+        server_world: &UWorld,
+        server: &mut NetworkSystem,
+        rep_client: &mut ReplicationClient,
+        client_world: &mut UWorld,
+        client: &NetworkSystem,
+        server_peer: u64,
+    ) {
+        rep_server.gather(server_world, server);
+        rep_server.send_updates(server);
+        for _ in 0..5 {
+            server.pump().unwrap();
+            client.pump().unwrap();
+            for (_, msg) in client.drain_received() {
+                assert!(rep_client.handle_message(client, client_world, &msg.data).unwrap());
+            }
+            rep_client.flush_acks(client, server_peer).unwrap();
+            client.pump().unwrap();
+            server.pump().unwrap();
+            for (peer, msg) in server.drain_received() {
+                assert!(rep_server.handle_message(server, peer, &msg.data));
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+    }
+
+    #[test]
+    fn test_actor_properties_replicate_with_on_rep() {
+        let (mut server, client, server_peer) = connected_pair();
+        let client_peer = server.connected_peers()[0];
+
+        let mut server_world = UWorld::new("Server");
+        let mut door = AActor::new(500, "Door");
+        door.add_component(UActorComponent::new(5001, "Root", EComponentType::Scene));
+        door.replicates = true;
+        door.declare_property(UProperty {
+            name: "Health".to_string(),
+            category: "Gameplay".to_string(),
+            flags: vec![PropertyFlags::RepNotify],
+            default_value: UValue::Float(100.0),
+        });
+        door.custom_properties.insert("ServerOnly".to_string(), UValue::Integer(7));
+        server_world.spawn_actor_direct(door);
+        let mut hidden = AActor::new(501, "NotReplicated");
+        hidden.add_component(UActorComponent::new(5011, "Root", EComponentType::Scene));
+        server_world.spawn_actor_direct(hidden);
+        server.set_entity_owner(500, Some(client_peer));
+
+        let mut rep_server = ReplicationServer::new();
+        let mut rep_client = ReplicationClient::new();
+        let notified = Arc::new(Mutex::new(Vec::new()));
+        let log = notified.clone();
+        rep_client.on_rep("Health", move |actor, old| {
+            log.lock().unwrap().push((actor.id, old.clone(), actor.custom_properties["Health"].clone()));
+        });
+        let mut client_world = UWorld::new("Client");
+
+        exchange(&mut rep_server, &server_world, &mut server, &mut rep_client, &mut client_world, &client, server_peer);
+        assert_eq!(rep_server.replicated_actor_count(), 1);
+        let proxy = &client_world.actors[&500];
+        assert_eq!(proxy.custom_properties["Health"], UValue::Float(100.0));
+        assert!(!proxy.custom_properties.contains_key("ServerOnly"));
+        assert!(!client_world.actors.contains_key(&501));
+        assert_eq!(rep_client.owner_of(500), Some(client_peer));
+        assert!(!server.needs_entity_update(client_peer, 500));
+
+        // Change a property and move the actor: a delta goes out, OnRep fires once
+        let door = server_world.actors.get_mut(&500).unwrap();
+        door.custom_properties.insert("Health".to_string(), UValue::Float(25.0));
+        door.set_actor_location(Vec3::new(10.0, 0.0, 5.0));
+        exchange(&mut rep_server, &server_world, &mut server, &mut rep_client, &mut client_world, &client, server_peer);
+        assert_eq!(client_world.actors[&500].get_actor_location(), Vec3::new(10.0, 0.0, 5.0));
+        assert_eq!(*notified.lock().unwrap(), vec![
+            (500, UValue::None, UValue::Float(100.0)),
+            (500, UValue::Float(100.0), UValue::Float(25.0)),
+        ]);
+        assert!(server.entity_replication_costs()[0].delta_updates >= 1);
+
+        // Nothing changed: no traffic for acked actors
+        assert_eq!(rep_server.gather(&server_world, &mut server), 0);
+        assert_eq!(rep_server.send_updates(&server), 0);
+
+        // An unreliable update still in flight when the destroy lands must not respawn the actor
+        let late = ReplicationMessage::Update { epoch: 0, state: server.build_entity_update(client_peer, 500).unwrap() };
+        server_world.actors.remove(&500);
+        exchange(&mut rep_server, &server_world, &mut server, &mut rep_client, &mut client_world, &client, server_peer);
+        assert!(client_world.actors.is_empty());
+        assert_eq!(rep_client.owner_of(500), None);
+        assert!(rep_client.handle_message(&client, &mut client_world, &late.encode()).unwrap());
+        assert!(client_world.actors.is_empty());
+
+        // Reusing the id starts a new epoch that the tombstone lets through
+        let mut door = AActor::new(500, "Door");
+        door.add_component(UActorComponent::new(5001, "Root", EComponentType::Scene));
+        door.replicates = true;
+        server_world.spawn_actor_direct(door);
+        exchange(&mut rep_server, &server_world, &mut server, &mut rep_client, &mut client_world, &client, server_peer);
+        assert_eq!(client_world.actors[&500].name, "Door");
+        assert!(!server.needs_entity_update(client_peer, 500));
+
+        // Once the ack window has moved past the destroy, no late update can
+        // arrive and the tombstone is dropped
+        assert_eq!(rep_client.tombstones.len(), 1);
+        for _ in 0..40 {
+            let filler = NetworkMessage { id: 0, channel: ChannelType::UnreliableUnordered, data: vec![0], timestamp: 0, sequence: 0 };
+            server.send_message(client_peer, filler).unwrap();
+            server.pump().unwrap();
+            client.pump().unwrap();
+            client.drain_received();
+            rep_client.flush_acks(&client, server_peer).unwrap();
+        }
+        assert!(rep_client.tombstones.is_empty());
+    }
+
+    #[test]
+    fn test_on_rep_skips_plain_replicated_properties() {
+        let (mut server, client, server_peer) = connected_pair();
+
+        let mut server_world = UWorld::new("Server");
+        let mut crate_actor = AActor::new(600, "Crate");
+        crate_actor.add_component(UActorComponent::new(6001, "Root", EComponentType::Scene));
+        crate_actor.replicates = true;
+        crate_actor.declare_property(UProperty {
+            name: "Ammo".to_string(),
+            category: "Gameplay".to_string(),
+            flags: vec![PropertyFlags::Replicated],
+            default_value: UValue::Integer(30),
+        });
+        server_world.spawn_actor_direct(crate_actor);
+
+        let mut rep_server = ReplicationServer::new();
+        let mut rep_client = ReplicationClient::new();
+        let fired = Arc::new(Mutex::new(0));
+        let count = fired.clone();
+        rep_client.on_rep("Ammo", move |_, _| *count.lock().unwrap() += 1);
+        let mut client_world = UWorld::new("Client");
+
+        exchange(&mut rep_server, &server_world, &mut server, &mut rep_client, &mut client_world, &client, server_peer);
+        server_world.actors.get_mut(&600).unwrap().custom_properties.insert("Ammo".to_string(), UValue::Integer(12));
+        exchange(&mut rep_server, &server_world, &mut server, &mut rep_client, &mut client_world, &client, server_peer);
+
+        // The value still replicates, but only RepNotify properties run OnRep
+        assert_eq!(client_world.actors[&600].custom_properties["Ammo"], UValue::Integer(12));
+        assert_eq!(*fired.lock().unwrap(), 0);
+    }
+}
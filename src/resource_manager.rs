@@ -181,9 +181,10 @@ pub struct ResourceManager {
     texture_views: RwLock<Vec<Option<Arc<wgpu::TextureView>>>>,
     texture_samplers: RwLock<Vec<Option<Arc<wgpu::Sampler>>>>,
     
-    mesh_vertex_buffers: RwLock<Vec<Option<wgpu::Buffer>>>,
-    mesh_index_buffers: RwLock<Vec<Option<wgpu::Buffer>>>,
+    mesh_vertex_buffers: RwLock<Vec<Option<Arc<wgpu::Buffer>>>>,
+    mesh_index_buffers: RwLock<Vec<Option<Arc<wgpu::Buffer>>>>,
     mesh_index_counts: RwLock<Vec<u32>>,
+    mesh_layouts: RwLock<Vec<(u64, wgpu::IndexFormat)>>,
     
     material_buffers: RwLock<Vec<Option<wgpu::Buffer>>>,
     material_textures: RwLock<Vec<MaterialTextureHandles>>,
@@ -215,6 +216,17 @@ struct MaterialTextureHandles {
     ao: Option<Handle>,
 }
 
+/// GPU buffers of a loaded mesh, shared so draws can be recorded without
+/// holding the manager's locks
+#[derive(Clone)]
+pub struct MeshBuffers {
+    pub vertex_buffer: Arc<wgpu::Buffer>,
+    pub index_buffer: Arc<wgpu::Buffer>,
+    pub index_count: u32,
+    pub index_format: wgpu::IndexFormat,
+    pub vertex_stride: u64,
+}
+
 // ---------- BindGroup cache key ----------
 #[derive(Hash, PartialEq, Eq)]
 struct BindGroupKey {
@@ -230,33 +242,37 @@ impl ResourceManager {
         let mut mesh_pool = HandlePool::new(cfg.max_mesh_handles);
         let mut material_pool = HandlePool::new(cfg.max_material_handles);
         
-        // Create dummy texture
-        let dummy_tex = Self::create_dummy_texture(&device, &queue);
+        // Per-slot storage covers every pre-allocated handle
+        let (textures, meshes, materials) = (cfg.max_texture_handles, cfg.max_mesh_handles, cfg.max_material_handles);
         
-        let rm = Self {
+        let mut rm = Self {
             device,
             queue,
             cfg,
             texture_pool: RwLock::new(texture_pool),
             mesh_pool: RwLock::new(mesh_pool),
             material_pool: RwLock::new(material_pool),
-            texture_gens: RwLock::new(Vec::with_capacity(256)),
-            mesh_gens: RwLock::new(Vec::with_capacity(128)),
-            material_gens: RwLock::new(Vec::with_capacity(256)),
-            texture_views: RwLock::new(Vec::with_capacity(256)),
-            texture_samplers: RwLock::new(Vec::with_capacity(256)),
-            mesh_vertex_buffers: RwLock::new(Vec::with_capacity(128)),
-            mesh_index_buffers: RwLock::new(Vec::with_capacity(128)),
-            mesh_index_counts: RwLock::new(Vec::with_capacity(128)),
-            material_buffers: RwLock::new(Vec::with_capacity(256)),
-            material_textures: RwLock::new(Vec::with_capacity(256)),
+            texture_gens: RwLock::new(vec![0; textures]),
+            mesh_gens: RwLock::new(vec![0; meshes]),
+            material_gens: RwLock::new(vec![0; materials]),
+            texture_views: RwLock::new(vec![None; textures]),
+            texture_samplers: RwLock::new(vec![None; textures]),
+            mesh_vertex_buffers: RwLock::new(vec![None; meshes]),
+            mesh_index_buffers: RwLock::new(vec![None; meshes]),
+            mesh_index_counts: RwLock::new(vec![0; meshes]),
+            mesh_layouts: RwLock::new(vec![(0, wgpu::IndexFormat::Uint16); meshes]),
+            material_buffers: RwLock::new((0..materials).map(|_| None).collect()),
+            material_textures: RwLock::new((0..materials).map(|_| MaterialTextureHandles { base: None, mr: None, normal: None, ao: None }).collect()),
             texture_hash_map: RwLock::new(FxHashMap::default()),
             texture_lru: Mutex::new(LruCache::new(NonZeroUsize::new(1024).unwrap())),
             current_texture_bytes: Mutex::new(0),
             upload_queue: Mutex::new(VecDeque::with_capacity(32)),
             staging_pool: Mutex::new(Vec::with_capacity(4)),
-            dummy_texture: dummy_tex,
+            dummy_texture: Handle::invalid(),
         };
+        
+        // White texture standing in for missing material textures
+        rm.dummy_texture = rm.create_dummy_texture();
 
         rm
     }
@@ -377,6 +393,7 @@ impl ResourceManager {
                 self.mesh_vertex_buffers.write().push(None);
                 self.mesh_index_buffers.write().push(None);
                 self.mesh_index_counts.write().push(0);
+                self.mesh_layouts.write().push((0, index_format));
                 idx
             });
             
@@ -404,13 +421,37 @@ impl ResourceManager {
         }) as u32;
 
         // Store in pools
-        self.mesh_vertex_buffers.write()[idx] = Some(vb);
-        self.mesh_index_buffers.write()[idx] = Some(ib);
+        self.mesh_vertex_buffers.write()[idx] = Some(Arc::new(vb));
+        self.mesh_index_buffers.write()[idx] = Some(Arc::new(ib));
         self.mesh_index_counts.write()[idx] = index_count;
+        self.mesh_layouts.write()[idx] = (vertex_stride, index_format);
 
         Ok(Handle::new(idx as u32, gen))
     }
 
+    /// Get mesh buffers for drawing.
+    #[inline(always)]
+    pub fn get_mesh(&self, h: Handle) -> Option<MeshBuffers> {
+        if !h.is_valid() { return None; }
+        
+        let idx = h.index();
+        
+        let vertex_buffers = self.mesh_vertex_buffers.read();
+        let index_buffers = self.mesh_index_buffers.read();
+        
+        if idx >= vertex_buffers.len() { return None; }
+        
+        let (vertex_stride, index_format) = self.mesh_layouts.read()[idx];
+        
+        Some(MeshBuffers {
+            vertex_buffer: Arc::clone(vertex_buffers[idx].as_ref()?),
+            index_buffer: Arc::clone(index_buffers[idx].as_ref()?),
+            index_count: self.mesh_index_counts.read()[idx],
+            index_format,
+            vertex_stride,
+        })
+    }
+
     /// Create material record
     pub fn create_material(&self, params_bytes: &[u8], base: Option<Handle>, mr: Option<Handle>, normal: Option<Handle>, ao: Option<Handle>) -> Result<Handle> {
         // Allocate slot
@@ -640,7 +681,8 @@ impl ResourceManager {
 
     // ---------- Helpers ----------
 
-    fn create_dummy_texture(device: &wgpu::Device, queue: &wgpu::Queue) -> Handle {
+    fn create_dummy_texture(&self) -> Handle {
+        let (device, queue) = (&self.device, &self.queue);
         let rgba = [255u8, 255u8, 255u8, 255u8];
         let size = wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 };
         let tex = device.create_texture(&wgpu::TextureDescriptor {
@@ -662,7 +704,19 @@ impl ResourceManager {
         let view = tex.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
 
-        Handle::invalid() // Caller will set up properly
+        // Pinned slot: refcount never drops, so LRU eviction skips it
+        let index = self.texture_pool.write().alloc(HandleMeta {
+            generation: 1,
+            refcount: 1,
+            size_bytes: 0,
+            hash: 0,
+        });
+        let Some(index) = index else { return Handle::invalid() };
+        self.texture_gens.write()[index as usize] = 1;
+        self.texture_views.write()[index as usize] = Some(Arc::new(view));
+        self.texture_samplers.write()[index as usize] = Some(Arc::new(sampler));
+
+        Handle::new(index, 1)
     }
 
     /// Get memory statistics
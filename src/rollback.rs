@@ -0,0 +1,527 @@
+// src/rollback.rs
+//! GGPO-style rollback session
+//!
+//! - Remote inputs are predicted (last confirmed input repeats) so the
+//!   simulation never waits on the network
+//! - Confirmed inputs that contradict a prediction trigger a load + resimulate
+//!   through user-provided save/load/advance callbacks
+//! - Local input delay and a bounded prediction window
+//! - Desync detection by exchanging state hashes inside `GameInput::checksum`
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::network::{GameInput, RollbackState};
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+const DEFAULT_INPUT_DELAY: u32 = 2;
+const DEFAULT_MAX_PREDICTION: u32 = 8;
+const CHECKSUM_HISTORY: u32 = 128;
+
+// ============================================================================
+// CONFIG & CALLBACKS
+// ============================================================================
+
+#[derive(Clone, Copy, Debug)]
+pub struct RollbackConfig {
+    /// Frames between sampling a local input and simulating it
+    pub input_delay: u32,
+    /// How far the simulation may run past the last fully confirmed frame
+    pub max_prediction_frames: u32,
+    pub desync_detection: bool,
+}
+
+impl Default for RollbackConfig {
+    fn default() -> Self {
+        Self {
+            input_delay: DEFAULT_INPUT_DELAY,
+            max_prediction_frames: DEFAULT_MAX_PREDICTION,
+            desync_detection: true,
+        }
+    }
+}
+
+/// The game simulation, as seen by the rollback session. Everything here must
+/// be deterministic: the same state and inputs always produce the same result.
+pub trait RollbackCallbacks {
+    /// Snapshot the simulation at the start of `frame`, returning the
+    /// serialized state and a hash used for desync detection
+    fn save_state(&mut self, frame: u32) -> (Vec<u8>, u32);
+
+    /// Restore a snapshot previously returned by `save_state`
+    fn load_state(&mut self, frame: u32, state: &[u8]);
+
+    /// Step the simulation one frame with one input per player, in session player order
+    fn advance_frame(&mut self, frame: u32, inputs: &[GameInput]);
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RollbackEvent {
+    /// Frames `from_frame..to_frame` were resimulated after a misprediction
+    Rollback { from_frame: u32, to_frame: u32 },
+    /// A remote player's state hash disagrees with ours
+    Desync { frame: u32, player_id: u64, local_hash: u32, remote_hash: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RollbackError {
+    /// Advancing would exceed `max_prediction_frames`; wait for remote inputs
+    PredictionThreshold,
+    UnknownPlayer(u64),
+    /// A local input was already queued for this frame
+    InputAlreadyAdded(u32),
+}
+
+impl std::fmt::Display for RollbackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RollbackError::PredictionThreshold => write!(f, "Prediction window exhausted"),
+            RollbackError::UnknownPlayer(id) => write!(f, "Unknown player {}", id),
+            RollbackError::InputAlreadyAdded(frame) => write!(f, "Input already added for frame {}", frame),
+        }
+    }
+}
+
+// ============================================================================
+// ROLLBACK SESSION
+// ============================================================================
+
+struct SavedFrame {
+    state: RollbackState,
+    data: Vec<u8>,
+}
+
+pub struct RollbackSession {
+    config: RollbackConfig,
+    local_player: u64,
+    players: Vec<u64>,
+
+    current_frame: u32,
+    confirmed: HashMap<u64, BTreeMap<u32, GameInput>>,
+    /// Highest frame through which each player's inputs are contiguous
+    confirmed_through: HashMap<u64, Option<u32>>,
+    /// Inputs actually simulated per frame (confirmed or predicted)
+    used_inputs: BTreeMap<u32, Vec<GameInput>>,
+    saved: VecDeque<SavedFrame>,
+    pending_rollback: Option<u32>,
+
+    local_hashes: BTreeMap<u32, u32>,
+    remote_hashes: Vec<(u64, u32, u32)>,
+
+    outgoing: Vec<GameInput>,
+    events: VecDeque<RollbackEvent>,
+}
+
+impl RollbackSession {
+    /// `players` fixes the input order handed to `advance_frame` and must
+    /// match on every peer. Fails with `UnknownPlayer` if it doesn't include
+    /// `local_player`.
+    pub fn new(config: RollbackConfig, local_player: u64, players: &[u64]) -> Result<Self, RollbackError> {
+        if !players.contains(&local_player) {
+            return Err(RollbackError::UnknownPlayer(local_player));
+        }
+        let mut session = Self {
+            config,
+            local_player,
+            players: players.to_vec(),
+            current_frame: 0,
+            confirmed: HashMap::new(),
+            confirmed_through: HashMap::new(),
+            used_inputs: BTreeMap::new(),
+            saved: VecDeque::new(),
+            pending_rollback: None,
+            local_hashes: BTreeMap::new(),
+            remote_hashes: Vec::new(),
+            outgoing: Vec::new(),
+            events: VecDeque::new(),
+        };
+
+        // Nobody can have input for the delay frames, so every peer agrees they are empty
+        for &player in players {
+            session.confirmed.insert(player, BTreeMap::new());
+            session.confirmed_through.insert(player, None);
+            for frame in 0..config.input_delay {
+                session.confirm(empty_input(player, frame));
+            }
+        }
+
+        Ok(session)
+    }
+
+    pub fn current_frame(&self) -> u32 {
+        self.current_frame
+    }
+
+    /// Last frame for which every player's input is known, if any
+    pub fn confirmed_frame(&self) -> Option<u32> {
+        self.players.iter()
+            .map(|p| self.confirmed_through.get(p).copied().flatten())
+            .min()
+            .flatten()
+    }
+
+    /// Frames simulated on predicted input
+    pub fn frames_ahead(&self) -> u32 {
+        match self.confirmed_frame() {
+            Some(frame) => self.current_frame.saturating_sub(frame + 1),
+            None => self.current_frame,
+        }
+    }
+
+    /// The saved snapshot metadata for the start of `frame`, if still held
+    pub fn state_at(&self, frame: u32) -> Option<&RollbackState> {
+        self.saved.iter().find(|s| s.state.frame == frame).map(|s| &s.state)
+    }
+
+    pub fn poll_events(&mut self) -> Vec<RollbackEvent> {
+        self.events.drain(..).collect()
+    }
+
+    /// Local inputs produced since the last call, to be sent to every remote peer
+    pub fn drain_outgoing_inputs(&mut self) -> Vec<GameInput> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    // ============================================
+    // INPUT
+    // ============================================
+
+    /// Queue the local player's input sampled this frame. It is scheduled
+    /// `input_delay` frames ahead; returns the frame it will be simulated on.
+    pub fn add_local_input(&mut self, inputs: Vec<u8>) -> Result<u32, RollbackError> {
+        let frame = self.current_frame + self.config.input_delay;
+        if self.confirmed[&self.local_player].contains_key(&frame) {
+            return Err(RollbackError::InputAlreadyAdded(frame));
+        }
+
+        let input = GameInput {
+            player_id: self.local_player,
+            frame,
+            inputs,
+            checksum: self.outgoing_checksum(frame),
+            timestamp: 0,
+        };
+        self.outgoing.push(input.clone());
+        self.confirm(input);
+        Ok(frame)
+    }
+
+    /// Feed a confirmed input received from a remote peer. Mispredictions are
+    /// resolved on the next `advance_frame`.
+    pub fn add_remote_input(&mut self, input: GameInput) -> Result<(), RollbackError> {
+        if !self.players.contains(&input.player_id) {
+            return Err(RollbackError::UnknownPlayer(input.player_id));
+        }
+        if self.confirmed[&input.player_id].contains_key(&input.frame) {
+            return Ok(());
+        }
+
+        if let Some(frame) = self.checksum_frame(input.frame) {
+            // Zero means the sender had no settled hash to offer
+            if self.config.desync_detection && input.checksum != 0 {
+                self.remote_hashes.push((input.player_id, frame, input.checksum));
+            }
+        }
+
+        if input.frame < self.current_frame {
+            let predicted = self.used_inputs.get(&input.frame)
+                .and_then(|inputs| inputs.iter().find(|i| i.player_id == input.player_id));
+            if predicted.is_some_and(|p| p.inputs != input.inputs) {
+                let earliest = self.pending_rollback.map_or(input.frame, |f| f.min(input.frame));
+                self.pending_rollback = Some(earliest);
+            }
+        }
+
+        self.confirm(input);
+        Ok(())
+    }
+
+    fn confirm(&mut self, input: GameInput) {
+        let player = input.player_id;
+        let inputs = self.confirmed.entry(player).or_default();
+        inputs.insert(input.frame, input);
+
+        let through = self.confirmed_through.entry(player).or_insert(None);
+        let mut next = through.map_or(0, |f| f + 1);
+        while inputs.contains_key(&next) {
+            *through = Some(next);
+            next += 1;
+        }
+    }
+
+    /// Confirmed input for `frame`, or a prediction repeating the player's latest earlier input
+    fn input_for(&self, player: u64, frame: u32) -> GameInput {
+        let inputs = &self.confirmed[&player];
+        if let Some(input) = inputs.get(&frame) {
+            return input.clone();
+        }
+        match inputs.range(..frame).next_back() {
+            Some((_, last)) => GameInput { frame, checksum: 0, ..last.clone() },
+            None => empty_input(player, frame),
+        }
+    }
+
+    // ============================================
+    // SIMULATION
+    // ============================================
+
+    /// Resolve any pending rollback, then simulate the current frame.
+    /// Returns `PredictionThreshold` without advancing if the simulation
+    /// is already `max_prediction_frames` ahead of confirmed input.
+    pub fn advance_frame(&mut self, callbacks: &mut impl RollbackCallbacks) -> Result<(), RollbackError> {
+        if let Some(from) = self.pending_rollback.take() {
+            self.resimulate(from, callbacks);
+        }
+
+        self.check_desync();
+
+        if self.frames_ahead() >= self.config.max_prediction_frames {
+            return Err(RollbackError::PredictionThreshold);
+        }
+
+        // The local player must never leave a gap peers would wait on forever
+        let local_frame = self.current_frame + self.config.input_delay;
+        if !self.confirmed[&self.local_player].contains_key(&local_frame) {
+            let repeat = self.input_for(self.local_player, local_frame).inputs;
+            self.add_local_input(repeat)?;
+        }
+
+        let frame = self.current_frame;
+        self.save(frame, callbacks);
+        self.step(frame, callbacks);
+        self.current_frame += 1;
+
+        self.prune();
+        Ok(())
+    }
+
+    fn resimulate(&mut self, from: u32, callbacks: &mut impl RollbackCallbacks) {
+        let to = self.current_frame;
+        let data = match self.saved.iter().find(|s| s.state.frame == from) {
+            Some(saved) => saved.data.clone(),
+            None => {
+                log::warn!("Rollback to frame {} impossible: state no longer saved", from);
+                return;
+            }
+        };
+
+        callbacks.load_state(from, &data);
+        for frame in from..to {
+            if frame != from {
+                self.save(frame, callbacks);
+            }
+            self.step(frame, callbacks);
+        }
+
+        log::debug!("Rolled back {} frames ({}..{})", to - from, from, to);
+        self.events.push_back(RollbackEvent::Rollback { from_frame: from, to_frame: to });
+    }
+
+    fn step(&mut self, frame: u32, callbacks: &mut impl RollbackCallbacks) {
+        let inputs: Vec<GameInput> = self.players.iter().map(|&p| self.input_for(p, frame)).collect();
+        callbacks.advance_frame(frame, &inputs);
+        if let Some(saved) = self.saved.iter_mut().find(|s| s.state.frame == frame) {
+            saved.state.inputs = inputs.clone();
+        }
+        self.used_inputs.insert(frame, inputs);
+    }
+
+    fn save(&mut self, frame: u32, callbacks: &mut impl RollbackCallbacks) {
+        let (data, state_hash) = callbacks.save_state(frame);
+        self.local_hashes.insert(frame, state_hash);
+
+        let state = RollbackState {
+            frame,
+            state_hash,
+            inputs: Vec::new(),
+            timestamp: 0,
+        };
+        self.saved.retain(|s| s.state.frame != frame);
+        self.saved.push_back(SavedFrame { state, data });
+    }
+
+    /// Drop state that can never be rolled back to again
+    fn prune(&mut self) {
+        // Rollbacks start at the first unconfirmed frame at the earliest
+        let keep_from = self.confirmed_frame().map_or(0, |f| f + 1);
+        self.saved.retain(|s| s.state.frame >= keep_from);
+        self.used_inputs.retain(|frame, _| *frame >= keep_from);
+
+        let horizon = self.current_frame.saturating_sub(CHECKSUM_HISTORY);
+        self.local_hashes.retain(|frame, _| *frame >= horizon);
+        for inputs in self.confirmed.values_mut() {
+            while inputs.len() > 1 && inputs.keys().next().is_some_and(|f| *f < horizon) {
+                inputs.pop_first();
+            }
+        }
+    }
+
+    // ============================================
+    // DESYNC DETECTION
+    // ============================================
+
+    /// Each input for frame `F` carries the sender's hash of frame
+    /// `F - input_delay - max_prediction_frames - 1`, which the prediction
+    /// window guarantees has settled on the sender by the time it is sent.
+    fn checksum_frame(&self, input_frame: u32) -> Option<u32> {
+        input_frame.checked_sub(self.config.input_delay + self.config.max_prediction_frames + 1)
+    }
+
+    fn outgoing_checksum(&self, input_frame: u32) -> u32 {
+        if !self.config.desync_detection {
+            return 0;
+        }
+        match self.checksum_frame(input_frame) {
+            Some(frame) if self.pending_rollback.is_none_or(|f| f > frame) => {
+                self.local_hashes.get(&frame).copied().unwrap_or(0)
+            }
+            _ => 0,
+        }
+    }
+
+    fn check_desync(&mut self) {
+        // A hash is only final once every earlier frame was simulated on confirmed input
+        let settled_through = self.confirmed_frame().map_or(0, |f| f + 1);
+
+        let mut remaining = Vec::new();
+        for (player_id, frame, remote_hash) in self.remote_hashes.drain(..) {
+            if frame > settled_through || frame >= self.current_frame {
+                remaining.push((player_id, frame, remote_hash));
+                continue;
+            }
+            if let Some(&local_hash) = self.local_hashes.get(&frame) {
+                if local_hash != remote_hash {
+                    log::warn!(
+                        "Desync with player {} at frame {}: local {:08x} remote {:08x}",
+                        player_id, frame, local_hash, remote_hash
+                    );
+                    self.events.push_back(RollbackEvent::Desync { frame, player_id, local_hash, remote_hash });
+                }
+            }
+        }
+        self.remote_hashes = remaining;
+    }
+}
+
+fn empty_input(player_id: u64, frame: u32) -> GameInput {
+    GameInput { player_id, frame, inputs: Vec::new(), checksum: 0, timestamp: 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Order-sensitive toy simulation
+    #[derive(Default)]
+    struct Counter {
+        value: u64,
+        advances: usize,
+        corrupt_at: Option<u32>,
+    }
+
+    impl RollbackCallbacks for Counter {
+        fn save_state(&mut self, _frame: u32) -> (Vec<u8>, u32) {
+            (self.value.to_le_bytes().to_vec(), (self.value ^ (self.value >> 32)) as u32 | 1)
+        }
+
+        fn load_state(&mut self, _frame: u32, state: &[u8]) {
+            self.value = u64::from_le_bytes(state.try_into().unwrap());
+        }
+
+        fn advance_frame(&mut self, frame: u32, inputs: &[GameInput]) {
+            for input in inputs {
+                let sum: u64 = input.inputs.iter().map(|b| *b as u64).sum();
+                self.value = self.value.wrapping_mul(31).wrapping_add(sum * input.player_id + 1);
+            }
+            if self.corrupt_at == Some(frame) {
+                self.value ^= 0xDEAD;
+            }
+            self.advances += 1;
+        }
+    }
+
+    /// Two peers exchanging inputs with `latency` frames of delay
+    fn run_pair(frames: u32, latency: usize, corrupt_b_at: Option<u32>) -> (RollbackSession, RollbackSession, Counter, Counter, Vec<RollbackEvent>, Vec<RollbackEvent>) {
+        let config = RollbackConfig { input_delay: 1, max_prediction_frames: 8, desync_detection: true };
+        let mut a = RollbackSession::new(config, 1, &[1, 2]).unwrap();
+        let mut b = RollbackSession::new(config, 2, &[1, 2]).unwrap();
+        let mut sim_a = Counter::default();
+        let mut sim_b = Counter { corrupt_at: corrupt_b_at, ..Default::default() };
+        let mut a_to_b: VecDeque<Vec<GameInput>> = VecDeque::new();
+        let mut b_to_a: VecDeque<Vec<GameInput>> = VecDeque::new();
+        let (mut events_a, mut events_b) = (Vec::new(), Vec::new());
+
+        for tick in 0..frames {
+            a.add_local_input(vec![(tick / 3) as u8]).unwrap();
+            b.add_local_input(vec![(tick / 5) as u8 + 1]).unwrap();
+            a.advance_frame(&mut sim_a).unwrap();
+            b.advance_frame(&mut sim_b).unwrap();
+            a_to_b.push_back(a.drain_outgoing_inputs());
+            b_to_a.push_back(b.drain_outgoing_inputs());
+
+            if a_to_b.len() > latency {
+                for input in a_to_b.pop_front().unwrap() {
+                    b.add_remote_input(input).unwrap();
+                }
+                for input in b_to_a.pop_front().unwrap() {
+                    a.add_remote_input(input).unwrap();
+                }
+            }
+            events_a.extend(a.poll_events());
+            events_b.extend(b.poll_events());
+        }
+
+        (a, b, sim_a, sim_b, events_a, events_b)
+    }
+
+    #[test]
+    fn test_rollback_converges() {
+        let (a, b, sim_a, sim_b, events_a, events_b) = run_pair(60, 3, None);
+
+        assert!(events_a.iter().any(|e| matches!(e, RollbackEvent::Rollback { .. })));
+        assert!(!events_a.iter().chain(&events_b).any(|e| matches!(e, RollbackEvent::Desync { .. })));
+        // Resimulation means strictly more advances than frames
+        assert!(sim_a.advances > 60 && sim_b.advances > 60);
+
+        let settled = a.confirmed_frame().unwrap().min(b.confirmed_frame().unwrap());
+        assert!(settled > 50);
+        assert_eq!(a.state_at(settled).unwrap().state_hash, b.state_at(settled).unwrap().state_hash);
+    }
+
+    #[test]
+    fn test_desync_detected() {
+        let (_, _, _, _, events_a, _) = run_pair(60, 2, Some(20));
+        assert!(events_a.iter().any(|e| matches!(e, RollbackEvent::Desync { player_id: 2, .. })));
+    }
+
+    #[test]
+    fn test_prediction_window_and_input_delay() {
+        let config = RollbackConfig { input_delay: 2, max_prediction_frames: 4, desync_detection: false };
+        let mut session = RollbackSession::new(config, 1, &[1, 2]).unwrap();
+        let mut sim = Counter::default();
+
+        assert_eq!(session.add_local_input(vec![7]), Ok(2));
+        assert_eq!(session.add_local_input(vec![8]), Err(RollbackError::InputAlreadyAdded(2)));
+        assert_eq!(session.confirmed_frame(), Some(1));
+
+        // Delay frames are confirmed for everyone, so 2 + 4 frames can run unanswered
+        for _ in 0..6 {
+            session.advance_frame(&mut sim).unwrap();
+        }
+        assert_eq!(session.advance_frame(&mut sim), Err(RollbackError::PredictionThreshold));
+        assert_eq!(session.current_frame(), 6);
+
+        session.add_remote_input(GameInput { player_id: 2, frame: 2, inputs: vec![1], checksum: 0, timestamp: 0 }).unwrap();
+        session.advance_frame(&mut sim).unwrap();
+        assert_eq!(session.poll_events(), vec![RollbackEvent::Rollback { from_frame: 2, to_frame: 6 }]);
+        assert_eq!(session.drain_outgoing_inputs().len(), 7);
+
+        assert_eq!(
+            session.add_remote_input(GameInput { player_id: 9, frame: 0, inputs: vec![], checksum: 0, timestamp: 0 }),
+            Err(RollbackError::UnknownPlayer(9))
+        );
+        assert!(matches!(RollbackSession::new(config, 3, &[1, 2]), Err(RollbackError::UnknownPlayer(3))));
+    }
+}
@@ -0,0 +1,499 @@
+// src/rpc.rs
+//! Remote procedure calls for `unreal_framework` actors
+//!
+//! - RPCs are registered by name per actor class (`AActor::tag`) and marked
+//!   Server, Client or Multicast, reliable or unreliable
+//! - Calls carry `UValue` arguments and travel over `NetworkSystem` channels:
+//!   reliable RPCs on `ReliableOrdered`, unreliable ones on `UnreliableUnordered`
+//! - Server RPCs are only accepted from the actor's owning peer and pass
+//!   through an optional validation hook before they run
+//! - Clients send to, and only accept Client/Multicast RPCs from, the server
+//!   peer set with `set_server_peer`
+//! - An RPC runs its native handler (if any), then the Blueprint event of the
+//!   same name with the arguments bound to registers `0..n`
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::{ChannelType, NetworkError, NetworkMessage, NetworkRole, NetworkSystem};
+use crate::unreal_framework::{AActor, BlueprintVM, FRemoteCall, FWorldCommandBuffer, UValue, UWorld};
+
+/// First byte of every RPC message, so RPCs can share channels with game traffic
+const RPC_TAG: u8 = 0xA8;
+
+// ============================================================================
+// DESCRIPTORS
+// ============================================================================
+
+/// Where an RPC executes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RpcTarget {
+    /// Called by the owning client, runs on the server
+    Server,
+    /// Called by the server, runs on the owning client
+    Client,
+    /// Called by the server, runs on the server and every client
+    Multicast,
+}
+
+/// Server-side check run before a Server RPC executes: (sender, actor, args)
+pub type RpcValidateFn = Box<dyn Fn(u64, &AActor, &[UValue]) -> bool + Send + Sync>;
+/// Native implementation: (actor, args, sending peer or `None` when local)
+pub type RpcHandlerFn = Box<dyn FnMut(&mut AActor, &[UValue], Option<u64>) + Send>;
+
+pub struct RpcDescriptor {
+    pub name: String,
+    pub target: RpcTarget,
+    pub reliable: bool,
+    validate: Option<RpcValidateFn>,
+    handler: Option<RpcHandlerFn>,
+}
+
+impl RpcDescriptor {
+    pub fn new(name: impl Into<String>, target: RpcTarget, reliable: bool) -> Self {
+        Self {
+            name: name.into(),
+            target,
+            reliable,
+            validate: None,
+            handler: None,
+        }
+    }
+
+    /// Reject Server RPCs whose arguments fail `validate` (WithValidation)
+    pub fn with_validation(mut self, validate: impl Fn(u64, &AActor, &[UValue]) -> bool + Send + Sync + 'static) -> Self {
+        self.validate = Some(Box::new(validate));
+        self
+    }
+
+    pub fn with_handler(mut self, handler: impl FnMut(&mut AActor, &[UValue], Option<u64>) + Send + 'static) -> Self {
+        self.handler = Some(Box::new(handler));
+        self
+    }
+
+    fn channel(&self) -> ChannelType {
+        if self.reliable { ChannelType::ReliableOrdered } else { ChannelType::UnreliableUnordered }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RpcError {
+    UnknownActor(u64),
+    UnknownFunction { class: String, function: String },
+    /// Called from the wrong side, e.g. a client invoking a Multicast
+    WrongRole,
+    /// Server RPC from a peer that does not own the actor
+    NotOwner { actor_id: u64, peer_id: u64 },
+    /// Client RPC on an actor without an owning connection
+    NoOwningConnection(u64),
+    ValidationFailed { function: String, peer_id: u64 },
+    /// Client or Multicast RPC received from a peer other than the server
+    NotServer(u64),
+    NotConnected,
+    MalformedMessage,
+    /// The transport refused the message, e.g. arguments too large for one datagram
+    SendFailed(String),
+    /// A Multicast ran locally and reached every other peer, but these sends failed
+    MulticastFailed(Vec<(u64, RpcError)>),
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RpcError::UnknownActor(id) => write!(f, "Unknown actor {}", id),
+            RpcError::UnknownFunction { class, function } => write!(f, "No RPC {} registered on {}", function, class),
+            RpcError::WrongRole => write!(f, "RPC called from the wrong network role"),
+            RpcError::NotOwner { actor_id, peer_id } => write!(f, "Peer {} does not own actor {}", peer_id, actor_id),
+            RpcError::NoOwningConnection(id) => write!(f, "Actor {} has no owning connection", id),
+            RpcError::ValidationFailed { function, peer_id } => write!(f, "RPC {} from peer {} failed validation", function, peer_id),
+            RpcError::NotServer(id) => write!(f, "Peer {} is not the server", id),
+            RpcError::NotConnected => write!(f, "Not connected"),
+            RpcError::MalformedMessage => write!(f, "Malformed RPC message"),
+            RpcError::SendFailed(reason) => write!(f, "RPC send failed: {}", reason),
+            RpcError::MulticastFailed(failures) => {
+                write!(f, "Multicast failed for {} peer(s)", failures.len())?;
+                for (peer_id, e) in failures {
+                    write!(f, "; peer {}: {}", peer_id, e)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct RpcMessage {
+    actor_id: u64,
+    function: String,
+    args: Vec<UValue>,
+}
+
+impl RpcMessage {
+    fn encode(&self) -> Vec<u8> {
+        let mut data = vec![RPC_TAG];
+        data.extend(bincode::serialize(self).unwrap_or_default());
+        data
+    }
+
+    fn decode(data: &[u8]) -> Option<Option<Self>> {
+        match data.split_first() {
+            Some((&RPC_TAG, rest)) => Some(bincode::deserialize(rest).ok()),
+            _ => None,
+        }
+    }
+}
+
+// ============================================================================
+// RPC SYSTEM
+// ============================================================================
+
+#[derive(Default)]
+pub struct RpcSystem {
+    /// Actor class -> function name -> descriptor
+    classes: HashMap<String, HashMap<String, RpcDescriptor>>,
+    /// Client only: the peer `NetworkSystem::connect` returned
+    server_peer: Option<u64>,
+}
+
+impl RpcSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an RPC on every actor whose `tag` is `class`. Both the
+    /// server and clients must register the same RPCs.
+    pub fn register(&mut self, class: &str, descriptor: RpcDescriptor) {
+        self.classes
+            .entry(class.to_string())
+            .or_default()
+            .insert(descriptor.name.clone(), descriptor);
+    }
+
+    /// Client only: the server's peer id, as returned by `NetworkSystem::connect`
+    pub fn set_server_peer(&mut self, peer_id: u64) {
+        self.server_peer = Some(peer_id);
+    }
+
+    fn descriptor(&self, actor: &AActor, function: &str) -> Result<&RpcDescriptor, RpcError> {
+        self.classes
+            .get(&actor.tag)
+            .and_then(|functions| functions.get(function))
+            .ok_or_else(|| RpcError::UnknownFunction { class: actor.tag.clone(), function: function.to_string() })
+    }
+
+    // ============================================
+    // Calling
+    // ============================================
+
+    /// Invoke `function` on `actor_id`, sending it wherever its target says
+    /// it runs. Server RPCs called on the server, and Multicasts, also run locally.
+    /// A Multicast is offered to every peer even if some sends fail; those
+    /// are reported together as `RpcError::MulticastFailed`.
+    pub fn call(
+        &mut self,
+        network: &NetworkSystem,
+        world: &mut UWorld,
+        actor_id: u64,
+        function: &str,
+        args: Vec<UValue>,
+    ) -> Result<(), RpcError> {
+        let authority = network.role() != NetworkRole::Client;
+        let actor = find_actor(world, actor_id).ok_or(RpcError::UnknownActor(actor_id))?;
+        let descriptor = self.descriptor(actor, function)?;
+        let (target, channel) = (descriptor.target, descriptor.channel());
+        let message = RpcMessage { actor_id, function: function.to_string(), args };
+
+        match (target, authority) {
+            (RpcTarget::Server, true) => self.execute(world, &message, None),
+            (RpcTarget::Server, false) => {
+                let server = self.server_peer.ok_or(RpcError::NotConnected)?;
+                send(network, server, rpc_message(channel, &message))
+            }
+            (RpcTarget::Client, true) => {
+                let owner = network.entity_owner(actor_id).ok_or(RpcError::NoOwningConnection(actor_id))?;
+                send(network, owner, rpc_message(channel, &message))
+            }
+            (RpcTarget::Multicast, true) => {
+                let failures: Vec<(u64, RpcError)> = network.connected_peers()
+                    .into_iter()
+                    .filter_map(|peer_id| send(network, peer_id, rpc_message(channel, &message)).err().map(|e| (peer_id, e)))
+                    .collect();
+                self.execute(world, &message, None)?;
+                if failures.is_empty() { Ok(()) } else { Err(RpcError::MulticastFailed(failures)) }
+            }
+            (RpcTarget::Client | RpcTarget::Multicast, false) => Err(RpcError::WrongRole),
+        }
+    }
+
+    /// Send every remote event Blueprints raised during `world.tick`.
+    /// Failed calls are logged and dropped.
+    pub fn flush_world_calls(&mut self, network: &NetworkSystem, world: &mut UWorld) {
+        for FRemoteCall { actor_id, function, args } in std::mem::take(&mut world.pending_rpcs) {
+            if let Err(e) = self.call(network, world, actor_id, &function, args) {
+                log::warn!("Blueprint RPC {} on actor {} failed: {}", function, actor_id, e);
+            }
+        }
+    }
+
+    // ============================================
+    // Receiving
+    // ============================================
+
+    /// Execute an RPC received from `from_peer`. Returns Ok(false) if `data`
+    /// is not an RPC message.
+    pub fn handle_message(
+        &mut self,
+        network: &NetworkSystem,
+        world: &mut UWorld,
+        from_peer: u64,
+        data: &[u8],
+    ) -> Result<bool, RpcError> {
+        let message = match RpcMessage::decode(data) {
+            None => return Ok(false),
+            Some(None) => return Err(RpcError::MalformedMessage),
+            Some(Some(m)) => m,
+        };
+        let authority = network.role() != NetworkRole::Client;
+        let actor = find_actor(world, message.actor_id).ok_or(RpcError::UnknownActor(message.actor_id))?;
+        let descriptor = self.descriptor(actor, &message.function)?;
+
+        match (descriptor.target, authority) {
+            (RpcTarget::Server, true) => {
+                if network.entity_owner(message.actor_id) != Some(from_peer) {
+                    log::warn!("Rejecting RPC {} from peer {}: not the owner of actor {}", message.function, from_peer, message.actor_id);
+                    return Err(RpcError::NotOwner { actor_id: message.actor_id, peer_id: from_peer });
+                }
+                if let Some(validate) = &descriptor.validate {
+                    if !validate(from_peer, actor, &message.args) {
+                        log::warn!("RPC {} from peer {} failed validation", message.function, from_peer);
+                        return Err(RpcError::ValidationFailed { function: message.function, peer_id: from_peer });
+                    }
+                }
+            }
+            (RpcTarget::Client | RpcTarget::Multicast, false) => {
+                if self.server_peer != Some(from_peer) {
+                    log::warn!("Rejecting RPC {} from peer {}: not the server", message.function, from_peer);
+                    return Err(RpcError::NotServer(from_peer));
+                }
+            }
+            _ => return Err(RpcError::WrongRole),
+        }
+
+        self.execute(world, &message, Some(from_peer))?;
+        Ok(true)
+    }
+
+    /// Run the native handler, then the Blueprint event, and apply any
+    /// world commands the Blueprint queued
+    fn execute(&mut self, world: &mut UWorld, message: &RpcMessage, sender: Option<u64>) -> Result<(), RpcError> {
+        let actor = find_actor_mut(world, message.actor_id).ok_or(RpcError::UnknownActor(message.actor_id))?;
+        let class = actor.tag.clone();
+        if let Some(handler) = self.classes
+            .get_mut(&class)
+            .and_then(|functions| functions.get_mut(&message.function))
+            .and_then(|d| d.handler.as_mut())
+        {
+            handler(actor, &message.args, sender);
+        }
+
+        let Some(graph) = actor.blueprint_graph.clone() else {
+            return Ok(());
+        };
+        let mut cmd_buffer = FWorldCommandBuffer::default();
+        if let Err(e) = BlueprintVM::execute_event_with_args(&graph, &message.function, actor, &mut cmd_buffer, &message.args) {
+            log::warn!("Blueprint event {} failed: {}", message.function, e);
+        }
+        for new_actor in cmd_buffer.pending_spawns {
+            world.actors.insert(new_actor.id, new_actor);
+        }
+        for destroy_id in cmd_buffer.pending_destroys {
+            world.destroy_actor(destroy_id);
+        }
+        world.pending_rpcs.extend(cmd_buffer.pending_rpcs);
+        Ok(())
+    }
+}
+
+fn rpc_message(channel: ChannelType, message: &RpcMessage) -> NetworkMessage {
+    NetworkMessage {
+        id: 0,
+        channel,
+        data: message.encode(),
+        timestamp: 0,
+        sequence: 0,
+    }
+}
+
+fn send(network: &NetworkSystem, peer_id: u64, message: NetworkMessage) -> Result<(), RpcError> {
+    network.send_message(peer_id, message).map_err(|e| match e {
+        NetworkError::NotConnected => RpcError::NotConnected,
+        e => RpcError::SendFailed(e.to_string()),
+    })
+}
+
+fn find_actor(world: &UWorld, actor_id: u64) -> Option<&AActor> {
+    world.actors.get(&actor_id)
+        .or_else(|| world.characters.get(&actor_id).map(|c| &c.base_actor))
+}
+
+fn find_actor_mut(world: &mut UWorld, actor_id: u64) -> Option<&mut AActor> {
+    match world.characters.get_mut(&actor_id) {
+        Some(character) => Some(&mut character.base_actor),
+        None => world.actors.get_mut(&actor_id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::test_support::{connected_pair, pump_until};
+    use crate::unreal_framework::{BlueprintGraph, EBlueprintOpcode};
+    use std::sync::Arc;
+
+    fn connected() -> (NetworkSystem, NetworkSystem, u64, u64) {
+        let (server, client, server_peer) = connected_pair();
+        let client_peer = server.connected_peers()[0];
+        (server, client, server_peer, client_peer)
+    }
+
+    fn deliver(from: &NetworkSystem, to: &NetworkSystem) -> Vec<(u64, NetworkMessage)> {
+        let mut received = Vec::new();
+        pump_until(from, to, || {
+            received.extend(to.drain_received());
+            !received.is_empty()
+        });
+        received
+    }
+
+    /// Weapon whose "FireWeapon" Blueprint event stores its first argument and
+    /// whose "EventTick" raises "FireWeapon" remotely
+    fn weapon(id: u64) -> AActor {
+        let mut graph = BlueprintGraph::new("BP_Weapon");
+        graph.entry_points.insert("FireWeapon".to_string(), 0);
+        graph.instructions.push(EBlueprintOpcode::EventCustom { name: "FireWeapon".to_string() });
+        graph.instructions.push(EBlueprintOpcode::SetActorLocation { target_actor_id: 0, location_pin: 0 });
+        graph.instructions.push(EBlueprintOpcode::Return);
+        graph.entry_points.insert("EventTick".to_string(), 3);
+        graph.registers[1] = UValue::Vector(glam::Vec3::new(0.0, 0.0, 9.0));
+        graph.instructions.push(EBlueprintOpcode::EventTick);
+        graph.instructions.push(EBlueprintOpcode::CallRemoteEvent { function: "FireWeapon".to_string(), arg_pins: vec![1] });
+        graph.instructions.push(EBlueprintOpcode::Return);
+
+        let mut actor = AActor::new(id, "Rifle");
+        actor.tag = "Weapon".to_string();
+        actor.add_component(crate::unreal_framework::UActorComponent::new(
+            id * 10, "Root", crate::unreal_framework::EComponentType::Scene,
+        ));
+        actor.blueprint_graph = Some(Arc::new(graph));
+        actor
+    }
+
+    fn register(rpc: &mut RpcSystem) {
+        rpc.register("Weapon", RpcDescriptor::new("FireWeapon", RpcTarget::Server, true)
+            .with_validation(|_, _, args| matches!(args, [UValue::Vector(v)] if v.length() < 100.0)));
+        rpc.register("Weapon", RpcDescriptor::new("PlayHitEffect", RpcTarget::Multicast, false)
+            .with_handler(|actor, args, _| {
+                actor.custom_properties.insert("LastHit".to_string(), args[0].clone());
+            }));
+    }
+
+    #[test]
+    fn test_server_rpc_from_owner_runs_blueprint_on_authority() {
+        let (server, client, server_peer, client_peer) = connected();
+        let (mut server_world, mut client_world) = (UWorld::new("Server"), UWorld::new("Client"));
+        server_world.spawn_actor_direct(weapon(7));
+        client_world.spawn_actor_direct(weapon(7));
+        let (mut server_rpc, mut client_rpc) = (RpcSystem::new(), RpcSystem::new());
+        register(&mut server_rpc);
+        register(&mut client_rpc);
+        client_rpc.set_server_peer(server_peer);
+
+        // Blueprint on the client raises FireWeapon; it runs only on the server
+        client_world.tick(0.016);
+        assert_eq!(client_world.pending_rpcs.len(), 1);
+        client_rpc.flush_world_calls(&client, &mut client_world);
+
+        // Not the owner yet: rejected
+        let received = deliver(&client, &server);
+        assert_eq!(received.len(), 1);
+        let err = server_rpc.handle_message(&server, &mut server_world, received[0].0, &received[0].1.data);
+        assert_eq!(err, Err(RpcError::NotOwner { actor_id: 7, peer_id: client_peer }));
+
+        server.set_entity_owner(7, Some(client_peer));
+        client_rpc.call(&client, &mut client_world, 7, "FireWeapon", vec![UValue::Vector(glam::Vec3::Z)]).unwrap();
+        client_rpc.call(&client, &mut client_world, 7, "FireWeapon", vec![UValue::Vector(glam::Vec3::splat(500.0))]).unwrap();
+        let received = deliver(&client, &server);
+        assert_eq!(received.len(), 2);
+        assert_eq!(server_rpc.handle_message(&server, &mut server_world, received[0].0, &received[0].1.data), Ok(true));
+        assert_eq!(server_world.actors[&7].get_actor_location(), glam::Vec3::Z);
+        assert!(matches!(
+            server_rpc.handle_message(&server, &mut server_world, received[1].0, &received[1].1.data),
+            Err(RpcError::ValidationFailed { .. })
+        ));
+        assert_eq!(client_world.actors[&7].get_actor_location(), glam::Vec3::ZERO);
+
+        // Clients cannot multicast; the server's multicast runs everywhere
+        assert_eq!(client_rpc.call(&client, &mut client_world, 7, "PlayHitEffect", vec![UValue::Integer(1)]), Err(RpcError::WrongRole));
+        server_rpc.call(&server, &mut server_world, 7, "PlayHitEffect", vec![UValue::Integer(3)]).unwrap();
+        assert_eq!(server_world.actors[&7].custom_properties["LastHit"], UValue::Integer(3));
+        let received = deliver(&server, &client);
+        assert_eq!(client_rpc.handle_message(&client, &mut client_world, received[0].0, &received[0].1.data), Ok(true));
+        assert_eq!(client_world.actors[&7].custom_properties["LastHit"], UValue::Integer(3));
+
+        assert_eq!(client_rpc.handle_message(&client, &mut client_world, 1, b"not an rpc"), Ok(false));
+        assert!(matches!(client_rpc.call(&client, &mut client_world, 7, "Reload", vec![]), Err(RpcError::UnknownFunction { .. })));
+    }
+
+    #[test]
+    fn test_client_rejects_rpcs_not_from_server() {
+        let (_server, client, server_peer, _) = connected();
+        let mut client_world = UWorld::new("Client");
+        client_world.spawn_actor_direct(weapon(7));
+        let mut client_rpc = RpcSystem::new();
+        register(&mut client_rpc);
+
+        // No server peer yet: nothing to send to, nothing trusted
+        assert_eq!(client_rpc.call(&client, &mut client_world, 7, "FireWeapon", vec![UValue::Vector(glam::Vec3::Z)]), Err(RpcError::NotConnected));
+        let multicast = RpcMessage { actor_id: 7, function: "PlayHitEffect".to_string(), args: vec![UValue::Integer(5)] }.encode();
+        assert_eq!(client_rpc.handle_message(&client, &mut client_world, server_peer, &multicast), Err(RpcError::NotServer(server_peer)));
+
+        // A multicast claiming to come from another peer is dropped; the server's runs
+        client_rpc.set_server_peer(server_peer);
+        let impostor = server_peer + 1;
+        assert_eq!(client_rpc.handle_message(&client, &mut client_world, impostor, &multicast), Err(RpcError::NotServer(impostor)));
+        assert!(!client_world.actors[&7].custom_properties.contains_key("LastHit"));
+        assert_eq!(client_rpc.handle_message(&client, &mut client_world, server_peer, &multicast), Ok(true));
+        assert_eq!(client_world.actors[&7].custom_properties["LastHit"], UValue::Integer(5));
+    }
+
+    #[test]
+    fn test_multicast_tries_every_peer_and_runs_locally() {
+        let (server, _client, _, _) = connected();
+        let mut second = NetworkSystem::new(NetworkRole::Client);
+        second.connect(&server.local_addr().unwrap().to_string()).unwrap();
+        assert!(pump_until(&second, &server, || server.connected_peers().len() == 2));
+
+        let mut server_world = UWorld::new("Server");
+        server_world.spawn_actor_direct(weapon(7));
+        let mut server_rpc = RpcSystem::new();
+        register(&mut server_rpc);
+
+        // Incompressible arguments too large for a datagram: every send fails,
+        // yet each peer is still tried and the local execution still happens
+        let noise: String = (0..crate::network::MAX_MESSAGE_PAYLOAD).map(|_| rand::random::<char>()).collect();
+        let err = server_rpc.call(&server, &mut server_world, 7, "PlayHitEffect", vec![UValue::String(noise.clone())]);
+        let Err(RpcError::MulticastFailed(failures)) = err else {
+            panic!("expected MulticastFailed, got {:?}", err);
+        };
+        let mut failed: Vec<u64> = failures.iter().map(|(peer_id, _)| *peer_id).collect();
+        failed.sort();
+        let mut peers = server.connected_peers();
+        peers.sort();
+        assert_eq!(failed, peers);
+        assert!(failures.iter().all(|(_, e)| matches!(e, RpcError::SendFailed(_))));
+        assert_eq!(server_world.actors[&7].custom_properties["LastHit"], UValue::String(noise));
+    }
+}
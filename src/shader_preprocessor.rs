@@ -0,0 +1,580 @@
+// src/shader_preprocessor.rs
+//! WGSL PREPROCESSOR
+//!
+//! The sources in `shaders.rs` are templates. Before compiling, a small
+//! C-style preprocessor runs over them:
+//! - `#include "name"` pastes a registered chunk, at most once per shader
+//! - `#define NAME [value]` / `#undef NAME`; defined names are replaced by
+//!   their value in the WGSL that follows (outside `//` comments)
+//! - `#if EXPR`, `#ifdef NAME`, `#ifndef NAME`, `#elif EXPR`, `#else` and
+//!   `#endif`. Expressions take integers, defined names (an empty value
+//!   counts as 1, an undefined name as 0), `defined(NAME)`, `!`, `&&`, `||`,
+//!   comparisons and parentheses.
+//!
+//! Each `ShaderFeatures` bit sets one define (see `SHADER_OPTIONS`), so a
+//! feature set picks a permutation of a template. `ShaderCache` compiles
+//! each permutation once.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::ops::{BitAnd, BitOr};
+
+use crate::shaders::{SHADER_INCLUDES, SHADER_OPTIONS};
+
+/// Bitmask of the optional features a shader permutation is compiled with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ShaderFeatures(u32);
+
+impl ShaderFeatures {
+    pub const NONE: Self = Self(0);
+    /// Cascaded shadow lookups in the main shader
+    pub const SHADOWS: Self = Self(1);
+    /// AO composite in the post shader
+    pub const SSAO: Self = Self(1 << 1);
+    /// Bloom composite in the post shader
+    pub const BLOOM: Self = Self(1 << 2);
+    /// Chromatic aberration, vignette and film grain in the post shader
+    pub const POST_EFFECTS: Self = Self(1 << 3);
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn set(&mut self, other: Self, enabled: bool) {
+        if enabled {
+            self.0 |= other.0;
+        } else {
+            self.0 &= !other.0;
+        }
+    }
+
+    /// Defines the enabled features set
+    pub fn defines(self) -> impl Iterator<Item = &'static str> {
+        SHADER_OPTIONS.iter().filter(move |(feature, _)| self.contains(*feature)).map(|&(_, define)| define)
+    }
+
+    /// Every subset of these features, the empty set included
+    pub fn subsets(self) -> impl Iterator<Item = Self> {
+        // Counts down through the bits of the mask: `(n - 1) & mask`
+        let mask = self.0;
+        std::iter::successors(Some(mask), move |&n| (n != 0).then(|| (n - 1) & mask)).map(Self)
+    }
+}
+
+impl BitOr for ShaderFeatures {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for ShaderFeatures {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreprocessError {
+    /// `#include` of a name nothing was registered under
+    UnknownInclude(String),
+    /// Unknown directive, or one missing its argument
+    InvalidDirective(String),
+    /// `#if` or `#elif` expression that doesn't parse or evaluate
+    InvalidExpression(String),
+    /// `#elif`, `#else` or `#endif` without an open `#if`, or after `#else`
+    UnmatchedDirective(String),
+    /// The named file ended inside an `#if` block
+    UnterminatedIf(String),
+}
+
+impl std::fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PreprocessError::UnknownInclude(name) => write!(f, "No shader include named {}", name),
+            PreprocessError::InvalidDirective(line) => write!(f, "Invalid directive: {}", line),
+            PreprocessError::InvalidExpression(expr) => write!(f, "Invalid #if expression: {}", expr),
+            PreprocessError::UnmatchedDirective(line) => write!(f, "{} without a matching #if", line),
+            PreprocessError::UnterminatedIf(file) => write!(f, "Unterminated #if in {}", file),
+        }
+    }
+}
+
+impl std::error::Error for PreprocessError {}
+
+/// Source passed to `process`, as named in errors
+const ROOT_FILE: &str = "<source>";
+
+/// One open `#if` block
+struct Conditional {
+    /// Whether the enclosing block is emitting
+    parent: bool,
+    /// Whether the current branch is emitting
+    active: bool,
+    /// Whether any branch so far was taken
+    taken: bool,
+    seen_else: bool,
+}
+
+/// State carried across a source and everything it includes
+struct Expansion<'a> {
+    defines: HashMap<String, String>,
+    included: HashSet<&'a str>,
+    output: String,
+}
+
+pub struct Preprocessor<'a> {
+    includes: HashMap<&'a str, &'a str>,
+    defines: HashMap<String, String>,
+}
+
+impl Default for Preprocessor<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Preprocessor<'a> {
+    /// Preprocessor knowing the built-in `SHADER_INCLUDES`, with nothing defined
+    pub fn new() -> Self {
+        Self { includes: SHADER_INCLUDES.iter().copied().collect(), defines: HashMap::new() }
+    }
+
+    /// Preprocessor defining the features' `SHADER_OPTIONS` names (as 1)
+    pub fn with_features(features: ShaderFeatures) -> Self {
+        let mut preprocessor = Self::new();
+        for define in features.defines() {
+            preprocessor.define(define, "1");
+        }
+        preprocessor
+    }
+
+    /// Register (or replace) a chunk for `#include "name"`
+    pub fn add_include(&mut self, name: &'a str, source: &'a str) {
+        self.includes.insert(name, source);
+    }
+
+    pub fn define(&mut self, name: &str, value: &str) {
+        self.defines.insert(name.to_string(), value.to_string());
+    }
+
+    /// Expand `source`; sources may `#define` on top of the preprocessor's
+    /// own defines, which are left as they were
+    pub fn process(&self, source: &str) -> Result<String, PreprocessError> {
+        let mut expansion = Expansion { defines: self.defines.clone(), included: HashSet::new(), output: String::with_capacity(source.len()) };
+        self.expand(ROOT_FILE, source, &mut expansion)?;
+        Ok(expansion.output)
+    }
+
+    /// `SHADER_OPTIONS` features that `#if`-family directives in `source`
+    /// (or anything it includes) name. Other features can't change the
+    /// output, so they can be dropped from a permutation's key.
+    pub fn used_features(&self, source: &str) -> ShaderFeatures {
+        let mut used = ShaderFeatures::NONE;
+        let mut pending = vec![source];
+        let mut seen = HashSet::new();
+        while let Some(source) = pending.pop() {
+            for (keyword, argument) in source.lines().filter_map(directive) {
+                match keyword {
+                    "if" | "elif" | "ifdef" | "ifndef" => {
+                        for &(feature, define) in SHADER_OPTIONS {
+                            if identifiers(argument).any(|name| name == define) {
+                                used = used | feature;
+                            }
+                        }
+                    }
+                    "include" => {
+                        if let Some((name, chunk)) = include_name(argument).and_then(|name| self.includes.get_key_value(name)) {
+                            if seen.insert(*name) {
+                                pending.push(chunk);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        used
+    }
+
+    fn expand(&self, file: &str, source: &str, expansion: &mut Expansion<'a>) -> Result<(), PreprocessError> {
+        let mut stack: Vec<Conditional> = Vec::new();
+        for line in source.lines() {
+            let emitting = stack.last().is_none_or(|block| block.active);
+            let Some((keyword, argument)) = directive(line) else {
+                if emitting {
+                    substitute(line, &expansion.defines, &mut expansion.output);
+                    expansion.output.push('\n');
+                }
+                continue;
+            };
+            let invalid = || PreprocessError::InvalidDirective(line.trim().to_string());
+            let unmatched = || PreprocessError::UnmatchedDirective(line.trim().to_string());
+
+            match keyword {
+                "if" | "ifdef" | "ifndef" => {
+                    let active = emitting
+                        && match keyword {
+                            "if" => evaluate(argument, &expansion.defines)?,
+                            _ => {
+                                let name = single_identifier(argument).ok_or_else(invalid)?;
+                                expansion.defines.contains_key(name) == (keyword == "ifdef")
+                            }
+                        };
+                    stack.push(Conditional { parent: emitting, active, taken: active, seen_else: false });
+                }
+                "elif" => {
+                    let block = stack.last_mut().filter(|block| !block.seen_else).ok_or_else(unmatched)?;
+                    block.active = block.parent && !block.taken && evaluate(argument, &expansion.defines)?;
+                    block.taken |= block.active;
+                }
+                "else" => {
+                    let block = stack.last_mut().filter(|block| !block.seen_else).ok_or_else(unmatched)?;
+                    block.active = block.parent && !block.taken;
+                    block.taken = true;
+                    block.seen_else = true;
+                }
+                "endif" => {
+                    stack.pop().ok_or_else(unmatched)?;
+                }
+                // Anything else in a skipped block is ignored, unknown or not
+                _ if !emitting => {}
+                "define" => {
+                    let (name, value) = argument.split_once(char::is_whitespace).unwrap_or((argument, ""));
+                    let name = single_identifier(name).ok_or_else(invalid)?;
+                    expansion.defines.insert(name.to_string(), value.trim().to_string());
+                }
+                "undef" => {
+                    let name = single_identifier(argument).ok_or_else(invalid)?;
+                    expansion.defines.remove(name);
+                }
+                "include" => {
+                    let name = include_name(argument).ok_or_else(invalid)?;
+                    let (&name, &chunk) = self.includes.get_key_value(name).ok_or_else(|| PreprocessError::UnknownInclude(name.to_string()))?;
+                    if expansion.included.insert(name) {
+                        self.expand(name, chunk, expansion)?;
+                    }
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        if stack.is_empty() {
+            Ok(())
+        } else {
+            Err(PreprocessError::UnterminatedIf(file.to_string()))
+        }
+    }
+}
+
+/// Keyword and argument of a directive line, without any `//` comment
+fn directive(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start().strip_prefix('#')?;
+    let line = line.split_once("//").map_or(line, |(code, _)| code).trim();
+    Some(line.split_once(char::is_whitespace).map_or((line, ""), |(keyword, argument)| (keyword, argument.trim())))
+}
+
+fn include_name(argument: &str) -> Option<&str> {
+    argument.strip_prefix('"')?.strip_suffix('"')
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn single_identifier(text: &str) -> Option<&str> {
+    let mut names = identifiers(text);
+    match (names.next(), names.next()) {
+        (Some(name), None) if name == text.trim() => Some(name),
+        _ => None,
+    }
+}
+
+/// Identifier tokens of `text` (numbers are skipped)
+fn identifiers(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !is_identifier_char(c)).filter(|word| word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_'))
+}
+
+/// Append `line` with defined names replaced by their values; `//` comments
+/// are copied as they are
+fn substitute(line: &str, defines: &HashMap<String, String>, output: &mut String) {
+    let (code, comment) = match line.find("//") {
+        Some(start) => line.split_at(start),
+        None => (line, ""),
+    };
+    let mut rest = code;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        // Skip digits glued to a preceding number (e.g. the `u` of `8u`)
+        let glued = rest[..start].ends_with(|c: char| c.is_ascii_digit());
+        let end = rest[start..].find(|c: char| !is_identifier_char(c)).map_or(rest.len(), |len| start + len);
+        output.push_str(&rest[..start]);
+        let word = &rest[start..end];
+        match defines.get(word) {
+            Some(value) if !glued => output.push_str(value),
+            _ => output.push_str(word),
+        }
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+    output.push_str(comment);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'e> {
+    Number(i64),
+    Name(&'e str),
+    Op(&'static str),
+}
+
+fn tokenize(expr: &str) -> Option<Vec<Token<'_>>> {
+    const OPS: [&str; 11] = ["&&", "||", "==", "!=", "<=", ">=", "<", ">", "!", "(", ")"];
+    let mut tokens = Vec::new();
+    let mut rest = expr.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_digit() {
+            let len = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+            tokens.push(Token::Number(rest[..len].trim_end_matches(['u', 'i']).parse().ok()?));
+            len
+        } else if is_identifier_char(c) {
+            let len = rest.find(|c: char| !is_identifier_char(c)).unwrap_or(rest.len());
+            tokens.push(Token::Name(&rest[..len]));
+            len
+        } else {
+            let op = OPS.into_iter().find(|op| rest.starts_with(op))?;
+            tokens.push(Token::Op(op));
+            op.len()
+        };
+        rest = rest[len..].trim_start();
+    }
+    Some(tokens)
+}
+
+/// Recursive-descent evaluator: `||` binds loosest, then `&&`, then
+/// comparisons, then `!`
+struct Evaluator<'e, 'd> {
+    tokens: &'e [Token<'e>],
+    defines: &'d HashMap<String, String>,
+}
+
+impl<'e> Evaluator<'e, '_> {
+    fn next(&mut self) -> Option<Token<'e>> {
+        let (&token, rest) = self.tokens.split_first()?;
+        self.tokens = rest;
+        Some(token)
+    }
+
+    fn eat(&mut self, op: &'static str) -> bool {
+        let matched = self.tokens.first() == Some(&Token::Op(op));
+        if matched {
+            self.tokens = &self.tokens[1..];
+        }
+        matched
+    }
+
+    fn or(&mut self) -> Option<i64> {
+        let mut value = self.and()?;
+        while self.eat("||") {
+            let rhs = self.and()?;
+            value = (value != 0 || rhs != 0) as i64;
+        }
+        Some(value)
+    }
+
+    fn and(&mut self) -> Option<i64> {
+        let mut value = self.comparison()?;
+        while self.eat("&&") {
+            let rhs = self.comparison()?;
+            value = (value != 0 && rhs != 0) as i64;
+        }
+        Some(value)
+    }
+
+    fn comparison(&mut self) -> Option<i64> {
+        let lhs = self.unary()?;
+        let compare: fn(&i64, &i64) -> bool = match self.tokens.first() {
+            Some(Token::Op("==")) => i64::eq,
+            Some(Token::Op("!=")) => i64::ne,
+            Some(Token::Op("<")) => i64::lt,
+            Some(Token::Op(">")) => i64::gt,
+            Some(Token::Op("<=")) => i64::le,
+            Some(Token::Op(">=")) => i64::ge,
+            _ => return Some(lhs),
+        };
+        self.next();
+        let rhs = self.unary()?;
+        Some(compare(&lhs, &rhs) as i64)
+    }
+
+    fn unary(&mut self) -> Option<i64> {
+        match self.next()? {
+            Token::Op("!") => Some((self.unary()? == 0) as i64),
+            Token::Op("(") => {
+                let value = self.or()?;
+                self.eat(")").then_some(value)
+            }
+            Token::Number(value) => Some(value),
+            Token::Name("defined") => {
+                let parenthesized = self.eat("(");
+                let Token::Name(name) = self.next()? else { return None };
+                if parenthesized && !self.eat(")") {
+                    return None;
+                }
+                Some(self.defines.contains_key(name) as i64)
+            }
+            Token::Name(name) => match self.defines.get(name).map(|value| value.trim()) {
+                None => Some(0),
+                Some("") => Some(1),
+                Some(value) => match tokenize(value)?.as_slice() {
+                    [Token::Number(value)] => Some(*value),
+                    _ => None,
+                },
+            },
+            Token::Op(_) => None,
+        }
+    }
+}
+
+fn evaluate(expr: &str, defines: &HashMap<String, String>) -> Result<bool, PreprocessError> {
+    let invalid = || PreprocessError::InvalidExpression(expr.to_string());
+    let tokens = tokenize(expr).ok_or_else(invalid)?;
+    let mut evaluator = Evaluator { tokens: &tokens, defines };
+    match evaluator.or() {
+        Some(value) if evaluator.tokens.is_empty() => Ok(value != 0),
+        _ => Err(invalid()),
+    }
+}
+
+/// Compiled shader modules keyed by source and feature bitmask. Features a
+/// source never tests are masked out of its key, so switching bloom on or
+/// off reuses the main shader's module.
+pub struct ShaderCache {
+    features: ShaderFeatures,
+    modules: HashMap<(&'static str, ShaderFeatures), wgpu::ShaderModule>,
+}
+
+impl ShaderCache {
+    pub fn new(features: ShaderFeatures) -> Self {
+        Self { features, modules: HashMap::new() }
+    }
+
+    pub fn features(&self) -> ShaderFeatures {
+        self.features
+    }
+
+    /// Features modules requested from now on are compiled with
+    pub fn set_features(&mut self, features: ShaderFeatures) {
+        self.features = features;
+    }
+
+    /// Number of permutations compiled so far
+    pub fn len(&self) -> usize {
+        self.modules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    /// Module for `source` with the current features, compiled on first use.
+    /// Built-in sources are checked by the tests in every permutation, so a
+    /// preprocessing error here is a bug and panics.
+    pub fn module(&mut self, device: &wgpu::Device, label: &str, source: &'static str) -> &wgpu::ShaderModule {
+        let preprocessor = Preprocessor::new();
+        let features = self.features & preprocessor.used_features(source);
+        self.modules.entry((source, features)).or_insert_with(|| {
+            let wgsl = Preprocessor::with_features(features).process(source).unwrap_or_else(|err| panic!("{}: {}", label, err));
+            device.create_shader_module(wgpu::ShaderModuleDescriptor { label: Some(label), source: wgpu::ShaderSource::Wgsl(Cow::Owned(wgsl)) })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(source: &str, defines: &[(&str, &str)]) -> Result<String, PreprocessError> {
+        let mut preprocessor = Preprocessor::new();
+        for (name, value) in defines {
+            preprocessor.define(name, value);
+        }
+        preprocessor.process(source)
+    }
+
+    fn lines(output: &str) -> Vec<&str> {
+        output.lines().map(str::trim).filter(|line| !line.is_empty()).collect()
+    }
+
+    #[test]
+    fn test_conditionals_pick_one_branch() {
+        let source = "
+            #if A && !B
+            a_only
+            #elif B || C == 2
+            b_or_c2
+            #else
+            neither
+            #endif
+            #ifdef B
+            #ifndef C
+            b_without_c
+            #endif
+            #endif
+        ";
+        assert_eq!(lines(&process(source, &[("A", "")]).unwrap()), ["a_only"]);
+        assert_eq!(lines(&process(source, &[("A", ""), ("B", "1")]).unwrap()), ["b_or_c2", "b_without_c"]);
+        assert_eq!(lines(&process(source, &[("C", "2")]).unwrap()), ["b_or_c2"]);
+        assert_eq!(lines(&process(source, &[("C", "3"), ("B", "0")]).unwrap()), ["neither"]);
+        // Defined as 0 is false in #if but still defined for #ifdef
+        assert_eq!(lines(&process("#if defined(B) && (B < 1)\nyes\n#endif", &[("B", "0")]).unwrap()), ["yes"]);
+    }
+
+    #[test]
+    fn test_defines_substitute_whole_identifiers() {
+        let source = "#define STEPS 16u\nfor (var i = 0u; i < STEPS; i++) { let MY_STEPS = 8STEPS; } // STEPS\n#undef STEPS\nSTEPS";
+        assert_eq!(lines(&process(source, &[]).unwrap()), ["for (var i = 0u; i < 16u; i++) { let MY_STEPS = 8STEPS; } // STEPS", "STEPS"]);
+    }
+
+    #[test]
+    fn test_includes_expand_once() {
+        let mut preprocessor = Preprocessor::new();
+        preprocessor.add_include("common.wgsl", "#include \"leaf.wgsl\"\ncommon");
+        preprocessor.add_include("leaf.wgsl", "#ifdef LEAF\nleaf\n#endif");
+        preprocessor.define("LEAF", "");
+        let output = preprocessor.process("#include \"common.wgsl\"\n#include \"leaf.wgsl\" // again\nmain").unwrap();
+        assert_eq!(lines(&output), ["leaf", "common", "main"]);
+
+        // Includes under a false #if are never looked up
+        assert_eq!(lines(&preprocessor.process("#if 0\n#include \"missing.wgsl\"\n#endif").unwrap()), Vec::<&str>::new());
+        assert_eq!(preprocessor.used_features("#include \"common.wgsl\"\n#if SSAO\n#endif"), ShaderFeatures::SSAO);
+    }
+
+    #[test]
+    fn test_malformed_sources_are_errors() {
+        assert_eq!(process("#include \"missing.wgsl\"", &[]), Err(PreprocessError::UnknownInclude("missing.wgsl".into())));
+        assert_eq!(process("#if A\nx", &[]), Err(PreprocessError::UnterminatedIf(ROOT_FILE.into())));
+        assert_eq!(process("#else", &[]), Err(PreprocessError::UnmatchedDirective("#else".into())));
+        assert_eq!(process("#if 1\n#else\n#elif 1\n#endif", &[]), Err(PreprocessError::UnmatchedDirective("#elif 1".into())));
+        assert_eq!(process("#if (A && B", &[]), Err(PreprocessError::InvalidExpression("(A && B".into())));
+        assert_eq!(process("#if A", &[("A", "vec3")]), Err(PreprocessError::InvalidExpression("A".into())));
+        assert_eq!(process("#pragma once", &[]), Err(PreprocessError::InvalidDirective("#pragma once".into())));
+        assert_eq!(process("#define", &[]), Err(PreprocessError::InvalidDirective("#define".into())));
+    }
+
+    #[test]
+    fn test_feature_defines_and_subsets() {
+        let features = ShaderFeatures::SSAO | ShaderFeatures::BLOOM;
+        assert_eq!(features.defines().collect::<Vec<_>>(), ["SSAO", "BLOOM"]);
+        assert!(features.contains(ShaderFeatures::BLOOM) && !features.contains(ShaderFeatures::SHADOWS));
+
+        let subsets: Vec<_> = features.subsets().collect();
+        assert_eq!(subsets, [features, ShaderFeatures::BLOOM, ShaderFeatures::SSAO, ShaderFeatures::NONE]);
+        assert_eq!(ShaderFeatures::NONE.subsets().count(), 1);
+    }
+}
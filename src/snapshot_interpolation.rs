@@ -0,0 +1,334 @@
+// src/snapshot_interpolation.rs
+//! Client-side snapshot interpolation for remote entities
+//!
+//! - Per-entity jitter buffer of received `ReplicatedState`s, ordered by
+//!   server timestamp, with stale and duplicate updates dropped
+//! - Entities render `interpolation_delay_ms` in the past so there is
+//!   (usually) a snapshot on either side of the render time
+//! - Cubic Hermite position, slerped rotation, and bounded extrapolation
+//!   when the buffer runs dry on packet loss
+//! - `sample_all` yields `EntitySnapshot`s ready for `SceneSnapshot::entities`
+
+use std::collections::{HashMap, VecDeque};
+
+use glam::{EulerRot, Quat, Vec3};
+
+use crate::network::{EntitySnapshot, ReplicatedState, SnapshotQuantization};
+
+/// Weight of each new sample in the server clock offset estimate
+const CLOCK_OFFSET_SMOOTHING: f64 = 0.1;
+
+// ============================================================================
+// CONFIG
+// ============================================================================
+
+#[derive(Clone, Copy, Debug)]
+pub struct InterpolationConfig {
+    /// How far behind the estimated server time entities are rendered.
+    /// Two to three network ticks absorbs one lost packet plus jitter.
+    pub interpolation_delay_ms: f64,
+    /// Longest a stale entity keeps moving along its last velocity
+    pub max_extrapolation_ms: f64,
+    /// Snapshots kept per entity
+    pub buffer_size: usize,
+    /// Must match the server's quantization of `EntitySnapshot`s
+    pub quantization: SnapshotQuantization,
+}
+
+impl Default for InterpolationConfig {
+    fn default() -> Self {
+        Self {
+            interpolation_delay_ms: 100.0,
+            max_extrapolation_ms: 250.0,
+            buffer_size: 32,
+            quantization: SnapshotQuantization::default(),
+        }
+    }
+}
+
+// ============================================================================
+// JITTER BUFFER
+// ============================================================================
+
+#[derive(Clone, Debug)]
+struct TimedSnapshot {
+    sequence: u32,
+    server_time_ms: f64,
+    snapshot: EntitySnapshot,
+}
+
+/// Snapshots of one entity ordered by server time
+#[derive(Default)]
+struct EntityBuffer {
+    samples: VecDeque<TimedSnapshot>,
+}
+
+impl EntityBuffer {
+    fn insert(&mut self, sample: TimedSnapshot, capacity: usize) -> bool {
+        if self.samples.iter().any(|s| s.sequence == sample.sequence) {
+            return false;
+        }
+        // Older than everything we've already dropped past is useless
+        if self.samples.len() >= capacity
+            && self.samples.front().is_some_and(|s| sample.server_time_ms < s.server_time_ms)
+        {
+            return false;
+        }
+        let index = self.samples.partition_point(|s| s.server_time_ms <= sample.server_time_ms);
+        self.samples.insert(index, sample);
+        while self.samples.len() > capacity {
+            self.samples.pop_front();
+        }
+        true
+    }
+
+    /// Drop samples no longer needed for interpolation or tangents at `render_time`
+    fn prune(&mut self, render_time: f64) {
+        while self.samples.len() > 3 && self.samples[2].server_time_ms <= render_time {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Finite-difference velocity at sample `i` (units per ms)
+    fn velocity(&self, i: usize) -> Vec3 {
+        let prev = &self.samples[i.saturating_sub(1)];
+        let next = &self.samples[(i + 1).min(self.samples.len() - 1)];
+        let dt = next.server_time_ms - prev.server_time_ms;
+        if dt <= 0.0 {
+            return Vec3::ZERO;
+        }
+        (next.snapshot.position - prev.snapshot.position) / dt as f32
+    }
+
+    fn sample(&self, render_time: f64, max_extrapolation_ms: f64) -> Option<EntitySnapshot> {
+        let first = self.samples.front()?;
+        let last = self.samples.back()?;
+
+        if render_time <= first.server_time_ms {
+            return Some(first.snapshot.clone());
+        }
+
+        if render_time >= last.server_time_ms {
+            let ahead = (render_time - last.server_time_ms).min(max_extrapolation_ms);
+            let velocity = self.velocity(self.samples.len() - 1);
+            let mut snapshot = last.snapshot.clone();
+            translate(&mut snapshot, velocity * ahead as f32);
+            return Some(snapshot);
+        }
+
+        let next = self.samples.partition_point(|s| s.server_time_ms <= render_time);
+        let (a, b) = (&self.samples[next - 1], &self.samples[next]);
+        let span = b.server_time_ms - a.server_time_ms;
+        let t = ((render_time - a.server_time_ms) / span) as f32;
+
+        let position = hermite(
+            a.snapshot.position,
+            self.velocity(next - 1) * span as f32,
+            b.snapshot.position,
+            self.velocity(next) * span as f32,
+            t,
+        );
+        let rotation = euler_to_quat(a.snapshot.rotation)
+            .slerp(euler_to_quat(b.snapshot.rotation), t);
+        let scale = a.snapshot.scale.lerp(b.snapshot.scale, t);
+
+        Some(EntitySnapshot::new(a.snapshot.id, position, quat_to_euler(rotation), scale))
+    }
+}
+
+// ============================================================================
+// SNAPSHOT INTERPOLATOR
+// ============================================================================
+
+pub struct SnapshotInterpolator {
+    config: InterpolationConfig,
+    entities: HashMap<u64, EntityBuffer>,
+    /// Smoothed (server timestamp - local receive time)
+    clock_offset_ms: Option<f64>,
+}
+
+impl SnapshotInterpolator {
+    pub fn new(config: InterpolationConfig) -> Self {
+        Self {
+            config,
+            entities: HashMap::new(),
+            clock_offset_ms: None,
+        }
+    }
+
+    pub fn config(&self) -> &InterpolationConfig {
+        &self.config
+    }
+
+    pub fn entity_count(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn remove_entity(&mut self, entity_id: u64) {
+        self.entities.remove(&entity_id);
+    }
+
+    // ============================================
+    // Receiving
+    // ============================================
+
+    /// Buffer an update whose full state has already been reconstructed
+    /// (see `NetworkSystem::apply_entity_update`). Returns false if it was a
+    /// duplicate, too old, or did not decode.
+    pub fn push_update(&mut self, update: &ReplicatedState, full_state: &[u8], local_time_ms: f64) -> bool {
+        let Some(snapshot) = EntitySnapshot::dequantize(update.entity_id, full_state, &self.config.quantization) else {
+            log::warn!("Snapshot interpolation: undecodable state for entity {}", update.entity_id);
+            return false;
+        };
+        self.push_snapshot(snapshot, update.sequence_number, update.timestamp as f64, local_time_ms)
+    }
+
+    /// Buffer a decoded snapshot stamped with the server time it was taken at
+    pub fn push_snapshot(&mut self, snapshot: EntitySnapshot, sequence: u32, server_time_ms: f64, local_time_ms: f64) -> bool {
+        let capacity = self.config.buffer_size.max(2);
+        let inserted = self.entities
+            .entry(snapshot.id)
+            .or_default()
+            .insert(TimedSnapshot { sequence, server_time_ms, snapshot }, capacity);
+
+        if inserted {
+            let sample_offset = server_time_ms - local_time_ms;
+            self.clock_offset_ms = Some(match self.clock_offset_ms {
+                Some(offset) => offset + (sample_offset - offset) * CLOCK_OFFSET_SMOOTHING,
+                None => sample_offset,
+            });
+        }
+        inserted
+    }
+
+    // ============================================
+    // Sampling
+    // ============================================
+
+    /// Server time currently being rendered
+    pub fn render_time(&self, local_time_ms: f64) -> Option<f64> {
+        self.clock_offset_ms
+            .map(|offset| local_time_ms + offset - self.config.interpolation_delay_ms)
+    }
+
+    pub fn sample(&self, entity_id: u64, local_time_ms: f64) -> Option<EntitySnapshot> {
+        let render_time = self.render_time(local_time_ms)?;
+        self.entities.get(&entity_id)?.sample(render_time, self.config.max_extrapolation_ms)
+    }
+
+    /// Sample every buffered entity (sorted by id) and drop samples that can
+    /// no longer be rendered. Call once per rendered frame.
+    pub fn sample_all(&mut self, local_time_ms: f64) -> Vec<EntitySnapshot> {
+        let Some(render_time) = self.render_time(local_time_ms) else {
+            return Vec::new();
+        };
+        let mut out: Vec<EntitySnapshot> = self.entities
+            .values_mut()
+            .filter_map(|buffer| {
+                buffer.prune(render_time);
+                buffer.sample(render_time, self.config.max_extrapolation_ms)
+            })
+            .collect();
+        out.sort_by_key(|s| s.id);
+        out
+    }
+}
+
+impl Default for SnapshotInterpolator {
+    fn default() -> Self {
+        Self::new(InterpolationConfig::default())
+    }
+}
+
+fn hermite(p0: Vec3, m0: Vec3, p1: Vec3, m1: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    p0 * (2.0 * t3 - 3.0 * t2 + 1.0)
+        + m0 * (t3 - 2.0 * t2 + t)
+        + p1 * (-2.0 * t3 + 3.0 * t2)
+        + m1 * (t3 - t2)
+}
+
+fn translate(snapshot: &mut EntitySnapshot, offset: Vec3) {
+    snapshot.position += offset;
+    snapshot.bounds_min += offset;
+    snapshot.bounds_max += offset;
+}
+
+fn euler_to_quat(rotation: Vec3) -> Quat {
+    Quat::from_euler(EulerRot::XYZ, rotation.x, rotation.y, rotation.z)
+}
+
+fn quat_to_euler(rotation: Quat) -> Vec3 {
+    let (x, y, z) = rotation.to_euler(EulerRot::XYZ);
+    Vec3::new(x, y, z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(x: f32, yaw: f32) -> EntitySnapshot {
+        EntitySnapshot::new(5, Vec3::new(x, 0.0, 0.0), Vec3::new(0.0, 0.0, yaw), Vec3::ONE)
+    }
+
+    #[test]
+    fn test_interpolates_out_of_order_snapshots() {
+        let mut interp = SnapshotInterpolator::new(InterpolationConfig {
+            interpolation_delay_ms: 100.0,
+            ..Default::default()
+        });
+        // 20Hz updates of an entity moving at 0.1 units/ms, arriving 30ms
+        // after they were taken and with 2 and 3 swapped
+        for (seq, server_time) in [(0u32, 0.0), (1, 50.0), (3, 150.0), (2, 100.0), (4, 200.0)] {
+            let s = snapshot(server_time as f32 * 0.1, server_time as f32 * 0.001);
+            assert!(interp.push_snapshot(s, seq, server_time, server_time + 30.0));
+        }
+        assert!(!interp.push_snapshot(snapshot(0.0, 0.0), 2, 100.0, 260.0));
+
+        // Local 255ms -> server 225 -> render 125, between seq 2 and 3
+        let sampled = interp.sample(5, 255.0).unwrap();
+        assert!((sampled.position.x - 12.5).abs() < 1e-3, "{}", sampled.position.x);
+        assert!((sampled.rotation.z - 0.125).abs() < 1e-3);
+        assert_eq!(interp.sample_all(255.0).len(), 1);
+    }
+
+    #[test]
+    fn test_bounded_extrapolation_on_loss() {
+        let mut interp = SnapshotInterpolator::new(InterpolationConfig {
+            interpolation_delay_ms: 50.0,
+            max_extrapolation_ms: 100.0,
+            ..Default::default()
+        });
+        interp.push_snapshot(snapshot(0.0, 0.0), 0, 0.0, 0.0);
+        interp.push_snapshot(snapshot(5.0, 0.0), 1, 50.0, 50.0);
+
+        // Render time 100: 50ms past the newest snapshot, keep moving
+        let ahead = interp.sample(5, 150.0).unwrap();
+        assert!((ahead.position.x - 10.0).abs() < 1e-3);
+        assert!((ahead.center().x - 10.0).abs() < 1e-3);
+        // After a long outage the entity stops at the extrapolation bound
+        let held = interp.sample(5, 1000.0).unwrap();
+        assert!((held.position.x - 15.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_push_update_decodes_replicated_state() {
+        let q = SnapshotQuantization::default();
+        let state = snapshot(12.0, 0.5).quantize(&q);
+        let update = ReplicatedState {
+            entity_id: 5,
+            state_data: state.clone(),
+            sequence_number: 1,
+            timestamp: 1_000,
+            delta_from: 0,
+            is_delta: false,
+        };
+        let mut interp = SnapshotInterpolator::default();
+        assert!(interp.push_update(&update, &state, 0.0));
+        let sampled = interp.sample_all(0.0);
+        assert_eq!(sampled.len(), 1);
+        assert!((sampled[0].position.x - 12.0).abs() < 0.01);
+        assert!(!interp.push_update(&update, &[], 0.0));
+    }
+}
@@ -22,6 +22,10 @@ pub enum PropertyFlags {
     EditInstanceOnly,
     BlueprintReadOnly,
     BlueprintReadWrite,
+    /// Sent from the server to clients when the value changes
+    Replicated,
+    /// Replicated, and clients run the property's OnRep callback on change
+    RepNotify,
 }
 
 /// Dynamic Value types supported by Unreal Properties and Blueprint Data Pins
@@ -75,6 +79,16 @@ pub struct UProperty {
     pub default_value: UValue,
 }
 
+impl UProperty {
+    pub fn is_replicated(&self) -> bool {
+        self.flags.iter().any(|f| matches!(f, PropertyFlags::Replicated | PropertyFlags::RepNotify))
+    }
+
+    pub fn is_rep_notify(&self) -> bool {
+        self.flags.contains(&PropertyFlags::RepNotify)
+    }
+}
+
 // ============================================================================
 // 2. TRANSFORM & SCENE COMPONENT HIERARCHY
 // ============================================================================
@@ -190,6 +204,10 @@ pub struct AActor {
     pub can_ever_tick: bool,
     pub is_pending_kill: bool,
     pub custom_properties: HashMap<String, UValue>,
+    /// Reflection metadata for entries in `custom_properties`
+    pub property_metadata: HashMap<String, UProperty>,
+    /// bReplicates: the server sends this actor's transform and replicated properties
+    pub replicates: bool,
     pub blueprint_graph: Option<Arc<BlueprintGraph>>,
 }
 
@@ -206,10 +224,20 @@ impl AActor {
             can_ever_tick: true,
             is_pending_kill: false,
             custom_properties: HashMap::new(),
+            property_metadata: HashMap::new(),
+            replicates: false,
             blueprint_graph: None,
         }
     }
 
+    /// Register a UPROPERTY, initialising its value to the default if unset
+    pub fn declare_property(&mut self, property: UProperty) {
+        self.custom_properties
+            .entry(property.name.clone())
+            .or_insert_with(|| property.default_value.clone());
+        self.property_metadata.insert(property.name.clone(), property);
+    }
+
     pub fn get_actor_location(&self) -> Vec3 {
         if let Some(root_id) = self.root_component_id {
             if let Some(comp) = self.components.get(&root_id) {
@@ -253,6 +281,12 @@ impl AActor {
         self.set_actor_location(current + world_delta);
     }
 
+    /// Smallest component id above every id this actor uses. Component ids
+    /// only need to be unique within their actor.
+    pub fn next_component_id(&self) -> u64 {
+        self.components.keys().max().map_or(1, |id| id + 1)
+    }
+
     pub fn add_component(&mut self, comp: UActorComponent) -> u64 {
         let id = comp.id;
         if self.root_component_id.is_none() {
@@ -424,11 +458,20 @@ impl Default for AGameModeBase {
 // 5. HIGH-PERFORMANCE BLUEPRINT VISUAL SCRIPTING BYTECODE VM
 // ============================================================================
 
+/// Remote event raised by a Blueprint graph, sent over the network by `rpc::RpcSystem`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FRemoteCall {
+    pub actor_id: u64,
+    pub function: String,
+    pub args: Vec<UValue>,
+}
+
 /// World Command Buffer for Blueprint VM operations
 #[derive(Debug, Default)]
 pub struct FWorldCommandBuffer {
     pub pending_spawns: Vec<AActor>,
     pub pending_destroys: Vec<u64>,
+    pub pending_rpcs: Vec<FRemoteCall>,
 }
 
 impl FWorldCommandBuffer {
@@ -439,6 +482,10 @@ impl FWorldCommandBuffer {
     pub fn destroy_actor(&mut self, id: u64) {
         self.pending_destroys.push(id);
     }
+
+    pub fn call_remote(&mut self, actor_id: u64, function: impl Into<String>, args: Vec<UValue>) {
+        self.pending_rpcs.push(FRemoteCall { actor_id, function: function.into(), args });
+    }
 }
 
 /// Blueprint Node Opcode for Zero-Cost VM Execution
@@ -465,6 +512,9 @@ pub enum EBlueprintOpcode {
     PrintString { message_pin: usize, duration: f32 },
     PlaySoundAtLocation { sound_name: String, location_pin: usize },
 
+    // Networking
+    CallRemoteEvent { function: String, arg_pins: Vec<usize> },
+
     // Flow Control
     Branch { condition_pin: usize, true_target_pc: usize, false_target_pc: usize },
     Goto { target_pc: usize },
@@ -501,6 +551,17 @@ impl BlueprintVM {
         event_name: &str,
         actor: &mut AActor,
         cmd_buffer: &mut FWorldCommandBuffer,
+    ) -> Result<()> {
+        Self::execute_event_with_args(graph, event_name, actor, cmd_buffer, &[])
+    }
+
+    /// Execute an event whose parameters are bound to registers `0..args.len()`
+    pub fn execute_event_with_args(
+        graph: &BlueprintGraph,
+        event_name: &str,
+        actor: &mut AActor,
+        cmd_buffer: &mut FWorldCommandBuffer,
+        args: &[UValue],
     ) -> Result<()> {
         let entry_pc = match graph.entry_points.get(event_name) {
             Some(&pc) => pc,
@@ -508,6 +569,9 @@ impl BlueprintVM {
         };
 
         let mut registers = graph.registers.clone();
+        for (register, arg) in registers.iter_mut().zip(args) {
+            *register = arg.clone();
+        }
         let mut pc = entry_pc;
 
         while pc < graph.instructions.len() {
@@ -588,6 +652,12 @@ impl BlueprintVM {
                     pc += 1;
                 }
 
+                EBlueprintOpcode::CallRemoteEvent { function, arg_pins } => {
+                    let args = arg_pins.iter().map(|pin| registers[*pin].clone()).collect();
+                    cmd_buffer.call_remote(actor.id, function.clone(), args);
+                    pc += 1;
+                }
+
                 EBlueprintOpcode::Branch { condition_pin, true_target_pc, false_target_pc } => {
                     let cond = registers[*condition_pin].as_bool();
                     if cond {
@@ -636,6 +706,8 @@ pub struct UWorld {
     pub real_time_seconds: f32,
     pub delta_seconds: f32,
     pub time_dilation: f32,
+    /// Remote events raised by Blueprints during `tick`, drained by `rpc::RpcSystem`
+    pub pending_rpcs: Vec<FRemoteCall>,
     next_id: u64,
 }
 
@@ -650,6 +722,7 @@ impl UWorld {
             real_time_seconds: 0.0,
             delta_seconds: 0.0166,
             time_dilation: 1.0,
+            pending_rpcs: Vec::new(),
             next_id: 100,
         }
     }
@@ -759,6 +832,7 @@ impl UWorld {
         for destroy_id in cmd_buffer.pending_destroys {
             self.destroy_actor(destroy_id);
         }
+        self.pending_rpcs.extend(cmd_buffer.pending_rpcs);
 
         // 3. Garbage Collection / Prune Pending Kill Actors
         self.actors.retain(|_, a| !a.is_pending_kill);
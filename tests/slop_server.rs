@@ -0,0 +1,102 @@
+// tests/slop_server.rs
+//! Runs the `slop_server` binary on localhost and talks to it over UDP
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use slop_engine::lobby::{LobbyClient, LobbyMessage};
+use slop_engine::network::{ConnectionEvent, DisconnectReason};
+
+/// Kills the server if the test fails before stopping it. Holds on to its
+/// stdout so the server's later prints don't hit a closed pipe.
+struct Server {
+    child: Child,
+    _stdout: BufReader<ChildStdout>,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Start the server on an ephemeral port and return the address it printed
+fn start_server() -> (Server, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_slop_server"))
+        .args(["--bind", "127.0.0.1:0", "--tick-rate", "120"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("spawn slop_server");
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    while stdout.read_line(&mut line).unwrap() > 0 {
+        if let Some(addr) = line.trim_end().strip_prefix("Listening on ") {
+            let addr = addr.to_string();
+            return (Server { child, _stdout: stdout }, addr);
+        }
+        line.clear();
+    }
+    panic!("slop_server exited without printing its address");
+}
+
+/// Pump the client until `done` holds or two seconds pass
+fn update_until(client: &mut LobbyClient, mut done: impl FnMut(&mut LobbyClient) -> bool) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while Instant::now() < deadline {
+        client.update().unwrap();
+        if done(client) {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(2));
+    }
+    false
+}
+
+#[test]
+fn test_server_handshake_lobby_and_shutdown() {
+    let (mut server, addr) = start_server();
+    let mut client = LobbyClient::connect(&addr).unwrap();
+
+    // Handshake, then the server's welcome
+    assert!(update_until(&mut client, |c| c.peer_id().is_some()));
+    let peer_id = client.peer_id().unwrap();
+
+    client.create_lobby("Integration", 4).unwrap();
+    let mut inbox = Vec::new();
+    assert!(update_until(&mut client, |c| {
+        inbox.extend(c.poll_messages());
+        inbox.iter().any(|m| matches!(m, LobbyMessage::Joined { .. }))
+    }));
+    match inbox.iter().find(|m| matches!(m, LobbyMessage::Joined { .. })) {
+        Some(LobbyMessage::Joined { session, members }) => {
+            assert_eq!(session.name, "Integration");
+            assert_eq!(members, &vec![peer_id]);
+        }
+        _ => unreachable!(),
+    }
+
+    // SIGTERM drains the loop: a clean exit and a goodbye to the client
+    #[cfg(unix)]
+    {
+        let status = Command::new("kill").args(["-TERM", &server.child.id().to_string()]).status().unwrap();
+        assert!(status.success());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let exit = loop {
+            if let Some(exit) = server.child.try_wait().unwrap() {
+                break exit;
+            }
+            assert!(Instant::now() < deadline, "slop_server ignored SIGTERM");
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert!(exit.success());
+
+        let closed = |e: &ConnectionEvent| matches!(e, ConnectionEvent::Disconnected { reason: DisconnectReason::RemoteClosed, .. });
+        assert!(
+            update_until(&mut client, |c| c.network().poll_events().iter().any(closed)),
+            "client never saw the server disconnect"
+        );
+    }
+}